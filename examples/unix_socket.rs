@@ -26,6 +26,13 @@ async fn main() -> wreq::Result<()> {
         .await?;
     println!("{}", resp.text().await?);
 
+    // Or send every request of a client over the Unix socket
+    let client = wreq::Client::builder()
+        .unix_socket("/var/run/docker.sock")
+        .build()?;
+    let resp = client.get("http://localhost/v1.41/info").send().await?;
+    println!("{}", resp.text().await?);
+
     Ok(())
 }
 
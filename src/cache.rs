@@ -0,0 +1,807 @@
+//! HTTP response caching ([RFC 9111])
+//!
+//! A client built with [`ClientBuilder::http_cache`](crate::ClientBuilder::http_cache)
+//! keeps the responses to its GET requests in a [`CacheStorage`], and answers
+//! later requests for the same URL from it while they are fresh. Once stale, a
+//! response with an `ETag` or `Last-Modified` validator is revalidated with a
+//! conditional request, and a `304 Not Modified` answer serves the stored body.
+//!
+//! [RFC 9111]: https://www.rfc-editor.org/rfc/rfc9111
+
+use std::{
+    fmt, fs,
+    future::{self, Future},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode, Version};
+use schnellru::ByLength;
+
+use crate::{
+    hash::LruMap,
+    header::{
+        AGE, AUTHORIZATION, CACHE_CONTROL, DATE, EXPIRES, HeaderName, HeaderValue, LAST_MODIFIED,
+        PRAGMA, VARY,
+    },
+    sync::Mutex,
+    util::parse_http_date,
+};
+
+/// Default number of responses kept by [`MemoryStorage`]
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// Default size above which a response body is not stored
+const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+/// Upper bound of the freshness lifetime guessed from `Last-Modified`
+const MAX_HEURISTIC_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// First line of a response encoded with [`CachedResponse::encode`]
+const ENCODING_MAGIC: &[u8] = b"wreq-cache 1\n";
+
+/// Alias for the `Future` type returned by a [`CacheStorage`].
+pub type Caching<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Where a client keeps cached responses.
+///
+/// Keys are the URLs of the requests. Methods are called on the request path and
+/// their futures awaited there, so implementations doing blocking I/O should
+/// move it off the async runtime, as [`DiskStorage`] does.
+pub trait CacheStorage: Send + Sync {
+    /// Returns the response stored under `key`.
+    fn get(&self, key: &str) -> Caching<Option<CachedResponse>>;
+
+    /// Stores `response` under `key`, replacing the response stored before.
+    fn put(&self, key: &str, response: CachedResponse) -> Caching<()>;
+
+    /// Removes the response stored under `key`.
+    fn remove(&self, key: &str) -> Caching<()>;
+}
+
+/// Configuration of the HTTP cache of a client.
+///
+/// See [`ClientBuilder::http_cache`](crate::ClientBuilder::http_cache).
+#[derive(Clone)]
+pub struct CacheConfig {
+    storage: Arc<dyn CacheStorage>,
+    max_body_size: usize,
+}
+
+/// A response kept in a [`CacheStorage`], with its body read to the end.
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    /// Values of the request headers named by `Vary`
+    varied: HeaderMap,
+    stored_at: SystemTime,
+}
+
+/// A [`CacheStorage`] keeping responses in memory, evicting the least recently
+/// used ones beyond a maximum number.
+pub struct MemoryStorage {
+    entries: Mutex<LruMap<String, CachedResponse>>,
+}
+
+/// A [`CacheStorage`] keeping responses as files in a directory, so they outlive
+/// the process.
+///
+/// Each response is a file named after the SHA-256 hash of its key. Files are read and
+/// written on the blocking thread pool of the Tokio runtime.
+#[derive(Debug)]
+pub struct DiskStorage {
+    dir: PathBuf,
+}
+
+/// Directives of a `Cache-Control` header, the ones a private cache acts on.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct CacheControl {
+    pub(crate) no_store: bool,
+    pub(crate) no_cache: bool,
+    pub(crate) max_age: Option<Duration>,
+    /// `public`, `s-maxage` or `must-revalidate`, which allow storing a response
+    /// to a request with credentials
+    pub(crate) shareable: bool,
+}
+
+// ===== impl CacheConfig =====
+
+impl CacheConfig {
+    /// Keeps responses in `storage`.
+    pub fn new<S>(storage: S) -> CacheConfig
+    where
+        S: CacheStorage + 'static,
+    {
+        CacheConfig::shared(Arc::new(storage))
+    }
+
+    /// Keeps up to `max_entries` responses in memory.
+    pub fn memory(max_entries: usize) -> CacheConfig {
+        CacheConfig::new(MemoryStorage::new(max_entries))
+    }
+
+    /// Keeps responses as files in `dir`, which is created if missing.
+    pub fn disk<P>(dir: P) -> crate::Result<CacheConfig>
+    where
+        P: Into<PathBuf>,
+    {
+        Ok(CacheConfig::new(DiskStorage::new(dir)?))
+    }
+
+    /// Keeps responses in `storage`, shared with whoever else holds it.
+    pub fn shared(storage: Arc<dyn CacheStorage>) -> CacheConfig {
+        CacheConfig {
+            storage,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Only stores responses whose body is at most `max` bytes. Defaults to 8 MiB.
+    ///
+    /// Larger responses are returned without being stored, and their body is
+    /// streamed once the first `max` bytes have been read.
+    pub fn max_body_size(mut self, max: usize) -> CacheConfig {
+        self.max_body_size = max;
+        self
+    }
+
+    pub(crate) fn storage(&self) -> &Arc<dyn CacheStorage> {
+        &self.storage
+    }
+
+    pub(crate) fn max_body(&self) -> usize {
+        self.max_body_size
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig::memory(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl fmt::Debug for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheConfig")
+            .field("max_body_size", &self.max_body_size)
+            .finish_non_exhaustive()
+    }
+}
+
+// ===== impl CachedResponse =====
+
+impl CachedResponse {
+    /// Creates a response stored at `stored_at`, for a request with the
+    /// `request_headers`.
+    pub fn new(
+        status: StatusCode,
+        version: Version,
+        headers: HeaderMap,
+        body: Bytes,
+        request_headers: &HeaderMap,
+        stored_at: SystemTime,
+    ) -> CachedResponse {
+        let mut varied = HeaderMap::new();
+        for name in vary_names(&headers) {
+            for value in request_headers.get_all(&name) {
+                varied.append(name.clone(), value.clone());
+            }
+        }
+
+        CachedResponse {
+            status,
+            version,
+            headers,
+            body,
+            varied,
+            stored_at,
+        }
+    }
+
+    /// Get the status code of the response.
+    #[inline]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the HTTP version of the response.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Get the headers of the response.
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of the response.
+    #[inline]
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Get the time the response was stored, or last revalidated.
+    #[inline]
+    pub fn stored_at(&self) -> SystemTime {
+        self.stored_at
+    }
+
+    /// Encodes the response into bytes, for storages that keep bytes.
+    ///
+    /// See [`CachedResponse::decode`].
+    pub fn encode(&self) -> Bytes {
+        let stored_at = self
+            .stored_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut buf = Vec::with_capacity(self.body.len() + 512);
+        buf.extend_from_slice(ENCODING_MAGIC);
+        buf.extend_from_slice(
+            format!(
+                "{} {} {:?}\n",
+                stored_at.as_secs(),
+                self.status.as_u16(),
+                self.version
+            )
+            .as_bytes(),
+        );
+        for headers in [&self.headers, &self.varied] {
+            buf.extend_from_slice(format!("{}\n", headers.len()).as_bytes());
+            for (name, value) in headers {
+                buf.extend_from_slice(name.as_str().as_bytes());
+                buf.extend_from_slice(b": ");
+                buf.extend_from_slice(value.as_bytes());
+                buf.push(b'\n');
+            }
+        }
+        buf.extend_from_slice(format!("{}\n", self.body.len()).as_bytes());
+        buf.extend_from_slice(&self.body);
+        Bytes::from(buf)
+    }
+
+    /// Decodes a response encoded with [`CachedResponse::encode`], returning `None`
+    /// if `bytes` are not one.
+    pub fn decode(bytes: &[u8]) -> Option<CachedResponse> {
+        let mut rest = bytes.strip_prefix(ENCODING_MAGIC)?;
+        let mut line = || {
+            let end = rest.iter().position(|&b| b == b'\n')?;
+            let line = &rest[..end];
+            rest = &rest[end + 1..];
+            Some(line)
+        };
+
+        let first = std::str::from_utf8(line()?).ok()?;
+        let mut fields = first.split(' ');
+        let stored_at = Duration::from_secs(fields.next()?.parse().ok()?);
+        let status = StatusCode::from_u16(fields.next()?.parse().ok()?).ok()?;
+        let version = match fields.next()? {
+            "HTTP/0.9" => Version::HTTP_09,
+            "HTTP/1.0" => Version::HTTP_10,
+            "HTTP/1.1" => Version::HTTP_11,
+            "HTTP/2.0" => Version::HTTP_2,
+            "HTTP/3.0" => Version::HTTP_3,
+            _ => return None,
+        };
+
+        let mut header_maps = [HeaderMap::new(), HeaderMap::new()];
+        for headers in &mut header_maps {
+            let count: usize = std::str::from_utf8(line()?).ok()?.parse().ok()?;
+            for _ in 0..count {
+                let line = line()?;
+                let split = line.windows(2).position(|w| w == b": ")?;
+                let name = HeaderName::from_bytes(&line[..split]).ok()?;
+                let value = HeaderValue::from_bytes(&line[split + 2..]).ok()?;
+                headers.append(name, value);
+            }
+        }
+
+        let len: usize = std::str::from_utf8(line()?).ok()?.parse().ok()?;
+        if rest.len() != len {
+            return None;
+        }
+        let [headers, varied] = header_maps;
+
+        Some(CachedResponse {
+            status,
+            version,
+            headers,
+            body: Bytes::copy_from_slice(rest),
+            varied,
+            stored_at: SystemTime::UNIX_EPOCH + stored_at,
+        })
+    }
+
+    /// Returns true if the response was stored for a request with the same
+    /// values of the headers named by `Vary` as `request_headers`.
+    pub(crate) fn matches_vary(&self, request_headers: &HeaderMap) -> bool {
+        vary_names(&self.headers).all(|name| {
+            self.varied
+                .get_all(&name)
+                .iter()
+                .eq(request_headers.get_all(&name).iter())
+        })
+    }
+
+    /// Returns true if the response can be used without revalidation at `now`,
+    /// for a request allowing responses up to `max_age` old.
+    pub(crate) fn is_fresh(&self, now: SystemTime, max_age: Option<Duration>) -> bool {
+        if CacheControl::from_headers(&self.headers).no_cache {
+            return false;
+        }
+        let age = self.age(now);
+        let lifetime = self.freshness_lifetime();
+        age < lifetime && max_age.is_none_or(|max_age| age <= max_age)
+    }
+
+    /// Returns the response updated with the headers of a `304 Not Modified`
+    /// answer received at `now`.
+    pub(crate) fn revalidated(mut self, headers: &HeaderMap, now: SystemTime) -> CachedResponse {
+        crate::util::replace_headers(&mut self.headers, headers.clone());
+        self.stored_at = now;
+        self
+    }
+
+    /// Age of the response at `now`, including the age it had when stored.
+    fn age(&self, now: SystemTime) -> Duration {
+        let age = self
+            .headers
+            .get(AGE)
+            .and_then(|age| age.to_str().ok()?.parse().ok())
+            .map_or(Duration::ZERO, Duration::from_secs);
+        age + now.duration_since(self.stored_at).unwrap_or_default()
+    }
+
+    /// How long the response is fresh for, from `max-age`, `Expires`, or a tenth
+    /// of the time since `Last-Modified`.
+    fn freshness_lifetime(&self) -> Duration {
+        if let Some(max_age) = CacheControl::from_headers(&self.headers).max_age {
+            return max_age;
+        }
+
+        let date = |name: HeaderName| parse_http_date(self.headers.get(name)?.to_str().ok()?);
+        let served = date(DATE).unwrap_or(self.stored_at);
+        if self.headers.contains_key(EXPIRES) {
+            // Invalid dates, such as `0`, mean already expired
+            return date(EXPIRES)
+                .and_then(|expires| expires.duration_since(served).ok())
+                .unwrap_or_default();
+        }
+
+        match date(LAST_MODIFIED) {
+            Some(modified) if is_heuristically_cacheable(self.status) => served
+                .duration_since(modified)
+                .map_or(Duration::ZERO, |since| {
+                    (since / 10).min(MAX_HEURISTIC_LIFETIME)
+                }),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+// ===== impl MemoryStorage =====
+
+impl MemoryStorage {
+    /// Creates a storage keeping up to `max_entries` responses.
+    pub fn new(max_entries: usize) -> MemoryStorage {
+        MemoryStorage {
+            entries: Mutex::new(LruMap::with_hasher(
+                ByLength::new(max_entries.try_into().unwrap_or(u32::MAX)),
+                HASHER,
+            )),
+        }
+    }
+
+    /// Returns the number of stored responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns true if no response is stored.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    /// Removes every stored response.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage::new(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Caching<Option<CachedResponse>> {
+        Box::pin(future::ready(self.entries.lock().get(key).cloned()))
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) -> Caching<()> {
+        self.entries.lock().insert(key.to_owned(), response);
+        Box::pin(future::ready(()))
+    }
+
+    fn remove(&self, key: &str) -> Caching<()> {
+        self.entries.lock().remove(key);
+        Box::pin(future::ready(()))
+    }
+}
+
+impl fmt::Debug for MemoryStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStorage")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+// ===== impl DiskStorage =====
+
+impl DiskStorage {
+    /// Creates a storage keeping responses in `dir`, which is created if missing.
+    pub fn new<P>(dir: P) -> crate::Result<DiskStorage>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(crate::Error::builder)?;
+        Ok(DiskStorage { dir })
+    }
+
+    /// Returns the directory responses are kept in.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hash = boring2::sha::sha256(key.as_bytes());
+        let name: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.dir.join(name)
+    }
+}
+
+impl CacheStorage for DiskStorage {
+    fn get(&self, key: &str) -> Caching<Option<CachedResponse>> {
+        let path = self.path(key);
+        let key = key.to_owned();
+        Box::pin(async move {
+            let read = tokio::task::spawn_blocking(move || {
+                let bytes = fs::read(path).ok()?;
+                // The key is stored first, to tell apart keys with the same hash
+                let rest = bytes.strip_prefix(key.as_bytes())?.strip_prefix(b"\n")?;
+                CachedResponse::decode(rest)
+            });
+            read.await.ok().flatten()
+        })
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) -> Caching<()> {
+        let mut bytes = Vec::from(key.as_bytes());
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&response.encode());
+
+        // Write to a temporary file of its own first, so readers never see a
+        // partial response and concurrent writers never write to the same file
+        static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(key);
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        Box::pin(async move {
+            let write = tokio::task::spawn_blocking(move || {
+                let written = fs::write(&tmp, bytes).and_then(|()| fs::rename(&tmp, &path));
+                if let Err(_err) = written {
+                    debug!("error writing cached response {}: {}", path.display(), _err);
+                    let _ = fs::remove_file(&tmp);
+                }
+            });
+            let _ = write.await;
+        })
+    }
+
+    fn remove(&self, key: &str) -> Caching<()> {
+        let path = self.path(key);
+        Box::pin(async move {
+            let _ = tokio::task::spawn_blocking(move || fs::remove_file(path)).await;
+        })
+    }
+}
+
+// ===== impl CacheControl =====
+
+impl CacheControl {
+    /// Parses the `Cache-Control` headers of `headers`, falling back to
+    /// `Pragma: no-cache` when there is none.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> CacheControl {
+        let mut cc = CacheControl::default();
+        if !headers.contains_key(CACHE_CONTROL) {
+            cc.no_cache = headers
+                .get_all(PRAGMA)
+                .iter()
+                .any(|value| value.as_bytes().eq_ignore_ascii_case(b"no-cache"));
+            return cc;
+        }
+
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            if name.eq_ignore_ascii_case("no-store") {
+                cc.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                cc.no_cache = true;
+            } else if name.eq_ignore_ascii_case("public")
+                || name.eq_ignore_ascii_case("s-maxage")
+                || name.eq_ignore_ascii_case("must-revalidate")
+            {
+                cc.shareable = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                cc.max_age = arg
+                    .and_then(|arg| arg.parse().ok())
+                    .map(Duration::from_secs)
+                    .or(Some(Duration::ZERO));
+            }
+        }
+        cc
+    }
+}
+
+/// Returns true if a response with `status` to a request with `request_headers`
+/// may be stored.
+///
+/// Responses are stored under their URL alone, so a response to a request with
+/// an `Authorization` header is only stored when it says it may be shared
+/// (RFC 9111 section 3.5).
+pub(crate) fn is_storable(
+    request_headers: &HeaderMap,
+    status: StatusCode,
+    headers: &HeaderMap,
+) -> bool {
+    let understood = matches!(status.as_u16(), 200 | 203 | 204 | 300 | 301 | 308)
+        || is_heuristically_cacheable(status);
+    let response = CacheControl::from_headers(headers);
+    understood
+        && !response.no_store
+        && !CacheControl::from_headers(request_headers).no_store
+        && (!request_headers.contains_key(AUTHORIZATION) || response.shareable)
+        && !vary_names(headers).any(|name| name == "*")
+        && (response.max_age.is_some()
+            || headers.contains_key(EXPIRES)
+            || headers.contains_key(LAST_MODIFIED)
+            || headers.contains_key(crate::header::ETAG))
+}
+
+/// Returns true if a response with `status` may be given a freshness lifetime
+/// without one in its headers (RFC 9110 section 15.1).
+///
+/// `206 Partial Content` is left out: storing a range would need combining it
+/// with the other ranges of the response, which this cache does not do.
+fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// Returns the lowercased header names listed by the `Vary` headers of `headers`.
+fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = HeaderName> + '_ {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)], stored_at: SystemTime) -> CachedResponse {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        CachedResponse::new(
+            StatusCode::OK,
+            Version::HTTP_11,
+            map,
+            Bytes::from_static(b"body"),
+            &HeaderMap::new(),
+            stored_at,
+        )
+    }
+
+    #[test]
+    fn test_freshness_from_max_age_expires_and_last_modified() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let later = |secs| now + Duration::from_secs(secs);
+
+        let max_age = response(&[("cache-control", "public, max-age=60")], now);
+        assert!(max_age.is_fresh(later(59), None));
+        assert!(!max_age.is_fresh(later(60), None));
+        // A request may ask for a younger response
+        assert!(!max_age.is_fresh(later(30), Some(Duration::from_secs(10))));
+
+        let aged = response(&[("cache-control", "max-age=60"), ("age", "50")], now);
+        assert!(!aged.is_fresh(later(10), None));
+
+        let expires = response(
+            &[
+                ("date", "Sun, 09 Sep 2001 01:46:40 GMT"),
+                ("expires", "Sun, 09 Sep 2001 01:47:40 GMT"),
+            ],
+            now,
+        );
+        assert!(expires.is_fresh(later(59), None));
+        assert!(!expires.is_fresh(later(61), None));
+        assert!(!response(&[("expires", "0")], now).is_fresh(now, None));
+
+        // A tenth of the 1000s since the last modification
+        let modified = response(
+            &[
+                ("date", "Sun, 09 Sep 2001 01:46:40 GMT"),
+                ("last-modified", "Sun, 09 Sep 2001 01:30:00 GMT"),
+            ],
+            now,
+        );
+        assert!(modified.is_fresh(later(99), None));
+        assert!(!modified.is_fresh(later(101), None));
+
+        let no_cache = response(&[("cache-control", "no-cache, max-age=60")], now);
+        assert!(!no_cache.is_fresh(now, None));
+    }
+
+    #[test]
+    fn test_is_storable() {
+        let headers = |cache_control: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CACHE_CONTROL, HeaderValue::from_str(cache_control).unwrap());
+            headers
+        };
+        let none = HeaderMap::new();
+
+        assert!(is_storable(&none, StatusCode::OK, &headers("max-age=60")));
+        assert!(!is_storable(&none, StatusCode::OK, &headers("no-store")));
+        assert!(!is_storable(
+            &headers("no-store"),
+            StatusCode::OK,
+            &headers("max-age=60")
+        ));
+        assert!(!is_storable(&none, StatusCode::OK, &none));
+        assert!(!is_storable(
+            &none,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &headers("max-age=60")
+        ));
+        assert!(!is_storable(
+            &none,
+            StatusCode::PARTIAL_CONTENT,
+            &headers("max-age=60")
+        ));
+
+        let mut authorized = HeaderMap::new();
+        authorized.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        assert!(!is_storable(
+            &authorized,
+            StatusCode::OK,
+            &headers("max-age=60")
+        ));
+        assert!(is_storable(
+            &authorized,
+            StatusCode::OK,
+            &headers("public, max-age=60")
+        ));
+        assert!(is_storable(
+            &authorized,
+            StatusCode::OK,
+            &headers("max-age=60, s-maxage=600")
+        ));
+        assert!(is_storable(
+            &authorized,
+            StatusCode::OK,
+            &headers("max-age=60, must-revalidate")
+        ));
+
+        let mut vary_any = headers("max-age=60");
+        vary_any.insert(VARY, HeaderValue::from_static("*"));
+        assert!(!is_storable(&none, StatusCode::OK, &vary_any));
+    }
+
+    #[test]
+    fn test_matches_vary() {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert("accept-language", HeaderValue::from_static("en"));
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static("Accept-Language"));
+        let cached = CachedResponse::new(
+            StatusCode::OK,
+            Version::HTTP_11,
+            headers,
+            Bytes::new(),
+            &request_headers,
+            SystemTime::now(),
+        );
+
+        assert!(cached.matches_vary(&request_headers));
+        request_headers.insert("accept-language", HeaderValue::from_static("de"));
+        assert!(!cached.matches_vary(&request_headers));
+        assert!(!cached.matches_vary(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let stored_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let cached = response(
+            &[
+                ("etag", "\"v1\""),
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+            ],
+            stored_at,
+        );
+
+        let decoded = CachedResponse::decode(&cached.encode()).unwrap();
+        assert_eq!(decoded.status(), cached.status());
+        assert_eq!(decoded.version(), cached.version());
+        assert_eq!(decoded.headers(), cached.headers());
+        assert_eq!(decoded.body(), cached.body());
+        assert_eq!(decoded.stored_at(), stored_at);
+
+        assert!(CachedResponse::decode(b"not a cached response").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disk_storage() {
+        let dir = std::env::temp_dir().join(format!("wreq-http-cache-{}", std::process::id()));
+        let storage = DiskStorage::new(&dir).unwrap();
+        let cached = response(&[("cache-control", "max-age=60")], SystemTime::now());
+
+        storage.put("http://example.com/a", cached.clone()).await;
+        let read = storage.get("http://example.com/a").await.unwrap();
+        assert_eq!(read.body(), cached.body());
+        assert!(storage.get("http://example.com/b").await.is_none());
+
+        // Concurrent writes of one key each go through a temporary file of their own
+        let puts = (0..8).map(|_| storage.put("http://example.com/a", cached.clone()));
+        futures_util::future::join_all(puts).await;
+        assert!(storage.get("http://example.com/a").await.is_some());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Files are named after the SHA-256 hash of their key
+        assert!(
+            dir.join("5bd48fa66118084cc32779267a31116dc05c70bcbca0f28e990cd58ce10afeae")
+                .exists()
+        );
+
+        storage.remove("http://example.com/a").await;
+        assert!(storage.get("http://example.com/a").await.is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
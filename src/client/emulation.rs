@@ -1,8 +1,14 @@
+use std::{collections::BTreeMap, fmt};
+
 use http::HeaderMap;
 
 use crate::{
-    core::client::options::TransportOptions, header::OrigHeaderMap, http1::Http1Options,
-    http2::Http2Options, tls::TlsOptions,
+    core::client::options::TransportOptions,
+    header::OrigHeaderMap,
+    http1::Http1Options,
+    http2::Http2Options,
+    tls::TlsOptions,
+    util::{SeededRandom, fast_random},
 };
 
 /// Factory trait for creating emulation configurations.
@@ -36,6 +42,34 @@ pub struct Emulation {
     headers: HeaderMap,
     orig_headers: OrigHeaderMap,
     transport: TransportOptions,
+    randomization: Option<Randomization>,
+}
+
+/// How the ClientHello of an [`Emulation`] is randomized when it is applied.
+#[derive(Debug, Clone, Copy)]
+struct Randomization {
+    seed: Option<u64>,
+}
+
+/// A flattened, comparable view of an [`Emulation`].
+///
+/// Every header, TLS, HTTP/1 and HTTP/2 setting is recorded as a named field
+/// (`"header.user-agent"`, `"tls.cipher_list"`, `"http2.initial_window_size"`, ...)
+/// with its value rendered as a string. The [`Display`](fmt::Display) impl writes one
+/// `field = value` line per field, and [`FingerprintConfig::diff`] lists the fields
+/// that differ between two configurations, which helps keeping browser presets in
+/// sync with captured traffic.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FingerprintConfig {
+    fields: BTreeMap<String, String>,
+}
+
+/// A field that differs between two [`FingerprintConfig`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    field: String,
+    left: Option<String>,
+    right: Option<String>,
 }
 
 // ==== impl EmulationBuilder ====
@@ -76,6 +110,12 @@ impl EmulationBuilder {
         self
     }
 
+    /// Returns the [`FingerprintConfig`] of the configuration built so far.
+    #[inline]
+    pub fn fingerprint(&self) -> FingerprintConfig {
+        self.emulation.fingerprint()
+    }
+
     /// Builds the [`Emulation`] instance.
     #[inline]
     pub fn build(self) -> Emulation {
@@ -94,6 +134,42 @@ impl Emulation {
         }
     }
 
+    /// Randomizes the TLS ClientHello each time this emulation is applied to a
+    /// client or request, so that a fleet of clients does not share a single
+    /// JA3 fingerprint.
+    ///
+    /// Only emulations whose TLS options
+    /// [`permute_extensions`](crate::tls::TlsOptionsBuilder::permute_extensions),
+    /// as Chrome does, are randomized. Any extension order is plausible for
+    /// them, while a changed order would give away other implementations. Their
+    /// ClientHello changes as follows:
+    ///
+    /// - An extension order set with
+    ///   [`extension_permutation`](crate::tls::TlsOptionsBuilder::extension_permutation)
+    ///   is shuffled once and then kept for every handshake, instead of being
+    ///   permuted per connection.
+    /// - Unless set, the AES hardware override is chosen at random, which moves
+    ///   ChaCha20-Poly1305 ahead of AES-GCM in the cipher list like on devices
+    ///   without AES instructions.
+    ///
+    /// GREASE values and their positions are left as the TLS library places
+    /// them. The JA4 fingerprint, which sorts extensions and ciphers, is not
+    /// affected.
+    #[inline]
+    pub fn randomized(mut self) -> Emulation {
+        self.randomization = Some(Randomization { seed: None });
+        self
+    }
+
+    /// Like [`randomized`](Emulation::randomized), but derives the changes from
+    /// `seed`, so that every client given the same seed presents the same
+    /// ClientHello.
+    #[inline]
+    pub fn randomized_with_seed(mut self, seed: u64) -> Emulation {
+        self.randomization = Some(Randomization { seed: Some(seed) });
+        self
+    }
+
     /// Returns a mutable reference to the TLS options, if set.
     #[inline]
     pub fn tls_options_mut(&mut self) -> &mut Option<TlsOptions> {
@@ -124,13 +200,267 @@ impl Emulation {
         &mut self.orig_headers
     }
 
-    /// Decomposes the [`Emulation`] into its components.
-    #[inline]
-    pub(crate) fn into_parts(self) -> (TransportOptions, HeaderMap, OrigHeaderMap) {
+    /// Flattens this configuration into a [`FingerprintConfig`], which can be
+    /// printed or compared field by field with another one.
+    pub fn fingerprint(&self) -> FingerprintConfig {
+        let mut fields = BTreeMap::new();
+
+        for name in self.headers.keys() {
+            let values = self
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                .collect::<Vec<_>>();
+            fields.insert(format!("header.{name}"), values.join(", "));
+        }
+
+        if !self.orig_headers.is_empty() {
+            let order = self
+                .orig_headers
+                .iter()
+                .map(|(_, orig)| String::from_utf8_lossy(orig.as_ref()))
+                .collect::<Vec<_>>();
+            fields.insert("header_order".to_owned(), order.join(", "));
+        }
+
+        // The options are destructured without `..`, so a field added to them does
+        // not compile until it is recorded here too.
+        macro_rules! record {
+            ($prefix:literal, $opts:expr, $ty:ident { $($field:ident),* $(,)? }) => {
+                if let Some($ty { $($field),* }) = $opts {
+                    $(
+                        fields.insert(
+                            concat!($prefix, ".", stringify!($field)).to_owned(),
+                            format!("{:?}", $field),
+                        );
+                    )*
+                }
+            };
+        }
+
+        record!(
+            "tls",
+            self.transport.tls_options(),
+            TlsOptions {
+                alpn_protocols,
+                alps_protocols,
+                alps_use_new_codepoint,
+                session_ticket,
+                min_tls_version,
+                max_tls_version,
+                pre_shared_key,
+                enable_ech_grease,
+                permute_extensions,
+                grease_enabled,
+                enable_ocsp_stapling,
+                enable_signed_cert_timestamps,
+                record_size_limit,
+                psk_skip_session_ticket,
+                key_shares_limit,
+                psk_dhe_ke,
+                renegotiation,
+                delegated_credentials,
+                curves_list,
+                cipher_list,
+                sigalgs_list,
+                certificate_compression_algorithms,
+                extension_permutation,
+                aes_hw_override,
+                preserve_tls13_cipher_list,
+                random_aes_hw_override,
+                enable_early_data,
+                max_early_data_size,
+            }
+        );
+
+        record!(
+            "http1",
+            self.transport.http1_options(),
+            Http1Options {
+                h09_responses,
+                h1_writev,
+                h1_max_headers,
+                h1_read_buf_exact_size,
+                h1_max_buf_size,
+                ignore_invalid_headers_in_responses,
+                allow_spaces_after_header_name_in_responses,
+                allow_obsolete_multiline_headers_in_responses,
+            }
+        );
+
+        record!(
+            "http2",
+            self.transport.http2_options(),
+            Http2Options {
+                adaptive_window,
+                initial_stream_id,
+                initial_conn_window_size,
+                initial_window_size,
+                initial_max_send_streams,
+                max_frame_size,
+                keep_alive_interval,
+                keep_alive_timeout,
+                keep_alive_while_idle,
+                max_concurrent_reset_streams,
+                max_send_buffer_size,
+                max_concurrent_streams,
+                max_header_list_size,
+                max_pending_accept_reset_streams,
+                enable_push,
+                header_table_size,
+                enable_connect_protocol,
+                no_rfc7540_priorities,
+                headers_pseudo_order,
+                headers_stream_dependency,
+                experimental_settings,
+                settings_order,
+                priorities,
+            }
+        );
+
+        if let Some(randomization) = self.randomization {
+            let value = match randomization.seed {
+                Some(seed) => format!("seed {seed}"),
+                None => "random".to_owned(),
+            };
+            fields.insert("tls.randomized".to_owned(), value);
+        }
+
+        FingerprintConfig { fields }
+    }
+
+    /// Decomposes the [`Emulation`] into its components, applying its
+    /// randomization.
+    pub(crate) fn into_parts(mut self) -> (TransportOptions, HeaderMap, OrigHeaderMap) {
+        if let (Some(randomization), Some(opts)) = (
+            self.randomization,
+            self.transport.tls_options_mut().as_mut(),
+        ) {
+            randomization.apply(opts);
+        }
         (self.transport, self.headers, self.orig_headers)
     }
 }
 
+// ==== impl Randomization ====
+
+impl Randomization {
+    fn apply(self, opts: &mut TlsOptions) {
+        if opts.permute_extensions != Some(true) {
+            return;
+        }
+
+        let mut random = SeededRandom::new(self.seed.unwrap_or_else(fast_random));
+
+        if let Some(ref mut order) = opts.extension_permutation {
+            let order = order.to_mut();
+            for i in (1..order.len()).rev() {
+                order.swap(i, random.below(i + 1));
+            }
+            opts.permute_extensions = Some(false);
+        }
+
+        if opts.aes_hw_override.is_none() && !opts.random_aes_hw_override {
+            opts.aes_hw_override = Some(random.below(2) == 0);
+        }
+    }
+}
+
+// ==== impl FingerprintConfig ====
+
+impl FingerprintConfig {
+    /// Returns the value recorded for `field`, e.g. `"tls.cipher_list"` or
+    /// `"header.user-agent"`.
+    #[inline]
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(String::as_str)
+    }
+
+    /// Iterates over the recorded fields and their values, sorted by field name.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+    }
+
+    /// Returns every field whose value differs between `self` and `other`,
+    /// sorted by field name.
+    ///
+    /// A field recorded on only one side is reported with `None` on the other.
+    pub fn diff(&self, other: &FingerprintConfig) -> Vec<FieldDiff> {
+        let mut diffs = Vec::new();
+
+        for (field, left) in &self.fields {
+            let right = other.fields.get(field);
+            if right != Some(left) {
+                diffs.push(FieldDiff {
+                    field: field.clone(),
+                    left: Some(left.clone()),
+                    right: right.cloned(),
+                });
+            }
+        }
+
+        for (field, right) in &other.fields {
+            if !self.fields.contains_key(field) {
+                diffs.push(FieldDiff {
+                    field: field.clone(),
+                    left: None,
+                    right: Some(right.clone()),
+                });
+            }
+        }
+
+        diffs.sort_by(|a, b| a.field.cmp(&b.field));
+        diffs
+    }
+}
+
+impl fmt::Display for FingerprintConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (field, value) in &self.fields {
+            writeln!(f, "{field} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+// ==== impl FieldDiff ====
+
+impl FieldDiff {
+    /// Returns the name of the field that differs.
+    #[inline]
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Returns the value on the left-hand side, `None` if the field is not set there.
+    #[inline]
+    pub fn left(&self) -> Option<&str> {
+        self.left.as_deref()
+    }
+
+    /// Returns the value on the right-hand side, `None` if the field is not set there.
+    #[inline]
+    pub fn right(&self) -> Option<&str> {
+        self.right.as_deref()
+    }
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            self.left.as_deref().unwrap_or("<unset>"),
+            self.right.as_deref().unwrap_or("<unset>")
+        )
+    }
+}
+
 impl EmulationFactory for Emulation {
     #[inline]
     fn emulation(self) -> Emulation {
@@ -158,3 +488,43 @@ impl EmulationFactory for TlsOptions {
         Emulation::builder().tls_options(self).build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use http::header::USER_AGENT;
+
+    use super::*;
+
+    fn preset(cipher_list: &'static str, user_agent: &'static str) -> Emulation {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, user_agent.parse().unwrap());
+
+        Emulation::builder()
+            .tls_options(TlsOptions::builder().cipher_list(cipher_list).build())
+            .http2_options(Http2Options::default())
+            .headers(headers)
+            .build()
+    }
+
+    #[test]
+    fn diff_reports_cipher_list_and_user_agent() {
+        let chrome = preset("TLS_AES_128_GCM_SHA256", "Chrome").fingerprint();
+        let firefox = preset("TLS_CHACHA20_POLY1305_SHA256", "Firefox").fingerprint();
+
+        assert!(chrome.diff(&chrome).is_empty());
+
+        let diffs = firefox.diff(&chrome);
+        assert_eq!(diffs.len(), 2);
+
+        assert_eq!(diffs[0].field(), "header.user-agent");
+        assert_eq!(diffs[0].left(), Some("Firefox"));
+        assert_eq!(diffs[0].right(), Some("Chrome"));
+
+        assert_eq!(diffs[1].field(), "tls.cipher_list");
+        assert_eq!(
+            diffs[1].left(),
+            Some("Some(\"TLS_CHACHA20_POLY1305_SHA256\")")
+        );
+        assert_eq!(diffs[1].right(), Some("Some(\"TLS_AES_128_GCM_SHA256\")"));
+    }
+}
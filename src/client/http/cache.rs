@@ -0,0 +1,216 @@
+//! Answering GET requests from an HTTP cache, and revalidating stale responses
+
+use std::{sync::Arc, time::SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{StreamExt, future, stream};
+use http::{
+    HeaderMap, Method, StatusCode, Uri,
+    header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, RANGE},
+};
+use http_body::Frame;
+use http_body_util::{BodyStream, StreamBody};
+
+use super::{Body, Pending, Response};
+use crate::{
+    Validators,
+    cache::{self, CacheConfig, CacheControl, CacheStorage, CachedResponse},
+    client::layer::config::RequestDefaultHeaders,
+    core::ext::RequestConfig,
+    ext::RequestUri,
+};
+#[cfg(feature = "cookies")]
+use crate::{client::layer::config::RequestCookieStore, cookie::CookieStore, header::COOKIE};
+
+/// The HTTP cache of a client, configured with `ClientBuilder::http_cache`.
+pub(super) struct HttpCache {
+    storage: Arc<dyn CacheStorage>,
+    max_body_size: usize,
+    /// The default headers of the client, which its service adds after the cache
+    default_headers: HeaderMap,
+    /// The cookie store of the client, whose cookies its service adds after the cache
+    #[cfg(feature = "cookies")]
+    cookie_store: Option<Arc<dyn CookieStore>>,
+}
+
+impl HttpCache {
+    pub(super) fn new(config: CacheConfig, default_headers: HeaderMap) -> HttpCache {
+        HttpCache {
+            storage: config.storage().clone(),
+            max_body_size: config.max_body(),
+            default_headers,
+            #[cfg(feature = "cookies")]
+            cookie_store: None,
+        }
+    }
+
+    #[cfg(feature = "cookies")]
+    pub(super) fn with_cookie_store(
+        mut self,
+        cookie_store: Option<Arc<dyn CookieStore>>,
+    ) -> HttpCache {
+        self.cookie_store = cookie_store;
+        self
+    }
+
+    /// Returns the headers `req` is sent with, once the client service added its
+    /// default headers and cookies, which `Vary` must be matched against.
+    fn sent_headers(&self, req: &http::Request<Body>) -> HeaderMap {
+        let defaults = RequestConfig::<RequestDefaultHeaders>::get(req.extensions())
+            .copied()
+            .unwrap_or(true);
+        let mut headers = if defaults {
+            let mut headers = self.default_headers.clone();
+            crate::util::replace_headers(&mut headers, req.headers().clone());
+            headers
+        } else {
+            req.headers().clone()
+        };
+
+        #[cfg(feature = "cookies")]
+        if let Some(ref cookie_store) = self.cookie_store {
+            if !headers.contains_key(COOKIE) {
+                for cookie in cookie_store.cookies(req.uri()) {
+                    headers.append(COOKIE, cookie);
+                }
+            }
+        }
+
+        headers
+    }
+
+    /// Answers `req` from the cache if it can, and otherwise with `send`, storing
+    /// the response when it may be.
+    pub(super) async fn execute<F>(
+        self: Arc<Self>,
+        mut req: http::Request<Body>,
+        send: F,
+    ) -> crate::Result<Response>
+    where
+        F: FnOnce(http::Request<Body>) -> Pending,
+    {
+        let uri = req.uri().clone();
+        let key = uri.to_string();
+        if req.method() != Method::GET {
+            // A successful unsafe request invalidates the response stored for its URL
+            let invalidates = !req.method().is_safe();
+            let res = send(req).await?;
+            if invalidates && (res.status().is_success() || res.status().is_redirection()) {
+                self.storage.remove(&key).await;
+            }
+            return Ok(res);
+        }
+
+        // A range is answered with part of the response, which is not stored
+        if req.headers().contains_key(RANGE) {
+            return send(req).await;
+        }
+
+        // Cookies of a jar of its own may make the response one of its identity
+        #[cfg(feature = "cookies")]
+        if RequestConfig::<RequestCookieStore>::get(req.extensions()).is_some() {
+            return send(req).await;
+        }
+
+        let request_headers = self.sent_headers(&req);
+        let request_cc = CacheControl::from_headers(&request_headers);
+        let stored = self
+            .storage
+            .get(&key)
+            .await
+            .filter(|stored| !request_cc.no_store && stored.matches_vary(&request_headers));
+
+        let mut revalidating = None;
+        if let Some(stored) = stored {
+            if !request_cc.no_cache && stored.is_fresh(SystemTime::now(), request_cc.max_age) {
+                trace!("Using cached response for {}", key);
+                return Ok(to_response(&stored, uri));
+            }
+
+            // Revalidate the stale response, unless the caller sent validators of its own
+            let validators = Validators::from_headers(stored.headers());
+            let conditional = request_headers.contains_key(IF_NONE_MATCH)
+                || request_headers.contains_key(IF_MODIFIED_SINCE);
+            if !validators.is_empty() && !conditional {
+                let headers = req.headers_mut();
+                if let Some(etag) = validators.etag() {
+                    headers.insert(IF_NONE_MATCH, etag.clone());
+                }
+                if let Some(last_modified) = validators.last_modified() {
+                    headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+                }
+                revalidating = Some(stored);
+            }
+        }
+
+        let res = send(req).await?;
+        let now = SystemTime::now();
+
+        if let Some(stored) = revalidating {
+            if res.status() == StatusCode::NOT_MODIFIED {
+                trace!("Cached response for {} is still valid", key);
+                let stored = stored.revalidated(res.headers(), now);
+                self.storage.put(&key, stored.clone()).await;
+                return Ok(to_response(&stored, uri));
+            }
+        }
+
+        // A response reached through redirects is not the one for the requested URL
+        if res.uri() != &uri || !cache::is_storable(&request_headers, res.status(), res.headers()) {
+            return Ok(res);
+        }
+
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let extensions = res.extensions().clone();
+        let body = match read_body(res, self.max_body_size).await? {
+            Ok(body) => body,
+            Err(res) => {
+                debug!("response for {} is too large to be cached", key);
+                return Ok(res);
+            }
+        };
+        let stored = CachedResponse::new(status, version, headers, body, &request_headers, now);
+        self.storage.put(&key, stored.clone()).await;
+
+        let mut res = to_response(&stored, uri);
+        *res.extensions_mut() = extensions;
+        Ok(res)
+    }
+}
+
+/// Reads the body of `res` if it is at most `max` bytes long.
+///
+/// Otherwise `res` is returned as an error, with the part of its body read so far
+/// put back in front of the rest.
+pub(super) async fn read_body(
+    mut res: Response,
+    max: usize,
+) -> crate::Result<Result<Bytes, Response>> {
+    if res.content_length().is_some_and(|len| len > max as u64) {
+        return Ok(Err(res));
+    }
+
+    let mut body = BytesMut::new();
+    while let Some(chunk) = res.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max {
+            let res = http::Response::<Body>::from(res);
+            let (parts, rest) = res.into_parts();
+            let read = stream::once(future::ready(Ok(Frame::data(body.freeze()))));
+            let body = Body::wrap(StreamBody::new(read.chain(BodyStream::new(rest))));
+            return Ok(Err(Response::from(http::Response::from_parts(parts, body))));
+        }
+    }
+    Ok(Ok(body.freeze()))
+}
+
+fn to_response(stored: &CachedResponse, uri: Uri) -> Response {
+    let mut res = http::Response::new(stored.body().clone());
+    *res.status_mut() = stored.status();
+    *res.version_mut() = stored.version();
+    *res.headers_mut() = stored.headers().clone();
+    res.extensions_mut().insert(RequestUri(uri));
+    Response::from(res)
+}
@@ -0,0 +1,285 @@
+//! Sharing one upstream response between identical concurrent GET requests
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_channel::oneshot;
+use http::{Extensions, HeaderMap, Method, StatusCode, Uri, Version};
+
+use super::{Pending, Request, Response, cache::read_body};
+#[cfg(feature = "cookies")]
+use crate::client::layer::config::RequestCookieStore;
+use crate::{
+    Error,
+    cache::CacheControl,
+    core::{
+        client::options::RequestOptions,
+        ext::{RequestConfig, RequestLayerOptions},
+    },
+    ext::RequestUri,
+    hash::{HASHER, HashMap},
+    sync::Mutex,
+};
+
+/// Largest response body buffered to share with the waiting requests.
+///
+/// A larger one is streamed to the request that went upstream, and the waiting
+/// requests go upstream on their own.
+const MAX_SHARED_BODY: usize = 1024 * 1024;
+
+/// What the request that went upstream hands to the requests waiting on it.
+///
+/// `Ok(None)` tells them the response may not be shared, so each goes upstream
+/// on its own.
+type Outcome = Result<Option<Arc<Buffered>>, Arc<Error>>;
+
+/// Tracks the GET requests in flight, by key, with the callers waiting on each.
+#[derive(Default)]
+pub(super) struct Coalescer {
+    in_flight: Mutex<HashMap<String, Vec<oneshot::Sender<Outcome>>>>,
+}
+
+/// A response with its body read to the end, so it can be handed to every caller.
+struct Buffered {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    extensions: Extensions,
+    uri: Uri,
+    body: Bytes,
+}
+
+/// Removes the in-flight entry if the leading request is dropped before it completes,
+/// which lets the waiting requests go upstream on their own.
+struct InFlight<'a> {
+    coalescer: &'a Coalescer,
+    key: Option<String>,
+}
+
+impl Coalescer {
+    /// Returns the key identical requests share, `None` if the request cannot be coalesced.
+    ///
+    /// Only GET requests without a body are coalesced. The key covers the URI and
+    /// every header, so requests with different credentials or cookies never share
+    /// a response. Requests with per-request options that change how they are sent,
+    /// such as a proxy, an emulation or a cookie jar, or with `no-store`, are not
+    /// coalesced.
+    pub(super) fn key(request: &Request) -> Option<String> {
+        if request.method() != Method::GET || request.body().is_some() {
+            return None;
+        }
+
+        let extensions = request.extensions();
+        if RequestConfig::<RequestLayerOptions>::get(extensions)
+            .is_some_and(|opts| *opts != RequestOptions::default())
+        {
+            return None;
+        }
+        #[cfg(feature = "cookies")]
+        if RequestConfig::<RequestCookieStore>::get(extensions).is_some() {
+            return None;
+        }
+        if CacheControl::from_headers(request.headers()).no_store {
+            return None;
+        }
+
+        let mut key = request.uri().to_string();
+        for (name, value) in request.headers() {
+            key.push('\n');
+            key.push_str(name.as_str());
+            key.push(':');
+            key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+        }
+        Some(key)
+    }
+
+    /// Drives `pending`, or waits for an identical request already in flight.
+    ///
+    /// The response of the request that went upstream is only buffered when other
+    /// requests wait for it.
+    pub(super) async fn execute(
+        self: Arc<Self>,
+        key: String,
+        pending: Pending,
+    ) -> crate::Result<Response> {
+        let waiting = {
+            let mut in_flight = self.in_flight.lock();
+            match in_flight.get_mut(&key) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    Some(rx)
+                }
+                None => {
+                    in_flight.insert(key.clone(), Vec::new());
+                    None
+                }
+            }
+        };
+
+        if let Some(rx) = waiting {
+            return match rx.await {
+                Ok(Ok(Some(buffered))) => Ok(buffered.to_response()),
+                Ok(Err(err)) => Err(Error::shared(&err)),
+                // The response may not be shared, or the leading request was
+                // dropped, go upstream instead
+                Ok(Ok(None)) | Err(_) => pending.await,
+            };
+        }
+
+        let mut guard = InFlight {
+            coalescer: &self,
+            key: Some(key),
+        };
+        let res = pending.await;
+
+        let waiters = guard
+            .key
+            .take()
+            .and_then(|key| self.in_flight.lock().remove(&key))
+            .unwrap_or_default();
+        // Nobody can join anymore, so without waiters there is nothing to share
+        if waiters.is_empty() {
+            return res;
+        }
+        let send = |outcome: Outcome| {
+            for tx in waiters {
+                let _ = tx.send(outcome.clone());
+            }
+        };
+
+        let buffered = match res {
+            Ok(res) if CacheControl::from_headers(res.headers()).no_store => {
+                send(Ok(None));
+                return Ok(res);
+            }
+            Ok(res) => Buffered::read(res).await,
+            Err(err) => Err(err),
+        };
+
+        match buffered {
+            Ok(Ok(buffered)) => {
+                send(Ok(Some(buffered.clone())));
+                Ok(buffered.to_response())
+            }
+            Ok(Err(res)) => {
+                send(Ok(None));
+                Ok(res)
+            }
+            Err(err) => {
+                let err = Arc::new(err);
+                send(Err(err.clone()));
+                Err(Arc::try_unwrap(err).unwrap_or_else(|err| Error::shared(&err)))
+            }
+        }
+    }
+}
+
+impl Buffered {
+    /// Reads the body of `res`, unless it is larger than [`MAX_SHARED_BODY`]:
+    /// then `res` is handed back as `Err`.
+    async fn read(res: Response) -> crate::Result<Result<Arc<Buffered>, Response>> {
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let extensions = res.extensions().clone();
+        let uri = res.uri().clone();
+        let body = match read_body(res, MAX_SHARED_BODY).await? {
+            Ok(body) => body,
+            Err(res) => return Ok(Err(res)),
+        };
+
+        Ok(Ok(Arc::new(Buffered {
+            status,
+            version,
+            headers,
+            extensions,
+            uri,
+            body,
+        })))
+    }
+
+    fn to_response(&self) -> Response {
+        let mut res = http::Response::new(self.body.clone());
+        *res.status_mut() = self.status;
+        *res.version_mut() = self.version;
+        *res.headers_mut() = self.headers.clone();
+        *res.extensions_mut() = self.extensions.clone();
+        res.extensions_mut().insert(RequestUri(self.uri.clone()));
+        Response::from(res)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.coalescer.in_flight.lock().remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_bodyless_gets_are_coalesced() {
+        let client = crate::Client::new();
+
+        let get = client.get("http://example.com/a").build().unwrap();
+        let other = client.get("http://example.com/b").build().unwrap();
+        assert!(Coalescer::key(&get).is_some());
+        assert_ne!(Coalescer::key(&get), Coalescer::key(&other));
+
+        let authed = client
+            .get("http://example.com/a")
+            .header("authorization", "Bearer x")
+            .build()
+            .unwrap();
+        assert_ne!(Coalescer::key(&get), Coalescer::key(&authed));
+
+        let post = client.post("http://example.com/a").build().unwrap();
+        assert!(Coalescer::key(&post).is_none());
+
+        let with_body = client
+            .get("http://example.com/a")
+            .body("payload")
+            .build()
+            .unwrap();
+        assert!(Coalescer::key(&with_body).is_none());
+    }
+
+    #[test]
+    fn requests_with_options_are_not_coalesced() {
+        let client = crate::Client::new();
+        let key = |builder: crate::RequestBuilder| Coalescer::key(&builder.build().unwrap());
+        let uri = "http://example.com/a";
+
+        assert!(key(client.get(uri).version(http::Version::HTTP_2)).is_none());
+        assert!(key(client.get(uri).resolve_to(([127, 0, 0, 1], 8080).into())).is_none());
+        assert!(
+            key(client
+                .get(uri)
+                .local_address(std::net::IpAddr::from([127, 0, 0, 1])))
+            .is_none()
+        );
+        assert!(
+            key(client
+                .get(uri)
+                .proxy(crate::Proxy::all("http://proxy.example:8080").unwrap()))
+            .is_none()
+        );
+        assert!(key(client.get(uri).header("cache-control", "no-store")).is_none());
+        #[cfg(feature = "cookies")]
+        assert!(
+            key(client
+                .get(uri)
+                .cookie_jar(Arc::new(crate::cookie::Jar::default())))
+            .is_none()
+        );
+
+        // Options that do not change the response are not compared
+        let timeout = client.get(uri).timeout(std::time::Duration::from_secs(5));
+        assert_eq!(key(timeout), key(client.get(uri)));
+    }
+}
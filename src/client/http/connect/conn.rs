@@ -1,12 +1,15 @@
 use std::{
     io::{self, IoSlice},
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
 use pin_project_lite::pin_project;
 #[cfg(unix)]
 use tokio::net::UnixStream;
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::NamedPipeClient;
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
     net::TcpStream,
@@ -16,7 +19,7 @@ use tokio_boring2::SslStream;
 use super::{AsyncConnWithInfo, TlsInfoFactory};
 use crate::{
     Extension,
-    core::client::connect::{Connected, Connection},
+    core::client::connect::{ConnectAttempt, Connected, Connection, proxy::h2::Http2Tunnel},
     tls::{TlsInfo, conn::MaybeHttpsStream},
 };
 
@@ -30,6 +33,7 @@ pin_project! {
         pub inner: Box<dyn AsyncConnWithInfo>,
         pub tls_info: bool,
         pub is_proxy: bool,
+        pub attempts: Option<Arc<[ConnectAttempt]>>,
     }
 }
 
@@ -49,7 +53,11 @@ pin_project! {
 
 impl Connection for Conn {
     fn connected(&self) -> Connected {
-        let connected = self.inner.connected().proxy(self.is_proxy);
+        let mut connected = self.inner.connected().proxy(self.is_proxy);
+
+        if let Some(ref attempts) = self.attempts {
+            connected = connected.extra(Extension(attempts.clone()));
+        }
 
         if self.tls_info {
             if let Some(tls_info) = self.inner.tls_info() {
@@ -172,6 +180,45 @@ impl Connection for TlsConn<MaybeHttpsStream<UnixStream>> {
     }
 }
 
+// ===== impl NamedPipeClient =====
+
+#[cfg(windows)]
+impl Connection for TlsConn<NamedPipeClient> {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.get_ref().connected();
+        if self.inner.ssl().selected_alpn_protocol() == Some(b"h2") {
+            connected.negotiated_h2()
+        } else {
+            connected
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Connection for TlsConn<MaybeHttpsStream<NamedPipeClient>> {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.get_ref().connected();
+        if self.inner.ssl().selected_alpn_protocol() == Some(b"h2") {
+            connected.negotiated_h2()
+        } else {
+            connected
+        }
+    }
+}
+
+// ===== impl Http2Tunnel =====
+
+impl Connection for TlsConn<Http2Tunnel> {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.get_ref().connected();
+        if self.inner.ssl().selected_alpn_protocol() == Some(b"h2") {
+            connected.negotiated_h2()
+        } else {
+            connected
+        }
+    }
+}
+
 impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsConn<T> {
     #[inline]
     fn poll_read(
@@ -1,3 +1,5 @@
+#[cfg(windows)]
+use std::ffi::OsStr;
 #[cfg(unix)]
 use std::path::Path;
 use std::{
@@ -8,13 +10,16 @@ use std::{
     time::Duration,
 };
 
-use http::Uri;
-use tokio::io::{AsyncRead, AsyncWrite};
+use http::{Uri, header::PROXY_AUTHORIZATION};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::Semaphore,
+};
 use tokio_boring2::SslStream;
 use tower::{
     Service, ServiceBuilder, ServiceExt,
     timeout::TimeoutLayer,
-    util::{BoxCloneSyncService, MapRequestLayer},
+    util::{BoxCloneSyncService, BoxService, MapRequestLayer},
 };
 
 use super::{
@@ -23,12 +28,20 @@ use super::{
     conn::{Conn, TlsConn},
     verbose::Verbose,
 };
+#[cfg(windows)]
+use crate::core::client::connect::NamedPipeConnector;
 #[cfg(unix)]
 use crate::core::client::connect::UnixConnector;
 use crate::{
-    core::client::{
-        ConnectExtra, ConnectRequest,
-        connect::{Connection, proxy},
+    core::{
+        client::{
+            ConnectExtra, ConnectRequest,
+            connect::{
+                AttemptLog, Connection,
+                proxy::{self, h2::Http2Tunnels},
+            },
+        },
+        rt::{Executor, TokioExecutor},
     },
     dns::DynResolver,
     error::{BoxError, TimedOut, map_timeout_to_connector_error},
@@ -44,10 +57,14 @@ use crate::{
 
 type Connecting = Pin<Box<dyn Future<Output = Result<Conn, BoxError>> + Send>>;
 
+type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
 /// Configuration for the connector service.
 #[derive(Clone)]
 struct Config {
     proxies: Arc<Vec<ProxyMatcher>>,
+    /// The Unix socket or named pipe carrying every connection, before any proxy
+    local_transport: Option<Intercepted>,
     verbose: Verbose,
     tcp_nodelay: bool,
     tls_info: bool,
@@ -56,6 +73,10 @@ struct Config {
     /// This lets us avoid an extra `Box::pin` indirection layer
     /// since `tokio::time::Timeout` is `Unpin`
     timeout: Option<Duration>,
+    /// Bounds the connects in progress, shared by every clone of the connector.
+    connect_limit: Option<Arc<Semaphore>>,
+    /// The HTTP/2 connections to proxies, shared by the tunnels through them.
+    h2_tunnels: Arc<Http2Tunnels>,
 }
 
 /// Builder for `Connector`.
@@ -99,6 +120,12 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Returns the configured HTTP connector.
+    #[inline]
+    pub fn http(&self) -> &HttpConnector {
+        &self.http
+    }
+
     /// Set the TLS connector builder to use.
     #[inline]
     pub fn with_tls<F>(mut self, call: F) -> ConnectorBuilder
@@ -119,6 +146,32 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets the Unix socket or named pipe every connection is made over.
+    #[inline]
+    pub fn local_transport(mut self, transport: Option<Intercepted>) -> ConnectorBuilder {
+        self.config.local_transport = transport;
+        self
+    }
+
+    /// Limit the number of connects in progress at once.
+    #[inline]
+    pub fn max_concurrent_connects(mut self, max: Option<usize>) -> ConnectorBuilder {
+        self.config.connect_limit =
+            max.map(|max| Arc::new(Semaphore::new(max.clamp(1, Semaphore::MAX_PERMITS))));
+        self
+    }
+
+    /// Sets the executor running the HTTP/2 connections to proxies, and how
+    /// long one may go without opening a tunnel before it is closed.
+    #[inline]
+    pub fn h2_tunnels<E>(mut self, exec: E, idle_timeout: Option<Duration>) -> ConnectorBuilder
+    where
+        E: Executor<BoxSendFuture> + Send + Sync + 'static,
+    {
+        self.config.h2_tunnels = Arc::new(Http2Tunnels::new(exec, idle_timeout));
+        self
+    }
+
     /// Set connecting verbose mode.
     #[inline]
     pub fn verbose(mut self, enabled: bool) -> ConnectorBuilder {
@@ -142,14 +195,24 @@ impl ConnectorBuilder {
         self
     }
 
-    /// Build a [`Connector`] with the provided layers.
-    pub fn build(self, layers: Vec<BoxedConnectorLayer>) -> crate::Result<Connector> {
+    /// Build the default [`TlsConnector`] from the configured TLS options.
+    #[inline]
+    pub fn tls(&self) -> crate::Result<TlsConnector> {
+        self.tls_builder.build(&self.tls_options)
+    }
+
+    /// Build a [`Connector`] with the provided default TLS connector and layers.
+    pub fn build(
+        self,
+        tls: TlsConnector,
+        layers: Vec<BoxedConnectorLayer>,
+    ) -> crate::Result<Connector> {
         let mut service = ConnectorService {
             config: self.config,
             #[cfg(feature = "socks")]
             resolver: self.resolver.clone(),
             http: self.http,
-            tls: self.tls_builder.build(&self.tls_options)?,
+            tls,
             tls_builder: Arc::new(self.tls_builder),
         };
 
@@ -211,10 +274,13 @@ impl Connector {
         ConnectorBuilder {
             config: Config {
                 proxies,
+                local_transport: None,
                 verbose: Verbose::OFF,
                 tcp_nodelay: false,
                 tls_info: false,
                 timeout: None,
+                connect_limit: None,
+                h2_tunnels: Arc::new(Http2Tunnels::new(TokioExecutor::new(), None)),
             },
             #[cfg(feature = "socks")]
             resolver: resolver.clone(),
@@ -267,11 +333,13 @@ impl ConnectorService {
                 inner: self.config.verbose.wrap(inner),
                 tls_info: false,
                 is_proxy: is_proxy1,
+                attempts: None,
             },
             MaybeHttpsStream::Https(inner) => Conn {
                 inner: self.config.verbose.wrap(TlsConn::new(inner)),
                 tls_info: self.config.tls_info,
                 is_proxy: is_proxy2,
+                attempts: None,
             },
         };
 
@@ -295,11 +363,13 @@ impl ConnectorService {
                 inner: self.config.verbose.wrap(inner),
                 tls_info: false,
                 is_proxy,
+                attempts: None,
             },
             MaybeHttpsStream::Https(inner) => Conn {
                 inner: self.config.verbose.wrap(TlsConn::new(inner)),
                 tls_info: self.config.tls_info,
                 is_proxy: false,
+                attempts: None,
             },
         };
 
@@ -307,12 +377,20 @@ impl ConnectorService {
     }
 
     /// Builds an [`HttpsConnector<HttpConnector>`] from a basic [`HttpConnector`],
-    /// applying TCP and TLS configuration from the provided [`ConnectExtra`].
+    /// applying TCP and TLS configuration from the [`ConnectExtra`] of `req`.
+    ///
+    /// Addresses tried while connecting are recorded into `log`, if given.
     fn build_https_connector(
         &self,
-        extra: &ConnectExtra,
+        req: &ConnectRequest,
+        log: Option<&AttemptLog>,
     ) -> Result<HttpsConnector<HttpConnector>, BoxError> {
+        let extra = req.extra();
         let mut http = self.http.clone();
+        http.set_attempt_log(log.cloned());
+        http.set_trace_id(req.trace_id().cloned());
+        #[cfg(feature = "cancel-token")]
+        http.set_cancel_token(req.cancel_token().cloned());
 
         // Disable Nagle's algorithm for TLS handshake
         //
@@ -326,6 +404,9 @@ impl ConnectorService {
             http.set_connect_options(opts.clone());
         }
 
+        // Connect to the forced remote address, if any, instead of resolving the host
+        http.set_remote_address(extra.remote_addr());
+
         self.build_tls_connector_generic(http, extra)
     }
 
@@ -341,6 +422,17 @@ impl ConnectorService {
         self.build_tls_connector_generic(UnixConnector(unix_socket), extra)
     }
 
+    /// Builds an [`HttpsConnector<NamedPipeConnector>`] for secure communication over a Windows
+    /// named pipe.
+    #[cfg(windows)]
+    fn build_named_pipe_connector(
+        &self,
+        pipe: Arc<OsStr>,
+        extra: &ConnectExtra,
+    ) -> Result<HttpsConnector<NamedPipeConnector>, BoxError> {
+        self.build_tls_connector_generic(NamedPipeConnector(pipe), extra)
+    }
+
     /// Creates an [`HttpsConnector`] from a given connector and TLS configuration.
     fn build_tls_connector_generic<S, T>(
         &self,
@@ -369,7 +461,8 @@ impl ConnectorService {
     async fn connect_direct(self, req: ConnectRequest, is_proxy: bool) -> Result<Conn, BoxError> {
         trace!("connect with maybe proxy: {:?}", is_proxy);
 
-        let mut connector = self.build_https_connector(req.extra())?;
+        let log = AttemptLog::default();
+        let mut connector = self.build_https_connector(&req, Some(&log))?;
 
         // When using a proxy for HTTPS targets, disable ALPN to avoid protocol negotiation issues
         if is_proxy && req.uri().is_https() {
@@ -385,7 +478,9 @@ impl ConnectorService {
 
         // If the connection is HTTPS, wrap the TLS stream in a TlsConn for unified handling.
         // For plain HTTP, use the stream directly without additional wrapping.
-        self.conn_from_stream(io, is_proxy, is_proxy)
+        let mut conn = self.conn_from_stream(io, is_proxy, is_proxy)?;
+        conn.attempts = Some(log.snapshot());
+        Ok(conn)
     }
 
     /// Establishes a connection through a specified proxy.
@@ -401,16 +496,10 @@ impl ConnectorService {
                 let proxy_uri = proxy.uri().clone();
 
                 #[cfg(feature = "socks")]
-                use proxy::socks::{DnsResolve, SocksConnector, Version};
+                use proxy::socks::SocksConnector;
 
                 #[cfg(feature = "socks")]
-                if let Some((version, dns_resolve)) = match proxy.uri().scheme_str() {
-                    Some("socks4") => Some((Version::V4, DnsResolve::Local)),
-                    Some("socks4a") => Some((Version::V4, DnsResolve::Remote)),
-                    Some("socks5") => Some((Version::V5, DnsResolve::Local)),
-                    Some("socks5h") => Some((Version::V5, DnsResolve::Remote)),
-                    _ => None,
-                } {
+                if let Some((version, dns_resolve)) = socks_mode(&proxy_uri) {
                     trace!("connecting via SOCKS proxy: {:?}", proxy_uri);
 
                     // Build a SOCKS connector, configuring authentication, version, and DNS
@@ -428,7 +517,7 @@ impl ConnectorService {
                     };
 
                     // Build an HTTPS connector for TLS if needed.
-                    let mut connector = self.build_https_connector(req.extra())?;
+                    let mut connector = self.build_https_connector(&req, None)?;
 
                     // Connect to the proxy and establish the SOCKS connection.
                     let conn = socks.call(uri).await?;
@@ -444,12 +533,44 @@ impl ConnectorService {
                     return self.conn_from_stream(io, false, false);
                 }
 
+                // Multiplex the tunnel over the HTTP/2 connection to the proxy
+                if uri.is_https() && proxy.http2() {
+                    trace!("tunneling over HTTP/2 proxy: {:?}", proxy_uri);
+
+                    // Build an HTTPS connector for the connection to the proxy.
+                    let log = AttemptLog::default();
+                    let mut connector = self.build_https_connector(&req, Some(&log))?;
+
+                    // The CONNECT request carries the proxy auth and custom headers.
+                    let mut headers = proxy.custom_headers().cloned().unwrap_or_default();
+                    if let Some(auth) = proxy.basic_auth() {
+                        let mut auth = auth.clone();
+                        auth.set_sensitive(true);
+                        headers.insert(PROXY_AUTHORIZATION, auth);
+                    }
+
+                    // Open a stream of the HTTP/2 connection to the proxy as a tunnel.
+                    let tunneled = self
+                        .config
+                        .h2_tunnels
+                        .tunnel(connector.clone(), &proxy_uri, &uri, headers)
+                        .await?;
+
+                    // Wrap the established tunneled stream with TLS.
+                    let io = connector.call(EstablishedConn::new(tunneled, req)).await?;
+
+                    let mut conn = self.conn_from_stream(io, false, false)?;
+                    conn.attempts = Some(log.snapshot());
+                    return Ok(conn);
+                }
+
                 // Handle HTTPS proxy tunneling connection
                 if uri.is_https() {
                     trace!("tunneling over HTTP(s) proxy: {:?}", proxy_uri);
 
                     // Build an HTTPS connector for the underlying connection to the proxy.
-                    let mut connector = self.build_https_connector(req.extra())?;
+                    let log = AttemptLog::default();
+                    let mut connector = self.build_https_connector(&req, Some(&log))?;
 
                     // Build a tunnel connector to establish the CONNECT tunnel.
                     let mut tunnel = {
@@ -480,12 +601,67 @@ impl ConnectorService {
                         io.get_ref().get_ref().set_nodelay(false)?;
                     }
 
-                    return self.conn_from_nested_stream(io, true);
+                    let mut conn = self.conn_from_nested_stream(io, true)?;
+                    conn.attempts = Some(log.snapshot());
+                    return Ok(conn);
                 }
 
                 *req.uri_mut() = proxy_uri;
                 self.connect_direct(req, true).await
             }
+            Intercepted::Chain(hops) => {
+                trace!("connecting via proxy chain: {:?}", hops);
+
+                // Build an HTTPS connector for the connection to the first proxy.
+                let log = AttemptLog::default();
+                let mut connector = self.build_https_connector(&req, Some(&log))?;
+
+                // Wrap the connector once per proxy, so that each one connects
+                // through the proxies before it, then tunnels to its destination.
+                let mut chain: BoxService<Uri, _, BoxError> = BoxService::new(connector.clone());
+                for hop in hops.iter() {
+                    let proxy_uri = hop.uri().clone();
+
+                    #[cfg(feature = "socks")]
+                    if let Some((version, dns_resolve)) = socks_mode(&proxy_uri) {
+                        let mut socks = proxy::socks::SocksConnector::new_with_resolver(
+                            proxy_uri,
+                            chain,
+                            self.resolver.clone(),
+                        );
+                        socks.set_auth(hop.raw_auth());
+                        socks.set_version(version);
+                        socks.set_dns_mode(dns_resolve);
+                        chain = BoxService::new(socks.map_err(BoxError::from));
+                        continue;
+                    }
+
+                    let mut tunnel = proxy::tunnel::TunnelConnector::new(proxy_uri, chain);
+                    if let Some(auth) = hop.basic_auth() {
+                        tunnel = tunnel.with_auth(auth.clone());
+                    }
+                    if let Some(headers) = hop.custom_headers() {
+                        tunnel = tunnel.with_headers(headers.clone());
+                    }
+                    chain = BoxService::new(tunnel.map_err(BoxError::from));
+                }
+
+                // Connect through every proxy and tunnel to the target.
+                let tunneled = chain.call(uri).await?;
+
+                // Wrap the established tunneled stream with TLS if needed.
+                let io = connector.call(EstablishedConn::new(tunneled, req)).await?;
+
+                // Re-enable Nagle's algorithm if it was disabled earlier
+                if !self.config.tcp_nodelay {
+                    io.get_ref().get_ref().set_nodelay(false)?;
+                }
+
+                // The target is tunneled to, so requests use the origin form.
+                let mut conn = self.conn_from_nested_stream(io, false)?;
+                conn.attempts = Some(log.snapshot());
+                Ok(conn)
+            }
             #[cfg(unix)]
             Intercepted::Unix(unix_socket) => {
                 trace!("connecting via Unix socket: {:?}", unix_socket);
@@ -518,6 +694,30 @@ impl ConnectorService {
                 // For plain HTTP, use the Unix connector directly.
                 let io = connector.call(req).await?;
 
+                self.conn_from_stream(io, is_proxy, is_proxy)
+            }
+            #[cfg(windows)]
+            Intercepted::NamedPipe(pipe) => {
+                trace!("connecting via named pipe: {:?}", pipe);
+
+                let mut connector = self.build_named_pipe_connector(pipe, req.extra())?;
+                let is_proxy = false;
+
+                // As over a Unix socket, HTTPS is tunneled with CONNECT.
+                if uri.is_https() {
+                    let proxy_uri = Uri::from_static("http://localhost");
+                    let mut tunnel =
+                        proxy::tunnel::TunnelConnector::new(proxy_uri, connector.clone());
+                    let tunneled = tunnel.call(uri).await?;
+
+                    let established_conn = EstablishedConn::new(tunneled, req);
+                    let io = connector.call(established_conn).await?;
+
+                    return self.conn_from_nested_stream(io, is_proxy);
+                }
+
+                let io = connector.call(req).await?;
+
                 self.conn_from_stream(io, is_proxy, is_proxy)
             }
         }
@@ -525,22 +725,47 @@ impl ConnectorService {
 
     /// Automatically selects direct or proxy connection.
     async fn connect_auto(self, req: ConnectRequest) -> Result<Conn, BoxError> {
-        debug!("starting new connection: {:?}", req.uri());
+        debug!(
+            trace_id = req.trace_id().map(AsRef::<str>::as_ref),
+            "starting new connection: {:?}",
+            req.uri()
+        );
 
         let timeout = self.config.timeout;
 
+        // Wait for a free slot, held until the connection is established or failed.
+        let _permit = match self.config.connect_limit {
+            Some(ref limit) => Some(limit.clone().acquire_owned().await?),
+            None => None,
+        };
+
+        // Tag the logs of this connection with the request's trace ID, if any.
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "connect",
+            uri = %req.uri(),
+            trace_id = req.trace_id().map(AsRef::<str>::as_ref),
+        );
+
         // Determine if a proxy should be used for this request.
         let fut = async {
-            let intercepted = req
-                .extra()
-                .proxy_matcher()
-                .and_then(|prox| prox.intercept(req.uri()))
-                .or_else(|| {
-                    self.config
-                        .proxies
-                        .iter()
-                        .find_map(|prox| prox.intercept(req.uri()))
-                });
+            // A local transport carries every connection, and otherwise a forced
+            // remote address always connects directly.
+            let intercepted = if let Some(ref local) = self.config.local_transport {
+                Some(local.clone())
+            } else if req.extra().remote_addr().is_some() {
+                None
+            } else {
+                req.extra()
+                    .proxy_matcher()
+                    .and_then(|prox| prox.intercept(req.uri()))
+                    .or_else(|| {
+                        self.config
+                            .proxies
+                            .iter()
+                            .find_map(|prox| prox.intercept(req.uri()))
+                    })
+            };
 
             // If a proxy is matched, connect via proxy; otherwise, connect directly.
             if let Some(intercepted) = intercepted {
@@ -550,6 +775,9 @@ impl ConnectorService {
             }
         };
 
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span);
+
         // Apply timeout if configured.
         if let Some(to) = timeout {
             tokio::time::timeout(to, fut)
@@ -561,6 +789,20 @@ impl ConnectorService {
     }
 }
 
+/// Returns the SOCKS version and DNS resolution mode of a SOCKS proxy URI.
+#[cfg(feature = "socks")]
+fn socks_mode(uri: &Uri) -> Option<(proxy::socks::Version, proxy::socks::DnsResolve)> {
+    use proxy::socks::{DnsResolve, Version};
+
+    match uri.scheme_str() {
+        Some("socks4") => Some((Version::V4, DnsResolve::Local)),
+        Some("socks4a") => Some((Version::V4, DnsResolve::Remote)),
+        Some("socks5") => Some((Version::V5, DnsResolve::Local)),
+        Some("socks5h") => Some((Version::V5, DnsResolve::Remote)),
+        _ => None,
+    }
+}
+
 impl Service<ConnectRequest> for ConnectorService {
     type Response = Conn;
     type Error = BoxError;
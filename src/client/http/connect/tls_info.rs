@@ -1,9 +1,14 @@
 use tokio::net::TcpStream;
 #[cfg(unix)]
 use tokio::net::UnixStream;
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::NamedPipeClient;
 use tokio_boring2::SslStream;
 
-use crate::tls::{TlsInfo, conn::MaybeHttpsStream};
+use crate::{
+    core::client::connect::proxy::h2::Http2Tunnel,
+    tls::{TlsInfo, conn::MaybeHttpsStream},
+};
 
 /// A trait for extracting TLS information from a connection.
 ///
@@ -23,9 +28,7 @@ impl TlsInfoFactory for TcpStream {
 
 impl TlsInfoFactory for SslStream<TcpStream> {
     fn tls_info(&self) -> Option<TlsInfo> {
-        self.ssl().peer_certificate().map(|c| TlsInfo {
-            peer_certificate: c.to_der().ok(),
-        })
+        Some(TlsInfo::from_ssl(self.ssl()))
     }
 }
 
@@ -40,9 +43,21 @@ impl TlsInfoFactory for MaybeHttpsStream<TcpStream> {
 
 impl TlsInfoFactory for SslStream<MaybeHttpsStream<TcpStream>> {
     fn tls_info(&self) -> Option<TlsInfo> {
-        self.ssl().peer_certificate().map(|c| TlsInfo {
-            peer_certificate: c.to_der().ok(),
-        })
+        Some(TlsInfo::from_ssl(self.ssl()))
+    }
+}
+
+// ===== impl Http2Tunnel =====
+
+impl TlsInfoFactory for Http2Tunnel {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        None
+    }
+}
+
+impl TlsInfoFactory for SslStream<Http2Tunnel> {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        Some(TlsInfo::from_ssl(self.ssl()))
     }
 }
 
@@ -58,9 +73,7 @@ impl TlsInfoFactory for UnixStream {
 #[cfg(unix)]
 impl TlsInfoFactory for SslStream<UnixStream> {
     fn tls_info(&self) -> Option<TlsInfo> {
-        self.ssl().peer_certificate().map(|c| TlsInfo {
-            peer_certificate: c.to_der().ok(),
-        })
+        Some(TlsInfo::from_ssl(self.ssl()))
     }
 }
 
@@ -77,8 +90,39 @@ impl TlsInfoFactory for MaybeHttpsStream<UnixStream> {
 #[cfg(unix)]
 impl TlsInfoFactory for SslStream<MaybeHttpsStream<UnixStream>> {
     fn tls_info(&self) -> Option<TlsInfo> {
-        self.ssl().peer_certificate().map(|c| TlsInfo {
-            peer_certificate: c.to_der().ok(),
-        })
+        Some(TlsInfo::from_ssl(self.ssl()))
+    }
+}
+
+// ===== impl NamedPipeClient =====
+
+#[cfg(windows)]
+impl TlsInfoFactory for NamedPipeClient {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        None
+    }
+}
+
+#[cfg(windows)]
+impl TlsInfoFactory for SslStream<NamedPipeClient> {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        Some(TlsInfo::from_ssl(self.ssl()))
+    }
+}
+
+#[cfg(windows)]
+impl TlsInfoFactory for MaybeHttpsStream<NamedPipeClient> {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        match self {
+            MaybeHttpsStream::Https(tls) => tls.tls_info(),
+            MaybeHttpsStream::Http(_) => None,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl TlsInfoFactory for SslStream<MaybeHttpsStream<NamedPipeClient>> {
+    fn tls_info(&self) -> Option<TlsInfo> {
+        Some(TlsInfo::from_ssl(self.ssl()))
     }
 }
@@ -0,0 +1,256 @@
+use std::fmt::Write;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_PRIORITY: u8 = 0x2;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_WINDOW_UPDATE: u8 = 0x8;
+
+const FLAG_ACK: u8 = 0x1;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// The fingerprints of the first messages a [`Client`](super::Client) sends,
+/// returned by [`Client::tls_fingerprint`](super::Client::tls_fingerprint).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFingerprint {
+    ja3: String,
+    ja4: String,
+    http2: Option<String>,
+}
+
+impl TlsFingerprint {
+    pub(super) fn new(ja3: String, ja4: String, http2: Option<String>) -> TlsFingerprint {
+        TlsFingerprint { ja3, ja4, http2 }
+    }
+
+    /// Returns the [JA3] fingerprint of the TLS ClientHello.
+    ///
+    /// [JA3]: https://github.com/salesforce/ja3
+    #[inline]
+    pub fn ja3(&self) -> &str {
+        &self.ja3
+    }
+
+    /// Returns the [JA4] fingerprint of the TLS ClientHello.
+    ///
+    /// [JA4]: https://github.com/FoxIO-LLC/ja4
+    #[inline]
+    pub fn ja4(&self) -> &str {
+        &self.ja4
+    }
+
+    /// Returns the Akamai fingerprint of the HTTP/2 connection, or `None` if the
+    /// client only speaks HTTP/1.
+    ///
+    /// The fingerprint is made of four `|` separated parts, as described in
+    /// [Passive Fingerprinting of HTTP/2 Clients][akamai]:
+    ///
+    /// 1. the `id:value` pairs of the initial SETTINGS frame, separated by `;`,
+    /// 2. the increment of the connection WINDOW_UPDATE frame, or `00`,
+    /// 3. the `stream:exclusive:dependency:weight` of each PRIORITY frame,
+    ///    separated by `,`, or `0`,
+    /// 4. the order of the pseudo-headers of a request, such as `m,a,s,p`.
+    ///
+    /// [akamai]: https://www.blackhat.com/docs/eu-17/materials/eu-17-Shuster-Passive-Fingerprinting-Of-HTTP2-Clients-wp.pdf
+    #[inline]
+    pub fn http2(&self) -> Option<&str> {
+        self.http2.as_deref()
+    }
+}
+
+/// Computes the Akamai fingerprint of what an HTTP/2 client writes before
+/// hearing from the server.
+pub(super) fn akamai(written: &[u8]) -> Option<String> {
+    let mut frames = written.strip_prefix(PREFACE)?;
+
+    let mut settings = None;
+    let mut window_update = None;
+    let mut priorities = Vec::new();
+    let mut pseudo_order = None;
+
+    while frames.len() >= 9 {
+        let len =
+            (usize::from(frames[0]) << 16) | (usize::from(frames[1]) << 8) | usize::from(frames[2]);
+        let (ty, flags) = (frames[3], frames[4]);
+        let stream_id = u32_at(frames, 5)? & 0x7fff_ffff;
+        let payload = frames.get(9..9 + len)?;
+        frames = &frames[9 + len..];
+
+        match ty {
+            FRAME_SETTINGS if flags & FLAG_ACK == 0 && settings.is_none() => {
+                let pairs = payload
+                    .chunks_exact(6)
+                    .map(|s| {
+                        format!(
+                            "{}:{}",
+                            u16::from_be_bytes([s[0], s[1]]),
+                            u32_at(s, 2).unwrap_or(0)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                settings = Some(pairs.join(";"));
+            }
+            FRAME_WINDOW_UPDATE if stream_id == 0 && window_update.is_none() => {
+                window_update = Some(u32_at(payload, 0)? & 0x7fff_ffff);
+            }
+            FRAME_PRIORITY => {
+                let dependency = u32_at(payload, 0)?;
+                let weight = *payload.get(4)?;
+                priorities.push(format!(
+                    "{}:{}:{}:{}",
+                    stream_id,
+                    dependency >> 31,
+                    dependency & 0x7fff_ffff,
+                    u16::from(weight) + 1
+                ));
+            }
+            FRAME_HEADERS if pseudo_order.is_none() => {
+                let mut block = payload;
+                if flags & FLAG_PADDED != 0 {
+                    let pad = usize::from(*block.first()?);
+                    block = block.get(1..block.len().checked_sub(pad)?)?;
+                }
+                if flags & FLAG_PRIORITY != 0 {
+                    block = block.get(5..)?;
+                }
+                pseudo_order = Some(pseudo_headers(block)?);
+            }
+            _ => {}
+        }
+    }
+
+    let mut fingerprint = settings?;
+    match window_update {
+        Some(increment) => write!(fingerprint, "|{increment}").ok()?,
+        None => fingerprint.push_str("|00"),
+    }
+    if priorities.is_empty() {
+        fingerprint.push_str("|0");
+    } else {
+        write!(fingerprint, "|{}", priorities.join(",")).ok()?;
+    }
+    write!(fingerprint, "|{}", pseudo_order?).ok()?;
+    Some(fingerprint)
+}
+
+/// Reads the pseudo-headers at the start of an HPACK encoded header block,
+/// returning their order as `m,a,s,p`.
+///
+/// The first request of a connection starts with an empty dynamic table, so
+/// the pseudo-headers are always named through the static table.
+fn pseudo_headers(mut block: &[u8]) -> Option<String> {
+    let mut order = Vec::new();
+
+    while let Some(&first) = block.first() {
+        let (index, has_value) = match first {
+            // Indexed header field
+            b if b & 0x80 != 0 => (integer(&mut block, 7)?, false),
+            // Literal header field with incremental indexing
+            b if b & 0xc0 == 0x40 => (integer(&mut block, 6)?, true),
+            // Dynamic table size update
+            b if b & 0xe0 == 0x20 => {
+                integer(&mut block, 5)?;
+                continue;
+            }
+            // Literal header field without indexing, or never indexed
+            _ => (integer(&mut block, 4)?, true),
+        };
+
+        let name = match index {
+            1 => 'a',
+            2 | 3 => 'm',
+            4 | 5 => 'p',
+            6 | 7 => 's',
+            // A regular header, which must follow all pseudo-headers
+            _ => break,
+        };
+        order.push(name.to_string());
+
+        if has_value {
+            let len = usize::try_from(integer(&mut block, 7)?).ok()?;
+            block = block.get(len..)?;
+        }
+    }
+
+    Some(order.join(","))
+}
+
+/// Reads an HPACK integer with an `n` bit prefix.
+fn integer(block: &mut &[u8], n: u32) -> Option<u64> {
+    let (&first, mut rest) = block.split_first()?;
+    let max = (1u64 << n) - 1;
+    let mut value = u64::from(first) & max;
+    if value == max {
+        let mut shift = 0;
+        loop {
+            let (&byte, tail) = rest.split_first()?;
+            rest = tail;
+            value = value.checked_add(u64::from(byte & 0x7f).checked_shl(shift)?)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    *block = rest;
+    Some(value)
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ty: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len().to_be_bytes();
+        let mut frame = len[len.len() - 3..].to_vec();
+        frame.extend_from_slice(&[ty, flags]);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn akamai_fingerprint_of_client_preface() {
+        let mut written = PREFACE.to_vec();
+        written.extend(frame(
+            FRAME_SETTINGS,
+            0,
+            0,
+            &[0, 1, 0, 1, 0, 0, 0, 4, 0, 0x60, 0, 0],
+        ));
+        written.extend(frame(FRAME_WINDOW_UPDATE, 0, 0, &15663105u32.to_be_bytes()));
+        written.extend(frame(FRAME_PRIORITY, 0, 3, &[0, 0, 0, 0, 200]));
+        // :method GET, :authority example.com (literal), :scheme https, :path /,
+        // then a regular header
+        let mut block = vec![0x82, 0x41, 11];
+        block.extend_from_slice(b"example.com");
+        block.extend_from_slice(&[0x87, 0x84, 0x40 | 58]);
+        written.extend(frame(FRAME_HEADERS, FLAG_PRIORITY | 0x5, 1, &{
+            let mut payload = vec![0x80, 0, 0, 0, 255];
+            payload.extend(block);
+            payload
+        }));
+
+        assert_eq!(
+            akamai(&written).as_deref(),
+            Some("1:65536;4:6291456|15663105|3:0:0:201|m,a,s,p")
+        );
+    }
+
+    #[test]
+    fn akamai_fingerprint_without_window_update_or_priorities() {
+        let mut written = PREFACE.to_vec();
+        written.extend(frame(FRAME_SETTINGS, 0, 0, &[0, 2, 0, 0, 0, 0]));
+        written.extend(frame(FRAME_HEADERS, 0x5, 1, &[0x82, 0x84, 0x87, 0x01, 0]));
+
+        assert_eq!(akamai(&written).as_deref(), Some("2:0|00|0|m,p,s,a"));
+        assert_eq!(akamai(b"GET / HTTP/1.1\r\n\r\n"), None);
+    }
+}
@@ -12,6 +12,8 @@ use crate::{Error, client::body, ext::RequestUri};
 
 type ResponseFuture = Oneshot<ClientRef, Request<Body>>;
 
+type BoxedFuture = Pin<Box<dyn Future<Output = Result<Response, Error>> + Send>>;
+
 pin_project! {
     /// [`Pending`] is a future representing the state of an HTTP request, which may be either
     /// an in-flight request (with its associated future and URI) or an error state.
@@ -22,6 +24,10 @@ pin_project! {
             uri: Uri,
             fut: Pin<Box<ResponseFuture>>,
         },
+        Boxed {
+            uri: Uri,
+            fut: BoxedFuture,
+        },
         Error {
             error: Option<Error>,
         },
@@ -38,6 +44,13 @@ impl Pending {
         }
     }
 
+    /// Creates a new [`Pending`] driving a request through `fut`, such as one sharing
+    /// its response with identical requests, or one that can be cancelled.
+    #[inline]
+    pub(crate) fn boxed(uri: Uri, fut: BoxedFuture) -> Self {
+        Pending::Boxed { uri, fut }
+    }
+
     /// Creates a new [`Pending`] with an error.
     #[inline]
     pub(crate) fn error(error: Error) -> Self {
@@ -51,6 +64,16 @@ impl Future for Pending {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let (uri, res) = match self.project() {
             PendingProj::Request { uri, fut } => (uri, fut.as_mut().poll(cx)),
+            PendingProj::Boxed { uri, fut } => {
+                let res = ready!(fut.as_mut().poll(cx)).map_err(|err| {
+                    if err.uri().is_none() {
+                        err.with_uri(uri.clone())
+                    } else {
+                        err
+                    }
+                });
+                return Poll::Ready(res);
+            }
             PendingProj::Error { error } => {
                 let err = error
                     .take()
@@ -1,5 +1,9 @@
+mod cache;
+mod coalesce;
 mod connect;
+mod fingerprint;
 mod future;
+mod probe;
 mod service;
 
 use std::{
@@ -13,11 +17,16 @@ use std::{
     time::Duration,
 };
 
+use cache::HttpCache;
+use coalesce::Coalescer;
 use connect::{
     BoxedConnectorLayer, BoxedConnectorService, Conn, Connector, HttpConnector, Unnameable,
 };
+pub use fingerprint::TlsFingerprint;
 pub use future::Pending;
-use http::header::{HeaderMap, HeaderValue, USER_AGENT};
+use http::header::{ACCEPT_LANGUAGE, HeaderMap, HeaderValue, USER_AGENT};
+use ipnet::IpNet;
+pub use probe::ConnectivityReport;
 use service::{ConfigService, ConfigServiceLayer};
 use tower::{
     Layer, Service, ServiceBuilder, ServiceExt,
@@ -46,31 +55,51 @@ use super::{
             TimeoutOptions,
         },
     },
+    middleware::{Middleware, Next},
     request::{Request, RequestBuilder},
-    response::Response,
+    response::{Response, RevalidatedBody},
 };
 #[cfg(feature = "hickory-dns")]
-use crate::dns::hickory::HickoryDnsResolver;
+use crate::dns::{doh::DohResolver, hickory::HickoryDnsResolver};
+#[cfg(unix)]
+use crate::proxy::IntoUnixSocket;
 use crate::{
     IntoUri, Method, Proxy,
+    cache::CacheConfig,
     core::{
         client::{
-            HttpClient, body::Incoming, connect::TcpConnectOptions, options::TransportOptions,
+            ConnectionPool, HttpClient,
+            body::Incoming,
+            conn::http2::client_preface,
+            connect::TcpConnectOptions,
+            metrics::{PoolEvent, PoolEventCallback, PoolStats},
+            options::TransportOptions,
         },
         rt::{TokioExecutor, TokioTimer},
     },
-    dns::{DnsResolverWithOverrides, DynResolver, GaiResolver, IntoResolve, Resolve},
+    dns::{
+        DnsCache, DnsCacheConfig, DnsCacheStats, DnsResolverWithOverrides, DynResolver,
+        GaiResolver, IntoResolve, IpVersion, IpVersionResolver, Resolve,
+    },
     error::{self, BoxError, Error},
     header::OrigHeaderMap,
     http1::Http1Options,
     http2::Http2Options,
-    proxy::Matcher as ProxyMatcher,
+    proxy::{Intercepted, Matcher as ProxyMatcher, ProxySelector},
     redirect::{self, FollowRedirectPolicy},
     retry,
     tls::{
-        AlpnProtocol, CertStore, Identity, KeyLog, TlsOptions, TlsVersion,
-        conn::TlsConnectorBuilder,
+        AlpnProtocol, CertPins, CertStore, Certificate, EchMode, Identity, KeyLog, Sha256Pin,
+        TlsOptions, TlsVersion,
+        conn::{TlsConnector, TlsConnectorBuilder},
+        fingerprint::ClientHello,
     },
+    util::{self, Base64Encode},
+};
+#[cfg(feature = "cancel-token")]
+use crate::{
+    core::ext::{RequestCancelToken, RequestConfig},
+    error::Canceled,
 };
 
 /// Service type for cookie handling. Identity type when cookies feature is disabled.
@@ -170,6 +199,17 @@ type ClientRef = Either<GenericClientService, BoxedClientService>;
 #[derive(Clone)]
 pub struct Client {
     inner: Arc<ClientRef>,
+    http: HttpConnector,
+    tls: TlsConnector,
+    http2_options: Option<Arc<Http2Options>>,
+    pool: ConnectionPool<Body>,
+    dns_cache: DnsCache,
+    coalescer: Option<Arc<Coalescer>>,
+    http_cache: Option<Arc<HttpCache>>,
+    middleware: Arc<[Arc<dyn Middleware>]>,
+    base64_encode: Base64Encode,
+    #[cfg(feature = "cookies")]
+    cookie_store: Option<Arc<dyn cookie::CookieStore>>,
 }
 
 /// A [`ClientBuilder`] can be used to create a [`Client`] with custom configuration.
@@ -198,10 +238,15 @@ struct Config {
     ))]
     accept_encoding: AcceptEncoding,
     connect_timeout: Option<Duration>,
+    max_concurrent_connects: Option<usize>,
     connection_verbose: bool,
     pool_idle_timeout: Option<Duration>,
     pool_max_idle_per_host: usize,
     pool_max_size: Option<NonZeroU32>,
+    pool_event_callback: Option<PoolEventCallback>,
+    max_connections_per_host: Option<usize>,
+    max_total_connections: Option<usize>,
+    check_connection_on_reuse: bool,
     tcp_nodelay: bool,
     tcp_reuse_address: bool,
     tcp_keepalive: Option<Duration>,
@@ -214,31 +259,52 @@ struct Config {
     tcp_happy_eyeballs_timeout: Option<Duration>,
     tcp_connect_options: TcpConnectOptions,
     proxies: Vec<ProxyMatcher>,
+    proxy_selector: Option<Arc<dyn ProxySelector>>,
+    local_transport: Option<Intercepted>,
     auto_sys_proxy: bool,
     retry_policy: retry::Policy,
     redirect_policy: redirect::Policy,
     redirect_history: bool,
+    redirect_time: Option<Duration>,
     referer: bool,
     timeout_options: TimeoutOptions,
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
     #[cfg(feature = "hickory-dns")]
     hickory_dns: bool,
+    #[cfg(feature = "hickory-dns")]
+    doh_url: Option<http::Uri>,
+    #[cfg(feature = "dns-over-tls")]
+    dns_over_tls: Option<(SocketAddr, String)>,
     dns_overrides: HashMap<Cow<'static, str>, Vec<SocketAddr>>,
     dns_resolver: Option<Arc<dyn Resolve>>,
+    dns_cache: DnsCacheConfig,
+    dns_negative_cache_ttl: Option<Duration>,
+    ip_version: Option<IpVersion>,
+    allowed_ports: Option<Arc<[u16]>>,
+    denied_ip_ranges: Option<Arc<[IpNet]>>,
     http_version_pref: HttpVersionPref,
     https_only: bool,
+    base64_encode: Base64Encode,
+    coalesce_requests: bool,
+    http_cache: Option<CacheConfig>,
     layers: Vec<BoxedClientLayer>,
     connector_layers: Vec<BoxedConnectorLayer>,
+    middleware: Vec<Arc<dyn Middleware>>,
     keylog: Option<KeyLog>,
     tls_info: bool,
     tls_sni: bool,
+    ech: Option<EchMode>,
+    post_quantum: Option<bool>,
     verify_hostname: bool,
     identity: Option<Identity>,
-    cert_store: CertStore,
+    cert_store: Option<CertStore>,
+    root_certs: Vec<Certificate>,
+    cert_pins: CertPins,
     cert_verification: bool,
     min_tls_version: Option<TlsVersion>,
     max_tls_version: Option<TlsVersion>,
+    shuffle_tls_extensions: Option<bool>,
     transport_options: TransportOptions,
 }
 
@@ -281,10 +347,15 @@ impl Client {
                 ))]
                 accept_encoding: AcceptEncoding::default(),
                 connect_timeout: None,
+                max_concurrent_connects: None,
                 connection_verbose: false,
                 pool_idle_timeout: Some(Duration::from_secs(90)),
                 pool_max_idle_per_host: usize::MAX,
                 pool_max_size: None,
+                pool_event_callback: None,
+                max_connections_per_host: None,
+                max_total_connections: None,
+                check_connection_on_reuse: false,
                 tcp_keepalive: Some(Duration::from_secs(15)),
                 tcp_keepalive_interval: Some(Duration::from_secs(15)),
                 tcp_keepalive_retries: Some(3),
@@ -297,31 +368,52 @@ impl Client {
                 tcp_recv_buffer_size: None,
                 tcp_happy_eyeballs_timeout: Some(Duration::from_millis(300)),
                 proxies: Vec::new(),
+                proxy_selector: None,
+                local_transport: None,
                 auto_sys_proxy: true,
                 retry_policy: retry::Policy::default(),
                 redirect_policy: redirect::Policy::none(),
                 redirect_history: false,
+                redirect_time: None,
                 referer: true,
                 timeout_options: TimeoutOptions::default(),
                 #[cfg(feature = "hickory-dns")]
                 hickory_dns: cfg!(feature = "hickory-dns"),
+                #[cfg(feature = "hickory-dns")]
+                doh_url: None,
+                #[cfg(feature = "dns-over-tls")]
+                dns_over_tls: None,
                 #[cfg(feature = "cookies")]
                 cookie_store: None,
                 dns_overrides: HashMap::new(),
                 dns_resolver: None,
+                dns_cache: DnsCacheConfig::default(),
+                dns_negative_cache_ttl: None,
+                ip_version: None,
+                allowed_ports: None,
+                denied_ip_ranges: None,
                 http_version_pref: HttpVersionPref::All,
                 https_only: false,
+                base64_encode: util::base64_encode,
+                coalesce_requests: false,
+                http_cache: None,
                 layers: Vec::new(),
                 connector_layers: Vec::new(),
+                middleware: Vec::new(),
                 keylog: None,
                 tls_info: false,
                 tls_sni: true,
+                ech: None,
+                post_quantum: None,
                 verify_hostname: true,
                 identity: None,
-                cert_store: CertStore::default(),
+                cert_store: None,
+                root_certs: Vec::new(),
+                cert_pins: CertPins::default(),
                 cert_verification: true,
                 min_tls_version: None,
                 max_tls_version: None,
+                shuffle_tls_extensions: None,
                 transport_options: TransportOptions::default(),
             },
         }
@@ -434,12 +526,227 @@ impl Client {
     /// This method fails if there was an error while sending request,
     /// redirect loop was detected or redirect limit was exhausted.
     pub fn execute(&self, request: Request) -> Pending {
-        let req = http::Request::<Body>::from(request);
+        if self.middleware.is_empty() {
+            return self.execute_direct(request);
+        }
+
+        let uri = request.uri().clone();
+        let next = Next::new(self.clone(), self.middleware.clone());
+        Pending::boxed(uri, next.run(request))
+    }
+
+    /// Returns the base64 engine used for `Authorization: Basic` values.
+    pub(crate) fn base64_encode(&self) -> Base64Encode {
+        self.base64_encode
+    }
+
+    /// Executes `request` without passing it through the middleware.
+    pub(crate) fn execute_direct(&self, request: Request) -> Pending {
+        let key = self
+            .coalescer
+            .as_ref()
+            .and_then(|_| Coalescer::key(&request));
+        let mut req = http::Request::<Body>::from(request);
+        #[cfg(feature = "cancel-token")]
+        let cancel = RequestConfig::<RequestCancelToken>::get(req.extensions()).cloned();
+        let revalidated = req.extensions_mut().remove::<RevalidatedBody>();
+        let uri = req.uri().clone();
+        let pending = match self.http_cache.clone() {
+            Some(http_cache) => {
+                let client = self.clone();
+                let send = move |req| client.dispatch(req, key);
+                Pending::boxed(uri.clone(), Box::pin(http_cache.execute(req, send)))
+            }
+            None => self.dispatch(req, key),
+        };
+
+        // Answer a 304 to a revalidated request with the cached body
+        let pending = match revalidated {
+            Some(RevalidatedBody(body)) => Pending::boxed(
+                uri.clone(),
+                Box::pin(async move { Ok(pending.await?.revalidated(body)) }),
+            ),
+            None => pending,
+        };
+
+        #[cfg(feature = "cancel-token")]
+        if let Some(token) = cancel {
+            if token.is_cancelled() {
+                return Pending::error(Error::request(Canceled).with_uri(uri));
+            }
+            return Pending::boxed(
+                uri,
+                Box::pin(async move {
+                    token
+                        .run_until_cancelled(pending)
+                        .await
+                        .unwrap_or_else(|| Err(Error::request(Canceled)))
+                }),
+            );
+        }
+
+        pending
+    }
+
+    /// Sends `req` through the client service, sharing the response with identical
+    /// requests in flight when coalescing under `key`.
+    fn dispatch(&self, req: http::Request<Body>, key: Option<String>) -> Pending {
         // Prepare the future request by ensuring we use the exact same Service instance
         // for both poll_ready and call.
         let uri = req.uri().clone();
         let fut = Oneshot::new(self.inner.as_ref().clone(), req);
-        Pending::request(uri, fut)
+        match (self.coalescer.clone(), key) {
+            (Some(coalescer), Some(key)) => {
+                let pending = Pending::request(uri.clone(), fut);
+                Pending::boxed(uri, Box::pin(coalescer.execute(key, pending)))
+            }
+            _ => Pending::request(uri, fut),
+        }
+    }
+
+    /// Checks that `host` can be resolved and connected to on `port`.
+    ///
+    /// This runs the same resolve and TCP connect steps a request would, using the
+    /// client's resolver, DNS overrides, TCP options and allowed ports, but stops
+    /// once the connection is established: no TLS handshake or HTTP request is made,
+    /// and the connection is not added to the pool.
+    ///
+    /// The probe never fails; errors, including hitting `timeout`, are reported in
+    /// the returned [`ConnectivityReport`].
+    pub async fn check_connectivity(
+        &self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+    ) -> ConnectivityReport {
+        probe::check_connectivity(self.http.clone(), host, port, timeout).await
+    }
+
+    /// Returns the [JA3] fingerprint of the TLS ClientHello this client presents.
+    ///
+    /// The ClientHello is generated from the client's default TLS options without
+    /// connecting anywhere, so it can be compared against known browser
+    /// fingerprints. GREASE values are ignored. Options that randomize the
+    /// ClientHello, such as extension permutation, yield a different JA3 on
+    /// each call. TLS options set on individual requests are not reflected.
+    ///
+    /// [JA3]: https://github.com/salesforce/ja3
+    pub fn ja3(&self) -> crate::Result<String> {
+        self.client_hello().map(|hello| hello.ja3())
+    }
+
+    /// Returns the [JA4] fingerprint of the TLS ClientHello this client presents.
+    ///
+    /// See [`Client::ja3`] for how the ClientHello is obtained.
+    ///
+    /// [JA4]: https://github.com/FoxIO-LLC/ja4
+    pub fn ja4(&self) -> crate::Result<String> {
+        self.client_hello().map(|hello| hello.ja4())
+    }
+
+    /// Returns the JA3, JA4 and HTTP/2 Akamai fingerprints of the connections
+    /// this client opens.
+    ///
+    /// Like [`Client::ja3`], the TLS ClientHello and the opening HTTP/2 frames
+    /// are generated from the client's configuration without connecting
+    /// anywhere, so this shows what an emulation actually sends. The HTTP/2
+    /// frames are those of a `GET` request with the client's HTTP/2 options.
+    ///
+    /// To see what was negotiated with a server, enable
+    /// [`ClientBuilder::tls_info`] and use [`Response::tls_info`].
+    pub fn tls_fingerprint(&self) -> crate::Result<TlsFingerprint> {
+        let hello = self.client_hello()?;
+        let http2 = match self.http2_options {
+            Some(ref opts) => {
+                let written = client_preface(Http2Options::clone(opts)).map_err(Error::builder)?;
+                let akamai = fingerprint::akamai(&written)
+                    .ok_or_else(|| Error::builder("malformed HTTP/2 connection preface"))?;
+                Some(akamai)
+            }
+            None => None,
+        };
+        Ok(TlsFingerprint::new(hello.ja3(), hello.ja4(), http2))
+    }
+
+    /// Closes the pooled connections to `host`, returning how many were closed.
+    ///
+    /// Idle connections to `host` are closed right away and counted. Connections
+    /// to it in use by a request are left to complete it, but are not reused
+    /// afterwards, so the next request to `host` opens a new connection.
+    ///
+    /// This pairs with [`DnsCache::remove`](crate::dns::DnsCache::remove) when a
+    /// host is known to have moved: purge its cached addresses and its
+    /// connections, and the next request resolves and connects afresh.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::new();
+    /// let closed = client.close_connections("example.com");
+    /// assert_eq!(closed, 0);
+    /// ```
+    pub fn close_connections(&self, host: &str) -> usize {
+        self.pool.close_connections(host)
+    }
+
+    /// Returns the cache of this client's built-in DNS resolvers.
+    ///
+    /// This is the cache chosen with [`ClientBuilder::dns_cache`]. A resolver set
+    /// with [`ClientBuilder::dns_resolver`] manages its own caching, and only uses
+    /// it if wrapped in a [`CachingResolver`](crate::dns::CachingResolver) given
+    /// the same cache.
+    pub fn dns_cache(&self) -> &DnsCache {
+        &self.dns_cache
+    }
+
+    /// Returns a snapshot of the counters of this client's DNS cache.
+    ///
+    /// Shorthand for `client.dns_cache().stats()`, to monitor the hit rate when
+    /// tuning the TTL and size of the cache.
+    pub fn dns_cache_stats(&self) -> DnsCacheStats {
+        self.dns_cache.stats()
+    }
+
+    /// Returns a snapshot of this client's connection pool.
+    ///
+    /// For each host, it counts the idle connections and those in use, the
+    /// connections established and reused since the client was built, and the
+    /// time their handshakes took. See [`ClientBuilder::on_pool_event`] to be told
+    /// of each connection instead.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// Writes the cookies of this client's cookie store to the file at `path`.
+    ///
+    /// The format is told by the extension of `path`, as for
+    /// [`FileCookieStore`](crate::cookie::FileCookieStore), which can load the file
+    /// again. The file is replaced atomically.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the client has no cookie store, or the file cannot be
+    /// written. A custom [`CookieStore`](crate::cookie::CookieStore) only has
+    /// cookies to save if it implements `all_cookies`.
+    #[cfg(feature = "cookies")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookies")))]
+    pub fn save_cookies<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let store = self
+            .cookie_store
+            .as_ref()
+            .ok_or_else(|| std::io::Error::other("client has no cookie store"))?;
+        let path = path.as_ref();
+        cookie::save_cookies(
+            &store.all_cookies(),
+            path,
+            cookie::CookieFileFormat::from_path(path),
+        )
+    }
+
+    fn client_hello(&self) -> crate::Result<ClientHello> {
+        // Any domain name will do, it only makes the ClientHello carry SNI
+        let records = self.tls.client_hello("example.com").map_err(Error::tls)?;
+        ClientHello::parse(&records).ok_or_else(|| Error::tls("malformed ClientHello"))
     }
 }
 
@@ -491,6 +798,23 @@ impl ClientBuilder {
             return Err(err);
         }
 
+        // A selector may pick a different proxy for each of two identical requests
+        let coalesce_requests = config.coalesce_requests && config.proxy_selector.is_none();
+
+        // Trust the added root certificates alongside the built-in ones
+        let cert_store = match (config.cert_store, config.root_certs.is_empty()) {
+            (cert_store, true) => cert_store.unwrap_or_default(),
+            (None, false) => CertStore::builder()
+                .add_built_in_roots()
+                .add_der_certs(config.root_certs)
+                .build()?,
+            (Some(_), false) => {
+                return Err(Error::builder(
+                    "root certificates cannot be added to a custom cert store",
+                ));
+            }
+        };
+
         // Prepare proxies
         let proxies = {
             if config.auto_sys_proxy {
@@ -499,18 +823,74 @@ impl ClientBuilder {
             Arc::new(config.proxies)
         };
 
+        // The cache of the built-in resolvers
+        let dns_cache = match (config.dns_cache, config.dns_negative_cache_ttl) {
+            (DnsCacheConfig::PerClient(builder), Some(ttl)) => builder.negative_ttl(ttl).build(),
+            (dns_cache, _) => dns_cache.into_cache(),
+        };
+
         // Create base client service
-        let service = {
+        let (service, http, tls, http2_options, pool) = {
             let (tls_options, http1_options, http2_options) = config.transport_options.into_parts();
 
+            // Keep the HTTP/2 options around for fingerprinting, unless HTTP/2 is
+            // never negotiated
+            let fingerprint_http2_options = match config.http_version_pref {
+                HttpVersionPref::Http1 => None,
+                _ => Some(Arc::new(http2_options.clone().unwrap_or_default())),
+            };
+
+            // Without an explicit IP version, only resolve the addresses sockets
+            // bound to the local address or interface of the client can connect to.
+            // The interface is looked up once, here.
+            let ip_version = config
+                .ip_version
+                .or_else(|| config.tcp_connect_options.ip_version());
+
+            // The hickory resolver of the client, if any, also looks up the ECH
+            // configurations of hosts
+            #[cfg(feature = "hickory-dns")]
+            let mut ech_resolver = None;
+
             let resolver = {
+                #[cfg(feature = "hickory-dns")]
+                let hickory = || {
+                    let resolver = HickoryDnsResolver::new().with_cache(dns_cache.clone());
+                    match ip_version {
+                        Some(version) => resolver.with_ip_version(version),
+                        None => resolver,
+                    }
+                };
                 let mut resolver: Arc<dyn Resolve> = match config.dns_resolver {
                     Some(dns_resolver) => dns_resolver,
                     #[cfg(feature = "hickory-dns")]
-                    None if config.hickory_dns => Arc::new(HickoryDnsResolver::new()),
-                    None => Arc::new(GaiResolver::new()),
+                    None if config.doh_url.is_some() => {
+                        let url = config.doh_url.take().expect("checked doh url");
+                        Arc::new(DohResolver::new(url)?.with_cache(dns_cache.clone()))
+                    }
+                    #[cfg(feature = "dns-over-tls")]
+                    None if config.dns_over_tls.is_some() => {
+                        let (addr, server_name) = config
+                            .dns_over_tls
+                            .take()
+                            .expect("checked dns-over-tls server");
+                        let resolver = hickory().with_dns_over_tls(addr, server_name);
+                        ech_resolver = Some(resolver.clone());
+                        Arc::new(resolver)
+                    }
+                    #[cfg(feature = "hickory-dns")]
+                    None if config.hickory_dns => {
+                        let resolver = hickory();
+                        ech_resolver = Some(resolver.clone());
+                        Arc::new(resolver)
+                    }
+                    None => Arc::new(GaiResolver::new().with_cache(dns_cache.clone())),
                 };
 
+                if let Some(version) = ip_version {
+                    resolver = Arc::new(IpVersionResolver::new(resolver, version));
+                }
+
                 if !config.dns_overrides.is_empty() {
                     resolver = Arc::new(DnsResolverWithOverrides::new(
                         resolver,
@@ -521,6 +901,7 @@ impl ClientBuilder {
             };
 
             // Configured http connector options
+            let connector_dns_cache = dns_cache.clone();
             let http = |http: &mut HttpConnector| {
                 http.enforce_http(false);
                 http.set_keepalive(config.tcp_keepalive);
@@ -533,6 +914,9 @@ impl ClientBuilder {
                 http.set_send_buffer_size(config.tcp_send_buffer_size);
                 http.set_recv_buffer_size(config.tcp_recv_buffer_size);
                 http.set_happy_eyeballs_timeout(config.tcp_happy_eyeballs_timeout);
+                http.set_allowed_ports(config.allowed_ports);
+                http.set_denied_ip_ranges(config.denied_ip_ranges);
+                http.set_dns_cache(connector_dns_cache);
                 #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
                 http.set_tcp_user_timeout(config.tcp_user_timeout);
             };
@@ -544,29 +928,43 @@ impl ClientBuilder {
                     HttpVersionPref::Http2 => Some(AlpnProtocol::HTTP2),
                     _ => None,
                 };
+                #[cfg(feature = "hickory-dns")]
+                let tls = tls.ech_resolver(ech_resolver);
                 tls.alpn_protocol(alpn_protocol)
                     .max_version(config.max_tls_version)
                     .min_version(config.min_tls_version)
+                    .permute_extensions(config.shuffle_tls_extensions)
                     .tls_sni(config.tls_sni)
+                    .ech(config.ech)
+                    .post_quantum(config.post_quantum)
                     .verify_hostname(config.verify_hostname)
                     .cert_verification(config.cert_verification)
-                    .cert_store(config.cert_store)
+                    .cert_store(cert_store)
+                    .cert_pins(config.cert_pins)
                     .identity(config.identity)
                     .keylog(config.keylog)
             };
 
             // Build connector
             let connector = Connector::builder(proxies.clone(), resolver)
+                .local_transport(config.local_transport)
                 .timeout(config.connect_timeout)
+                .max_concurrent_connects(config.max_concurrent_connects)
+                .h2_tunnels(TokioExecutor::new(), config.pool_idle_timeout)
                 .tls_info(config.tls_info)
                 .tls_options(tls_options)
                 .verbose(config.connection_verbose)
                 .with_tls(tls)
-                .with_http(http)
-                .build(config.connector_layers)?;
+                .with_http(http);
+
+            // Keep the configured http and tls connectors around for connectivity
+            // checks and fingerprinting
+            let http = connector.http().clone();
+            let tls = connector.tls()?;
+            let connector = connector.build(tls.clone(), config.connector_layers)?;
 
             // Build client
-            HttpClient::builder(TokioExecutor::new())
+            let service = HttpClient::builder(TokioExecutor::new())
                 .http1_options(http1_options)
                 .http2_options(http2_options)
                 .http2_only(matches!(config.http_version_pref, HttpVersionPref::Http2))
@@ -575,10 +973,31 @@ impl ClientBuilder {
                 .pool_idle_timeout(config.pool_idle_timeout)
                 .pool_max_idle_per_host(config.pool_max_idle_per_host)
                 .pool_max_size(config.pool_max_size)
-                .build(connector)
-                .map_err(Into::into as _)
+                .pool_event_callback(config.pool_event_callback)
+                .max_connections_per_host(config.max_connections_per_host)
+                .max_total_connections(config.max_total_connections)
+                .check_connection_on_reuse(config.check_connection_on_reuse)
+                .build(connector);
+
+            // Keep a handle to the connection pool for closing connections on demand
+            let pool = service.connection_pool();
+            let service = service.map_err(Into::into as _);
+
+            (service, http, tls, fingerprint_http2_options, pool)
         };
 
+        #[cfg(feature = "cookies")]
+        let cookie_store = config.cookie_store.clone();
+
+        // The cache sees requests before the client service adds its default headers
+        // and cookies, which it must account for
+        let http_cache = config.http_cache.take().map(|cache| {
+            let http_cache = HttpCache::new(cache, config.headers.clone());
+            #[cfg(feature = "cookies")]
+            let http_cache = http_cache.with_cookie_store(config.cookie_store.clone());
+            Arc::new(http_cache)
+        });
+
         // Configured client service with layers
         let client = {
             #[cfg(feature = "cookies")]
@@ -602,13 +1021,15 @@ impl ClientBuilder {
                     config.headers,
                     config.orig_headers,
                     proxies,
+                    config.proxy_selector,
                 ))
                 .layer(RetryLayer::new(RetryPolicy::new(config.retry_policy)))
                 .layer({
                     let policy = FollowRedirectPolicy::new(config.redirect_policy)
                         .with_referer(config.referer)
                         .with_https_only(config.https_only)
-                        .with_history(config.redirect_history);
+                        .with_history(config.redirect_history)
+                        .with_max_time(config.redirect_time);
                     FollowRedirectLayer::with_policy(policy)
                 })
                 .layer(ResponseBodyTimeoutLayer::new(config.timeout_options))
@@ -639,6 +1060,17 @@ impl ClientBuilder {
 
         Ok(Client {
             inner: Arc::new(client),
+            http,
+            tls,
+            http2_options,
+            pool,
+            dns_cache,
+            coalescer: coalesce_requests.then(|| Arc::new(Coalescer::default())),
+            http_cache,
+            middleware: config.middleware.into(),
+            base64_encode: config.base64_encode,
+            #[cfg(feature = "cookies")]
+            cookie_store,
         })
     }
 
@@ -674,6 +1106,52 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the `Accept-Language` header from a list of locales and their q-values.
+    ///
+    /// Locales are sent in the given order, with their q-value unless it is `1.0`:
+    /// `[("en-US", 1.0), ("en", 0.9)]` becomes `en-US,en;q=0.9`. Q-values must lie
+    /// within `0.0..=1.0`, and are rounded to three decimals. An empty list removes
+    /// the header.
+    ///
+    /// Like [`user_agent`](ClientBuilder::user_agent), this replaces a header set
+    /// by an earlier emulation, and is overridden by a header set on the request.
+    pub fn accept_language(mut self, locales: &[(&str, f32)]) -> ClientBuilder {
+        let mut value = String::new();
+        for &(locale, q) in locales {
+            if !(0.0..=1.0).contains(&q) {
+                self.config.error = Some(Error::builder(format!(
+                    "q-value {q} of locale {locale:?} is not within 0.0..=1.0"
+                )));
+                return self;
+            }
+
+            if !value.is_empty() {
+                value.push(',');
+            }
+            value.push_str(locale);
+            if q < 1.0 {
+                let q = format!("{q:.3}");
+                value.push_str(";q=");
+                value.push_str(q.trim_end_matches('0').trim_end_matches('.'));
+            }
+        }
+
+        if value.is_empty() {
+            self.config.headers.remove(ACCEPT_LANGUAGE);
+            return self;
+        }
+
+        match HeaderValue::try_from(value) {
+            Ok(value) => {
+                self.config.headers.insert(ACCEPT_LANGUAGE, value);
+            }
+            Err(err) => {
+                self.config.error = Some(Error::builder(err));
+            }
+        }
+        self
+    }
+
     /// Sets the default headers for every request.
     ///
     /// # Example
@@ -949,6 +1427,31 @@ impl ClientBuilder {
         self
     }
 
+    /// Follow at most `max` redirects in a chain.
+    ///
+    /// This is a shorthand for [`redirect::Policy::limited`]. Exceeding the limit
+    /// fails the request with a redirect error reporting too many redirects.
+    #[inline]
+    pub fn max_redirects(self, max: usize) -> ClientBuilder {
+        self.redirect(redirect::Policy::limited(max))
+    }
+
+    /// Limit the cumulative time spent following a redirect chain.
+    ///
+    /// The time is counted from the first redirect response, and checked each
+    /// time a redirect is about to be followed: a chain still being followed past
+    /// `max_time` fails the request with a redirect error reporting the exceeded
+    /// time budget, distinct from the one of [`ClientBuilder::max_redirects`]. This
+    /// bounds chains of many slow hops; use [`ClientBuilder::timeout`] to bound a
+    /// single hop.
+    ///
+    /// Default is no limit.
+    #[inline]
+    pub fn max_redirect_time(mut self, max_time: Duration) -> ClientBuilder {
+        self.config.redirect_time = Some(max_time);
+        self
+    }
+
     /// Enable or disable redirect history tracking.
     ///
     /// Default is `false`.
@@ -1011,6 +1514,55 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets a [`ProxySelector`] picking the proxy of each request.
+    ///
+    /// The selector is asked for every request without its own
+    /// [`RequestBuilder::proxy`](crate::RequestBuilder::proxy). When it
+    /// returns `None`, the proxies added with [`ClientBuilder::proxy`] are
+    /// used. Connections through each proxy are pooled apart, so rotating
+    /// through a pool of proxies keeps reusing their connections.
+    #[inline]
+    pub fn proxy_selector<S: ProxySelector>(mut self, selector: S) -> ClientBuilder {
+        self.config.proxy_selector = Some(Arc::new(selector));
+        self
+    }
+
+    /// Send every request over the Unix domain socket at `path`, whatever the
+    /// host of its URL.
+    ///
+    /// This lets a normal `http://localhost/...` URL reach a local daemon such as
+    /// Docker or systemd. The socket takes precedence over any proxy, whether added
+    /// with [`ClientBuilder::proxy`], picked by a [`ProxySelector`] or set on the
+    /// request, and [`ClientBuilder::no_proxy`] leaves it in place. HTTPS requests
+    /// are tunneled over the socket with `CONNECT`.
+    ///
+    /// # Example
+    /// ```
+    /// # fn doc() -> Result<(), wreq::Error> {
+    /// let client = wreq::Client::builder()
+    ///     .unix_socket("/var/run/docker.sock")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn unix_socket<P: IntoUnixSocket>(mut self, path: P) -> ClientBuilder {
+        self.config.local_transport = Some(Intercepted::Unix(path.unix_socket()));
+        self
+    }
+
+    /// Send every request over the Windows named pipe `pipe`, such as
+    /// `\\.\pipe\docker_engine`, whatever the host of its URL.
+    ///
+    /// This is the Windows equivalent of [`unix_socket`](ClientBuilder::unix_socket).
+    /// While every instance of the pipe is busy, opening it is retried for up to
+    /// 5 seconds.
+    #[cfg(windows)]
+    pub fn named_pipe<P: AsRef<std::ffi::OsStr>>(mut self, pipe: P) -> ClientBuilder {
+        self.config.local_transport = Some(Intercepted::NamedPipe(Arc::from(pipe.as_ref())));
+        self
+    }
+
     // Timeout options
 
     /// Enables a request timeout.
@@ -1048,6 +1600,21 @@ impl ClientBuilder {
         self
     }
 
+    /// Limit the number of connections being established at once, across all hosts.
+    ///
+    /// Once `max` connects (including DNS resolution, proxy tunneling and the TLS
+    /// handshake) are in progress, requests needing a new connection wait for one
+    /// of them to finish. Requests that reuse a pooled connection are not limited.
+    /// This keeps a large fan-out from exhausting file descriptors and CPU on
+    /// simultaneous handshakes.
+    ///
+    /// Default is no limit.
+    #[inline]
+    pub fn max_concurrent_connects(mut self, max: usize) -> ClientBuilder {
+        self.config.max_concurrent_connects = Some(max);
+        self
+    }
+
     /// Set whether connections should emit verbose logs.
     ///
     /// Enabling this option will emit [log][] messages at the `TRACE` level
@@ -1064,7 +1631,10 @@ impl ClientBuilder {
 
     /// Set an optional timeout for idle sockets being kept-alive.
     ///
-    /// Pass `None` to disable timeout.
+    /// Idle connections are closed by a background task once the timeout elapses.
+    ///
+    /// Pass `None` to disable timeout, or `Duration::ZERO` to disable pooling
+    /// entirely so that each connection is closed after its request.
     ///
     /// Default is 90 seconds.
     #[inline]
@@ -1090,6 +1660,84 @@ impl ClientBuilder {
         self
     }
 
+    /// Limit the number of connections open to each host.
+    ///
+    /// Idle connections in the pool count toward the limit. Once `max`
+    /// connections to a host are open, requests to it wait for one of them to
+    /// be returned to the pool or closed, in the order they started waiting.
+    /// Idle connections a waiting request can't reuse, such as ones made
+    /// through another proxy, are closed to make room. This keeps a client
+    /// from tripping the per-IP connection limits of a site.
+    ///
+    /// A `max` of 0 is treated as 1. Default is no limit.
+    #[inline]
+    pub fn max_connections_per_host(mut self, max: usize) -> ClientBuilder {
+        self.config.max_connections_per_host = Some(max);
+        self
+    }
+
+    /// Limit the number of connections open at once, across all hosts.
+    ///
+    /// Idle connections in the pool count toward the limit. Once `max`
+    /// connections are open, the idle connections to the least recently used
+    /// host are closed to make room, or requests wait for a connection to be
+    /// closed or become idle, in the order they started waiting. This bounds the file
+    /// descriptors used by the client.
+    ///
+    /// A `max` of 0 is treated as 1. Default is no limit.
+    #[inline]
+    pub fn max_total_connections(mut self, max: usize) -> ClientBuilder {
+        self.config.max_total_connections = Some(max);
+        self
+    }
+
+    /// Sets a callback receiving the events of the connection pool.
+    ///
+    /// The callback is told of each connection established, with the time its
+    /// handshakes took, and of each pooled connection reused, to export them to
+    /// a metrics system. It runs on the task making the request, so it should
+    /// return quickly. [`Client::pool_stats`] returns the totals.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wreq::PoolEvent;
+    ///
+    /// let client = wreq::Client::builder()
+    ///     .on_pool_event(|event| match event {
+    ///         PoolEvent::Connected {
+    ///             host, handshake, ..
+    ///         } => println!("connected to {host} in {handshake:?}"),
+    ///         PoolEvent::Reused { host, .. } => println!("reused a connection to {host}"),
+    ///         _ => {}
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn on_pool_event<F>(mut self, callback: F) -> ClientBuilder
+    where
+        F: Fn(&PoolEvent) + Send + Sync + 'static,
+    {
+        self.config.pool_event_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Checks that an idle connection from the pool is still alive before reusing it.
+    ///
+    /// For HTTP/1, the connection is read without blocking, to notice if the server
+    /// closed it while idle. For HTTP/2, a PING is sent and its PONG awaited. If the
+    /// check fails, or takes longer than a second, the connection is discarded and
+    /// the request goes out on another one.
+    ///
+    /// This costs a round trip for every reused HTTP/2 connection.
+    ///
+    /// Defaults to false.
+    #[inline]
+    pub fn check_connection_on_reuse(mut self, enabled: bool) -> ClientBuilder {
+        self.config.check_connection_on_reuse = enabled;
+        self
+    }
+
     /// Restrict the Client to be used with HTTPS only requests.
     ///
     /// Defaults to false.
@@ -1099,6 +1747,73 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the base64 engine used to encode [`RequestBuilder::basic_auth`] credentials,
+    /// including those taken from the userinfo of a request URI.
+    ///
+    /// Defaults to the standard alphabet with padding, as encoded by the TLS backend.
+    /// Credentials set with [`Proxy::basic_auth`](crate::Proxy::basic_auth) are always
+    /// encoded with the default engine.
+    #[inline]
+    pub fn basic_auth_encoder(mut self, encode: fn(&[u8]) -> String) -> ClientBuilder {
+        self.config.base64_encode = encode;
+        self
+    }
+
+    /// Shares one upstream request between identical GET requests in flight at the same time.
+    ///
+    /// While a GET request without a body is in flight, further requests for the same
+    /// URI with the same headers wait for it instead of going upstream, and receive
+    /// a copy of its response. To share it, the response body is read into memory
+    /// before the response is returned, when other requests are waiting for it. A
+    /// body larger than 1 MiB is not shared: it is streamed to the request that went
+    /// upstream, and the waiting requests go upstream themselves. If the upstream
+    /// request fails, the waiting requests fail with a copy of its error.
+    ///
+    /// Requests carrying per-request options that may change the response, such as
+    /// a proxy, an emulation, a local address, a fixed remote address, an HTTP
+    /// version or a cookie jar, are never coalesced, nor are requests or responses
+    /// with `Cache-Control: no-store`. A timeout or other options that do not change
+    /// the response are not compared. Coalescing is disabled when a
+    /// [`ClientBuilder::proxy_selector`] is set.
+    ///
+    /// Defaults to false.
+    #[inline]
+    pub fn coalesce_requests(mut self, enabled: bool) -> ClientBuilder {
+        self.config.coalesce_requests = enabled;
+        self
+    }
+
+    /// Keep responses to GET requests in an HTTP cache, following [RFC 9111].
+    ///
+    /// While a stored response is fresh, as told by its `Cache-Control: max-age`
+    /// or `Expires` header, requests for the same URL are answered from the cache
+    /// without going upstream. Once stale, a response with an `ETag` or
+    /// `Last-Modified` validator is revalidated with `If-None-Match` and
+    /// `If-Modified-Since`, and a `304 Not Modified` answer serves the stored body.
+    /// Responses marked `no-store` are never stored, `no-cache` ones are always
+    /// revalidated, and the request headers named by `Vary`, including the default
+    /// headers and cookies the client adds, must match. A response to a request
+    /// with an `Authorization` header is only stored when marked `public`,
+    /// `s-maxage` or `must-revalidate`. A successful POST, PUT, PATCH or DELETE
+    /// request removes the response stored for its URL.
+    ///
+    /// Stored responses have their body read into memory before they are returned,
+    /// up to the size set with [`CacheConfig::max_body_size`]. Responses reached
+    /// through a redirect, partial responses and requests with a `Range` header
+    /// bypass the cache.
+    ///
+    /// Keep responses in memory with [`CacheConfig::memory`], on disk with
+    /// [`CacheConfig::disk`], or anywhere else with a
+    /// [`CacheStorage`](crate::cache::CacheStorage) of your own.
+    ///
+    /// Default is no cache.
+    ///
+    /// [RFC 9111]: https://www.rfc-editor.org/rfc/rfc9111
+    pub fn http_cache(mut self, config: CacheConfig) -> ClientBuilder {
+        self.config.http_cache = Some(config);
+        self
+    }
+
     /// Only use HTTP/1.
     #[inline]
     pub fn http1_only(mut self) -> ClientBuilder {
@@ -1238,7 +1953,8 @@ impl ClientBuilder {
     /// If hostname resolves to both IPv4 and IPv6 addresses and connection
     /// cannot be established using preferred address family before timeout
     /// elapses, then connector will in parallel attempt connection using other
-    /// address family.
+    /// address family. The losing attempt is cancelled, and the family that won
+    /// is tried first on the next connection to the same host.
     ///
     /// If `None`, parallel connection attempts are disabled.
     ///
@@ -1256,6 +1972,9 @@ impl ClientBuilder {
 
     /// Bind to a local IP Address.
     ///
+    /// Unless [`ip_version`](ClientBuilder::ip_version) is set, only addresses of
+    /// the IP version of `addr` are then resolved and connected to.
+    ///
     /// # Example
     ///
     /// ```
@@ -1280,6 +1999,9 @@ impl ClientBuilder {
     /// Set that all sockets are bound to the configured IPv4 or IPv6 address (depending on host's
     /// preferences) before connection.
     ///
+    /// When only one of them is set and no [`ip_version`](ClientBuilder::ip_version) is,
+    /// only addresses of its IP version are resolved and connected to.
+    ///
     ///  # Example
     /// ///
     /// ```
@@ -1321,6 +2043,11 @@ impl ClientBuilder {
     /// Note that connections will fail if the provided interface name is not a
     /// network interface that currently exists when a connection is established.
     ///
+    /// Unless [`ip_version`](ClientBuilder::ip_version) is set, an interface that
+    /// only has IPv4 or only has IPv6 addresses when the client is built limits
+    /// resolving and connecting to that IP version, so no AAAA records are looked
+    /// up for a v4-only interface. This is not done on Fuchsia.
+    ///
     /// # Example
     ///
     /// ```
@@ -1374,6 +2101,10 @@ impl ClientBuilder {
     // TLS options
 
     /// Sets the identity to be used for client certificate authentication.
+    ///
+    /// The certificate is only sent when the server asks for one, after the
+    /// ClientHello, so mutual TLS keeps the fingerprint of the emulation in use.
+    /// See [`Identity`] for the PEM, DER and PKCS #12 formats it is read from.
     #[inline]
     pub fn identity(mut self, identity: Identity) -> ClientBuilder {
         self.config.identity = Some(identity);
@@ -1384,9 +2115,75 @@ impl ClientBuilder {
     ///
     /// This method allows you to specify a custom verify certificate store to be used
     /// for TLS connections. By default, the system's verify certificate store is used.
+    ///
+    /// It cannot be combined with [`add_root_certificate`](ClientBuilder::add_root_certificate),
+    /// add the certificates to the store instead.
     #[inline]
     pub fn cert_store(mut self, store: CertStore) -> ClientBuilder {
-        self.config.cert_store = store;
+        self.config.cert_store = Some(store);
+        self
+    }
+
+    /// Trusts `cert` as a root certificate, in addition to the built-in roots.
+    ///
+    /// This allows talking to services with certificates issued by a private
+    /// CA, such as corporate-internal ones, without losing the trust of public
+    /// sites.
+    ///
+    /// # Errors
+    ///
+    /// Building the client fails if a cert store is also set with
+    /// [`cert_store`](ClientBuilder::cert_store).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let pem = std::fs::read("internal-ca.pem")?;
+    /// let client = wreq::Client::builder()
+    ///     .add_root_certificate(wreq::tls::Certificate::from_pem(&pem)?)
+    ///     .build()?;
+    /// # drop(client);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn add_root_certificate(mut self, cert: Certificate) -> ClientBuilder {
+        self.config.root_certs.push(cert);
+        self
+    }
+
+    /// Pins the public keys the certificates of `host` must use.
+    ///
+    /// After the TLS handshake with `host`, the leaf certificate and then each
+    /// intermediate that signed the certificate before it is checked, and the
+    /// connection is refused unless one of their public keys matches a pin,
+    /// with an error for which [`Error::is_cert_pin_mismatch`] returns true.
+    /// This happens in addition to the certificate validation, and does not
+    /// change the handshake itself, so it works alongside any emulation.
+    ///
+    /// `host` may start with `*.` to match any single label. Calling this again
+    /// for the same host adds to its pins, so a backup key can be pinned ahead
+    /// of a rotation.
+    ///
+    /// [`Error::is_cert_pin_mismatch`]: crate::Error::is_cert_pin_mismatch
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wreq::tls::Sha256Pin;
+    ///
+    /// let pin: Sha256Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+    ///     .parse()
+    ///     .unwrap();
+    /// let client = wreq::Client::builder()
+    ///     .cert_pin("example.com", &[pin])
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[inline]
+    pub fn cert_pin(mut self, host: &str, pins: &[Sha256Pin]) -> ClientBuilder {
+        self.config.cert_pins.add(host, pins);
         self
     }
 
@@ -1430,6 +2227,43 @@ impl ClientBuilder {
         self
     }
 
+    /// Configures [Encrypted Client Hello][ech] (ECH).
+    ///
+    /// With [`EchMode::Enable`], the ClientHello is encrypted for hosts that
+    /// publish an ECH configuration in their DNS HTTPS record, which is looked
+    /// up with the client's hickory resolver of the `hickory-dns` feature.
+    /// Other hosts get ECH GREASE. With another resolver and for requests
+    /// through a proxy, only the configurations sent by servers are used.
+    ///
+    /// This takes precedence over the ECH GREASE setting of the TLS options and
+    /// emulation. By default, the TLS options decide.
+    ///
+    /// [ech]: https://datatracker.ietf.org/doc/draft-ietf-tls-esni/
+    #[inline]
+    pub fn ech(mut self, mode: EchMode) -> ClientBuilder {
+        self.config.ech = Some(mode);
+        self
+    }
+
+    /// Configures whether the ClientHello offers a hybrid post-quantum key
+    /// share.
+    ///
+    /// When enabled, `X25519MLKEM768` is added in front of the curves list of
+    /// the TLS options, as Chrome 131 and later send it. Some servers and
+    /// middleboxes fail on the larger ClientHello: when the connection is
+    /// reset or closed before the server sends its certificate, the handshake
+    /// is retried without the post-quantum key share, which is then left out
+    /// for that host for the next 30 minutes. A TLS alert from the server
+    /// does not trigger the fallback.
+    ///
+    /// When disabled, post-quantum groups are removed from the curves list.
+    /// By default, the curves list of the TLS options is used as is.
+    #[inline]
+    pub fn post_quantum_key_share(mut self, enabled: bool) -> ClientBuilder {
+        self.config.post_quantum = Some(enabled);
+        self
+    }
+
     /// Configures TLS key logging for the client.
     #[inline]
     pub fn keylog(mut self, keylog: KeyLog) -> ClientBuilder {
@@ -1455,6 +2289,18 @@ impl ClientBuilder {
         self
     }
 
+    /// Randomize the order of TLS ClientHello extensions on every handshake.
+    ///
+    /// This matches recent Chrome, which shuffles its extensions per connection.
+    /// GREASE extensions and the pre-shared key extension keep their required
+    /// positions. Overrides the `permute_extensions` setting of the TLS options
+    /// in use, including those set by an emulation.
+    #[inline]
+    pub fn shuffle_tls_extensions(mut self, enabled: bool) -> ClientBuilder {
+        self.config.shuffle_tls_extensions = Some(enabled);
+        self
+    }
+
     /// Add TLS information as `TlsInfo` extension to responses.
     ///
     /// # Optional
@@ -1490,6 +2336,11 @@ impl ClientBuilder {
 
     /// Override DNS resolution for specific domains to a particular IP address.
     ///
+    /// Like curl's `--resolve`, the override is consulted before the DNS cache and
+    /// the resolver, while the URL is left untouched: the `Host` header and TLS SNI
+    /// still carry `domain`, which makes it handy for testing against a staging
+    /// address. Domains match regardless of case.
+    ///
     /// Warning
     ///
     /// Since the DNS protocol has no notion of ports, if you wish to send
@@ -1512,6 +2363,10 @@ impl ClientBuilder {
     /// traffic to a particular port you must include this port in the URI
     /// itself, any port in the overridden addresses will be ignored and traffic sent
     /// to the conventional port for the given scheme (e.g. 80 for http).
+    ///
+    /// See [`resolve`](ClientBuilder::resolve) for how overrides apply, and
+    /// [`resolve_to_addr_slice`](ClientBuilder::resolve_to_addr_slice) to pass a
+    /// `&[SocketAddr]`.
     #[inline]
     pub fn resolve_to_addrs<D, A>(mut self, domain: D, addrs: A) -> ClientBuilder
     where
@@ -1524,11 +2379,57 @@ impl ClientBuilder {
         self
     }
 
+    /// Override DNS resolution for specific domains to the IP addresses of a slice.
+    ///
+    /// Same as [`resolve_to_addrs`](ClientBuilder::resolve_to_addrs), for addresses
+    /// that are borrowed.
+    #[inline]
+    pub fn resolve_to_addr_slice<D>(self, domain: D, addrs: &[SocketAddr]) -> ClientBuilder
+    where
+        D: Into<Cow<'static, str>>,
+    {
+        self.resolve_to_addrs(domain, addrs.iter().copied())
+    }
+
     /// Override the DNS resolver implementation.
     ///
-    /// Pass any type implementing `IntoResolve`.
-    /// Overrides for specific names passed to `resolve` and `resolve_to_addrs` will
-    /// still be applied on top of this resolver.
+    /// Pass any type implementing `IntoResolve`, such as a resolver backed by
+    /// service discovery. Overrides for specific names passed to `resolve` and
+    /// `resolve_to_addrs` will still be applied on top of this resolver.
+    ///
+    /// A custom resolver does its own caching. Wrap it in a
+    /// [`CachingResolver`](crate::dns::CachingResolver) to cache its answers in a
+    /// [`DnsCache`], such as one shared with
+    /// [`dns_cache`](ClientBuilder::dns_cache).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::SocketAddr;
+    ///
+    /// use wreq::dns::{
+    ///     Addrs, CachingResolver, DnsCache, DnsCacheConfig, Name, Resolve, Resolving,
+    /// };
+    ///
+    /// // Resolves every service to the address found in a registry
+    /// struct Registry;
+    ///
+    /// impl Resolve for Registry {
+    ///     fn resolve(&self, _name: Name) -> Resolving {
+    ///         let addr = SocketAddr::from(([10, 0, 0, 1], 0));
+    ///         let addrs: Addrs = Box::new(std::iter::once(addr));
+    ///         Box::pin(std::future::ready(Ok(addrs)))
+    ///     }
+    /// }
+    ///
+    /// let cache = DnsCache::new();
+    /// let client = wreq::Client::builder()
+    ///     .dns_resolver(CachingResolver::new(Registry).with_cache(cache.clone()))
+    ///     .dns_cache(DnsCacheConfig::Shared(cache))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(client.dns_cache().is_empty());
+    /// ```
     #[inline]
     pub fn dns_resolver<R>(mut self, resolver: R) -> ClientBuilder
     where
@@ -1538,6 +2439,192 @@ impl ClientBuilder {
         self
     }
 
+    /// Choose the cache of the built-in DNS resolvers.
+    ///
+    /// By default each client has a cache of its own, so that clients resolving
+    /// differently, such as through other DNS-over-HTTPS endpoints or name
+    /// servers, never see each other's answers. Use [`DnsCacheConfig::Shared`] to
+    /// share a cache between chosen clients, or [`DnsCacheConfig::Global`] for the
+    /// process-wide cache. A resolver set with
+    /// [`dns_resolver`](ClientBuilder::dns_resolver) does not use this cache,
+    /// unless wrapped in a [`CachingResolver`](crate::dns::CachingResolver).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use wreq::dns::{DnsCache, DnsCacheConfig};
+    ///
+    /// let client = wreq::Client::builder()
+    ///     .dns_cache(DnsCacheConfig::PerClient(
+    ///         DnsCache::builder().default_ttl(Duration::from_secs(30)),
+    ///     ))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(client.dns_cache().is_empty());
+    /// ```
+    pub fn dns_cache(mut self, config: DnsCacheConfig) -> ClientBuilder {
+        self.config.dns_cache = config;
+        self
+    }
+
+    /// Restrict or order the addresses connected to by IP version.
+    ///
+    /// [`IpVersion::V4Only`] lets clients behind a broken IPv6 network connect over
+    /// IPv4 without a custom resolver, while [`IpVersion::Prefer4`] and
+    /// [`IpVersion::Prefer6`] only change which addresses are tried first. The
+    /// hickory resolver then only queries the records needed, and addresses of an
+    /// excluded version are dropped from every answer, including cached ones and
+    /// those of a resolver set with [`dns_resolver`](ClientBuilder::dns_resolver).
+    /// Overrides set with [`resolve`](ClientBuilder::resolve) are used as given.
+    ///
+    /// Default is to connect over both, in the order the resolver returns, or only
+    /// over the IP version of the [`local_address`](ClientBuilder::local_address)
+    /// or [`interface`](ClientBuilder::interface) connections are bound to, when it
+    /// has addresses of a single version. A local address or interface set on a
+    /// single request does not change what is resolved.
+    pub fn ip_version(mut self, version: IpVersion) -> ClientBuilder {
+        self.config.ip_version = Some(version);
+        self
+    }
+
+    /// Cache negative DNS answers for `ttl`.
+    ///
+    /// When the name servers answer that a host does not exist (NXDOMAIN) or has
+    /// no addresses, requests to it fail right away with the same error for `ttl`
+    /// instead of each querying the name servers again. Transient failures, such
+    /// as timeouts or SERVFAIL, are not cached. Keep it shorter than the TTL of
+    /// answers, so that a recovered host is retried soon. Negative answers are
+    /// cached by the built-in resolvers, the hickory, `getaddrinfo` and
+    /// DNS-over-HTTPS ones, in the cache chosen with
+    /// [`dns_cache`](ClientBuilder::dns_cache). A resolver set with
+    /// [`dns_resolver`](ClientBuilder::dns_resolver) caches as it sees fit.
+    ///
+    /// This sets the negative TTL of the client's own cache. A cache shared
+    /// through [`DnsCacheConfig::Shared`] or [`DnsCacheConfig::Global`] keeps the
+    /// one it was built with, see [`DnsCacheBuilder::negative_ttl`].
+    ///
+    /// Default is to not cache negative answers.
+    ///
+    /// [`DnsCacheBuilder::negative_ttl`]: crate::dns::DnsCacheBuilder::negative_ttl
+    pub fn dns_negative_cache_ttl(mut self, ttl: Duration) -> ClientBuilder {
+        self.config.dns_negative_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Resolve hosts over DNS-over-HTTPS, against the endpoint at `url`.
+    ///
+    /// Lookups are sent to an endpoint such as `https://1.1.1.1/dns-query`, for
+    /// environments where plaintext UDP DNS is filtered. See [`DohResolver`] for
+    /// how they are performed and cached.
+    ///
+    /// A resolver set with [`dns_resolver`](ClientBuilder::dns_resolver) takes
+    /// precedence over this one.
+    ///
+    /// [`DohResolver`]: crate::dns::DohResolver
+    #[cfg(feature = "hickory-dns")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hickory-dns")))]
+    pub fn doh_url<U: IntoUri>(mut self, url: U) -> ClientBuilder {
+        match url.into_uri() {
+            Ok(url) => self.config.doh_url = Some(url),
+            Err(err) => self.config.error = Some(err),
+        }
+        self
+    }
+
+    /// Resolve hosts over DNS-over-TLS, with the name server at `addr`.
+    ///
+    /// The name server's certificate is verified against `server_name`, for
+    /// instance `1.1.1.1:853` and `cloudflare-dns.com`. Lookups go through the
+    /// hickory-dns resolver and its cache, see
+    /// [`HickoryDnsResolver::with_dns_over_tls`].
+    ///
+    /// A resolver set with [`dns_resolver`](ClientBuilder::dns_resolver) or
+    /// [`doh_url`](ClientBuilder::doh_url) takes precedence over this one.
+    ///
+    /// [`HickoryDnsResolver::with_dns_over_tls`]: crate::dns::HickoryDnsResolver::with_dns_over_tls
+    #[cfg(feature = "dns-over-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-tls")))]
+    pub fn dns_over_tls<S>(mut self, addr: SocketAddr, server_name: S) -> ClientBuilder
+    where
+        S: Into<String>,
+    {
+        self.config.dns_over_tls = Some((addr, server_name.into()));
+        self
+    }
+
+    /// Restrict outgoing connections to the given remote ports.
+    ///
+    /// The port a connection would use is checked after DNS resolution, so this
+    /// also applies to resolver overrides and IP literals. Addresses on other ports
+    /// are skipped, and the request fails with a connect error if none remain.
+    /// Connections to proxies are checked the same way.
+    ///
+    /// Default is to allow any port.
+    #[inline]
+    pub fn allowed_ports<I>(mut self, ports: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        self.config.allowed_ports = Some(ports.into_iter().collect());
+        self
+    }
+
+    /// Refuse outgoing connections to the given IP ranges.
+    ///
+    /// Each range is a CIDR network such as `10.0.0.0/8` or `fe80::/10`, or a
+    /// single IP address. Like [`allowed_ports`](ClientBuilder::allowed_ports),
+    /// this is checked on every resolved address together with the port, so a
+    /// name that resolves into a denied range is not connected to. IPv4-mapped
+    /// IPv6 addresses are checked as the IPv4 address they map.
+    ///
+    /// Default is to allow any address.
+    ///
+    /// # Errors
+    ///
+    /// Building the client fails if a range can't be parsed.
+    pub fn denied_ip_ranges<I, S>(mut self, ranges: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let ranges = ranges
+            .into_iter()
+            .map(|range| {
+                let range = range.as_ref();
+                range
+                    .parse::<IpNet>()
+                    .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| Error::builder(format!("invalid IP range: {range}")))
+            })
+            .collect::<crate::Result<Arc<[IpNet]>>>();
+        match ranges {
+            Ok(ranges) => self.config.denied_ip_ranges = Some(ranges),
+            Err(err) => self.config.error = Some(err),
+        }
+        self
+    }
+
+    /// Adds a [`Middleware`] intercepting every request the client executes.
+    ///
+    /// Middleware runs in the order it is added: the first one sees the request
+    /// first and the response last. Each can change the request, answer it without
+    /// sending it, or change the response, see the [`middleware`](crate::middleware)
+    /// module. Requests that middleware sends with the client itself go through the
+    /// whole chain again.
+    ///
+    /// Middleware runs outside of everything else the client does, so a request it
+    /// passes on may still be retried, redirected or answered from the HTTP cache,
+    /// and the response it sees is the final one.
+    pub fn with<M>(mut self, middleware: M) -> ClientBuilder
+    where
+        M: Middleware + 'static,
+    {
+        self.config.middleware.push(Arc::new(middleware));
+        self
+    }
+
     // Tower middleware options
 
     /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
@@ -0,0 +1,88 @@
+use std::{
+    error::Error as StdError,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::connect::HttpConnector;
+use crate::error::{BoxError, TimedOut};
+
+/// The outcome of [`Client::check_connectivity`](super::Client::check_connectivity).
+///
+/// Timings are recorded for every step that was reached, so a failed probe still
+/// reports how long resolution took before the connect failed.
+#[derive(Debug)]
+pub struct ConnectivityReport {
+    dns_time: Option<Duration>,
+    connect_time: Option<Duration>,
+    addr: Option<SocketAddr>,
+    error: Option<BoxError>,
+}
+
+impl ConnectivityReport {
+    /// Returns true if the host was resolved and a TCP connection was established.
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns the time spent resolving the host, if resolution completed.
+    #[inline]
+    pub fn dns_time(&self) -> Option<Duration> {
+        self.dns_time
+    }
+
+    /// Returns the time spent connecting, if a connection was attempted.
+    #[inline]
+    pub fn connect_time(&self) -> Option<Duration> {
+        self.connect_time
+    }
+
+    /// Returns the address that was connected to.
+    #[inline]
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    /// Returns the error that made the probe fail.
+    #[inline]
+    pub fn error(&self) -> Option<&(dyn StdError + Send + Sync + 'static)> {
+        self.error.as_deref()
+    }
+}
+
+/// Resolves `host` and opens a TCP connection to it, within `timeout`.
+pub(super) async fn check_connectivity(
+    mut http: HttpConnector,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> ConnectivityReport {
+    let mut report = ConnectivityReport {
+        dns_time: None,
+        connect_time: None,
+        addr: None,
+        error: None,
+    };
+
+    let probe = async {
+        let start = Instant::now();
+        let addrs = http.resolve_host(host, port).await;
+        report.dns_time = Some(start.elapsed());
+
+        let start = Instant::now();
+        let tcp = http.connect_addrs(addrs?).await;
+        report.connect_time = Some(start.elapsed());
+
+        report.addr = tcp?.peer_addr().ok();
+        Ok::<_, BoxError>(())
+    };
+
+    let result = tokio::time::timeout(timeout, probe).await;
+    report.error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(err),
+        Err(_) => Some(TimedOut.into()),
+    };
+    report
+}
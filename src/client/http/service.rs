@@ -9,14 +9,14 @@ use tower::{Layer, Service};
 
 use crate::{
     Error,
-    client::layer::config::RequestDefaultHeaders,
+    client::layer::config::{RequestDefaultHeaders, RequestHeaderOrder},
     core::{
         client::options::RequestOptions,
         ext::{RequestConfig, RequestLayerOptions, RequestOrigHeaderMap},
     },
     ext::UriExt,
     header::OrigHeaderMap,
-    proxy::Matcher as ProxyMatcher,
+    proxy::{Matcher as ProxyMatcher, ProxySelector},
 };
 
 /// Configuration for the [`ConfigService`].
@@ -26,6 +26,7 @@ struct Config {
     orig_headers: RequestConfig<RequestOrigHeaderMap>,
     default_headers: RequestConfig<RequestDefaultHeaders>,
     proxies: Arc<Vec<ProxyMatcher>>,
+    proxy_selector: Option<Arc<dyn ProxySelector>>,
     proxies_maybe_http_auth: bool,
     proxies_maybe_http_custom_headers: bool,
 }
@@ -51,6 +52,7 @@ impl ConfigServiceLayer {
         headers: HeaderMap,
         orig_headers: OrigHeaderMap,
         proxies: Arc<Vec<ProxyMatcher>>,
+        proxy_selector: Option<Arc<dyn ProxySelector>>,
     ) -> Self {
         let org_headers = (!orig_headers.is_empty()).then_some(orig_headers);
         let proxies_maybe_http_auth = proxies.iter().any(ProxyMatcher::maybe_has_http_auth);
@@ -65,6 +67,7 @@ impl ConfigServiceLayer {
                 orig_headers: RequestConfig::new(org_headers),
                 default_headers: RequestConfig::new(Some(true)),
                 proxies,
+                proxy_selector,
                 proxies_maybe_http_auth,
                 proxies_maybe_http_custom_headers,
             }),
@@ -126,6 +129,31 @@ where
         // store the original headers in request extensions
         self.config.orig_headers.store(req.extensions_mut());
 
+        // move the headers of the request's header order to the front of the
+        // original headers, keeping the order and casing of all others
+        if let Some(order) = RequestConfig::<RequestHeaderOrder>::remove(req.extensions_mut()) {
+            RequestConfig::<RequestOrigHeaderMap>::get_mut(req.extensions_mut())
+                .get_or_insert_default()
+                .reorder(&order);
+        }
+
+        // let the proxy selector pick the proxy, unless the request sets one
+        let selected = match self.config.proxy_selector {
+            Some(ref selector)
+                if RequestConfig::<RequestLayerOptions>::get(req.extensions())
+                    .and_then(RequestOptions::proxy_matcher)
+                    .is_none() =>
+            {
+                selector.select(&uri)
+            }
+            _ => None,
+        };
+        if let Some(proxy) = selected {
+            *RequestConfig::<RequestLayerOptions>::get_mut(req.extensions_mut())
+                .get_or_insert_default()
+                .proxy_matcher_mut() = Some(proxy.into_matcher());
+        }
+
         // determine the proxy matcher to use
         let (http_auth_header, http_custom_headers) =
             RequestConfig::<RequestLayerOptions>::get(req.extensions())
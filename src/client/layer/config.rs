@@ -44,8 +44,23 @@ impl RequestConfigValue for RequestAcceptEncoding {
     type Value = crate::client::layer::decoder::AcceptEncoding;
 }
 
+#[cfg(feature = "cookies")]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestCookieStore;
+
+#[cfg(feature = "cookies")]
+impl RequestConfigValue for RequestCookieStore {
+    type Value = std::sync::Arc<dyn crate::cookie::CookieStore>;
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct RequestDefaultHeaders;
 impl RequestConfigValue for RequestDefaultHeaders {
     type Value = bool;
 }
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestHeaderOrder;
+impl RequestConfigValue for RequestHeaderOrder {
+    type Value = Vec<http::HeaderName>;
+}
@@ -7,7 +7,9 @@ use http::{Request, Response, Uri, header::COOKIE};
 use tower::{Layer, Service};
 
 use super::future::ResponseFuture;
-use crate::cookie::CookieStore;
+use crate::{
+    client::layer::config::RequestCookieStore, cookie::CookieStore, core::ext::RequestConfig,
+};
 
 /// Layer to apply [`CookieService`] middleware.
 #[derive(Clone)]
@@ -80,18 +82,21 @@ where
     }
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
-        // Check if cookie store is configured
-        let Some(cookie_store) = &self.cookie_store else {
+        // Check if cookie store is configured, preferring the one set for the request
+        let Some(cookie_store) = RequestConfig::<RequestCookieStore>::get(req.extensions())
+            .or(self.cookie_store.as_ref())
+            .cloned()
+        else {
             return ResponseFuture::Direct {
                 future: self.inner.call(req),
             };
         };
 
         // Try to inject cookies and get URI for response processing
-        match self.inject_cookies_if_needed(&mut req, cookie_store) {
+        match self.inject_cookies_if_needed(&mut req, &cookie_store) {
             Some(uri) => ResponseFuture::Managed {
                 future: self.inner.call(req),
-                cookie_store: cookie_store.clone(),
+                cookie_store,
                 uri,
             },
             None => ResponseFuture::Direct {
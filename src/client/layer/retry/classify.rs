@@ -78,6 +78,9 @@ pub(crate) enum Classifier {
     Never,
     /// Retry protocol-level errors (connection issues, timeouts, etc.).
     ProtocolNacks,
+    /// Retry connect errors and protocol NACKs, and `429` or `5xx` responses to
+    /// idempotent requests.
+    Transient,
     /// Use custom classification logic.
     Dyn(Arc<dyn Classify>),
 }
@@ -99,7 +102,29 @@ impl Classifier {
                     Action::Success
                 }
             }
+            Classifier::Transient => {
+                let is_transient = match req_rep.1 {
+                    Err(err) => {
+                        super::is_connect_error(&**err) || super::is_retryable_error(&**err)
+                    }
+                    Ok(status) => {
+                        is_idempotent(req_rep.method())
+                            && (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error())
+                    }
+                };
+                if is_transient {
+                    Action::Retryable
+                } else {
+                    Action::Success
+                }
+            }
             Classifier::Dyn(c) => c.classify(req_rep),
         }
     }
 }
+
+/// Returns true if sending a request with `method` twice has the same effect as
+/// sending it once (RFC 9110 section 9.2.2).
+fn is_idempotent(method: &Method) -> bool {
+    method.is_safe() || *method == Method::PUT || *method == Method::DELETE
+}
@@ -3,9 +3,15 @@
 mod classify;
 mod scope;
 
-use std::{error::Error as StdError, sync::Arc, time::Duration};
+use std::{
+    error::Error as StdError,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use http::{Request, Response};
+use futures_util::future::Either;
+use http::{Request, Response, StatusCode, header::RETRY_AFTER};
+use tokio::time::Sleep;
 use tower::retry::{
     Policy,
     budget::{Budget, TpsBudget},
@@ -23,7 +29,14 @@ pub(crate) use self::{
     scope::{ScopeFn, Scoped},
 };
 use super::timeout::TimeoutBody;
-use crate::{Body, core::client::body::Incoming, error::BoxError, retry};
+use crate::{
+    Body,
+    core::client::body::Incoming,
+    error::BoxError,
+    header::parse_retry_after,
+    retry::{self, Backoff},
+    util::fast_random,
+};
 
 /// A retry policy for HTTP requests.
 #[derive(Clone)]
@@ -33,6 +46,7 @@ pub struct RetryPolicy {
     max_retries_per_request: u32,
     retry_cnt: u32,
     scope: Scoped,
+    backoff: Option<Backoff>,
 }
 
 impl RetryPolicy {
@@ -47,6 +61,7 @@ impl RetryPolicy {
             max_retries_per_request: policy.max_retries_per_request,
             retry_cnt: 0,
             scope: policy.scope,
+            backoff: policy.backoff,
         }
     }
 }
@@ -70,7 +85,7 @@ type Res = Response<TimeoutBody<Incoming>>;
 type Res = Response<TimeoutBody<DecompressionBody<Incoming>>>;
 
 impl Policy<Req, Res, BoxError> for RetryPolicy {
-    type Future = std::future::Ready<()>;
+    type Future = Either<std::future::Ready<()>, Sleep>;
 
     fn retry(&mut self, req: &mut Req, result: &mut Result<Res, BoxError>) -> Option<Self::Future> {
         match self.classifier.classify(req, result) {
@@ -103,7 +118,14 @@ impl Policy<Req, Res, BoxError> for RetryPolicy {
                         }
                     );
 
-                    Some(std::future::ready(()))
+                    match self.backoff {
+                        Some(backoff) => {
+                            let delay = backoff.delay(self.retry_cnt, retry_after(result));
+                            trace!("Waiting {:?} before retrying", delay);
+                            Some(Either::Right(tokio::time::sleep(delay)))
+                        }
+                        None => Some(Either::Left(std::future::ready(()))),
+                    }
                 } else {
                     debug!(
                         "Request is retryable but retry budget exhausted: {} {}",
@@ -127,7 +149,10 @@ impl Policy<Req, Res, BoxError> for RetryPolicy {
             return None;
         }
 
-        let body = req.body().try_clone()?;
+        let Some(body) = req.body().try_clone() else {
+            trace!("request body cannot be sent again, not retrying");
+            return None;
+        };
         let mut new = http::Request::new(body);
         *new.method_mut() = req.method().clone();
         *new.uri_mut() = req.uri().clone();
@@ -139,6 +164,52 @@ impl Policy<Req, Res, BoxError> for RetryPolicy {
     }
 }
 
+impl Backoff {
+    /// Returns how long to wait before the `retry`th retry, the wait asked by
+    /// `retry_after` if any.
+    fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max);
+        }
+
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        let cap = self.base.saturating_mul(factor).min(self.max);
+        // Jitter between half and all of the cap. The product may round above
+        // the largest `Duration`, in which case the cap itself is used.
+        let jitter = (fast_random() >> 11) as f64 / (1u64 << 53) as f64;
+        Duration::try_from_secs_f64(cap.as_secs_f64() * (0.5 + jitter / 2.0)).unwrap_or(cap)
+    }
+}
+
+/// Returns the wait asked by the `Retry-After` header of a `429` or `503` response.
+fn retry_after(result: &Result<Res, BoxError>) -> Option<Duration> {
+    let res = result.as_ref().ok()?;
+    if !matches!(
+        res.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+    parse_retry_after(res.headers().get(RETRY_AFTER)?, SystemTime::now())
+}
+
+/// Determines whether the given error happened while connecting, before the
+/// request was sent.
+fn is_connect_error(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<crate::core::client::Error>() {
+            if err.is_connect() {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
 /// Determines whether the given error is considered retryable for HTTP/2 requests.
 ///
 /// Returns `true` if the error type or content indicates that the request can be retried,
@@ -170,3 +241,31 @@ fn is_retryable_error(err: &(dyn StdError + 'static)) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delay_saturates() {
+        let backoff = Backoff {
+            base: Duration::MAX,
+            max: Duration::MAX,
+        };
+        for retry in [1, 2, 64] {
+            let delay = backoff.delay(retry, None);
+            assert!(delay >= Duration::MAX / 2);
+        }
+
+        let backoff = Backoff {
+            base: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+        };
+        let delay = backoff.delay(3, None);
+        assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(400));
+        assert_eq!(
+            backoff.delay(1, Some(Duration::from_secs(5))),
+            Duration::from_secs(1)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Middleware intercepting the requests of a client
+//!
+//! A [`Middleware`] added with [`ClientBuilder::with`](crate::ClientBuilder::with)
+//! sees every request the client executes before it is sent, and the response or
+//! error after. It can change the request, such as adding fresh credentials,
+//! answer it without sending it, or change the response, such as logging it.
+//!
+//! Unlike a Tower [`layer`](crate::ClientBuilder::layer), which wraps the inner
+//! service and works on `http` types, middleware works on [`Request`] and
+//! [`Response`], so it can answer with a response built from bytes.
+//!
+//! # Example
+//!
+//! ```
+//! use wreq::{
+//!     Request,
+//!     header::{AUTHORIZATION, HeaderValue},
+//!     middleware::{Handling, Middleware, Next},
+//! };
+//!
+//! struct BearerAuth(HeaderValue);
+//!
+//! impl Middleware for BearerAuth {
+//!     fn handle(&self, mut req: Request, next: Next) -> Handling {
+//!         req.headers_mut().insert(AUTHORIZATION, self.0.clone());
+//!         next.run(req)
+//!     }
+//! }
+//!
+//! let client = wreq::Client::builder()
+//!     .with(BearerAuth(HeaderValue::from_static("Bearer token")))
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use super::{Client, Request, Response};
+
+/// Alias for the `Future` type returned by a [`Middleware`].
+pub type Handling = Pin<Box<dyn Future<Output = crate::Result<Response>> + Send>>;
+
+/// Intercepts the requests executed by a client.
+pub trait Middleware: Send + Sync {
+    /// Handles `req`, passing it on to the rest of the chain with
+    /// [`Next::run`], or answering it without doing so.
+    fn handle(&self, req: Request, next: Next) -> Handling;
+}
+
+/// The rest of the middleware chain, ending with sending the request.
+pub struct Next {
+    client: Client,
+    chain: Arc<[Arc<dyn Middleware>]>,
+    index: usize,
+}
+
+impl Next {
+    pub(crate) fn new(client: Client, chain: Arc<[Arc<dyn Middleware>]>) -> Next {
+        Next {
+            client,
+            chain,
+            index: 0,
+        }
+    }
+
+    /// Passes `req` to the next middleware, or sends it if there is none left.
+    pub fn run(mut self, req: Request) -> Handling {
+        match self.chain.get(self.index).cloned() {
+            Some(middleware) => {
+                self.index += 1;
+                middleware.handle(req, self)
+            }
+            None => Box::pin(self.client.execute_direct(req)),
+        }
+    }
+}
@@ -5,19 +5,24 @@ mod response;
 
 pub mod body;
 pub mod layer;
+pub mod middleware;
 #[cfg(feature = "multipart")]
 pub mod multipart;
+#[cfg(feature = "stream")]
+pub mod sse;
 #[cfg(feature = "ws")]
 pub mod ws;
 
 pub use self::{
     body::Body,
-    emulation::{Emulation, EmulationBuilder, EmulationFactory},
-    http::{Client, ClientBuilder},
+    emulation::{Emulation, EmulationBuilder, EmulationFactory, FieldDiff, FingerprintConfig},
+    http::{Client, ClientBuilder, ConnectivityReport, TlsFingerprint},
     request::{Request, RequestBuilder},
-    response::Response,
+    response::{Response, Validators},
 };
 pub use crate::core::client::{
+    connect::{ConnectAttempt, ConnectOutcome},
+    metrics::{HostPoolStats, PoolEvent, PoolStats},
     options::{http1, http2},
     upgrade::Upgraded,
 };
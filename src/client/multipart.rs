@@ -1,20 +1,29 @@
 //! multipart/form-data
 
-use std::{borrow::Cow, fmt, pin::Pin};
+use std::{borrow::Cow, fmt, pin::Pin, sync::Arc};
 
 use bytes::Bytes;
-use futures_util::{Stream, StreamExt, future, stream};
+use futures_util::{Stream, StreamExt, TryStreamExt, future, stream};
 use mime_guess::Mime;
 use percent_encoding::{self, AsciiSet, NON_ALPHANUMERIC};
 #[cfg(feature = "stream")]
-use {std::io, std::path::Path, tokio::fs::File};
+use {
+    std::io, std::path::Path, tokio::fs::File, tokio::io::AsyncRead, tokio_util::io::ReaderStream,
+};
 
 use super::Body;
 use crate::header::HeaderMap;
 
+/// The prefix of the boundaries of forms sent by Chrome, Edge and Safari.
+const WEBKIT_BOUNDARY_PREFIX: &str = "----WebKitFormBoundary";
+
+/// A callback told how many bytes of a form were sent, out of how many.
+type Progress = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
 /// An async multipart/form-data request.
 pub struct Form {
     inner: FormParts<Part>,
+    progress: Option<Progress>,
 }
 
 /// A field in a multipart form.
@@ -55,6 +64,7 @@ impl Form {
     pub fn new() -> Form {
         Form {
             inner: FormParts::new(),
+            progress: None,
         }
     }
 
@@ -64,6 +74,26 @@ impl Form {
         self.inner.boundary()
     }
 
+    /// Sets the boundary that this form will use.
+    ///
+    /// The boundary must be at most 70 characters long and must not appear in
+    /// any part. By default, a random one is generated.
+    pub fn with_boundary<T>(self, boundary: T) -> Form
+    where
+        T: Into<String>,
+    {
+        self.with_inner(|mut inner| {
+            inner.boundary = boundary.into();
+            inner
+        })
+    }
+
+    /// Uses a random boundary formatted as the ones of Chrome, Edge and Safari,
+    /// such as `----WebKitFormBoundary7MA4YWxkTrZu0gW`.
+    pub fn webkit_boundary(self) -> Form {
+        self.with_boundary(gen_webkit_boundary())
+    }
+
     /// Add a data field with supplied name and value.
     ///
     /// # Examples
@@ -132,6 +162,39 @@ impl Form {
         self.with_inner(|inner| inner.percent_encode_noop())
     }
 
+    /// Configure this `Form` to format part headers as browsers do.
+    ///
+    /// Following the HTML standard, names and file names are sent as UTF-8 with
+    /// only `"`, CR and LF escaped as `%22`, `%0D` and `%0A`, and a part with a
+    /// file name but no mime is sent as `application/octet-stream`. Together
+    /// with [`Form::webkit_boundary`], a form is then formatted as the browser
+    /// being emulated would send it.
+    pub fn percent_encode_html(self) -> Form {
+        self.with_inner(|inner| inner.percent_encode_html())
+    }
+
+    /// Calls `progress` with the number of bytes of this form sent so far, and
+    /// the length of the whole form when it is known, as each chunk is handed
+    /// to the connection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let form = wreq::multipart::Form::new()
+    ///     .text("key", "value")
+    ///     .on_progress(|sent, total| match total {
+    ///         Some(total) => println!("{sent}/{total} bytes sent"),
+    ///         None => println!("{sent} bytes sent"),
+    ///     });
+    /// ```
+    pub fn on_progress<F>(mut self, progress: F) -> Form
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
     /// Consume this instance and transform into an instance of Body for use in a request.
     pub(crate) fn stream(self) -> Body {
         if self.inner.fields.is_empty() {
@@ -150,6 +213,13 @@ impl Form {
             return empty_stream;
         }
 
+        // the length is computed before the fields are taken
+        let total = if self.progress.is_some() {
+            self.compute_length()
+        } else {
+            None
+        };
+
         // create initial part to init reduce chain
         let (name, part) = self.inner.fields.remove(0);
         let start = Box::pin(self.part_stream(name, part))
@@ -166,7 +236,17 @@ impl Form {
         let last = stream::once(future::ready(Ok(
             format!("--{}--\r\n", self.boundary()).into()
         )));
-        Box::pin(stream.chain(last))
+        let stream = Box::pin(stream.chain(last))
+            as Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send + Sync>>;
+
+        let Some(progress) = self.progress.take() else {
+            return stream;
+        };
+        let mut sent = 0;
+        Box::pin(stream.inspect_ok(move |chunk: &Bytes| {
+            sent += chunk.len() as u64;
+            progress(sent, total);
+        })) as Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send + Sync>>
     }
 
     /// Generate a crate::core::Body stream for a single Part instance of a Form request.
@@ -208,6 +288,7 @@ impl Form {
     {
         Form {
             inner: func(self.inner),
+            progress: self.progress,
         }
     }
 }
@@ -257,6 +338,28 @@ impl Part {
         Part::new(value.into(), Some(length))
     }
 
+    /// Makes a new parameter from an [`AsyncRead`], such as a socket or a
+    /// decompressor, streamed without buffering it in memory.
+    #[cfg(feature = "stream")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "stream")))]
+    pub fn reader<R>(reader: R) -> Part
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        Part::stream(Body::wrap_stream(ReaderStream::new(reader)))
+    }
+
+    /// Makes a new parameter from an [`AsyncRead`] of a known length, so the
+    /// form is sent with a `Content-Length`.
+    #[cfg(feature = "stream")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "stream")))]
+    pub fn reader_with_length<R>(reader: R, length: u64) -> Part
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        Part::stream_with_length(Body::wrap_stream(ReaderStream::new(reader)), length)
+    }
+
     /// Makes a file parameter.
     ///
     /// # Errors
@@ -394,10 +497,17 @@ impl<P: PartProps> FormParts<P> {
         self
     }
 
+    /// Configure this `Form` to escape as the HTML standard does
+    pub(crate) fn percent_encode_html(mut self) -> Self {
+        self.percent_encoding = PercentEncoding::Html;
+        self
+    }
+
     // If predictable, computes the length the request will have
     // The length should be predictable if only String and file fields have been added,
     // but not if a generic reader has been added;
     pub(crate) fn compute_length(&mut self) -> Option<u64> {
+        self.computed_headers.clear();
         let mut length = 0u64;
         for (name, field) in self.fields.iter() {
             match field.value_len() {
@@ -521,6 +631,7 @@ pub(crate) enum PercentEncoding {
     PathSegment,
     AttrChar,
     NoOp,
+    Html,
 }
 
 impl PercentEncoding {
@@ -529,6 +640,11 @@ impl PercentEncoding {
         buf.extend_from_slice(b"Content-Disposition: form-data; ");
 
         match self.percent_encode(name) {
+            value if matches!(self, Self::Html) => {
+                buf.extend_from_slice(b"name=\"");
+                buf.extend_from_slice(value.as_bytes());
+                buf.extend_from_slice(b"\"");
+            }
             Cow::Borrowed(value) => {
                 // nothing has been percent encoded
                 buf.extend_from_slice(b"name=\"");
@@ -546,18 +662,29 @@ impl PercentEncoding {
         // See https://github.com/seanmonstar/reqwest/issues/419.
         if let Some(filename) = &field.file_name {
             buf.extend_from_slice(b"; filename=\"");
-            let legal_filename = filename
-                .replace('\\', "\\\\")
-                .replace('"', "\\\"")
-                .replace('\r', "\\\r")
-                .replace('\n', "\\\n");
+            let legal_filename = match self {
+                Self::Html => html_escape(filename),
+                _ => filename
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\r', "\\\r")
+                    .replace('\n', "\\\n")
+                    .into(),
+            };
             buf.extend_from_slice(legal_filename.as_bytes());
             buf.extend_from_slice(b"\"");
         }
 
-        if let Some(mime) = &field.mime {
-            buf.extend_from_slice(b"\r\nContent-Type: ");
-            buf.extend_from_slice(mime.as_ref().as_bytes());
+        match &field.mime {
+            Some(mime) => {
+                buf.extend_from_slice(b"\r\nContent-Type: ");
+                buf.extend_from_slice(mime.as_ref().as_bytes());
+            }
+            // Browsers always send the type of a file
+            None if matches!(self, Self::Html) && field.file_name.is_some() => {
+                buf.extend_from_slice(b"\r\nContent-Type: application/octet-stream");
+            }
+            None => {}
         }
 
         for (k, v) in field.headers.iter() {
@@ -576,10 +703,24 @@ impl PercentEncoding {
             Self::PathSegment => percent_encode(value, PATH_SEGMENT_ENCODE_SET).into(),
             Self::AttrChar => percent_encode(value, ATTR_CHAR_ENCODE_SET).into(),
             Self::NoOp => value.into(),
+            Self::Html => html_escape(value),
         }
     }
 }
 
+/// Escapes `"`, CR and LF as the HTML standard does for the names and file
+/// names of `multipart/form-data` parts.
+fn html_escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\r', '\n']) {
+        return value.into();
+    }
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+        .into()
+}
+
 fn gen_boundary() -> String {
     use crate::util::fast_random as random;
 
@@ -591,6 +732,20 @@ fn gen_boundary() -> String {
     format!("{a:016x}-{b:016x}-{c:016x}-{d:016x}")
 }
 
+fn gen_webkit_boundary() -> String {
+    const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let mut boundary = String::from(WEBKIT_BOUNDARY_PREFIX);
+    for _ in 0..2 {
+        let mut random = crate::util::fast_random();
+        for _ in 0..8 {
+            boundary.push(char::from(ALPHANUMERIC[(random % 62) as usize]));
+            random /= 62;
+        }
+    }
+    boundary
+}
+
 #[cfg(test)]
 mod tests {
     use std::future;
@@ -743,5 +898,77 @@ mod tests {
             PercentEncoding::AttrChar.encode_headers(name, &field.meta),
             &b"Content-Disposition: form-data; name*=utf-8''start%25%27%22%0D%0A%C3%9Fend"[..]
         );
+
+        assert_eq!(
+            PercentEncoding::Html.encode_headers(name, &field.file_name("a\"b.txt").meta),
+            "Content-Disposition: form-data; name=\"start%'%22%0D%0Aßend\"; \
+             filename=\"a%22b.txt\"\r\nContent-Type: application/octet-stream"
+                .as_bytes()
+        );
+    }
+
+    #[test]
+    fn webkit_boundary() {
+        let form = Form::new().webkit_boundary();
+        let suffix = form
+            .boundary()
+            .strip_prefix("----WebKitFormBoundary")
+            .unwrap();
+        assert_eq!(suffix.len(), 16);
+        assert!(suffix.bytes().all(|b| b.is_ascii_alphanumeric()));
+
+        let form = form.with_boundary("boundary");
+        assert_eq!(form.boundary(), "boundary");
+    }
+
+    #[test]
+    fn progress_reports_sent_bytes() {
+        use std::sync::Mutex;
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut form = Form::new()
+            .with_boundary("boundary")
+            .text("key", "value")
+            .on_progress({
+                let reports = reports.clone();
+                move |sent, total| reports.lock().unwrap().push((sent, total))
+            });
+        let length = form.compute_length();
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("new rt");
+        let out = rt
+            .block_on(form.into_stream().map_ok(|c| c.to_vec()).try_concat())
+            .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(length, Some(out.len() as u64));
+        assert_eq!(reports.last(), Some(&(out.len() as u64, length)));
+        assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn reader_part() {
+        let mut form = Form::new()
+            .with_boundary("boundary")
+            .part("file", Part::reader(&b"contents"[..]).file_name("a.txt"));
+        assert_eq!(form.compute_length(), None);
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("new rt");
+        let out = rt
+            .block_on(form.into_stream().map_ok(|c| c.to_vec()).try_concat())
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n\
+             contents\r\n--boundary--\r\n"
+        );
     }
 }
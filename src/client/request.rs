@@ -2,12 +2,18 @@ use std::{
     convert::TryFrom,
     fmt,
     future::Future,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
     time::Duration,
 };
 
 use http::{Extensions, Request as HttpRequest, Uri, Version, request::Parts};
 use serde::Serialize;
+#[cfg(feature = "cancel-token")]
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "cancel-token")]
+use crate::core::ext::RequestCancelToken;
 
 #[cfg(any(
     feature = "gzip",
@@ -16,21 +22,29 @@ use serde::Serialize;
     feature = "deflate",
 ))]
 use super::layer::config::RequestAcceptEncoding;
+#[cfg(feature = "cookies")]
+use super::layer::config::RequestCookieStore;
 #[cfg(feature = "multipart")]
 use super::multipart;
 use super::{
     Body, EmulationFactory, Response,
     http::{Client, Pending},
-    layer::config::{RequestDefaultHeaders, RequestRedirectPolicy, RequestTimeoutOptions},
+    layer::config::{
+        RequestDefaultHeaders, RequestHeaderOrder, RequestRedirectPolicy, RequestTimeoutOptions,
+    },
+    response::{RevalidatedBody, Validators},
 };
 use crate::{
     Error, Method, Proxy,
     core::{
         client::options::RequestOptions,
-        ext::{RequestConfig, RequestConfigValue, RequestLayerOptions, RequestOrigHeaderMap},
+        ext::{
+            RequestConfig, RequestConfigValue, RequestLayerOptions, RequestOrigHeaderMap,
+            RequestTraceId,
+        },
     },
     ext::UriExt,
-    header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, OrigHeaderMap},
+    header::{CONTENT_TYPE, HOST, HeaderMap, HeaderName, HeaderValue, OrigHeaderMap},
     redirect,
 };
 
@@ -286,6 +300,21 @@ impl RequestBuilder {
         self
     }
 
+    /// Sets the `Host` header of this request, independently of the URL.
+    ///
+    /// The connection is still made to the URL's host, which is also used for
+    /// SNI and certificate verification; only the header sent to the server
+    /// changes. This allows reaching a virtual host through another name or
+    /// an IP address. Over HTTP/2, the `:authority` pseudo-header still carries
+    /// the URL's authority.
+    ///
+    /// The header is kept when following a redirect to the same origin, and
+    /// removed on a redirect to another scheme, host or port, so the new server
+    /// receives its own name.
+    pub fn host_header(self, host: &str) -> RequestBuilder {
+        self.header(HOST, host)
+    }
+
     /// Add a set of Headers to the existing ones on this Request.
     ///
     /// The headers will be merged in to any already set.
@@ -304,6 +333,19 @@ impl RequestBuilder {
         self
     }
 
+    /// Set the wire order of headers for this request.
+    ///
+    /// Headers named in `order` are sent first, in that order. Any other headers
+    /// keep the order and casing of the original headers in effect for the
+    /// request, set through [`RequestBuilder::orig_headers`] or by the client and
+    /// its emulation, and are otherwise sent in the order they were inserted.
+    pub fn header_order(mut self, order: &[HeaderName]) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.config_mut::<RequestHeaderOrder>() = order.to_vec();
+        }
+        self
+    }
+
     /// Enable or disable client default headers for this request.
     ///
     /// By default, client default headers are included. Set to `false` to skip them.
@@ -344,7 +386,8 @@ impl RequestBuilder {
         U: fmt::Display,
         P: fmt::Display,
     {
-        let header_value = crate::util::basic_auth(username, password);
+        let encode = self.client.base64_encode();
+        let header_value = crate::util::basic_auth_with(encode, username, password);
         self.header_operation(
             crate::header::AUTHORIZATION,
             header_value,
@@ -369,6 +412,46 @@ impl RequestBuilder {
         )
     }
 
+    /// Make this a conditional request revalidating a cached response.
+    ///
+    /// Adds `If-None-Match` from the stored `ETag` and `If-Modified-Since` from the
+    /// stored `Last-Modified`. If the cached copy is still fresh the server answers
+    /// `304 Not Modified`, see [`Response::is_not_modified`], and the body attached
+    /// with [`Validators::with_body`] is returned in place of the empty one.
+    ///
+    /// ```rust
+    /// # use wreq::Error;
+    /// # async fn run() -> Result<(), Error> {
+    /// let client = wreq::Client::new();
+    /// let res = client.get("http://httpbin.org/etag/abc").send().await?;
+    /// let validators = res.validators();
+    /// let validators = validators.with_body(res.bytes().await?);
+    ///
+    /// // The cached body is returned if the server answers 304 Not Modified
+    /// let body = client
+    ///     .get("http://httpbin.org/etag/abc")
+    ///     .revalidate_with(&validators)
+    ///     .send()
+    ///     .await?
+    ///     .bytes()
+    ///     .await?;
+    /// # let _ = body;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn revalidate_with(mut self, validators: &Validators) -> RequestBuilder {
+        if let Some(etag) = validators.etag() {
+            self = self.header(crate::header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = validators.last_modified() {
+            self = self.header(crate::header::IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        if let (Some(body), Ok(req)) = (validators.body(), self.request.as_mut()) {
+            req.extensions_mut().insert(RevalidatedBody(body.clone()));
+        }
+        self
+    }
+
     /// Enables a request timeout.
     ///
     /// The timeout is applied from when the request starts connecting until the
@@ -564,6 +647,45 @@ impl RequestBuilder {
         self
     }
 
+    /// Set the cookie store for this request, instead of the client's.
+    ///
+    /// Cookies for the request are read from `cookie_store`, and the cookies its
+    /// responses set, including along redirects, are stored in it. This keeps the
+    /// cookies of several identities apart while they share one client and its
+    /// connection pool. Such a request bypasses the HTTP cache of the client, so
+    /// that no identity is served a response stored for another.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use wreq::cookie::Jar;
+    ///
+    /// # async fn run() -> wreq::Result<()> {
+    /// let client = wreq::Client::new();
+    /// let alice = Arc::new(Jar::default());
+    /// let res = client
+    ///     .get("https://example.com/login")
+    ///     .cookie_jar(alice.clone())
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "cookies")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookies")))]
+    pub fn cookie_jar(
+        mut self,
+        cookie_store: Arc<dyn crate::cookie::CookieStore>,
+    ) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            RequestConfig::<RequestCookieStore>::get_mut(req.extensions_mut())
+                .replace(cookie_store);
+        }
+        self
+    }
+
     /// Sets if this request will announce that it accepts gzip encoding.
     #[cfg(feature = "gzip")]
     #[cfg_attr(docsrs, doc(cfg(feature = "gzip")))]
@@ -605,6 +727,9 @@ impl RequestBuilder {
     }
 
     /// Set the proxy for this request.
+    ///
+    /// This overrides the proxies of the client, and its
+    /// [`ProxySelector`](crate::ProxySelector), for this request only.
     pub fn proxy(mut self, proxy: Proxy) -> RequestBuilder {
         if let Ok(ref mut req) = self.request {
             *req.config_mut::<RequestLayerOptions>().proxy_matcher_mut() =
@@ -614,6 +739,10 @@ impl RequestBuilder {
     }
 
     /// Set the local address for this request.
+    ///
+    /// The outgoing socket is bound to this address before connecting, overriding
+    /// the client's local address. Pooled connections are only reused by requests
+    /// bound to the same address.
     pub fn local_address<V>(mut self, local_address: V) -> RequestBuilder
     where
         V: Into<Option<IpAddr>>,
@@ -640,6 +769,48 @@ impl RequestBuilder {
         self
     }
 
+    /// Connect to the given address for this request, bypassing DNS resolution.
+    ///
+    /// The `Host` header and the TLS SNI still use the hostname of the URL. If the
+    /// address has a port of `0`, the port of the URL is used instead. Connections
+    /// established this way are only reused by requests forced to the same address,
+    /// and proxies are not used for them.
+    pub fn resolve_to(mut self, addr: SocketAddr) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.config_mut::<RequestLayerOptions>().remote_addr_mut() = Some(addr);
+        }
+        self
+    }
+
+    /// Tag this request with an ID for correlating its logs.
+    ///
+    /// The ID is recorded as the `trace_id` field of the tracing spans opened while
+    /// connecting for this request, including the DNS resolution, so a single
+    /// request's connection setup can be followed among interleaved logs. It has
+    /// no effect on requests served by a pooled connection, nor without the
+    /// `tracing` feature.
+    pub fn trace_id(mut self, id: &str) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *RequestConfig::<RequestTraceId>::get_mut(req.extensions_mut()) = Some(Arc::from(id));
+        }
+        self
+    }
+
+    /// Cancel this request when `token` is cancelled.
+    ///
+    /// The request then fails promptly with an error for which
+    /// [`Error::is_canceled`] returns `true`, even while a DNS lookup for it is in
+    /// progress, instead of only being abandoned when its future is dropped. A token
+    /// that is already cancelled fails the request before it is sent.
+    #[cfg(feature = "cancel-token")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cancel-token")))]
+    pub fn cancel_token(mut self, token: CancellationToken) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *RequestConfig::<RequestCancelToken>::get_mut(req.extensions_mut()) = Some(token);
+        }
+        self
+    }
+
     /// Set the interface for this request.
     #[cfg(any(
         target_os = "android",
@@ -739,6 +910,31 @@ impl RequestBuilder {
         }
     }
 
+    /// Sends the request and reads its response as a stream of Server-Sent
+    /// Events, reconnecting whenever the connection ends.
+    ///
+    /// Like a browser's `EventSource`, the request is sent again after the delay
+    /// the server asked for, three seconds by default, with the id of the last
+    /// event received in a `Last-Event-ID` header. See the [`sse`](crate::sse)
+    /// module.
+    ///
+    /// # Errors
+    ///
+    /// Connection and body errors are yielded before reconnecting. An error
+    /// status, or a response that is not a `text/event-stream`, is yielded and
+    /// ends the stream, as does a `204 No Content` response. A request with a
+    /// streaming body cannot be sent again, so the stream ends with its first
+    /// response.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `stream` feature to be enabled.
+    #[cfg(feature = "stream")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "stream")))]
+    pub fn event_stream(self) -> super::sse::EventStream {
+        super::sse::event_source(self.client, self.request)
+    }
+
     /// Attempt to clone the RequestBuilder.
     ///
     /// `None` is returned if the RequestBuilder can not be cloned,
@@ -767,6 +963,153 @@ impl RequestBuilder {
                 request: Ok(req),
             })
     }
+
+    /// Renders the request as an equivalent `curl` command line, for debugging
+    /// and bug reports.
+    ///
+    /// The command carries the method, URI, HTTP version, the headers set on this
+    /// request and its body, each shell-escaped. Values of sensitive headers, such
+    /// as `Authorization` and `Cookie`, are replaced by `<redacted>`; use
+    /// [`RequestBuilder::to_curl_unredacted`] to keep them. Headers the client
+    /// adds when sending, such as its default headers, are not included, nor is a
+    /// streaming body. For the latter, `Content-Length` is left out as well, and so
+    /// is the `Content-Type` of a multipart form, whose boundary belongs to a body
+    /// curl would not send.
+    ///
+    /// If building the request failed, a shell comment holding the error is
+    /// returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let client = wreq::Client::new();
+    /// let curl = client
+    ///     .put("http://httpbin.org/put")
+    ///     .bearer_auth("secret")
+    ///     .body("it's")
+    ///     .to_curl();
+    /// assert_eq!(
+    ///     curl,
+    ///     "curl -X PUT 'http://httpbin.org/put' -H 'authorization: <redacted>' --data-raw 'it'\\''s'"
+    /// );
+    /// ```
+    pub fn to_curl(&self) -> String {
+        match self.request {
+            Ok(ref req) => curl_command(req, true),
+            Err(ref err) => curl_error(err),
+        }
+    }
+
+    /// Renders the request as an equivalent `curl` command line, sensitive header
+    /// values included.
+    ///
+    /// See [`RequestBuilder::to_curl`]. Mind that the output then holds credentials.
+    pub fn to_curl_unredacted(&self) -> String {
+        match self.request {
+            Ok(ref req) => curl_command(req, false),
+            Err(ref err) => curl_error(err),
+        }
+    }
+}
+
+/// Renders a request that failed to build as a shell comment.
+fn curl_error(err: &Error) -> String {
+    let err = err.to_string().replace(['\r', '\n'], " ");
+    format!("# request could not be built: {err}")
+}
+
+/// Renders `req` as a `curl` command line, replacing the values of sensitive
+/// headers if `redact` is set.
+fn curl_command(req: &Request, redact: bool) -> String {
+    let mut cmd = String::from("curl");
+
+    let body = req.body().and_then(Body::as_bytes);
+    let streaming = req.body().is_some() && body.is_none();
+    let has_body = body.is_some();
+    if req.method() == Method::HEAD {
+        // `-X HEAD` would leave curl waiting for a body
+        cmd.push_str(" --head");
+    } else if req.method() != Method::GET || has_body {
+        cmd.push_str(" -X ");
+        cmd.push_str(req.method().as_str());
+    }
+
+    cmd.push(' ');
+    cmd.push_str(&shell_quote(req.uri().to_string().as_bytes()));
+
+    match req.version() {
+        Some(Version::HTTP_10) => cmd.push_str(" --http1.0"),
+        Some(Version::HTTP_11) => cmd.push_str(" --http1.1"),
+        Some(Version::HTTP_2) => cmd.push_str(" --http2"),
+        _ => {}
+    }
+
+    for (name, value) in req.headers() {
+        if streaming
+            && (name == crate::header::CONTENT_LENGTH
+                || (name == crate::header::CONTENT_TYPE
+                    && value.as_bytes().starts_with(b"multipart/")))
+        {
+            continue;
+        }
+
+        let sensitive = value.is_sensitive()
+            || name == crate::header::AUTHORIZATION
+            || name == crate::header::PROXY_AUTHORIZATION
+            || name == crate::header::COOKIE;
+
+        let mut header = Vec::with_capacity(name.as_str().len() + value.len() + 2);
+        header.extend_from_slice(name.as_str().as_bytes());
+        if value.is_empty() {
+            // `name:` would make curl remove the header instead
+            header.push(b';');
+        } else if redact && sensitive {
+            header.extend_from_slice(b": ");
+            header.extend_from_slice(b"<redacted>");
+        } else {
+            header.extend_from_slice(b": ");
+            header.extend_from_slice(value.as_bytes());
+        }
+
+        cmd.push_str(" -H ");
+        cmd.push_str(&shell_quote(&header));
+    }
+
+    if let Some(body) = body {
+        cmd.push_str(" --data-raw ");
+        cmd.push_str(&shell_quote(body));
+    }
+
+    cmd
+}
+
+/// Quotes `value` as a single POSIX shell word.
+///
+/// Printable UTF-8 goes in single quotes. Anything else uses ANSI-C quoting
+/// (`$'...'`), which bash, zsh and ksh understand, with escapes for the bytes
+/// that cannot appear literally.
+fn shell_quote(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(s) if !s.chars().any(char::is_control) => {
+            format!("'{}'", s.replace('\'', "'\\''"))
+        }
+        _ => {
+            let mut quoted = String::from("$'");
+            for &byte in value {
+                match byte {
+                    b'\\' => quoted.push_str("\\\\"),
+                    b'\'' => quoted.push_str("\\'"),
+                    b'\n' => quoted.push_str("\\n"),
+                    b'\r' => quoted.push_str("\\r"),
+                    b'\t' => quoted.push_str("\\t"),
+                    0x20..=0x7e => quoted.push(byte as char),
+                    _ => quoted.push_str(&format!("\\x{byte:02x}")),
+                }
+            }
+            quoted.push('\'');
+            quoted
+        }
+    }
 }
 
 /// Check the request URI for a "username:password" type authority, and if
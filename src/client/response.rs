@@ -1,9 +1,9 @@
-use std::{fmt, net::SocketAddr};
+use std::{fmt, net::SocketAddr, sync::Arc};
 
 use bytes::Bytes;
 #[cfg(feature = "charset")]
 use encoding_rs::{Encoding, UTF_8};
-use http::{HeaderMap, StatusCode, Uri, Version};
+use http::{HeaderMap, HeaderValue, StatusCode, Uri, Version};
 #[cfg(feature = "charset")]
 use mime::Mime;
 #[cfg(feature = "json")]
@@ -14,8 +14,12 @@ use super::body::{Body, ResponseBody};
 use crate::cookie;
 use crate::{
     Error, Extension, Upgraded,
-    core::{client::connect::HttpInfo, ext::ReasonPhrase},
+    core::{
+        client::connect::{ConnectAttempt, HttpInfo},
+        ext::ReasonPhrase,
+    },
     ext::RequestUri,
+    tls::TlsInfo,
 };
 
 /// A Response to a submitted `Request`.
@@ -113,6 +117,47 @@ impl Response {
             .map(HttpInfo::remote_addr)
     }
 
+    /// Get the addresses tried while connecting, with the outcome of each.
+    ///
+    /// Useful to diagnose partial connectivity, such as broken IPv6 with working
+    /// IPv4. For a pooled connection, these are the attempts made when it was
+    /// first established. `None` if the connection was not made over TCP by this
+    /// client, for example through a SOCKS proxy.
+    pub fn connect_attempts(&self) -> Option<&[ConnectAttempt]> {
+        self.res
+            .extensions()
+            .get::<Extension<Arc<[ConnectAttempt]>>>()
+            .map(|Extension(attempts)| &**attempts)
+    }
+
+    /// Returns true if the server answered a conditional request with
+    /// `304 Not Modified`, meaning a previously cached body is still valid.
+    ///
+    /// If the request was revalidated with [`Validators`] carrying a body, that
+    /// body is returned by the body methods of this response.
+    #[inline]
+    pub fn is_not_modified(&self) -> bool {
+        self.status() == StatusCode::NOT_MODIFIED
+    }
+
+    /// Get the cache validators (`ETag` and `Last-Modified`) of this `Response`.
+    ///
+    /// Attach the cached body with [`Validators::with_body`] and pass them to
+    /// [`RequestBuilder::revalidate_with`](super::RequestBuilder::revalidate_with)
+    /// on the next request for the same URL.
+    #[inline]
+    pub fn validators(&self) -> Validators {
+        Validators::from_headers(self.headers())
+    }
+
+    /// Replaces the empty body of a `304 Not Modified` with the revalidated one.
+    pub(crate) fn revalidated(mut self, body: Bytes) -> Response {
+        if self.is_not_modified() {
+            *self.res.body_mut() = Body::from(body);
+        }
+        self
+    }
+
     // body methods
 
     /// Get the full response text.
@@ -353,6 +398,104 @@ impl Response {
         super::body::DataStream(self.res.into_body())
     }
 
+    /// Convert a `text/event-stream` response into a `Stream` of Server-Sent Events.
+    ///
+    /// The stream ends with the body. To reconnect when it does, use
+    /// [`RequestBuilder::event_stream`](crate::RequestBuilder::event_stream), see
+    /// the [`sse`](crate::sse) module.
+    ///
+    /// # Errors
+    ///
+    /// A body error is yielded, and ends the stream.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `stream` feature to be enabled.
+    #[cfg(feature = "stream")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "stream")))]
+    pub fn event_stream(self) -> super::sse::EventStream {
+        super::sse::events(Box::pin(self.bytes_stream()))
+    }
+
+    /// Convert a newline-delimited JSON response into a `Stream` of values.
+    ///
+    /// Each non-empty line of the body is deserialized as one `T`. Lines may be
+    /// split across body chunks; a final line without a trailing newline is
+    /// still yielded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # extern crate serde;
+    /// #
+    /// use futures_util::StreamExt;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Event {
+    ///     id: u64,
+    /// }
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut events = wreq::Client::new()
+    ///     .get("http://example.com/events")
+    ///     .send()
+    ///     .await?
+    ///     .json_lines::<Event>();
+    ///
+    /// while let Some(event) = events.next().await {
+    ///     println!("event: {}", event?.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An item fails if its line cannot be deserialized to `T`; the following
+    /// lines are still read. A body error ends the stream.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` and `stream` features to be enabled.
+    #[cfg(all(feature = "json", feature = "stream"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "json", feature = "stream"))))]
+    pub fn json_lines<T: DeserializeOwned>(
+        self,
+    ) -> impl futures_util::Stream<Item = crate::Result<T>> {
+        use futures_util::StreamExt;
+
+        let body = Box::pin(self.bytes_stream());
+        let state = (body, bytes::BytesMut::new(), false);
+        futures_util::stream::unfold(state, |(mut body, mut buf, mut eof)| async move {
+            loop {
+                let line = match buf.iter().position(|&b| b == b'\n') {
+                    Some(pos) => buf.split_to(pos + 1),
+                    None if eof && !buf.is_empty() => buf.split(),
+                    None if eof => return None,
+                    None => {
+                        match body.next().await {
+                            Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                            Some(Err(err)) => {
+                                buf.clear();
+                                return Some((Err(err), (body, buf, true)));
+                            }
+                            None => eof = true,
+                        }
+                        continue;
+                    }
+                };
+
+                let line = line.trim_ascii();
+                if !line.is_empty() {
+                    let item = serde_json::from_slice(line).map_err(Error::decode);
+                    return Some((item, (body, buf, eof)));
+                }
+            }
+        })
+    }
+
     // extension methods
 
     /// Get a reference to the associated extension of type `T`.
@@ -388,6 +531,16 @@ impl Response {
         self.res.extensions().get::<Extension<T>>()
     }
 
+    /// Returns the TLS parameters negotiated for the connection of this response.
+    ///
+    /// Only available when the client was built with
+    /// [`ClientBuilder::tls_info`](crate::ClientBuilder::tls_info) enabled, and the
+    /// request was sent over TLS.
+    #[inline]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.extension::<TlsInfo>().map(|Extension(info)| info)
+    }
+
     /// Returns a reference to the associated extensions.
     #[inline]
     pub fn extensions(&self) -> &http::Extensions {
@@ -475,6 +628,65 @@ impl Response {
     }
 }
 
+/// Cache validators of a response, used to revalidate a cached body.
+///
+/// See [`Response::validators`] and
+/// [`RequestBuilder::revalidate_with`](super::RequestBuilder::revalidate_with).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Validators {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    body: Option<Bytes>,
+}
+
+impl Validators {
+    /// Reads the `ETag` and `Last-Modified` headers from `headers`.
+    pub fn from_headers(headers: &HeaderMap) -> Validators {
+        Validators {
+            etag: headers.get(crate::header::ETAG).cloned(),
+            last_modified: headers.get(crate::header::LAST_MODIFIED).cloned(),
+            body: None,
+        }
+    }
+
+    /// Attaches the cached body these validators belong to.
+    ///
+    /// A request revalidated with them returns this body when the server answers
+    /// `304 Not Modified`, so the caller reads the same content either way.
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Validators {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Get the cached body, if one was attached.
+    #[inline]
+    pub fn body(&self) -> Option<&Bytes> {
+        self.body.as_ref()
+    }
+
+    /// Get the `ETag` validator.
+    #[inline]
+    pub fn etag(&self) -> Option<&HeaderValue> {
+        self.etag.as_ref()
+    }
+
+    /// Get the `Last-Modified` validator.
+    #[inline]
+    pub fn last_modified(&self) -> Option<&HeaderValue> {
+        self.last_modified.as_ref()
+    }
+
+    /// Returns true if there is no validator, so the response cannot be revalidated.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// The cached body of a revalidated request, returned on `304 Not Modified`.
+#[derive(Clone)]
+pub(crate) struct RevalidatedBody(pub(crate) Bytes);
+
 impl fmt::Debug for Response {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Response")
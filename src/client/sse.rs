@@ -0,0 +1,468 @@
+//! Server-Sent Events
+//!
+//! A `text/event-stream` response is read as a stream of [`Event`]s with
+//! [`Response::event_stream`](crate::Response::event_stream). To also reconnect
+//! when the connection drops, as a browser's `EventSource` does, send the request
+//! with [`RequestBuilder::event_stream`](crate::RequestBuilder::event_stream)
+//! instead: the request is sent again after the delay the server asked for with a
+//! `retry` field, with the id of the last event in a `Last-Event-ID` header.
+//! Reconnecting stops when the server answers with `204 No Content` or an error.
+//!
+//! # Example
+//!
+//! ```
+//! use futures_util::StreamExt;
+//!
+//! # async fn run() -> wreq::Result<()> {
+//! let mut events = wreq::Client::new()
+//!     .get("http://example.com/events")
+//!     .event_stream();
+//!
+//! while let Some(event) = events.next().await {
+//!     let event = event?;
+//!     println!("{}: {}", event.event(), event.data());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt, mem,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
+use http::{
+    HeaderMap, HeaderValue, StatusCode,
+    header::{ACCEPT, CONTENT_TYPE},
+};
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+
+use super::{Client, Request};
+use crate::Error;
+
+/// The delay before reconnecting, until the server sets one with a `retry` field.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// An event received from a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    event: String,
+    data: String,
+    id: Option<String>,
+}
+
+impl Event {
+    /// Returns the type of the event, `message` unless the server set another.
+    #[inline]
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    /// Returns the data of the event, with the lines of multi-line data joined
+    /// by `\n`.
+    #[inline]
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// Returns the last event id the server set, if any.
+    ///
+    /// An id applies to the event it is set in and the following ones, until
+    /// another is set.
+    #[inline]
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Deserializes the data of the event as JSON.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the data is not valid JSON for `T`.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn json<T: DeserializeOwned>(&self) -> crate::Result<T> {
+        serde_json::from_str(&self.data).map_err(Error::decode)
+    }
+}
+
+/// A stream of the [`Event`]s of a response.
+#[must_use = "streams do nothing unless polled"]
+pub struct EventStream {
+    inner: Pin<Box<dyn Stream<Item = crate::Result<Event>> + Send>>,
+}
+
+impl Stream for EventStream {
+    type Item = crate::Result<Event>;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream").finish()
+    }
+}
+
+/// Parses events out of the bytes of an event stream.
+#[derive(Default)]
+pub(crate) struct Parser {
+    buf: BytesMut,
+    started: bool,
+    event: String,
+    data: String,
+    last_id: String,
+    retry: Option<Duration>,
+}
+
+impl Parser {
+    /// Appends a chunk of the stream.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Marks the end of the stream, so that a trailing CR ends its line.
+    pub(crate) fn finish(&mut self) {
+        if self.buf.last() == Some(&b'\r') {
+            self.buf.extend_from_slice(b"\n");
+        }
+    }
+
+    /// Forgets the partly received event, before reading a new connection.
+    ///
+    /// The last event id and the reconnection delay are kept.
+    pub(crate) fn reset(&mut self) {
+        self.buf.clear();
+        self.started = false;
+        self.event.clear();
+        self.data.clear();
+    }
+
+    /// Returns the last event id the server set.
+    pub(crate) fn last_event_id(&self) -> &str {
+        &self.last_id
+    }
+
+    /// Returns the reconnection delay the server asked for.
+    pub(crate) fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Returns the next complete event, if one has been received.
+    pub(crate) fn next_event(&mut self) -> Option<Event> {
+        if !self.started {
+            const BOM: &[u8] = b"\xEF\xBB\xBF";
+            if self.buf.len() < BOM.len() && BOM.starts_with(&self.buf) {
+                return None;
+            }
+            if self.buf.starts_with(BOM) {
+                let _ = self.buf.split_to(BOM.len());
+            }
+            self.started = true;
+        }
+
+        while let Some(line) = self.next_line() {
+            if let Some(event) = self.process_line(&line) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    /// Splits the next line off the buffer, ended by CRLF, LF or CR.
+    fn next_line(&mut self) -> Option<BytesMut> {
+        let pos = self.buf.iter().position(|&b| b == b'\n' || b == b'\r')?;
+        let end = if self.buf[pos] == b'\n' {
+            pos + 1
+        } else {
+            match self.buf.get(pos + 1) {
+                Some(b'\n') => pos + 2,
+                Some(_) => pos + 1,
+                // A LF may follow the CR in the next chunk
+                None => return None,
+            }
+        };
+        let mut line = self.buf.split_to(end);
+        line.truncate(pos);
+        Some(line)
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        let (field, value) = match line.iter().position(|&b| b == b':') {
+            // A comment
+            Some(0) => return None,
+            Some(pos) => {
+                let value = &line[pos + 1..];
+                (&line[..pos], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &[][..]),
+        };
+        let value = String::from_utf8_lossy(value);
+
+        match field {
+            b"event" => self.event = value.into_owned(),
+            b"data" => {
+                self.data.push_str(&value);
+                self.data.push('\n');
+            }
+            b"id" if !value.contains('\0') => self.last_id = value.into_owned(),
+            b"retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let mut data = mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            event: if event.is_empty() {
+                "message".to_owned()
+            } else {
+                event
+            },
+            data,
+            id: (!self.last_id.is_empty()).then(|| self.last_id.clone()),
+        })
+    }
+}
+
+type BodyStream = Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send>>;
+
+/// Reads the events of `body`, ending with it.
+pub(crate) fn events(body: BodyStream) -> EventStream {
+    let state = (body, Parser::default(), false);
+    let inner = futures_util::stream::unfold(state, |(mut body, mut parser, mut eof)| async move {
+        loop {
+            if let Some(event) = parser.next_event() {
+                return Some((Ok(event), (body, parser, eof)));
+            }
+            if eof {
+                return None;
+            }
+
+            match body.next().await {
+                Some(Ok(chunk)) => parser.feed(&chunk),
+                Some(Err(err)) => {
+                    parser.reset();
+                    return Some((Err(err), (body, parser, true)));
+                }
+                None => {
+                    parser.finish();
+                    eof = true;
+                }
+            }
+        }
+    });
+    EventStream {
+        inner: Box::pin(inner),
+    }
+}
+
+struct EventSource {
+    client: Client,
+    request: Option<Request>,
+    error: Option<Error>,
+    body: Option<BodyStream>,
+    parser: Parser,
+    reconnecting: bool,
+}
+
+/// Sends `request` and reads the events of its response, sending it again
+/// whenever the connection ends.
+pub(crate) fn event_source(client: Client, request: crate::Result<Request>) -> EventStream {
+    let (request, error) = match request {
+        Ok(request) => (Some(request), None),
+        Err(err) => (None, Some(err)),
+    };
+    let state = EventSource {
+        client,
+        request,
+        error,
+        body: None,
+        parser: Parser::default(),
+        reconnecting: false,
+    };
+
+    let inner = futures_util::stream::unfold(state, |mut state| async move {
+        if let Some(err) = state.error.take() {
+            return Some((Err(err), state));
+        }
+
+        loop {
+            if let Some(event) = state.parser.next_event() {
+                return Some((Ok(event), state));
+            }
+
+            if let Some(body) = state.body.as_mut() {
+                match body.next().await {
+                    Some(Ok(chunk)) => state.parser.feed(&chunk),
+                    Some(Err(err)) => {
+                        state.body = None;
+                        state.parser.reset();
+                        return Some((Err(err), state));
+                    }
+                    None => {
+                        state.body = None;
+                        state.parser.finish();
+                    }
+                }
+                continue;
+            }
+
+            // Without a request left to send, the stream is over
+            let template = state.request.as_ref()?;
+            if state.reconnecting {
+                let delay = state.parser.retry().unwrap_or(DEFAULT_RETRY);
+                trace!("reconnecting event stream in {:?}", delay);
+                tokio::time::sleep(delay).await;
+            }
+            state.reconnecting = true;
+            state.parser.reset();
+
+            // A request with a streaming body can only be sent once
+            let mut request = match template.try_clone() {
+                Some(request) => request,
+                None => state.request.take()?,
+            };
+            let headers = request.headers_mut();
+            headers
+                .entry(ACCEPT)
+                .or_insert(HeaderValue::from_static("text/event-stream"));
+            if let Some(id) = HeaderValue::from_str(state.parser.last_event_id())
+                .ok()
+                .filter(|id| !id.is_empty())
+            {
+                headers.insert(LAST_EVENT_ID, id);
+            }
+
+            let res = match state.client.execute(request).await {
+                Ok(res) => res,
+                // The connection failed, try again after the delay
+                Err(err) => return Some((Err(err), state)),
+            };
+
+            // The server asks not to reconnect with a 204, and any other
+            // unexpected response is final too
+            if res.status() == StatusCode::NO_CONTENT {
+                return None;
+            }
+            if let Err(err) = res.error_for_status_ref() {
+                state.request = None;
+                return Some((Err(err), state));
+            }
+            if !is_event_stream(res.headers()) {
+                state.request = None;
+                let err = Error::decode("response is not a text/event-stream");
+                return Some((Err(err.with_uri(res.uri().clone())), state));
+            }
+
+            state.body = Some(Box::pin(res.bytes_stream()));
+        }
+    });
+    EventStream {
+        inner: Box::pin(inner),
+    }
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&str]) -> Vec<Event> {
+        let mut parser = Parser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk.as_bytes());
+            events.extend(std::iter::from_fn(|| parser.next_event()));
+        }
+        parser.finish();
+        events.extend(std::iter::from_fn(|| parser.next_event()));
+        events
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> Event {
+        Event {
+            event: event.to_owned(),
+            data: data.to_owned(),
+            id: id.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parses_fields_comments_and_multi_line_data() {
+        let events = parse(&[
+            "\u{feff}: a comment\n",
+            "data: first\ndata:second\n\n",
+            "event: update\nid: 7\ndata: {\"n\":1}\n\n",
+            "data\n\n",
+            "event: ignored\n\n",
+            "data: still 7\n\n",
+        ]);
+
+        assert_eq!(
+            events,
+            [
+                event("message", "first\nsecond", None),
+                event("update", "{\"n\":1}", Some("7")),
+                event("message", "", Some("7")),
+                event("message", "still 7", Some("7")),
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_line_endings_split_across_chunks() {
+        let events = parse(&["data: a\r", "\n\r", "\ndata: b\r\r", "data: c\ndata: cut"]);
+        assert_eq!(
+            events,
+            [event("message", "a", None), event("message", "b", None)]
+        );
+    }
+
+    #[test]
+    fn keeps_retry_and_last_id_across_reset() {
+        let mut parser = Parser::default();
+        parser.feed(b"retry: 1500\nid: 42\ndata: x\n\nretry: soon\ndata: partial");
+        assert_eq!(parser.next_event(), Some(event("message", "x", Some("42"))));
+        assert_eq!(parser.next_event(), None);
+
+        parser.reset();
+        assert_eq!(parser.retry(), Some(Duration::from_millis(1500)));
+        assert_eq!(parser.last_event_id(), "42");
+
+        parser.feed(b"data: y\n\n");
+        assert_eq!(parser.next_event(), Some(event("message", "y", Some("42"))));
+    }
+}
@@ -11,6 +11,7 @@ use std::{
     ops::{Deref, DerefMut},
     pin::Pin,
     task::{Context, Poll, ready},
+    time::Duration,
 };
 
 use futures_util::{Sink, SinkExt, Stream, StreamExt, stream::FusedStream};
@@ -20,6 +21,7 @@ use http::{
 use http2::ext::Protocol;
 use pin_project_lite::pin_project;
 use serde::Serialize;
+use tokio::time::{Instant, Sleep};
 use tokio_tungstenite::tungstenite::{
     self,
     protocol::{self, CloseFrame, WebSocketConfig},
@@ -36,11 +38,20 @@ type WebSocketStream = tokio_tungstenite::WebSocketStream<Upgraded>;
 
 /// Wrapper for [`RequestBuilder`] that performs the
 /// websocket handshake when sent.
+///
+/// The handshake is sent like any other request of the client, so it carries the
+/// TLS and HTTP/2 fingerprint, default headers and header order of its
+/// emulation profile. A profile can list `sec-websocket-key` and the other
+/// handshake headers in its order to place them where the browser does, and a
+/// `Sec-WebSocket-Extensions` header can be added to offer the same extensions.
+/// No extension is implemented though, so the handshake fails if the server
+/// accepts one.
 pub struct WebSocketRequestBuilder {
     inner: RequestBuilder,
     accept_key: Option<Cow<'static, str>>,
     protocols: Option<Vec<Cow<'static, str>>>,
     config: WebSocketConfig,
+    keepalive: Option<Duration>,
 }
 
 impl WebSocketRequestBuilder {
@@ -51,6 +62,7 @@ impl WebSocketRequestBuilder {
             accept_key: None,
             protocols: None,
             config: WebSocketConfig::default(),
+            keepalive: None,
         }
     }
 
@@ -107,6 +119,18 @@ impl WebSocketRequestBuilder {
         self
     }
 
+    /// Sends a ping whenever the connection has been silent for `interval`.
+    ///
+    /// If the server sends nothing back, not even a pong, within another
+    /// `interval`, the [`WebSocket`] stream yields an error once and then ends.
+    /// Pings are sent while the stream is being read, so keep polling it for the
+    /// keepalive to work.
+    #[inline]
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
     /// Sets the websocket max_frame_size configuration.
     #[inline]
     pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
@@ -191,6 +215,15 @@ impl WebSocketRequestBuilder {
         self
     }
 
+    /// Set the wire order of headers for the handshake request.
+    ///
+    /// See [`RequestBuilder::header_order`].
+    #[inline]
+    pub fn header_order(mut self, order: &[HeaderName]) -> Self {
+        self.inner = self.inner.header_order(order);
+        self
+    }
+
     /// Enable or disable client default headers for this request.
     ///
     /// By default, client default headers are included. Set to `false` to skip them.
@@ -339,8 +372,15 @@ impl WebSocketRequestBuilder {
                     .accept_key
                     .unwrap_or_else(|| Cow::Owned(tungstenite::handshake::client::generate_key()));
 
+                // Browsers send these with every handshake, as it must not be cached
+                headers
+                    .entry(header::PRAGMA)
+                    .or_insert(HeaderValue::from_static("no-cache"));
+                headers
+                    .entry(header::CACHE_CONTROL)
+                    .or_insert(HeaderValue::from_static("no-cache"));
                 headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
-                headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+                headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
                 headers.insert(
                     header::SEC_WEBSOCKET_KEY,
                     HeaderValue::from_str(&nonce).map_err(Error::builder)?,
@@ -390,6 +430,7 @@ impl WebSocketRequestBuilder {
                 accept_key,
                 protocols: self.protocols,
                 config: self.config,
+                keepalive: self.keepalive,
             })
     }
 }
@@ -404,6 +445,7 @@ pub struct WebSocketResponse {
     accept_key: Option<Cow<'static, str>>,
     protocols: Option<Vec<Cow<'static, str>>>,
     config: WebSocketConfig,
+    keepalive: Option<Duration>,
 }
 
 impl Deref for WebSocketResponse {
@@ -502,6 +544,17 @@ impl WebSocketResponse {
                 (None, None) => {}
             };
 
+            // Offering an extension, such as permessage-deflate to look like a browser,
+            // is allowed, but none is implemented, so the server must not choose one
+            if let Some(extensions) = headers
+                .get(header::SEC_WEBSOCKET_EXTENSIONS)
+                .filter(|extensions| !extensions.is_empty())
+            {
+                return Err(Error::upgrade(format!(
+                    "unsupported extension: {extensions:?}"
+                )));
+            }
+
             let upgraded = self.inner.upgrade().await?;
             let inner = WebSocketStream::from_raw_socket(
                 upgraded,
@@ -513,7 +566,18 @@ impl WebSocketResponse {
             (inner, protocol)
         };
 
-        Ok(WebSocket { inner, protocol })
+        let keepalive = self.keepalive.map(|interval| Keepalive {
+            interval,
+            sleep: Box::pin(tokio::time::sleep(interval)),
+            awaiting_pong: false,
+            timed_out: false,
+        });
+
+        Ok(WebSocket {
+            inner,
+            protocol,
+            keepalive,
+        })
     }
 }
 
@@ -548,9 +612,19 @@ pin_project! {
         #[pin]
         inner: WebSocketStream,
         protocol: Option<HeaderValue>,
+        keepalive: Option<Keepalive>,
     }
 }
 
+/// Pings sent on a silent connection, see [`WebSocketRequestBuilder::keepalive`].
+#[derive(Debug)]
+struct Keepalive {
+    interval: Duration,
+    sleep: Pin<Box<Sleep>>,
+    awaiting_pong: bool,
+    timed_out: bool,
+}
+
 impl WebSocket {
     /// Return the selected WebSocket subprotocol, if one has been chosen.
     #[inline]
@@ -560,7 +634,8 @@ impl WebSocket {
 
     /// Receive another message.
     ///
-    /// Returns `None` if the stream has closed.
+    /// Pings from the server are answered, and a close frame from it is replied to,
+    /// before being returned. Returns `None` if the stream has closed.
     #[inline]
     pub async fn recv(&mut self) -> Option<Result<Message, Error>> {
         self.next().await
@@ -593,6 +668,43 @@ impl WebSocket {
     }
 }
 
+impl WebSocket {
+    /// Sends a ping if the connection has been silent for the keepalive interval,
+    /// and fails if the previous one went unanswered.
+    fn poll_keepalive(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Result<(), Error> {
+        let mut this = self.project();
+        let Some(keepalive) = this.keepalive.as_mut() else {
+            return Ok(());
+        };
+
+        // Poll again after every reset so the new deadline registers a wakeup
+        while keepalive.sleep.as_mut().poll(cx).is_ready() {
+            if keepalive.awaiting_pong {
+                keepalive.timed_out = true;
+                return Err(Error::websocket("keepalive ping timed out"));
+            }
+
+            // Try again on the next poll if the sink is busy
+            if this.inner.as_mut().poll_ready(cx).is_pending() {
+                break;
+            }
+
+            this.inner
+                .as_mut()
+                .start_send(tungstenite::Message::Ping(Default::default()))
+                .map_err(Error::websocket)?;
+            if let Poll::Ready(Err(err)) = this.inner.as_mut().poll_flush(cx) {
+                return Err(Error::websocket(err));
+            }
+            keepalive.awaiting_pong = true;
+            let deadline = Instant::now() + keepalive.interval;
+            keepalive.sleep.as_mut().reset(deadline);
+        }
+
+        Ok(())
+    }
+}
+
 impl Sink<Message> for WebSocket {
     type Error = Error;
 
@@ -632,7 +744,7 @@ impl Sink<Message> for WebSocket {
 impl FusedStream for WebSocket {
     #[inline]
     fn is_terminated(&self) -> bool {
-        self.inner.is_terminated()
+        self.keepalive.as_ref().is_some_and(|k| k.timed_out) || self.inner.is_terminated()
     }
 }
 
@@ -640,9 +752,24 @@ impl Stream for WebSocket {
     type Item = Result<Message, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.keepalive.as_ref().is_some_and(|k| k.timed_out) {
+            return Poll::Ready(None);
+        }
+
         loop {
+            if let Err(err) = self.as_mut().poll_keepalive(cx) {
+                return Poll::Ready(Some(Err(err)));
+            }
+
             match ready!(self.inner.poll_next_unpin(cx)) {
                 Some(Ok(msg)) => {
+                    // Anything from the server shows the connection is alive
+                    if let Some(keepalive) = self.keepalive.as_mut() {
+                        keepalive.awaiting_pong = false;
+                        let deadline = Instant::now() + keepalive.interval;
+                        keepalive.sleep.as_mut().reset(deadline);
+                    }
+
                     if let Some(msg) = Message::from_tungstenite(msg) {
                         return Poll::Ready(Some(Ok(msg)));
                     }
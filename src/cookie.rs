@@ -2,7 +2,8 @@
 
 use std::{
     convert::TryInto,
-    fmt,
+    fmt, fs, io,
+    path::{Path, PathBuf},
     sync::Arc,
     time::{Duration, SystemTime},
 };
@@ -20,12 +21,27 @@ use crate::{
 };
 
 /// Actions for a persistent cookie store providing session support.
+///
+/// Implement it to keep cookies elsewhere than in memory, such as in Redis or
+/// SQLite, and set it on a client with
+/// [`ClientBuilder::cookie_provider`](crate::ClientBuilder::cookie_provider), or
+/// on a single request with
+/// [`RequestBuilder::cookie_jar`](crate::RequestBuilder::cookie_jar).
 pub trait CookieStore: Send + Sync {
     /// Store a set of Set-Cookie header values received from `uri`
     fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, uri: &Uri);
 
     /// Get any Cookie values in the store for `uri`
     fn cookies(&self, uri: &Uri) -> Vec<HeaderValue>;
+
+    /// Get every cookie in the store, with the domain it is stored for.
+    ///
+    /// Cookies without a domain attribute are only sent to that exact host. This
+    /// is what [`Client::save_cookies`](crate::Client::save_cookies) saves; the
+    /// default implementation returns no cookies, for stores that cannot list them.
+    fn all_cookies(&self) -> Vec<(String, Cookie<'static>)> {
+        Vec::new()
+    }
 }
 
 /// Trait for converting types into a shared cookie store ([`Arc<dyn CookieStore>`]).
@@ -46,6 +62,46 @@ pub trait IntoCookieStore {
 #[derive(Debug, Clone)]
 pub struct Cookie<'a>(RawCookie<'a>);
 
+/// The file formats a [`FileCookieStore`] reads and writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CookieFileFormat {
+    /// The Netscape `cookies.txt` format, read and written by curl, wget and
+    /// browser extensions.
+    Netscape,
+    /// A JSON array of cookie objects.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    Json,
+}
+
+/// A [`CookieStore`] kept in a file, so that cookies survive restarts.
+///
+/// The file is read when the store is opened, and written back by
+/// [`FileCookieStore::save`] and when the store is dropped. It is written to a
+/// temporary file first and then renamed over the old one, so a crash never
+/// leaves a partial file behind. Session cookies are saved too, like curl does,
+/// while expired cookies are dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use wreq::cookie::FileCookieStore;
+///
+/// let store = Arc::new(FileCookieStore::open("cookies.txt")?);
+/// let client = wreq::Client::builder()
+///     .cookie_provider(store.clone())
+///     .build()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FileCookieStore {
+    jar: Jar,
+    path: PathBuf,
+    format: CookieFileFormat,
+}
+
 /// A good default `CookieStore` implementation.
 ///
 /// This is the implementation used when simply calling `cookie_store(true)`.
@@ -430,6 +486,22 @@ impl CookieStore for Jar {
             })
             .collect()
     }
+
+    fn all_cookies(&self) -> Vec<(String, Cookie<'static>)> {
+        let mut cookies = Vec::new();
+        for (domain, path_map) in self.0.read().iter() {
+            for (path, name_map) in path_map {
+                for cookie in name_map.iter() {
+                    let mut cookie = cookie.clone().into_owned();
+                    if cookie.path().is_none() {
+                        cookie.set_path(path.clone());
+                    }
+                    cookies.push((domain.clone(), Cookie(cookie)));
+                }
+            }
+        }
+        cookies
+    }
 }
 
 impl Default for Jar {
@@ -438,6 +510,255 @@ impl Default for Jar {
     }
 }
 
+// ===== impl CookieFileFormat =====
+
+impl CookieFileFormat {
+    /// Returns the format of the file at `path`, told by its extension: JSON for
+    /// `.json` files, when the `json` feature is enabled, and Netscape otherwise.
+    pub fn from_path(path: &Path) -> CookieFileFormat {
+        #[cfg(feature = "json")]
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            return CookieFileFormat::Json;
+        }
+        #[cfg(not(feature = "json"))]
+        let _ = path;
+        CookieFileFormat::Netscape
+    }
+
+    /// Writes `cookies` in this format.
+    fn write(self, cookies: &[(String, Cookie<'static>)]) -> io::Result<Vec<u8>> {
+        let now = SystemTime::now();
+        let live = cookies
+            .iter()
+            .filter(|(_, cookie)| cookie.expires().is_none_or(|expires| expires > now));
+
+        match self {
+            CookieFileFormat::Netscape => {
+                let mut out = String::from("# Netscape HTTP Cookie File\n");
+                for (domain, cookie) in live {
+                    let entry = FileCookie::new(domain, cookie);
+                    let domain = match (entry.http_only, entry.include_subdomains) {
+                        (true, true) => format!("#HttpOnly_.{}", entry.domain),
+                        (true, false) => format!("#HttpOnly_{}", entry.domain),
+                        (false, true) => format!(".{}", entry.domain),
+                        (false, false) => entry.domain,
+                    };
+                    let flag = |set: bool| if set { "TRUE" } else { "FALSE" };
+                    out.push_str(&format!(
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                        domain,
+                        flag(entry.include_subdomains),
+                        entry.path,
+                        flag(entry.secure),
+                        entry.expires.unwrap_or(0),
+                        entry.name,
+                        entry.value,
+                    ));
+                }
+                Ok(out.into_bytes())
+            }
+            #[cfg(feature = "json")]
+            CookieFileFormat::Json => {
+                let entries: Vec<_> = live
+                    .map(|(domain, cookie)| FileCookie::new(domain, cookie))
+                    .collect();
+                serde_json::to_vec_pretty(&entries).map_err(io::Error::from)
+            }
+        }
+    }
+
+    /// Reads the cookies of `text` in this format, skipping malformed entries.
+    fn read(self, text: &str) -> io::Result<Vec<FileCookie>> {
+        match self {
+            CookieFileFormat::Netscape => Ok(text
+                .lines()
+                .filter_map(|line| {
+                    let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                        Some(line) => (line, true),
+                        None if line.starts_with('#') => return None,
+                        None => (line, false),
+                    };
+                    let mut fields = line.trim_end_matches('\r').split('\t');
+                    let domain = fields.next()?;
+                    let include_subdomains = fields.next()?.eq_ignore_ascii_case("TRUE");
+                    let path = fields.next()?.to_owned();
+                    let secure = fields.next()?.eq_ignore_ascii_case("TRUE");
+                    let expires = fields.next()?.parse::<u64>().ok()?;
+                    let name = fields.next()?.to_owned();
+                    let value = fields.next().unwrap_or_default().to_owned();
+                    Some(FileCookie {
+                        domain: domain.trim_start_matches('.').to_owned(),
+                        include_subdomains,
+                        path,
+                        secure,
+                        http_only,
+                        expires: (expires != 0).then_some(expires),
+                        name,
+                        value,
+                    })
+                })
+                .collect()),
+            #[cfg(feature = "json")]
+            CookieFileFormat::Json => serde_json::from_str(text).map_err(io::Error::from),
+        }
+    }
+}
+
+/// A cookie as saved in a file.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FileCookie {
+    domain: String,
+    include_subdomains: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    /// Expiry as seconds since the Unix epoch, `None` for session cookies
+    expires: Option<u64>,
+    name: String,
+    value: String,
+}
+
+impl FileCookie {
+    fn new(domain: &str, cookie: &Cookie<'_>) -> FileCookie {
+        FileCookie {
+            domain: domain.to_owned(),
+            include_subdomains: cookie.domain().is_some(),
+            path: cookie.path().unwrap_or(DEFAULT_PATH).to_owned(),
+            secure: cookie.secure(),
+            http_only: cookie.http_only(),
+            expires: cookie.expires().map(|expires| {
+                expires
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            }),
+            name: cookie.name().to_owned(),
+            value: cookie.value().to_owned(),
+        }
+    }
+
+    /// Adds the cookie to `jar`, for the host it was saved for.
+    fn add_to(self, jar: &Jar) {
+        let scheme = if self.secure { "https" } else { "http" };
+        let uri = format!("{}://{}{}", scheme, self.domain, self.path);
+
+        let mut cookie = RawCookie::new(self.name, self.value);
+        cookie.set_path(self.path);
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        if self.include_subdomains {
+            cookie.set_domain(self.domain);
+        }
+        if let Some(expires) = self.expires {
+            let expires = SystemTime::UNIX_EPOCH + Duration::from_secs(expires);
+            cookie.set_expires(cookie::time::OffsetDateTime::from(expires));
+        }
+        jar.add_cookie(cookie, uri.as_str());
+    }
+}
+
+/// Writes `cookies` to the file at `path` in `format`, through a temporary file.
+pub(crate) fn save_cookies(
+    cookies: &[(String, Cookie<'static>)],
+    path: &Path,
+    format: CookieFileFormat,
+) -> io::Result<()> {
+    let bytes = format.write(cookies)?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+// ===== impl FileCookieStore =====
+
+impl FileCookieStore {
+    /// Opens the store kept in the file at `path`, in the format told by its
+    /// extension, see [`CookieFileFormat::from_path`].
+    ///
+    /// The store starts empty if the file does not exist yet.
+    pub fn open<P>(path: P) -> io::Result<FileCookieStore>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let format = CookieFileFormat::from_path(&path);
+        FileCookieStore::open_with_format(path, format)
+    }
+
+    /// Opens the store kept in the file at `path`, in `format`.
+    ///
+    /// The store starts empty if the file does not exist yet.
+    pub fn open_with_format<P>(path: P, format: CookieFileFormat) -> io::Result<FileCookieStore>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let jar = Jar::default();
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                for cookie in format.read(&text)? {
+                    cookie.add_to(&jar);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(FileCookieStore { jar, path, format })
+    }
+
+    /// Returns the jar holding the cookies of the store.
+    #[inline]
+    pub fn jar(&self) -> &Jar {
+        &self.jar
+    }
+
+    /// Returns the path of the file the store is kept in.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the cookies of the store to its file.
+    pub fn save(&self) -> io::Result<()> {
+        save_cookies(&self.jar.all_cookies(), &self.path, self.format)
+    }
+}
+
+impl CookieStore for FileCookieStore {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, uri: &Uri) {
+        self.jar.set_cookies(cookie_headers, uri);
+    }
+
+    fn cookies(&self, uri: &Uri) -> Vec<HeaderValue> {
+        self.jar.cookies(uri)
+    }
+
+    fn all_cookies(&self) -> Vec<(String, Cookie<'static>)> {
+        self.jar.all_cookies()
+    }
+}
+
+impl Drop for FileCookieStore {
+    fn drop(&mut self) {
+        if let Err(_err) = self.save() {
+            debug!("error saving cookies to {}: {}", self.path.display(), _err);
+        }
+    }
+}
+
+impl fmt::Debug for FileCookieStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileCookieStore")
+            .field("path", &self.path)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
 const DEFAULT_PATH: &str = "/";
 
 /// Determines if the given `host` matches the cookie `domain` according to
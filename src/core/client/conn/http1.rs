@@ -19,6 +19,7 @@ use crate::core::{
         body::Incoming as IncomingBody,
         dispatch::{self, TrySendError},
         options::http1::Http1Options,
+        probe::{self, Probe},
         proto,
     },
     error::BoxError,
@@ -30,6 +31,7 @@ type Dispatcher<T, B> =
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::Sender<Request<B>, Response<IncomingBody>>,
+    probe: Option<Probe>,
 }
 
 /// Deconstructed parts of a `Connection`.
@@ -91,6 +93,7 @@ where
 #[derive(Clone, Debug)]
 pub struct Builder {
     opts: Http1Options,
+    probe: bool,
 }
 
 // ===== impl SendRequest
@@ -120,6 +123,17 @@ impl<B> SendRequest<B> {
     pub fn is_ready(&self) -> bool {
         self.dispatch.is_ready()
     }
+
+    /// Checks that the connection was not closed by the server while idle.
+    ///
+    /// Without liveness probes enabled on the [`Builder`], this is
+    /// [`is_ready`](SendRequest::is_ready).
+    pub(crate) async fn is_alive(&mut self) -> bool {
+        match self.probe {
+            Some(ref probe) => probe.check().await && self.is_ready(),
+            None => self.is_ready(),
+        }
+    }
 }
 
 impl<B> SendRequest<B>
@@ -231,9 +245,16 @@ impl Builder {
     pub fn new() -> Builder {
         Builder {
             opts: Default::default(),
+            probe: false,
         }
     }
 
+    /// Lets [`SendRequest`]s check that the connection is still alive before reuse.
+    #[inline]
+    pub(crate) fn probe(&mut self, enabled: bool) {
+        self.probe = enabled;
+    }
+
     /// Provide a options configuration for the HTTP/1 connection.
     #[inline]
     pub fn options(&mut self, opts: Http1Options) {
@@ -302,9 +323,21 @@ impl Builder {
         }
 
         let cd = proto::h1::dispatch::Client::new(rx);
-        let proto = proto::h1::Dispatcher::new(cd, conn);
-
-        Ok((SendRequest { dispatch: tx }, Connection { inner: proto }))
+        let mut proto = proto::h1::Dispatcher::new(cd, conn);
+
+        let probe = self.probe.then(|| {
+            let (probe, responder) = probe::channel();
+            proto.set_probe(responder);
+            probe
+        });
+
+        Ok((
+            SendRequest {
+                dispatch: tx,
+                probe,
+            },
+            Connection { inner: proto },
+        ))
     }
 }
 
@@ -3,14 +3,18 @@
 use std::{
     fmt,
     future::Future,
+    io,
     marker::PhantomData,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll, ready},
 };
 
+use bytes::Bytes;
+use futures_util::FutureExt;
 use http::{Request, Response};
 use http_body::Body;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 use crate::{
     core::{
@@ -25,17 +29,20 @@ use crate::{
         rt::{ArcTimer, Time, Timer},
     },
     http2::Http2Options,
+    sync::Mutex,
 };
 
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::UnboundedSender<Request<B>, Response<IncomingBody>>,
+    prober: Option<ping::Prober>,
 }
 
 impl<B> Clone for SendRequest<B> {
     fn clone(&self) -> SendRequest<B> {
         SendRequest {
             dispatch: self.dispatch.clone(),
+            prober: self.prober.clone(),
         }
     }
 }
@@ -68,6 +75,7 @@ pub struct Builder<Ex> {
     exec: Ex,
     timer: Time,
     opts: Http2Options,
+    probe: bool,
 }
 
 // ===== impl SendRequest
@@ -106,6 +114,22 @@ impl<B> SendRequest<B> {
     pub fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
+
+    /// Sends a PING and waits for the server's PONG.
+    ///
+    /// Without liveness probes enabled on the [`Builder`], this only checks that
+    /// the connection is not closed.
+    pub(crate) async fn is_alive(&mut self) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+
+        match self.prober.as_ref().and_then(ping::Prober::probe) {
+            Some(pong) => pong.await.is_ok() && !self.is_closed(),
+            // Probes are disabled, or the ping could not be sent
+            None => self.prober.is_none(),
+        }
+    }
 }
 
 impl<B> SendRequest<B>
@@ -199,6 +223,7 @@ where
             exec,
             timer: Time::Empty,
             opts: Default::default(),
+            probe: false,
         }
     }
 
@@ -217,6 +242,13 @@ where
         self.opts = opts;
     }
 
+    /// Lets [`SendRequest`]s ping the server to check that the connection is
+    /// alive before reuse.
+    #[inline]
+    pub(crate) fn probe(&mut self, enabled: bool) {
+        self.probe = enabled;
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::core::client::conn) for more.
     ///
@@ -232,63 +264,6 @@ where
     {
         trace!("client handshake HTTP/2");
 
-        // Crate the HTTP/2 client with the provided options.
-        let builder = {
-            let mut builder = http2::client::Builder::default();
-            builder
-                .initial_max_send_streams(self.opts.initial_max_send_streams)
-                .initial_window_size(self.opts.initial_window_size)
-                .initial_connection_window_size(self.opts.initial_conn_window_size)
-                .max_send_buffer_size(self.opts.max_send_buffer_size);
-            if let Some(id) = self.opts.initial_stream_id {
-                builder.initial_stream_id(id);
-            }
-            if let Some(max) = self.opts.max_pending_accept_reset_streams {
-                builder.max_pending_accept_reset_streams(max);
-            }
-            if let Some(max) = self.opts.max_concurrent_reset_streams {
-                builder.max_concurrent_reset_streams(max);
-            }
-            if let Some(max) = self.opts.max_concurrent_streams {
-                builder.max_concurrent_streams(max);
-            }
-            if let Some(max) = self.opts.max_header_list_size {
-                builder.max_header_list_size(max);
-            }
-            if let Some(opt) = self.opts.enable_push {
-                builder.enable_push(opt);
-            }
-            if let Some(max) = self.opts.max_frame_size {
-                builder.max_frame_size(max);
-            }
-            if let Some(max) = self.opts.header_table_size {
-                builder.header_table_size(max);
-            }
-            if let Some(v) = self.opts.enable_connect_protocol {
-                builder.enable_connect_protocol(v);
-            }
-            if let Some(v) = self.opts.no_rfc7540_priorities {
-                builder.no_rfc7540_priorities(v);
-            }
-            if let Some(order) = self.opts.settings_order {
-                builder.settings_order(order);
-            }
-            if let Some(experimental_settings) = self.opts.experimental_settings {
-                builder.experimental_settings(experimental_settings);
-            }
-            if let Some(stream_dependency) = self.opts.headers_stream_dependency {
-                builder.headers_stream_dependency(stream_dependency);
-            }
-            if let Some(order) = self.opts.headers_pseudo_order {
-                builder.headers_pseudo_order(order);
-            }
-            if let Some(priority) = self.opts.priorities {
-                builder.priorities(priority);
-            }
-
-            builder
-        };
-
         // Create the ping configuration for the connection.
         let ping_config = ping::Config::new(
             self.opts.adaptive_window,
@@ -296,14 +271,20 @@ where
             self.opts.keep_alive_interval,
             self.opts.keep_alive_timeout,
             self.opts.keep_alive_while_idle,
+            self.probe,
         );
 
+        // Crate the HTTP/2 client with the provided options.
+        let builder = client_builder(self.opts);
+
         let (tx, rx) = dispatch::channel();
         let h2 = proto::h2::client::handshake(io, rx, builder, ping_config, self.exec, self.timer)
             .await?;
+        let prober = h2.prober();
         Ok((
             SendRequest {
                 dispatch: tx.unbound(),
+                prober,
             },
             Connection {
                 inner: (PhantomData, h2),
@@ -311,3 +292,115 @@ where
         ))
     }
 }
+
+/// Creates the HTTP/2 client builder for `opts`.
+fn client_builder(opts: Http2Options) -> http2::client::Builder {
+    let mut builder = http2::client::Builder::default();
+    builder
+        .initial_max_send_streams(opts.initial_max_send_streams)
+        .initial_window_size(opts.initial_window_size)
+        .initial_connection_window_size(opts.initial_conn_window_size)
+        .max_send_buffer_size(opts.max_send_buffer_size);
+    if let Some(id) = opts.initial_stream_id {
+        builder.initial_stream_id(id);
+    }
+    if let Some(max) = opts.max_pending_accept_reset_streams {
+        builder.max_pending_accept_reset_streams(max);
+    }
+    if let Some(max) = opts.max_concurrent_reset_streams {
+        builder.max_concurrent_reset_streams(max);
+    }
+    if let Some(max) = opts.max_concurrent_streams {
+        builder.max_concurrent_streams(max);
+    }
+    if let Some(max) = opts.max_header_list_size {
+        builder.max_header_list_size(max);
+    }
+    if let Some(opt) = opts.enable_push {
+        builder.enable_push(opt);
+    }
+    if let Some(max) = opts.max_frame_size {
+        builder.max_frame_size(max);
+    }
+    if let Some(max) = opts.header_table_size {
+        builder.header_table_size(max);
+    }
+    if let Some(v) = opts.enable_connect_protocol {
+        builder.enable_connect_protocol(v);
+    }
+    if let Some(v) = opts.no_rfc7540_priorities {
+        builder.no_rfc7540_priorities(v);
+    }
+    if let Some(order) = opts.settings_order {
+        builder.settings_order(order);
+    }
+    if let Some(experimental_settings) = opts.experimental_settings {
+        builder.experimental_settings(experimental_settings);
+    }
+    if let Some(stream_dependency) = opts.headers_stream_dependency {
+        builder.headers_stream_dependency(stream_dependency);
+    }
+    if let Some(order) = opts.headers_pseudo_order {
+        builder.headers_pseudo_order(order);
+    }
+    if let Some(priority) = opts.priorities {
+        builder.priorities(priority);
+    }
+
+    builder
+}
+
+/// Returns what a connection with `opts` writes before hearing from the
+/// server: the connection preface, the initial frames and the HEADERS frame of
+/// a `GET https://example.com/` request.
+pub(crate) fn client_preface(opts: Http2Options) -> std::result::Result<Vec<u8>, BoxError> {
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let (mut send_request, conn) = client_builder(opts)
+        .handshake::<_, Bytes>(PrefaceCapture(written.clone()))
+        .now_or_never()
+        .ok_or("HTTP/2 handshake did not complete")??;
+
+    let req = Request::get("https://example.com/").body(())?;
+    let _stream = send_request.send_request(req, true)?;
+
+    // The capture never answers, so the connection stops once it has written
+    // everything it can
+    if let Some(res) = conn.now_or_never() {
+        res?;
+    }
+
+    let written = written.lock();
+    Ok(written.clone())
+}
+
+/// Records everything written to it and never yields data to read.
+struct PrefaceCapture(Arc<Mutex<Vec<u8>>>);
+
+impl AsyncRead for PrefaceCapture {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for PrefaceCapture {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0.lock().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
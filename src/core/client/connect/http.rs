@@ -8,22 +8,28 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{self, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures_util::future::Either;
 use http::uri::{Scheme, Uri};
+use ipnet::IpNet;
 use pin_project_lite::pin_project;
 use socket2::TcpKeepalive;
 use tokio::{
     net::{TcpSocket, TcpStream},
     time::Sleep,
 };
+#[cfg(feature = "cancel-token")]
+use tokio_util::sync::CancellationToken;
 
 use super::{Connected, Connection};
+#[cfg(feature = "cancel-token")]
+use crate::error::Canceled;
 use crate::{
     core::BoxError,
-    dns::{self, GaiResolver, InternalResolve, resolve},
+    dns::{self, GaiResolver, InternalResolve, lru::LruEntries, resolve},
+    sync::Mutex,
 };
 
 /// A connector for the `http` scheme.
@@ -66,6 +72,46 @@ pub struct HttpInfo {
     local_addr: SocketAddr,
 }
 
+/// The outcome of connecting to one address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectOutcome {
+    /// The connection was established.
+    Connected,
+    /// The peer refused the connection.
+    Refused,
+    /// The attempt timed out.
+    TimedOut,
+    /// No route to the host or network.
+    Unreachable,
+    /// The attempt failed for another reason.
+    Failed,
+}
+
+/// One address tried while establishing a connection.
+///
+/// With Happy Eyeballs, attempts to the preferred and the fallback address family
+/// run concurrently, and are listed in the order they finished. Attempts still in
+/// progress when another address connected are not listed.
+#[derive(Clone, Debug)]
+pub struct ConnectAttempt {
+    addr: SocketAddr,
+    outcome: ConnectOutcome,
+    elapsed: Duration,
+}
+
+/// Collects the attempts of a single connection, shared between racing attempts.
+#[derive(Clone, Default)]
+pub(crate) struct AttemptLog(Arc<Mutex<Vec<ConnectAttempt>>>);
+
+/// Maximum number of hosts whose winning address family is remembered
+const MAX_FAMILY_PREFERENCES: usize = 256;
+
+/// Remembers which address family won the last Happy Eyeballs race to each host,
+/// so that the next connection to it tries that family first.
+#[derive(Clone)]
+struct FamilyPreferences(Arc<Mutex<LruEntries<bool>>>);
+
 /// Options for configuring a TCP network connection.
 ///
 /// `TcpConnectOptions` allows fine-grained control over how TCP sockets
@@ -193,6 +239,88 @@ impl TcpConnectOptions {
         self.local_ipv4 = local_ipv4.into();
         self.local_ipv6 = local_ipv6.into();
     }
+
+    /// Returns the IP version sockets are limited to by the bound local
+    /// addresses and interface, if they only allow one.
+    ///
+    /// This scans the network interfaces, so it is called once when the client is
+    /// built rather than for each connection.
+    pub(crate) fn ip_version(&self) -> Option<dns::IpVersion> {
+        let (v4, v6) = match (self.local_ipv4, self.local_ipv6) {
+            (None, None) => (true, true),
+            (v4, v6) => (v4.is_some(), v6.is_some()),
+        };
+
+        #[cfg(any(
+            target_os = "android",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "solaris",
+            target_os = "tvos",
+            target_os = "visionos",
+            target_os = "watchos",
+        ))]
+        let (v4, v6) = match self
+            .interface
+            .as_ref()
+            .and_then(|interface| interface_ip_versions(interface.as_bytes()))
+        {
+            Some((has_v4, has_v6)) => (v4 && has_v4, v6 && has_v6),
+            None => (v4, v6),
+        };
+
+        match (v4, v6) {
+            (true, false) => Some(dns::IpVersion::V4Only),
+            (false, true) => Some(dns::IpVersion::V6Only),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether the network interface `name` has IPv4 and IPv6 addresses,
+/// or `None` if there is no such interface.
+#[cfg(any(
+    target_os = "android",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "solaris",
+    target_os = "tvos",
+    target_os = "visionos",
+    target_os = "watchos",
+))]
+#[allow(unsafe_code)]
+fn interface_ip_versions(name: &[u8]) -> Option<(bool, bool)> {
+    let mut addrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return None;
+    }
+
+    let (mut found, mut v4, mut v6) = (false, false, false);
+    let mut next = addrs;
+    // SAFETY: `getifaddrs` returned a valid linked list, freed only below
+    while let Some(ifa) = unsafe { next.as_ref() } {
+        next = ifa.ifa_next;
+        if ifa.ifa_name.is_null()
+            || unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) }.to_bytes() != name
+        {
+            continue;
+        }
+        found = true;
+        if let Some(addr) = unsafe { ifa.ifa_addr.as_ref() } {
+            match i32::from(addr.sa_family) {
+                libc::AF_INET => v4 = true,
+                libc::AF_INET6 => v6 = true,
+                _ => {}
+            }
+        }
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+
+    found.then_some((v4, v6))
 }
 
 #[derive(Clone)]
@@ -202,12 +330,21 @@ struct Config {
     happy_eyeballs_timeout: Option<Duration>,
     tcp_keepalive_config: TcpKeepaliveConfig,
     tcp_connect_options: TcpConnectOptions,
+    remote_addr: Option<SocketAddr>,
+    allowed_ports: Option<Arc<[u16]>>,
+    denied_ip_ranges: Option<Arc<[IpNet]>>,
     nodelay: bool,
     reuse_address: bool,
     send_buffer_size: Option<usize>,
     recv_buffer_size: Option<usize>,
     #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
     tcp_user_timeout: Option<Duration>,
+    attempt_log: Option<AttemptLog>,
+    trace_id: Option<Arc<str>>,
+    #[cfg(feature = "cancel-token")]
+    cancel_token: Option<CancellationToken>,
+    family_preferences: FamilyPreferences,
+    dns_cache: dns::DnsCache,
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -346,12 +483,21 @@ impl<R> HttpConnector<R> {
                 happy_eyeballs_timeout: Some(Duration::from_millis(300)),
                 tcp_keepalive_config: TcpKeepaliveConfig::default(),
                 tcp_connect_options: TcpConnectOptions::default(),
+                remote_addr: None,
+                allowed_ports: None,
+                denied_ip_ranges: None,
                 nodelay: false,
                 reuse_address: false,
                 send_buffer_size: None,
                 recv_buffer_size: None,
                 #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
                 tcp_user_timeout: None,
+                attempt_log: None,
+                trace_id: None,
+                #[cfg(feature = "cancel-token")]
+                cancel_token: None,
+                family_preferences: FamilyPreferences::new(),
+                dns_cache: dns::DnsCache::global(),
             }),
             resolver,
         }
@@ -442,6 +588,49 @@ impl<R> HttpConnector<R> {
         }
     }
 
+    /// Connect to a fixed remote address instead of resolving the URI host.
+    ///
+    /// A port of `0` is replaced by the port of the URI.
+    ///
+    /// Default is `None`.
+    #[inline]
+    pub fn set_remote_address(&mut self, addr: Option<SocketAddr>) {
+        self.config_mut().remote_addr = addr;
+    }
+
+    /// Restrict connections to the given remote ports.
+    ///
+    /// Addresses whose port is not in the list are skipped, and connecting fails
+    /// if none remain.
+    ///
+    /// Default is `None`, allowing any port.
+    #[inline]
+    pub fn set_allowed_ports(&mut self, ports: Option<Arc<[u16]>>) {
+        self.config_mut().allowed_ports = ports;
+    }
+
+    /// Set the IP ranges outgoing connections may not connect to.
+    ///
+    /// Addresses in one of the ranges are skipped along with those on ports
+    /// that are not allowed, and connecting fails if none remain.
+    ///
+    /// Default is `None`, allowing any address.
+    #[inline]
+    pub fn set_denied_ip_ranges(&mut self, ranges: Option<Arc<[IpNet]>>) {
+        self.config_mut().denied_ip_ranges = ranges;
+    }
+
+    /// Set the DNS cache that the resolver fills.
+    ///
+    /// When connecting to every resolved address fails, the host is evicted from
+    /// this cache before resolving it once more.
+    ///
+    /// Default is the global DNS cache, which the default resolver uses.
+    #[inline]
+    pub fn set_dns_cache(&mut self, cache: dns::DnsCache) {
+        self.config_mut().dns_cache = cache;
+    }
+
     /// Set the connect timeout.
     ///
     /// If a domain resolves to multiple IP addresses, the timeout will be
@@ -458,7 +647,8 @@ impl<R> HttpConnector<R> {
     /// If hostname resolves to both IPv4 and IPv6 addresses and connection
     /// cannot be established using preferred address family before timeout
     /// elapses, then connector will in parallel attempt connection using other
-    /// address family.
+    /// address family. The losing attempt is cancelled, and the family that won
+    /// is tried first on the next connection to the same host.
     ///
     /// If `None`, parallel connection attempts are disabled.
     ///
@@ -486,6 +676,25 @@ impl<R> HttpConnector<R> {
         self.config_mut().tcp_user_timeout = time;
     }
 
+    /// Records every address tried by this connector into `log`.
+    #[inline]
+    pub(crate) fn set_attempt_log(&mut self, log: Option<AttemptLog>) {
+        self.config_mut().attempt_log = log;
+    }
+
+    /// Records `id` as the `trace_id` of the DNS resolution spans of this connector.
+    #[inline]
+    pub(crate) fn set_trace_id(&mut self, id: Option<Arc<str>>) {
+        self.config_mut().trace_id = id;
+    }
+
+    /// Aborts DNS resolutions of this connector once `token` is cancelled.
+    #[cfg(feature = "cancel-token")]
+    #[inline]
+    pub(crate) fn set_cancel_token(&mut self, token: Option<CancellationToken>) {
+        self.config_mut().cancel_token = token;
+    }
+
     // private
 
     fn config_mut(&mut self) -> &mut Config {
@@ -499,6 +708,7 @@ impl<R> HttpConnector<R> {
 static INVALID_NOT_HTTP: &str = "invalid URI, scheme is not http";
 static INVALID_MISSING_SCHEME: &str = "invalid URI, scheme is missing";
 static INVALID_MISSING_HOST: &str = "invalid URI, host is missing";
+static ADDR_NOT_ALLOWED: &str = "no address with an allowed port and IP";
 
 // R: Debug required for now to allow adding it to debug output later...
 impl<R: fmt::Debug> fmt::Debug for HttpConnector<R> {
@@ -543,6 +753,7 @@ fn get_host_port<'u>(config: &Config, dst: &'u Uri) -> Result<(&'u str, u16), Co
                 msg: INVALID_NOT_HTTP,
                 addr: None,
                 cause: None,
+                attempts: None,
             });
         }
     } else if dst.scheme().is_none() {
@@ -550,6 +761,7 @@ fn get_host_port<'u>(config: &Config, dst: &'u Uri) -> Result<(&'u str, u16), Co
             msg: INVALID_MISSING_SCHEME,
             addr: None,
             cause: None,
+            attempts: None,
         });
     }
 
@@ -560,6 +772,7 @@ fn get_host_port<'u>(config: &Config, dst: &'u Uri) -> Result<(&'u str, u16), Co
                 msg: INVALID_MISSING_HOST,
                 addr: None,
                 cause: None,
+                attempts: None,
             });
         }
     };
@@ -587,26 +800,58 @@ where
         let (host, port) = get_host_port(config, &dst)?;
         let host = host.trim_start_matches('[').trim_end_matches(']');
 
-        // If the host is already an IP addr (v4 or v6),
+        // If a remote address is forced, or the host is already an IP addr (v4 or v6),
         // skip resolving the dns and start connecting right away.
-        let addrs = if let Some(addrs) = dns::SocketAddrs::try_parse(host, port) {
-            addrs
+        let explicit_port = dst.port().is_some();
+        let https = dst.scheme() == Some(&Scheme::HTTPS) && port == 443;
+        let (addrs, resolved) = if let Some(mut addr) = config.remote_addr {
+            set_port(&mut addr, port, false);
+            (dns::SocketAddrs::new(vec![addr]), false)
+        } else if let Some(addrs) = dns::SocketAddrs::try_parse(host, port) {
+            (addrs, false)
         } else {
-            let addrs = resolve(&mut self.resolver, dns::Name::new(host.into()))
-                .await
-                .map_err(ConnectError::dns)?;
-            let addrs = addrs
-                .map(|mut addr| {
-                    set_port(&mut addr, port, dst.port().is_some());
-                    addr
-                })
-                .collect();
-            dns::SocketAddrs::new(addrs)
+            let addrs =
+                resolve_addrs(&mut self.resolver, host, port, explicit_port, https, config).await?;
+            (addrs, true)
         };
 
-        let c = ConnectingTcp::new(addrs, config);
+        let addrs = filter_allowed_addrs(config, addrs)?;
 
-        let sock = c.connect().await?;
+        // Race the address families, starting with the one that won last time
+        let dual_stack = config.happy_eyeballs_timeout.is_some() && addrs.is_dual_stack();
+        let addrs = if dual_stack {
+            config.family_preferences.order(host, addrs)
+        } else {
+            addrs
+        };
+
+        let sock = match ConnectingTcp::new(addrs, config).connect().await {
+            // The resolved addresses may be stale: drop them from the cache and
+            // try once more with a fresh resolution.
+            Err(err) if resolved && err.is_unreachable() => {
+                debug!("connect to {} failed ({:?}), re-resolving", host, err);
+                config.dns_cache.evict(host, dns::EvictReason::Unreachable);
+                if https {
+                    config.dns_cache.evict(
+                        &dns::cache::https_cache_key(host),
+                        dns::EvictReason::Unreachable,
+                    );
+                }
+
+                let addrs =
+                    resolve_addrs(&mut self.resolver, host, port, explicit_port, https, config)
+                        .await?;
+                let addrs = filter_allowed_addrs(config, addrs)?;
+                ConnectingTcp::new(addrs, config).connect().await?
+            }
+            result => result?,
+        };
+
+        if dual_stack {
+            if let Ok(remote) = sock.peer_addr() {
+                config.family_preferences.record(host, remote);
+            }
+        }
 
         if let Err(_e) = sock.set_nodelay(config.nodelay) {
             warn!("tcp set_nodelay error: {_e}");
@@ -616,6 +861,113 @@ where
     }
 }
 
+impl<R> HttpConnector<R>
+where
+    R: InternalResolve,
+{
+    /// Resolve `host` the way a connection to it would, including the
+    /// allowed-ports check, without connecting.
+    pub(crate) async fn resolve_host(
+        &mut self,
+        host: &str,
+        port: u16,
+    ) -> Result<dns::SocketAddrs, ConnectError> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs = match dns::SocketAddrs::try_parse(host, port) {
+            Some(addrs) => addrs,
+            None => {
+                resolve_addrs(&mut self.resolver, host, port, true, false, &self.config).await?
+            }
+        };
+        filter_allowed_addrs(&self.config, addrs)
+    }
+
+    /// Connect to one of the given addresses using this connector's TCP settings.
+    pub(crate) async fn connect_addrs(
+        &self,
+        addrs: dns::SocketAddrs,
+    ) -> Result<TcpStream, ConnectError> {
+        ConnectingTcp::new(addrs, &self.config).connect().await
+    }
+}
+
+async fn resolve_addrs<R>(
+    resolver: &mut R,
+    host: &str,
+    port: u16,
+    explicit_port: bool,
+    https: bool,
+    _config: &Config,
+) -> Result<dns::SocketAddrs, ConnectError>
+where
+    R: InternalResolve,
+{
+    let resolving = resolve(resolver, dns::Name::new(host.into()).with_https(https));
+    #[cfg(feature = "tracing")]
+    let resolving = tracing::Instrument::instrument(
+        resolving,
+        tracing::debug_span!("resolve", host, trace_id = _config.trace_id.as_deref()),
+    );
+
+    #[cfg(feature = "cancel-token")]
+    let resolved = match _config.cancel_token {
+        Some(ref token) => match token.run_until_cancelled(resolving).await {
+            Some(resolved) => resolved,
+            None => return Err(ConnectError::dns(Canceled)),
+        },
+        None => resolving.await,
+    };
+    #[cfg(not(feature = "cancel-token"))]
+    let resolved = resolving.await;
+
+    let addrs = resolved
+        .map_err(ConnectError::dns)?
+        .map(|mut addr| {
+            set_port(&mut addr, port, explicit_port);
+            addr
+        })
+        .collect();
+    Ok(dns::SocketAddrs::new(addrs))
+}
+
+/// Drop any address whose port is not allowed, or whose IP is in a denied
+/// range, before connecting.
+fn filter_allowed_addrs(
+    config: &Config,
+    addrs: dns::SocketAddrs,
+) -> Result<dns::SocketAddrs, ConnectError> {
+    if config.allowed_ports.is_none() && config.denied_ip_ranges.is_none() {
+        return Ok(addrs);
+    }
+
+    let addrs = addrs.filter(|addr| is_allowed_addr(config, addr));
+    if addrs.is_empty() {
+        return Err(ConnectError {
+            msg: ADDR_NOT_ALLOWED,
+            addr: None,
+            cause: None,
+            attempts: None,
+        });
+    }
+    Ok(addrs)
+}
+
+fn is_allowed_addr(config: &Config, addr: &SocketAddr) -> bool {
+    if let Some(ref ports) = config.allowed_ports {
+        if !ports.contains(&addr.port()) {
+            return false;
+        }
+    }
+    if let Some(ref ranges) = config.denied_ip_ranges {
+        // An IPv4-mapped IPv6 address reaches the IPv4 host it maps
+        let ip = addr.ip().to_canonical();
+        if ranges.iter().any(|range| range.contains(&ip)) {
+            return false;
+        }
+    }
+    true
+}
+
 impl Connection for TcpStream {
     fn connected(&self) -> Connected {
         let connected = Connected::new();
@@ -630,6 +982,85 @@ impl Connection for TcpStream {
     }
 }
 
+impl ConnectOutcome {
+    fn from_error(err: &ConnectError) -> ConnectOutcome {
+        let kind = err
+            .cause
+            .as_ref()
+            .and_then(|cause| cause.downcast_ref::<io::Error>())
+            .map(io::Error::kind);
+
+        match kind {
+            Some(io::ErrorKind::ConnectionRefused) => ConnectOutcome::Refused,
+            Some(io::ErrorKind::TimedOut) => ConnectOutcome::TimedOut,
+            Some(io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable) => {
+                ConnectOutcome::Unreachable
+            }
+            _ => ConnectOutcome::Failed,
+        }
+    }
+}
+
+impl ConnectAttempt {
+    /// Get the address that was tried.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Get the outcome of the attempt.
+    pub fn outcome(&self) -> ConnectOutcome {
+        self.outcome
+    }
+
+    /// Get how long the attempt took.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl AttemptLog {
+    fn record(&self, addr: SocketAddr, outcome: ConnectOutcome, elapsed: Duration) {
+        self.0.lock().push(ConnectAttempt {
+            addr,
+            outcome,
+            elapsed,
+        });
+    }
+
+    /// Returns the attempts recorded so far.
+    pub(crate) fn snapshot(&self) -> Arc<[ConnectAttempt]> {
+        self.0.lock().as_slice().into()
+    }
+}
+
+impl FamilyPreferences {
+    fn new() -> Self {
+        FamilyPreferences(Arc::new(Mutex::new(LruEntries::with_capacity(0))))
+    }
+
+    /// Puts the addresses of the family that last won for `host` first
+    fn order(&self, host: &str, addrs: dns::SocketAddrs) -> dns::SocketAddrs {
+        match self.0.lock().get(host) {
+            Some(&ipv6) => addrs.prefer_family(ipv6),
+            None => addrs,
+        }
+    }
+
+    fn record(&self, host: &str, remote: SocketAddr) {
+        let mut preferences = self.0.lock();
+        if preferences.peek(host) == Some(&remote.is_ipv6()) {
+            return;
+        }
+        trace!("{} won the connection race to {}", remote, host);
+        if preferences.len() >= MAX_FAMILY_PREFERENCES && preferences.peek(host).is_none() {
+            if let Some(oldest) = preferences.oldest().cloned() {
+                preferences.remove(&oldest);
+            }
+        }
+        preferences.insert(host.to_owned(), remote.is_ipv6());
+    }
+}
+
 impl HttpInfo {
     /// Get the remote address of the transport used.
     pub fn remote_addr(&self) -> SocketAddr {
@@ -672,6 +1103,7 @@ pub struct ConnectError {
     msg: &'static str,
     addr: Option<SocketAddr>,
     cause: Option<BoxError>,
+    attempts: Option<Arc<[ConnectAttempt]>>,
 }
 
 impl ConnectError {
@@ -683,6 +1115,7 @@ impl ConnectError {
             msg,
             addr: None,
             cause: Some(cause.into()),
+            attempts: None,
         }
     }
 
@@ -693,6 +1126,28 @@ impl ConnectError {
         ConnectError::new("dns error", cause)
     }
 
+    /// Whether the peer actively refused the connection or could not be routed
+    /// to, which suggests the address itself is wrong rather than the network
+    /// being slow.
+    fn is_unreachable(&self) -> bool {
+        self.cause
+            .as_ref()
+            .and_then(|cause| cause.downcast_ref::<io::Error>())
+            .is_some_and(|err| {
+                matches!(
+                    err.kind(),
+                    io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::HostUnreachable
+                        | io::ErrorKind::NetworkUnreachable
+                )
+            })
+    }
+
+    /// The addresses tried before giving up, if they were recorded.
+    pub(crate) fn attempts(&self) -> Option<&[ConnectAttempt]> {
+        self.attempts.as_deref()
+    }
+
     fn m<E>(msg: &'static str) -> impl FnOnce(E) -> ConnectError
     where
         E: Into<BoxError>,
@@ -792,7 +1247,17 @@ impl ConnectingTcpRemote {
         let mut err = None;
         for addr in &mut self.addrs {
             debug!("connecting to {}", addr);
-            match connect(&addr, config, self.connect_timeout)?.await {
+            let start = Instant::now();
+            let result = connect(&addr, config, self.connect_timeout)?.await;
+            if let Some(ref log) = config.attempt_log {
+                let outcome = match result {
+                    Ok(_) => ConnectOutcome::Connected,
+                    Err(ref e) => ConnectOutcome::from_error(e),
+                };
+                log.record(addr, outcome, start.elapsed());
+            }
+
+            match result {
                 Ok(tcp) => {
                     debug!("connected to {}", addr);
                     return Ok(tcp);
@@ -977,7 +1442,15 @@ fn connect(
 }
 
 impl ConnectingTcp<'_> {
-    async fn connect(mut self) -> Result<TcpStream, ConnectError> {
+    async fn connect(self) -> Result<TcpStream, ConnectError> {
+        let log = self.config.attempt_log.clone();
+        self.race().await.map_err(|mut err| {
+            err.attempts = log.map(|log| log.snapshot());
+            err
+        })
+    }
+
+    async fn race(mut self) -> Result<TcpStream, ConnectError> {
         match self.fallback {
             None => self.preferred.connect(self.config).await,
             Some(mut fallback) => {
@@ -1023,3 +1496,33 @@ fn set_port(addr: &mut SocketAddr, host_port: u16, explicit: bool) {
         addr.set_port(host_port)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_addresses_limit_ip_version() {
+        let mut opts = TcpConnectOptions::default();
+        assert_eq!(opts.ip_version(), None);
+
+        opts.set_local_address(Some(Ipv4Addr::LOCALHOST.into()));
+        assert_eq!(opts.ip_version(), Some(dns::IpVersion::V4Only));
+
+        opts.set_local_address(Some(Ipv6Addr::LOCALHOST.into()));
+        assert_eq!(opts.ip_version(), None);
+
+        opts.set_local_addresses(None, Ipv6Addr::LOCALHOST);
+        assert_eq!(opts.ip_version(), Some(dns::IpVersion::V6Only));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unknown_interface_does_not_limit_ip_version() {
+        assert_eq!(interface_ip_versions(b"wreq-missing0"), None);
+
+        let mut opts = TcpConnectOptions::default();
+        opts.set_interface("wreq-missing0");
+        assert_eq!(opts.ip_version(), None);
+    }
+}
@@ -1,6 +1,8 @@
 //! Connectors used by the `Client`.
 
 mod http;
+#[cfg(windows)]
+mod named_pipe;
 #[cfg(unix)]
 mod uds;
 
@@ -16,7 +18,10 @@ use std::{
 
 use ::http::Extensions;
 
-pub use self::http::{HttpConnector, HttpInfo, TcpConnectOptions};
+pub(crate) use self::http::{AttemptLog, ConnectError};
+pub use self::http::{ConnectAttempt, ConnectOutcome, HttpConnector, HttpInfo, TcpConnectOptions};
+#[cfg(windows)]
+pub use self::named_pipe::NamedPipeConnector;
 #[cfg(unix)]
 pub use self::uds::UnixConnector;
 
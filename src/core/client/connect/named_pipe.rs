@@ -0,0 +1,64 @@
+use std::{
+    ffi::OsStr,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http::Uri;
+use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient};
+
+use super::{Connected, Connection};
+
+/// The Windows error returned while every instance of a pipe is in use.
+const ERROR_PIPE_BUSY: i32 = 231;
+
+/// How long to wait before opening a busy pipe again.
+const PIPE_BUSY_DELAY: Duration = Duration::from_millis(50);
+
+/// How long to keep opening a busy pipe before giving up.
+const PIPE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type ConnectResult = io::Result<NamedPipeClient>;
+type BoxConnecting = Pin<Box<dyn Future<Output = ConnectResult> + Send>>;
+
+#[derive(Clone)]
+pub struct NamedPipeConnector(pub(crate) Arc<OsStr>);
+
+impl tower::Service<Uri> for NamedPipeConnector {
+    type Response = NamedPipeClient;
+    type Error = io::Error;
+    type Future = BoxConnecting;
+
+    #[inline]
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Uri) -> Self::Future {
+        let pipe = self.0.clone();
+        Box::pin(async move {
+            let deadline = tokio::time::Instant::now() + PIPE_BUSY_TIMEOUT;
+            loop {
+                match ClientOptions::new().open(&pipe) {
+                    Err(e)
+                        if e.raw_os_error() == Some(ERROR_PIPE_BUSY)
+                            && tokio::time::Instant::now() < deadline =>
+                    {
+                        tokio::time::sleep(PIPE_BUSY_DELAY).await;
+                    }
+                    res => return res,
+                }
+            }
+        })
+    }
+}
+
+impl Connection for NamedPipeClient {
+    #[inline]
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{self, Poll, ready},
+    time::{Duration, Instant},
+};
+
+use bytes::{Buf, Bytes};
+use futures_util::FutureExt;
+use http::{HeaderMap, Method, Request, StatusCode, Uri};
+use http2::{
+    Reason, RecvStream, SendStream,
+    client::{Builder, SendRequest},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower::{Service, ServiceExt};
+
+use super::tunnel::TunnelError;
+use crate::{
+    core::{
+        BoxError,
+        client::{
+            common::{Exec, exec::BoxSendFuture},
+            connect::{Connected, Connection},
+        },
+        rt::Executor,
+    },
+    ext::UriExt,
+    hash::{HASHER, HashMap},
+    sync::Mutex,
+};
+
+/// The HTTP/2 connection to a proxy, if one was made. Locked across connecting,
+/// so that concurrent tunnels through the proxy wait for a single connection.
+type ProxySlot = Arc<tokio::sync::Mutex<Option<ProxyConn>>>;
+
+/// Tunnels via HTTP/2 CONNECT
+///
+/// Each tunnel is a stream of a single HTTP/2 connection to the proxy, so
+/// many tunnels through the same proxy share one connection instead of each
+/// opening its own.
+pub struct Http2Tunnels {
+    exec: Exec,
+    idle_timeout: Option<Duration>,
+    conns: Arc<Mutex<HashMap<Uri, ProxySlot>>>,
+    sweeping: AtomicBool,
+}
+
+/// A cached HTTP/2 connection to a proxy.
+struct ProxyConn {
+    send_request: SendRequest<Bytes>,
+    last_used: Instant,
+}
+
+/// A tunnel opened as a stream of an HTTP/2 connection to a proxy.
+pub struct Http2Tunnel {
+    send_stream: SendStream<Bytes>,
+    recv_stream: RecvStream,
+    buf: Bytes,
+}
+
+impl Http2Tunnels {
+    /// Creates the tunnels, running the connections to proxies on `exec`.
+    ///
+    /// A connection no tunnel was opened through for `idle_timeout` is no
+    /// longer reused, and closes once its open tunnels are done.
+    pub fn new<E>(exec: E, idle_timeout: Option<Duration>) -> Http2Tunnels
+    where
+        E: Executor<BoxSendFuture> + Send + Sync + 'static,
+    {
+        Http2Tunnels {
+            exec: Exec::new(exec),
+            idle_timeout,
+            conns: Arc::new(Mutex::new(HashMap::with_hasher(HASHER))),
+            sweeping: AtomicBool::new(false),
+        }
+    }
+
+    /// Opens a tunnel to `dst` through the proxy at `proxy_dst`, sending
+    /// `headers` with the CONNECT request.
+    ///
+    /// The HTTP/2 connection to the proxy is reused while it is open, and
+    /// created with `connector` otherwise.
+    pub async fn tunnel<C>(
+        &self,
+        connector: C,
+        proxy_dst: &Uri,
+        dst: &Uri,
+        headers: HeaderMap,
+    ) -> Result<Http2Tunnel, TunnelError>
+    where
+        C: Service<Uri>,
+        C::Response: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
+        C::Error: Into<BoxError>,
+    {
+        let host = dst.host().ok_or(TunnelError::MissingHost)?;
+        let mut req = Request::builder()
+            .method(Method::CONNECT)
+            .uri(format!("{host}:{}", dst.port_or_default()))
+            .body(())
+            .map_err(|e| TunnelError::ConnectFailed(e.into()))?;
+        *req.headers_mut() = headers;
+
+        let slot = self
+            .conns
+            .lock()
+            .entry(proxy_dst.clone())
+            .or_default()
+            .clone();
+
+        let send_request = {
+            let mut conn = slot.lock().await;
+            let cached = conn
+                .take()
+                .filter(|conn| !self.is_idle(conn))
+                .map(|conn| conn.send_request)
+                // A connection that failed errors right away, a busy one is
+                // waited for below
+                .filter(|send_request| {
+                    !matches!(send_request.clone().ready().now_or_never(), Some(Err(_)))
+                });
+            let send_request = match cached {
+                Some(send_request) => send_request,
+                None => {
+                    trace!("connecting to HTTP/2 proxy {:?}", proxy_dst);
+                    self.connect(connector, proxy_dst).await?
+                }
+            };
+            *conn = Some(ProxyConn {
+                send_request: send_request.clone(),
+                last_used: Instant::now(),
+            });
+            send_request
+        };
+
+        let mut send_request = send_request.ready().await.map_err(TunnelError::Http2)?;
+        let (response, send_stream) = send_request
+            .send_request(req, false)
+            .map_err(TunnelError::Http2)?;
+        let res = response.await.map_err(TunnelError::Http2)?;
+
+        match res.status() {
+            StatusCode::OK => Ok(Http2Tunnel {
+                send_stream,
+                recv_stream: res.into_body(),
+                buf: Bytes::new(),
+            }),
+            StatusCode::PROXY_AUTHENTICATION_REQUIRED => Err(TunnelError::ProxyAuthRequired),
+            _ => Err(TunnelError::TunnelUnsuccessful),
+        }
+    }
+
+    /// Connects to the proxy and starts an HTTP/2 connection with it.
+    async fn connect<C>(
+        &self,
+        connector: C,
+        proxy_dst: &Uri,
+    ) -> Result<SendRequest<Bytes>, TunnelError>
+    where
+        C: Service<Uri>,
+        C::Response: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
+        C::Error: Into<BoxError>,
+    {
+        let io = connector
+            .oneshot(proxy_dst.clone())
+            .await
+            .map_err(|e| TunnelError::ConnectFailed(e.into()))?;
+
+        // A TLS connection must have agreed on HTTP/2, a plain one assumes it
+        if proxy_dst.is_https() && !io.connected().is_negotiated_h2() {
+            return Err(TunnelError::Http2NotNegotiated);
+        }
+
+        let builder = Builder::default();
+        let (send_request, conn) = builder
+            .handshake::<_, Bytes>(io)
+            .await
+            .map_err(TunnelError::Http2)?;
+        self.exec.execute(async move {
+            if let Err(_e) = conn.await {
+                debug!("HTTP/2 proxy connection error: {}", _e);
+            }
+        });
+        self.spawn_idle_sweep();
+
+        send_request.ready().await.map_err(TunnelError::Http2)
+    }
+
+    fn is_idle(&self, conn: &ProxyConn) -> bool {
+        is_idle(conn, self.idle_timeout)
+    }
+
+    /// Starts dropping the connections that became idle, once.
+    fn spawn_idle_sweep(&self) {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+        if self.sweeping.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let conns = Arc::downgrade(&self.conns);
+        self.exec.execute(async move {
+            loop {
+                tokio::time::sleep(idle_timeout).await;
+                if !sweep(&conns, idle_timeout) {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Drops the cached connections unused for `idle_timeout`, returning whether
+/// the tunnels are still around.
+fn sweep(conns: &Weak<Mutex<HashMap<Uri, ProxySlot>>>, idle_timeout: Duration) -> bool {
+    let Some(conns) = conns.upgrade() else {
+        return false;
+    };
+
+    conns.lock().retain(|_proxy, slot| {
+        // A slot being connected or waited for is in use
+        let Ok(mut conn) = slot.try_lock() else {
+            return true;
+        };
+        if conn
+            .as_ref()
+            .is_some_and(|conn| is_idle(conn, Some(idle_timeout)))
+        {
+            trace!("closing idle HTTP/2 proxy connection to {:?}", _proxy);
+            *conn = None;
+        }
+        conn.is_some() || Arc::strong_count(slot) > 1
+    });
+    true
+}
+
+fn is_idle(conn: &ProxyConn, idle_timeout: Option<Duration>) -> bool {
+    idle_timeout.is_some_and(|timeout| conn.last_used.elapsed() >= timeout)
+}
+
+impl Connection for Http2Tunnel {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for Http2Tunnel {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        read_buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.buf.is_empty() {
+            self.buf = loop {
+                match ready!(self.recv_stream.poll_data(cx)) {
+                    None => return Poll::Ready(Ok(())),
+                    Some(Ok(buf)) if buf.is_empty() && !self.recv_stream.is_end_stream() => {
+                        continue;
+                    }
+                    Some(Ok(buf)) => break buf,
+                    Some(Err(e)) => {
+                        return Poll::Ready(match e.reason() {
+                            Some(Reason::NO_ERROR) | Some(Reason::CANCEL) => Ok(()),
+                            Some(Reason::STREAM_CLOSED) => {
+                                Err(io::Error::new(io::ErrorKind::BrokenPipe, e))
+                            }
+                            _ => Err(h2_to_io_error(e)),
+                        });
+                    }
+                }
+            };
+        }
+        let cnt = std::cmp::min(self.buf.len(), read_buf.remaining());
+        read_buf.put_slice(&self.buf[..cnt]);
+        self.buf.advance(cnt);
+        let _ = self.recv_stream.flow_control().release_capacity(cnt);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Http2Tunnel {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        self.send_stream.reserve_capacity(buf.len());
+
+        // Errors of `poll_capacity` and `send_data` are read from `poll_reset`
+        let cnt = match ready!(self.send_stream.poll_capacity(cx)) {
+            None => Some(0),
+            Some(Ok(cnt)) => self
+                .send_stream
+                .send_data(Bytes::copy_from_slice(&buf[..cnt]), false)
+                .ok()
+                .map(|()| cnt),
+            Some(Err(_)) => None,
+        };
+
+        if let Some(cnt) = cnt {
+            return Poll::Ready(Ok(cnt));
+        }
+
+        Poll::Ready(Err(h2_to_io_error(
+            match ready!(self.send_stream.poll_reset(cx)) {
+                Ok(Reason::NO_ERROR) | Ok(Reason::CANCEL) | Ok(Reason::STREAM_CLOSED) => {
+                    return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+                }
+                Ok(reason) => reason.into(),
+                Err(e) => e,
+            },
+        )))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        if self.send_stream.send_data(Bytes::new(), true).is_ok() {
+            return Poll::Ready(Ok(()));
+        }
+
+        Poll::Ready(Err(h2_to_io_error(
+            match ready!(self.send_stream.poll_reset(cx)) {
+                Ok(Reason::NO_ERROR) => return Poll::Ready(Ok(())),
+                Ok(Reason::CANCEL) | Ok(Reason::STREAM_CLOSED) => {
+                    return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+                }
+                Ok(reason) => reason.into(),
+                Err(e) => e,
+            },
+        )))
+    }
+}
+
+impl std::fmt::Debug for Http2Tunnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Http2Tunnel").finish()
+    }
+}
+
+fn h2_to_io_error(e: http2::Error) -> io::Error {
+    if e.is_io() {
+        e.into_io().unwrap()
+    } else {
+        io::Error::other(e)
+    }
+}
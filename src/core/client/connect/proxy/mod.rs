@@ -1,4 +1,5 @@
 //! Proxy helpers
+pub mod h2;
 #[cfg(feature = "socks")]
 pub mod socks;
 pub mod tunnel;
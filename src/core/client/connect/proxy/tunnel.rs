@@ -35,6 +35,8 @@ enum Headers {
 #[derive(Debug)]
 pub enum TunnelError {
     ConnectFailed(BoxError),
+    Http2(http2::Error),
+    Http2NotNegotiated,
     Io(std::io::Error),
     MissingHost,
     ProxyAuthRequired,
@@ -271,6 +273,8 @@ impl std::fmt::Display for TunnelError {
             TunnelError::TunnelUnexpectedEof => "unexpected end of file",
             TunnelError::TunnelUnsuccessful => "unsuccessful",
             TunnelError::ConnectFailed(_) => "failed to create underlying connection",
+            TunnelError::Http2(_) => "HTTP/2 error establishing tunnel",
+            TunnelError::Http2NotNegotiated => "proxy did not negotiate HTTP/2",
             TunnelError::Io(_) => "io error establishing tunnel",
         })
     }
@@ -280,6 +284,7 @@ impl std::error::Error for TunnelError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             TunnelError::Io(e) => Some(e),
+            TunnelError::Http2(e) => Some(e),
             TunnelError::ConnectFailed(e) => Some(&**e),
             _ => None,
         }
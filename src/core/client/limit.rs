@@ -0,0 +1,130 @@
+//! Limits on the number of connections a client keeps open.
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    hash::{HASHER, HashMap},
+    sync::Mutex,
+};
+
+/// Bounds the connections open to each host, and across hosts.
+///
+/// Requests waiting for a connection are served in the order they started
+/// waiting, as the underlying semaphores are fair.
+#[derive(Clone)]
+pub(crate) struct ConnectionLimits {
+    per_host: Option<usize>,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    total: Option<Arc<Semaphore>>,
+}
+
+/// Allows a connection to be open, until it is dropped.
+pub(crate) struct ConnectionPermit {
+    _host: Option<OwnedSemaphorePermit>,
+    _total: Option<OwnedSemaphorePermit>,
+}
+
+// ===== impl ConnectionLimits =====
+
+impl ConnectionLimits {
+    pub(crate) fn new(per_host: Option<usize>, total: Option<usize>) -> ConnectionLimits {
+        ConnectionLimits {
+            per_host: per_host.map(clamp),
+            hosts: Arc::new(Mutex::new(HashMap::with_hasher(HASHER))),
+            total: total.map(|max| Arc::new(Semaphore::new(clamp(max)))),
+        }
+    }
+
+    /// Returns whether any limit is set.
+    #[inline]
+    pub(crate) fn is_limited(&self) -> bool {
+        self.per_host.is_some() || self.total.is_some()
+    }
+
+    /// Returns whether a new connection would have to wait for the total limit.
+    pub(crate) fn is_total_exhausted(&self) -> bool {
+        self.total
+            .as_ref()
+            .is_some_and(|total| total.available_permits() == 0)
+    }
+
+    /// Returns whether a new connection to `host` would have to wait for the
+    /// per-host limit.
+    pub(crate) fn is_host_exhausted(&self, host: &str) -> bool {
+        self.per_host.is_some()
+            && self
+                .hosts
+                .lock()
+                .get(&host.to_ascii_lowercase())
+                .is_some_and(|semaphore| semaphore.available_permits() == 0)
+    }
+
+    /// Waits until a new connection to `host` is allowed.
+    pub(crate) async fn acquire(&self, host: &str) -> ConnectionPermit {
+        let host = match self.per_host {
+            Some(max) => {
+                let semaphore = self.host_semaphore(host, max);
+                semaphore.acquire_owned().await.ok()
+            }
+            None => None,
+        };
+        let total = match self.total {
+            Some(ref total) => total.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        ConnectionPermit {
+            _host: host,
+            _total: total,
+        }
+    }
+
+    fn host_semaphore(&self, host: &str, max: usize) -> Arc<Semaphore> {
+        let host = host.to_ascii_lowercase();
+        let mut hosts = self.hosts.lock();
+        if let Some(semaphore) = hosts.get(&host) {
+            return semaphore.clone();
+        }
+
+        // Forget the hosts no connection is open or waited for: their
+        // semaphores are only referenced by this map.
+        hosts.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        hosts
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(max)))
+            .clone()
+    }
+}
+
+fn clamp(max: usize) -> usize {
+    max.clamp(1, Semaphore::MAX_PERMITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_are_per_host_and_total() {
+        let limits = ConnectionLimits::new(Some(1), Some(2));
+
+        let a = limits.acquire("a.example").await;
+        let pending = limits.acquire("A.example");
+        futures_util::pin_mut!(pending);
+        assert!(pending.as_mut().now_or_never().is_none());
+
+        assert!(limits.is_host_exhausted("a.example"));
+        assert!(!limits.is_host_exhausted("b.example"));
+
+        let _b = limits.acquire("b.example").await;
+        assert!(limits.is_total_exhausted());
+
+        drop(a);
+        // The permit of `a` goes to the request waiting for it first
+        let _a = pending.await;
+        assert!(limits.is_total_exhausted());
+    }
+}
@@ -0,0 +1,298 @@
+//! Counters of the connection pool, and the events they are made of.
+
+use std::{num::NonZeroU32, sync::Arc, time::Duration};
+
+use schnellru::ByLength;
+
+use crate::{
+    hash::{HASHER, LruMap},
+    sync::Mutex,
+};
+
+/// Maximum number of hosts whose counters are kept.
+const MAX_HOSTS: u32 = 1024;
+
+/// A callback receiving the [`PoolEvent`]s of a client.
+pub(crate) type PoolEventCallback = Arc<dyn Fn(&PoolEvent) + Send + Sync>;
+
+/// Something that happened in the connection pool of a client.
+///
+/// Set a callback receiving them with
+/// [`ClientBuilder::on_pool_event`](crate::ClientBuilder::on_pool_event), to
+/// export them to a metrics system.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum PoolEvent {
+    /// A new connection was established.
+    #[non_exhaustive]
+    Connected {
+        /// The host connected to.
+        host: String,
+        /// The time taken to connect, including the TLS and HTTP/2 handshakes.
+        handshake: Duration,
+        /// Whether the connection speaks HTTP/2.
+        http2: bool,
+    },
+    /// An idle connection from the pool was used for a request.
+    #[non_exhaustive]
+    Reused {
+        /// The host of the connection.
+        host: String,
+    },
+}
+
+/// A snapshot of the connection pool of a client, see
+/// [`Client::pool_stats`](crate::Client::pool_stats).
+#[derive(Clone, Debug, Default)]
+pub struct PoolStats {
+    hosts: Vec<HostPoolStats>,
+}
+
+/// The connections to one host in a [`PoolStats`].
+#[derive(Clone, Debug, Default)]
+pub struct HostPoolStats {
+    host: String,
+    idle: usize,
+    active: usize,
+    created: u64,
+    reused: u64,
+    handshake_total: Duration,
+    handshake_max: Duration,
+}
+
+/// Records the connections made and reused by a client.
+#[derive(Clone)]
+pub(crate) struct PoolMetrics {
+    hosts: Arc<Mutex<LruMap<String, HostPoolStats>>>,
+    callback: Option<PoolEventCallback>,
+}
+
+// ===== impl PoolStats =====
+
+impl PoolStats {
+    /// Returns the statistics of each host with pooled connections, or
+    /// connections made since the client was built.
+    #[inline]
+    pub fn hosts(&self) -> &[HostPoolStats] {
+        &self.hosts
+    }
+
+    /// Returns the statistics of `host`, if any.
+    pub fn host(&self, host: &str) -> Option<&HostPoolStats> {
+        self.hosts
+            .iter()
+            .find(|stats| stats.host.eq_ignore_ascii_case(host))
+    }
+
+    /// Returns the number of idle connections, across hosts.
+    pub fn idle(&self) -> usize {
+        self.hosts.iter().map(HostPoolStats::idle).sum()
+    }
+
+    /// Returns the number of connections in use by a request, across hosts.
+    pub fn active(&self) -> usize {
+        self.hosts.iter().map(HostPoolStats::active).sum()
+    }
+
+    /// Returns the number of connections established, across hosts.
+    pub fn created(&self) -> u64 {
+        self.hosts.iter().map(HostPoolStats::created).sum()
+    }
+
+    /// Returns the number of times a pooled connection was reused, across hosts.
+    pub fn reused(&self) -> u64 {
+        self.hosts.iter().map(HostPoolStats::reused).sum()
+    }
+
+    /// Returns the share of requests sent on a reused connection, from 0 to 1.
+    pub fn reuse_ratio(&self) -> f64 {
+        reuse_ratio(self.created(), self.reused())
+    }
+}
+
+// ===== impl HostPoolStats =====
+
+impl HostPoolStats {
+    /// Returns the host.
+    #[inline]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the number of idle connections to the host.
+    ///
+    /// An HTTP/2 connection stays in the pool while requests share it, so it
+    /// is counted as idle.
+    #[inline]
+    pub fn idle(&self) -> usize {
+        self.idle
+    }
+
+    /// Returns the number of HTTP/1 connections to the host in use by a request.
+    #[inline]
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Returns the number of connections established to the host.
+    #[inline]
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns the number of times a pooled connection to the host was reused.
+    #[inline]
+    pub fn reused(&self) -> u64 {
+        self.reused
+    }
+
+    /// Returns the share of requests to the host sent on a reused connection,
+    /// from 0 to 1.
+    pub fn reuse_ratio(&self) -> f64 {
+        reuse_ratio(self.created, self.reused)
+    }
+
+    /// Returns the average time taken to connect to the host, including the
+    /// TLS and HTTP/2 handshakes.
+    pub fn handshake_avg(&self) -> Option<Duration> {
+        let created = u32::try_from(self.created).ok().and_then(NonZeroU32::new)?;
+        Some(self.handshake_total / created.get())
+    }
+
+    /// Returns the longest time taken to connect to the host.
+    #[inline]
+    pub fn handshake_max(&self) -> Duration {
+        self.handshake_max
+    }
+}
+
+fn reuse_ratio(created: u64, reused: u64) -> f64 {
+    match created + reused {
+        0 => 0.0,
+        total => reused as f64 / total as f64,
+    }
+}
+
+// ===== impl PoolMetrics =====
+
+impl PoolMetrics {
+    pub(crate) fn new(callback: Option<PoolEventCallback>) -> PoolMetrics {
+        PoolMetrics {
+            hosts: Arc::new(Mutex::new(LruMap::with_hasher(
+                ByLength::new(MAX_HOSTS),
+                HASHER,
+            ))),
+            callback,
+        }
+    }
+
+    /// Records a connection established to `host`.
+    pub(crate) fn connected(&self, host: &str, handshake: Duration, http2: bool) {
+        self.update(host, |stats| {
+            stats.created += 1;
+            stats.handshake_total += handshake;
+            stats.handshake_max = stats.handshake_max.max(handshake);
+        });
+
+        if let Some(ref callback) = self.callback {
+            callback(&PoolEvent::Connected {
+                host: host.to_owned(),
+                handshake,
+                http2,
+            });
+        }
+    }
+
+    /// Records a pooled connection to `host` being reused.
+    pub(crate) fn reused(&self, host: &str) {
+        self.update(host, |stats| stats.reused += 1);
+
+        if let Some(ref callback) = self.callback {
+            callback(&PoolEvent::Reused {
+                host: host.to_owned(),
+            });
+        }
+    }
+
+    fn update<F>(&self, host: &str, update: F)
+    where
+        F: FnOnce(&mut HostPoolStats),
+    {
+        let mut hosts = self.hosts.lock();
+        let stats = hosts.get_or_insert(host.to_owned(), || HostPoolStats {
+            host: host.to_owned(),
+            ..Default::default()
+        });
+        if let Some(stats) = stats {
+            update(stats);
+        }
+    }
+
+    /// Returns the statistics of each host, given the idle and active
+    /// connections currently pooled for each.
+    pub(crate) fn stats<I>(&self, connections: I) -> PoolStats
+    where
+        I: IntoIterator<Item = (String, usize, usize)>,
+    {
+        let mut hosts: Vec<HostPoolStats> = self
+            .hosts
+            .lock()
+            .iter()
+            .map(|(_, stats)| stats.clone())
+            .collect();
+
+        for (host, idle, active) in connections {
+            let index = match hosts
+                .iter()
+                .position(|stats| stats.host.eq_ignore_ascii_case(&host))
+            {
+                Some(index) => index,
+                None => {
+                    hosts.push(HostPoolStats {
+                        host,
+                        ..Default::default()
+                    });
+                    hosts.len() - 1
+                }
+            };
+            hosts[index].idle += idle;
+            hosts[index].active += active;
+        }
+
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+        PoolStats { hosts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_merge_counters_with_pooled_connections() {
+        let metrics = PoolMetrics::new(None);
+        metrics.connected("a.example", Duration::from_millis(30), false);
+        metrics.connected("a.example", Duration::from_millis(10), false);
+        metrics.reused("a.example");
+        metrics.reused("a.example");
+
+        let stats = metrics.stats([
+            ("a.example".to_owned(), 1, 1),
+            ("b.example".to_owned(), 2, 0),
+        ]);
+
+        let a = stats.host("a.example").unwrap();
+        assert_eq!(
+            (a.idle(), a.active(), a.created(), a.reused()),
+            (1, 1, 2, 2)
+        );
+        assert_eq!(a.handshake_avg(), Some(Duration::from_millis(20)));
+        assert_eq!(a.handshake_max(), Duration::from_millis(30));
+        assert_eq!(a.reuse_ratio(), 0.5);
+
+        let b = stats.host("b.example").unwrap();
+        assert_eq!((b.idle(), b.created(), b.handshake_avg()), (2, 0, None));
+        assert_eq!((stats.idle(), stats.active()), (3, 1));
+        assert_eq!(stats.reuse_ratio(), 0.5);
+    }
+}
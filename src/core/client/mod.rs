@@ -3,18 +3,21 @@
 mod bounds;
 mod common;
 mod dispatch;
+mod limit;
 mod pool;
+mod probe;
 mod proto;
 mod service;
 
 pub mod body;
 pub mod conn;
 pub mod connect;
+pub mod metrics;
 pub mod options;
 pub mod upgrade;
 
 pub(crate) use self::service::{
-    ConnectRequest, HttpClient,
+    ConnectRequest, ConnectionPool, HttpClient,
     error::Error,
     extra::{ConnectExtra, Identifier},
 };
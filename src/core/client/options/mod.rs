@@ -1,6 +1,8 @@
 pub mod http1;
 pub mod http2;
 
+use std::net::SocketAddr;
+
 use http::Version;
 use http1::Http1Options;
 use http2::Http2Options;
@@ -93,6 +95,7 @@ pub(crate) struct RequestOptions {
     enforced_version: Option<Version>,
     tcp_connect_opts: TcpConnectOptions,
     transport_opts: TransportOptions,
+    remote_addr: Option<SocketAddr>,
 }
 
 impl RequestOptions {
@@ -143,4 +146,16 @@ impl RequestOptions {
     pub fn transport_opts_mut(&mut self) -> &mut TransportOptions {
         &mut self.transport_opts
     }
+
+    /// Get the forced remote address, bypassing DNS resolution.
+    #[inline]
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Get a mutable reference to the forced remote address.
+    #[inline]
+    pub fn remote_addr_mut(&mut self) -> &mut Option<SocketAddr> {
+        &mut self.remote_addr
+    }
 }
@@ -14,7 +14,7 @@ use std::{
 };
 
 use schnellru::ByLength;
-use tokio::sync::oneshot;
+use tokio::sync::{Notify, oneshot};
 
 use crate::{
     core::{
@@ -79,6 +79,9 @@ struct PoolInner<T, K: Eq + Hash> {
     // should be shared. This prevents making multiple HTTP/2 connections
     // to the same host.
     connecting: HashSet<K>,
+    // The number of connections being established for each key, of any
+    // version.
+    pending: HashMap<K, usize>,
     // These are internal Conns sitting in the event loop in the KeepAlive
     // state, waiting to receive a new Request to send on the socket.
     idle: LruMap<K, Vec<Idle<T>>>,
@@ -93,6 +96,16 @@ struct PoolInner<T, K: Eq + Hash> {
     // them that the Conn could be used instead of waiting for a brand new
     // connection.
     waiters: HashMap<K, VecDeque<oneshot::Sender<T>>>,
+    // The number of unique connections checked out for each key, which hope
+    // to be reinserted into `idle` once their request completes.
+    checked_out: HashMap<K, usize>,
+    // When the connections of a key were last closed. Connections checked out
+    // or started connecting before then are dropped instead of being
+    // (re)inserted. Kept until nothing is checked out or connecting for the key.
+    closed_at: HashMap<K, Instant>,
+    // Notified whenever a connection is added to `idle`, so requests waiting
+    // on a connection limit can close it to make room.
+    idle_notify: Arc<Notify>,
     // A oneshot channel is used to allow the interval to be notified when
     // the Pool completely drops. That way, the interval can cancel immediately.
     idle_interval_ref: Option<oneshot::Sender<Infallible>>,
@@ -113,8 +126,10 @@ pub struct Config {
 }
 
 impl Config {
+    /// Pooling is disabled when no idle connections may be kept, or when they
+    /// would expire immediately.
     pub fn is_enabled(&self) -> bool {
-        self.max_idle_per_host > 0
+        self.max_idle_per_host > 0 && self.idle_timeout != Some(Duration::ZERO)
     }
 }
 
@@ -127,13 +142,17 @@ impl<T, K: Key> Pool<T, K> {
         let inner = if config.is_enabled() {
             Some(Arc::new(Mutex::new(PoolInner {
                 connecting: HashSet::with_hasher(HASHER),
+                pending: HashMap::with_hasher(HASHER),
                 idle: LruMap::with_hasher(
                     ByLength::new(config.max_pool_size.map_or(u32::MAX, NonZero::get)),
                     HASHER,
                 ),
+                idle_notify: Arc::new(Notify::new()),
                 idle_interval_ref: None,
                 max_idle_per_host: config.max_idle_per_host,
                 waiters: HashMap::with_hasher(HASHER),
+                checked_out: HashMap::with_hasher(HASHER),
+                closed_at: HashMap::with_hasher(HASHER),
                 exec: Exec::new(executor),
                 timer: timer.map(ArcTimer::new),
                 timeout: config.idle_timeout,
@@ -148,6 +167,104 @@ impl<T, K: Key> Pool<T, K> {
     pub(crate) fn is_enabled(&self) -> bool {
         self.inner.is_some()
     }
+
+    /// Closes the idle connections whose key matches `predicate`, returning how
+    /// many were closed.
+    ///
+    /// Connections checked out or still connecting for such a key are dropped
+    /// instead of being (re)inserted once their request completes, so no
+    /// connection started before this call is handed out again.
+    pub fn close_matching<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let Some(ref enabled) = self.inner else {
+            return 0;
+        };
+
+        let now = Instant::now();
+        let mut inner = enabled.lock();
+        let inner = &mut *inner;
+
+        let keys: Vec<K> = inner
+            .idle
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| predicate(key))
+            .cloned()
+            .collect();
+
+        let mut closed = 0;
+        for key in keys {
+            if let Some(list) = inner.idle.remove(&key) {
+                debug!("closing {} idle connection(s) for {:?}", list.len(), key);
+                closed += list.len();
+            }
+        }
+
+        let in_use = inner.checked_out.keys().chain(inner.pending.keys());
+        for key in in_use.filter(|key| predicate(key)) {
+            inner.closed_at.insert(key.clone(), now);
+        }
+
+        closed
+    }
+
+    /// Closes the idle connections of the least recently used key matching
+    /// `predicate`, returning how many were closed.
+    pub fn close_oldest_idle<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let Some(ref enabled) = self.inner else {
+            return 0;
+        };
+
+        let mut inner = enabled.lock();
+        let oldest = inner
+            .idle
+            .iter()
+            .filter(|(key, list)| !list.is_empty() && predicate(*key))
+            .map(|(key, _)| key)
+            .last()
+            .cloned();
+
+        match oldest.and_then(|key| inner.idle.remove(&key)) {
+            Some(list) => {
+                debug!("closing {} idle connection(s)", list.len());
+                list.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns a handle notified whenever a connection becomes idle.
+    pub fn idle_notify(&self) -> Option<Arc<Notify>> {
+        self.inner
+            .as_ref()
+            .map(|enabled| enabled.lock().idle_notify.clone())
+    }
+
+    /// Returns the number of idle and checked out connections of each key.
+    pub fn connection_counts(&self) -> Vec<(K, usize, usize)> {
+        let Some(ref enabled) = self.inner else {
+            return Vec::new();
+        };
+
+        let inner = enabled.lock();
+        let mut counts: Vec<(K, usize, usize)> = inner
+            .idle
+            .iter()
+            .map(|(key, list)| (key.clone(), list.len(), 0))
+            .collect();
+        for (key, checked_out) in inner.checked_out.iter() {
+            match counts.iter_mut().find(|(idle_key, ..)| idle_key == key) {
+                Some(count) => count.2 += checked_out,
+                None => counts.push((key.clone(), 0, *checked_out)),
+            }
+        }
+        counts
+    }
 }
 
 impl<T: Poolable, K: Key> Pool<T, K> {
@@ -164,28 +281,33 @@ impl<T: Poolable, K: Key> Pool<T, K> {
     /// Ensure that there is only ever 1 connecting task for HTTP/2
     /// connections. This does nothing for HTTP/1.
     pub fn connecting(&self, key: K, ver: Ver) -> Option<Connecting<T, K>> {
-        if ver == Ver::Http2 {
-            if let Some(ref enabled) = self.inner {
-                let mut inner = enabled.lock();
-                return if inner.connecting.insert(key.clone()) {
-                    let connecting = Connecting {
-                        key,
-                        pool: WeakOpt::downgrade(enabled),
-                    };
-                    Some(connecting)
-                } else {
-                    trace!("HTTP/2 connecting already in progress for {:?}", key);
-                    None
-                };
+        let Some(ref enabled) = self.inner else {
+            return Some(Connecting {
+                key,
+                pool: WeakOpt::none(),
+                pending: WeakOpt::none(),
+                started_at: Instant::now(),
+            });
+        };
+
+        let mut inner = enabled.lock();
+        let pool = if ver == Ver::Http2 {
+            if !inner.connecting.insert(key.clone()) {
+                trace!("HTTP/2 connecting already in progress for {:?}", key);
+                return None;
             }
-        }
+            WeakOpt::downgrade(enabled)
+        } else {
+            // in HTTP/1's case, there is never a lock to release in Drop.
+            WeakOpt::none()
+        };
+        *inner.pending.entry(key.clone()).or_insert(0) += 1;
 
-        // else
         Some(Connecting {
             key,
-            // in HTTP/1's case, there is never a lock, so we don't
-            // need to do anything in Drop.
-            pool: WeakOpt::none(),
+            pool,
+            pending: WeakOpt::downgrade(enabled),
+            started_at: Instant::now(),
         })
     }
 
@@ -194,7 +316,11 @@ impl<T: Poolable, K: Key> Pool<T, K> {
             match value.reserve() {
                 Reservation::Shared(to_insert, to_return) => {
                     let mut inner = enabled.lock();
-                    inner.put(&connecting.key, to_insert, enabled);
+                    if inner.started_before_close(&connecting.key, connecting.started_at) {
+                        trace!("connections closed, not pooling ({:?})", connecting.key);
+                    } else {
+                        inner.put(&connecting.key, to_insert, enabled);
+                    }
                     // Do this here instead of Drop for Connecting because we
                     // already have a lock, no need to lock the mutex twice.
                     inner.connected(&connecting.key);
@@ -222,9 +348,15 @@ impl<T: Poolable, K: Key> Pool<T, K> {
             (value, WeakOpt::none())
         };
 
+        if let Some(pool) = pool_ref.upgrade() {
+            pool.lock().check_out(&connecting.key);
+        }
+
         Pooled {
             key: connecting.key.clone(),
             is_reused: false,
+            // A close while still connecting must drop this connection too
+            checked_out_at: connecting.started_at,
             pool: pool_ref,
             value: Some(value),
         }
@@ -243,12 +375,14 @@ impl<T: Poolable, K: Key> Pool<T, K> {
         let mut pool_ref = WeakOpt::none();
         if !value.can_share() {
             if let Some(ref enabled) = self.inner {
+                enabled.lock().check_out(key);
                 pool_ref = WeakOpt::downgrade(enabled);
             }
         }
 
         Pooled {
             is_reused: true,
+            checked_out_at: Instant::now(),
             key: key.clone(),
             pool: pool_ref,
             value: Some(value),
@@ -368,6 +502,8 @@ impl<T: Poolable, K: Key> PoolInner<T, K> {
                 }
             }
 
+            self.idle_notify.notify_waiters();
+
             self.spawn_idle_interval(__pool_ref);
         } else {
             trace!("put; found waiter for {:?}", key)
@@ -433,6 +569,62 @@ impl<T: Poolable, K: Key> PoolInner<T, K> {
     }
 }
 
+impl<T, K: Key> PoolInner<T, K> {
+    /// A unique connection was handed out for `key`.
+    fn check_out(&mut self, key: &K) {
+        *self.checked_out.entry(key.clone()).or_insert(0) += 1;
+    }
+
+    /// A unique connection handed out for `key` at `checked_out_at` is done with.
+    ///
+    /// Returns `false` if the connections of `key` were closed since, in which
+    /// case it must not be reinserted.
+    fn check_in(&mut self, key: &K, checked_out_at: Instant) -> bool {
+        let remaining = match self.checked_out.get_mut(key) {
+            Some(count) => {
+                *count -= 1;
+                *count
+            }
+            None => 0,
+        };
+        if remaining == 0 {
+            self.checked_out.remove(key);
+        }
+
+        let closed = self.started_before_close(key, checked_out_at);
+        self.forget_close(key);
+        !closed
+    }
+
+    /// A connection for `key` stopped connecting, whether it was established
+    /// or not.
+    fn connect_done(&mut self, key: &K) {
+        if let Some(count) = self.pending.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                self.pending.remove(key);
+            }
+        }
+        self.forget_close(key);
+    }
+
+    /// Whether a connection for `key` dating from `since` predates the last
+    /// close of its key.
+    fn started_before_close(&self, key: &K, since: Instant) -> bool {
+        self.closed_at
+            .get(key)
+            .is_some_and(|&closed_at| since <= closed_at)
+    }
+
+    /// Once nothing is checked out or connecting for `key`, no connection can
+    /// predate the close anymore.
+    fn forget_close(&mut self, key: &K) {
+        if !self.checked_out.contains_key(key) && !self.pending.contains_key(key) {
+            self.closed_at.remove(key);
+        }
+    }
+}
+
 impl<T, K: Eq + Hash> PoolInner<T, K> {
     /// Any `FutureResponse`s that were created will have made a `Checkout`,
     /// and possibly inserted into the pool that it is waiting for an idle
@@ -500,6 +692,7 @@ impl<T, K: Key> Clone for Pool<T, K> {
 pub struct Pooled<T: Poolable, K: Key> {
     value: Option<T>,
     is_reused: bool,
+    checked_out_at: Instant,
     key: K,
     pool: WeakOpt<Mutex<PoolInner<T, K>>>,
 }
@@ -538,14 +731,19 @@ impl<T: Poolable, K: Key> DerefMut for Pooled<T, K> {
 impl<T: Poolable, K: Key> Drop for Pooled<T, K> {
     fn drop(&mut self) {
         if let Some(value) = self.value.take() {
-            if !value.is_open() {
-                // If we *already* know the connection is done here,
-                // it shouldn't be re-inserted back into the pool.
-                return;
-            }
-
             if let Some(pool) = self.pool.upgrade() {
                 let mut inner = pool.lock();
+                if !inner.check_in(&self.key, self.checked_out_at) {
+                    trace!("connections closed, dropping pooled ({:?})", self.key);
+                    return;
+                }
+
+                if !value.is_open() {
+                    // If we *already* know the connection is done here,
+                    // it shouldn't be re-inserted back into the pool.
+                    return;
+                }
+
                 inner.put(&self.key, value, &pool);
             } else if !value.can_share() {
                 trace!("pool dropped, dropping pooled ({:?})", self.key);
@@ -709,7 +907,11 @@ impl<T, K: Key> Drop for Checkout<T, K> {
 
 pub struct Connecting<T: Poolable, K: Key> {
     key: K,
+    // Set for HTTP/2, which holds the `connecting` lock of its key.
     pool: WeakOpt<Mutex<PoolInner<T, K>>>,
+    // Set whenever the pool is enabled, to count this connect as pending.
+    pending: WeakOpt<Mutex<PoolInner<T, K>>>,
+    started_at: Instant,
 }
 
 impl<T: Poolable, K: Key> Connecting<T, K> {
@@ -725,10 +927,13 @@ impl<T: Poolable, K: Key> Connecting<T, K> {
 
 impl<T: Poolable, K: Key> Drop for Connecting<T, K> {
     fn drop(&mut self) {
-        if let Some(pool) = self.pool.upgrade() {
+        if let Some(pool) = self.pending.upgrade() {
             // No need to panic on drop, that could abort!
             let mut inner = pool.lock();
-            inner.connected(&self.key);
+            if self.pool.0.is_some() {
+                inner.connected(&self.key);
+            }
+            inner.connect_done(&self.key);
         }
     }
 }
@@ -815,7 +1020,7 @@ mod tests {
         time::Duration,
     };
 
-    use super::{Connecting, Key, Pool, Poolable, Reservation, WeakOpt};
+    use super::{Connecting, Key, Pool, Poolable, Reservation, Ver, WeakOpt};
     use crate::{
         core::rt::{ArcTimer, TokioExecutor, TokioTimer},
         sync::MutexGuard,
@@ -846,6 +1051,8 @@ mod tests {
         Connecting {
             key,
             pool: WeakOpt::none(),
+            pending: WeakOpt::none(),
+            started_at: Instant::now(),
         }
     }
 
@@ -889,6 +1096,95 @@ mod tests {
         };
     }
 
+    #[tokio::test]
+    async fn test_pool_close_matching() {
+        let pool = pool_no_timer();
+        let foo = host_key("foo");
+        let bar = host_key("bar");
+
+        drop(pool.pooled(c(foo.clone()), Uniq(41)));
+        drop(pool.pooled(c(foo.clone()), Uniq(42)));
+        drop(pool.pooled(c(bar.clone()), Uniq(5)));
+        let active = pool.pooled(c(foo.clone()), Uniq(43));
+
+        assert_eq!(pool.close_matching(|key| *key == foo), 2);
+        assert!(pool.locked().idle.get(&foo).is_none());
+        assert_eq!(pool.locked().idle.get(&bar).map(|list| list.len()), Some(1));
+
+        // Checked out before the close, so it is not reinserted
+        drop(active);
+        assert!(pool.locked().idle.get(&foo).is_none());
+
+        // Connections made afterwards are pooled as usual
+        drop(pool.pooled(c(foo.clone()), Uniq(44)));
+        assert_eq!(pool.locked().idle.get(&foo).map(|list| list.len()), Some(1));
+        assert!(pool.locked().closed_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pool_close_matching_while_connecting() {
+        let pool = pool_no_timer();
+        let foo = host_key("foo");
+
+        let connecting = pool.connecting(foo.clone(), Ver::Auto).expect("connecting");
+        assert_eq!(pool.close_matching(|key| *key == foo), 0);
+        assert!(pool.locked().closed_at.contains_key(&foo));
+
+        // Started connecting before the close, so it is never pooled
+        drop(pool.pooled(connecting, Uniq(41)));
+        assert!(pool.locked().idle.get(&foo).is_none());
+        assert!(pool.locked().pending.is_empty());
+        assert!(pool.locked().closed_at.is_empty());
+
+        let connecting = pool.connecting(foo.clone(), Ver::Auto).expect("connecting");
+        drop(pool.pooled(connecting, Uniq(42)));
+        assert_eq!(pool.locked().idle.get(&foo).map(|list| list.len()), Some(1));
+    }
+
+    #[test]
+    fn test_pool_connection_counts() {
+        let pool = pool_no_timer();
+        let foo = host_key("foo");
+        let bar = host_key("bar");
+
+        drop(pool.pooled(c(foo.clone()), Uniq(41)));
+        let _active = pool.pooled(c(foo.clone()), Uniq(42));
+        let _other = pool.pooled(c(bar.clone()), Uniq(5));
+
+        let mut counts = pool.connection_counts();
+        counts.sort_by_key(|(key, ..)| key.1.to_string());
+        assert_eq!(counts, [(bar, 0, 1), (foo, 1, 1)]);
+    }
+
+    #[test]
+    fn test_pool_close_oldest_idle() {
+        let pool = pool_no_timer();
+        let foo = host_key("foo");
+        let bar = host_key("bar");
+
+        drop(pool.pooled(c(foo.clone()), Uniq(41)));
+        drop(pool.pooled(c(foo.clone()), Uniq(42)));
+        drop(pool.pooled(c(bar.clone()), Uniq(5)));
+
+        assert_eq!(pool.close_oldest_idle(|key| *key != foo), 1);
+        assert_eq!(pool.connection_counts(), [(foo.clone(), 2, 0)]);
+        assert_eq!(pool.close_oldest_idle(|_| true), 2);
+        assert_eq!(pool.close_oldest_idle(|_| true), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_idle_notify() {
+        let pool = pool_no_timer();
+        let key = host_key("foo");
+
+        let idle = pool.idle_notify().unwrap();
+        let mut notified = std::pin::pin!(idle.notified());
+        notified.as_mut().enable();
+
+        drop(pool.pooled(c(key), Uniq(41)));
+        assert!(futures_util::FutureExt::now_or_never(notified).is_some());
+    }
+
     /// Helper to check if the future is ready after polling once.
     struct PollOnce<'a, F>(&'a mut F);
 
@@ -945,6 +1241,21 @@ mod tests {
         assert!(pool.locked().idle.get(&key).is_none());
     }
 
+    #[test]
+    fn test_pool_disabled_with_zero_idle_timeout() {
+        let pool: Pool<Uniq<i32>, KeyImpl> = Pool::new(
+            super::Config {
+                idle_timeout: Some(Duration::ZERO),
+                max_idle_per_host: usize::MAX,
+                max_pool_size: None,
+            },
+            TokioExecutor::new(),
+            Option::<ArcTimer>::None,
+        );
+
+        assert!(!pool.is_enabled());
+    }
+
     #[test]
     fn test_pool_max_idle_per_host() {
         let pool = pool_max_idle_no_timer(2);
@@ -0,0 +1,128 @@
+//! Liveness checks of idle HTTP/1 connections, answered by the task driving the connection.
+//!
+//! An idle connection only learns that the server closed it when its task reads
+//! the EOF. A probe wakes that task and waits until it has polled the connection
+//! once more: if the connection is still there afterwards, the read side was
+//! found open.
+
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    task::{Context, Waker},
+};
+
+use tokio::sync::oneshot;
+
+/// Asks the connection task whether the connection is still alive.
+#[derive(Clone)]
+pub(crate) struct Probe {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// Held by the connection task, which answers the probes.
+pub(crate) struct Responder {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    task: Option<Waker>,
+    waiters: Vec<oneshot::Sender<()>>,
+    closed: bool,
+}
+
+pub(crate) fn channel() -> (Probe, Responder) {
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    (
+        Probe {
+            shared: shared.clone(),
+        },
+        Responder { shared },
+    )
+}
+
+// ===== impl Probe =====
+
+impl Probe {
+    /// Resolves to `true` once the connection task has polled the connection and
+    /// kept it open, `false` if the connection is gone.
+    pub(crate) async fn check(&self) -> bool {
+        let rx = {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.closed {
+                return false;
+            }
+
+            let (tx, rx) = oneshot::channel();
+            shared.waiters.push(tx);
+            if let Some(ref task) = shared.task {
+                task.wake_by_ref();
+            }
+            rx
+        };
+
+        rx.await.is_ok()
+    }
+}
+
+// ===== impl Responder =====
+
+impl Responder {
+    /// Takes the probes waiting for an answer, and registers the task to be woken
+    /// by new ones.
+    ///
+    /// The probes are answered with [`Responder::answer`] if the connection is
+    /// still open after this poll, and dropped otherwise.
+    pub(crate) fn poll(&self, cx: &mut Context<'_>) -> Vec<oneshot::Sender<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared
+            .task
+            .as_ref()
+            .is_some_and(|task| task.will_wake(cx.waker()))
+        {
+            shared.task = Some(cx.waker().clone());
+        }
+        mem::take(&mut shared.waiters)
+    }
+
+    /// Reports the connection alive to the probes taken by [`Responder::poll`].
+    pub(crate) fn answer(waiters: Vec<oneshot::Sender<()>>) {
+        for tx in waiters {
+            let _ = tx.send(());
+        }
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.closed = true;
+        shared.task = None;
+        // Dropping the senders fails the waiting probes
+        shared.waiters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answered_while_open_failed_once_closed() {
+        let (probe, responder) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let check = tokio::spawn({
+            let probe = probe.clone();
+            async move { probe.check().await }
+        });
+        while responder.shared.lock().unwrap().waiters.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        Responder::answer(responder.poll(&mut cx));
+        assert!(check.await.unwrap());
+
+        drop(responder);
+        assert!(!probe.check().await);
+    }
+}
@@ -16,6 +16,7 @@ use crate::core::{
     client::{
         body::{self, DecodedLength, Incoming as IncomingBody},
         dispatch::{self, TrySendError},
+        probe::Responder,
         proto::{self, BodyLength, Conn, Dispatched, MessageHead, RequestHead},
         upgrade::OnUpgrade,
     },
@@ -28,6 +29,7 @@ pub(crate) struct Dispatcher<D, Bs: Body, I, T> {
     body_tx: Option<body::Sender>,
     body_rx: Pin<Box<Option<Bs>>>,
     is_closing: bool,
+    probe: Option<Responder>,
 }
 
 pub(crate) trait Dispatch {
@@ -77,9 +79,15 @@ where
             body_tx: None,
             body_rx: Box::pin(None),
             is_closing: false,
+            probe: None,
         }
     }
 
+    /// Answers liveness probes of this connection once polled.
+    pub(crate) fn set_probe(&mut self, probe: Responder) {
+        self.probe = Some(probe);
+    }
+
     pub(crate) fn into_inner(self) -> (I, Bytes, D) {
         let (io, buf) = self.conn.into_inner();
         (io, buf, self.dispatch)
@@ -435,7 +443,14 @@ where
 
     #[inline]
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.poll_catch(cx, true)
+        // Probes taken before polling are answered only if the connection, read
+        // side included, was polled and is still open
+        let waiters = self.probe.as_ref().map(|probe| probe.poll(cx));
+        let res = self.poll_catch(cx, true);
+        if let Some(waiters) = waiters.filter(|_| res.is_pending()) {
+            Responder::answer(waiters);
+        }
+        res
     }
 }
 
@@ -683,4 +698,45 @@ mod tests {
         // If it is, it will trigger an assertion.
         assert!(dispatcher.poll().is_pending());
     }
+
+    #[test]
+    fn probe_sees_close_before_sender_does() {
+        let _ = pretty_env_logger::try_init();
+
+        let (io, handle) = tokio_test::io::Builder::new()
+            .write(b"GET / HTTP/1.1\r\n\r\n")
+            .read(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .build_with_handle();
+
+        let (mut tx, rx) = dispatch::channel();
+        let conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(io);
+        let (probe, responder) = crate::core::client::probe::channel();
+        let mut dispatcher = Dispatcher::new(Client::new(rx), conn);
+        dispatcher.set_probe(responder);
+        let mut dispatcher = tokio_test::task::spawn(dispatcher);
+
+        // First poll is needed to allow tx to send...
+        assert!(dispatcher.poll().is_pending());
+
+        let mut res_rx = tokio_test::task::spawn(
+            tx.try_send(http::Request::new(IncomingBody::empty()))
+                .unwrap(),
+        );
+        assert!(dispatcher.poll().is_pending());
+        tokio_test::assert_ready_ok!(res_rx.poll()).expect("response");
+        assert!(tx.is_ready());
+
+        // The server closes the idle connection. Until its task is polled again,
+        // the sender still looks ready to be reused.
+        drop(handle);
+        assert!(tx.is_ready());
+
+        let mut check = tokio_test::task::spawn(probe.check());
+        assert!(check.poll().is_pending());
+        assert!(dispatcher.is_woken());
+        tokio_test::assert_ready_ok!(dispatcher.poll());
+        assert!(!tokio_test::assert_ready!(check.poll()));
+        drop(dispatcher);
+        assert!(!tx.is_ready());
+    }
 }
@@ -381,6 +381,17 @@ where
     }
 }
 
+impl<B, E, T> ClientTask<B, E, T>
+where
+    B: Body,
+    E: Unpin,
+{
+    /// Returns a [`Prober`](ping::Prober) for the connection, if pings are enabled.
+    pub(crate) fn prober(&self) -> Option<ping::Prober> {
+        self.ping.prober()
+    }
+}
+
 impl<B, E, T> ClientTask<B, E, T>
 where
     B: Body + 'static + Unpin,
@@ -1,11 +1,12 @@
 //! HTTP2 Ping usage
 //!
-//! core uses HTTP2 pings for two purposes:
+//! core uses HTTP2 pings for three purposes:
 //!
 //! 1. Adaptive flow control using BDP
 //! 2. Connection keep-alive
+//! 3. Liveness probes, checking that an idle connection is alive before reuse
 //!
-//! Both cases are optional.
+//! All cases are optional.
 //!
 //! # BDP Algorithm
 //!
@@ -20,11 +21,12 @@ use std::{
     fmt,
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, Weak},
     task::{self, Poll},
     time::{Duration, Instant},
 };
 
+use futures_channel::oneshot;
 use http2::{Ping, PingPong};
 
 use crate::core::{
@@ -77,6 +79,7 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, timer: Time) -> (Reco
         is_keep_alive_timed_out: false,
         ping_pong,
         ping_sent_at: None,
+        probes: Vec::new(),
         next_bdp_at,
         timer,
     }));
@@ -103,6 +106,8 @@ pub(crate) struct Config {
     keep_alive_timeout: Duration,
     /// If true, sends pings even when there are no active streams.
     keep_alive_while_idle: bool,
+    /// If true, pings can be sent on demand through a [`Prober`].
+    probe: bool,
 }
 
 #[derive(Clone)]
@@ -110,6 +115,15 @@ pub(crate) struct Recorder {
     shared: Option<Arc<Mutex<Shared>>>,
 }
 
+/// Sends a ping on demand to check that the connection is alive.
+///
+/// Holds the shared state weakly, so that it does not keep the connection from
+/// being considered idle.
+#[derive(Clone)]
+pub(crate) struct Prober {
+    shared: Weak<Mutex<Shared>>,
+}
+
 pub(super) struct Ponger {
     bdp: Option<Bdp>,
     keep_alive: Option<KeepAlive>,
@@ -119,6 +133,8 @@ pub(super) struct Ponger {
 struct Shared {
     ping_pong: PingPong,
     ping_sent_at: Option<Instant>,
+    /// Liveness probes waiting for the pong of the ping in flight.
+    probes: Vec<oneshot::Sender<()>>,
 
     // bdp
     /// If `Some`, bdp is enabled, and this tracks how many bytes have been
@@ -189,6 +205,7 @@ impl Config {
         keep_alive_interval: Option<Duration>,
         keep_alive_timeout: Duration,
         keep_alive_while_idle: bool,
+        probe: bool,
     ) -> Self {
         Config {
             bdp_initial_window: if adaptive_window {
@@ -199,11 +216,12 @@ impl Config {
             keep_alive_interval,
             keep_alive_timeout,
             keep_alive_while_idle,
+            probe,
         }
     }
 
     pub(super) fn is_enabled(&self) -> bool {
-        self.bdp_initial_window.is_some() || self.keep_alive_interval.is_some()
+        self.bdp_initial_window.is_some() || self.keep_alive_interval.is_some() || self.probe
     }
 }
 
@@ -266,6 +284,13 @@ impl Recorder {
         }
     }
 
+    /// Returns a [`Prober`] for this connection, if pings are enabled.
+    pub(super) fn prober(&self) -> Option<Prober> {
+        self.shared.as_ref().map(|shared| Prober {
+            shared: Arc::downgrade(shared),
+        })
+    }
+
     pub(super) fn ensure_not_timed_out(&self) -> core::Result<()> {
         if let Some(ref shared) = self.shared {
             let locked = shared.lock().unwrap();
@@ -279,6 +304,31 @@ impl Recorder {
     }
 }
 
+// ===== impl Prober =====
+
+impl Prober {
+    /// Sends a ping, unless one is already in flight, and returns a receiver
+    /// completed by its pong.
+    ///
+    /// Returns `None` if the connection is gone or the ping cannot be sent. The
+    /// receiver fails if the connection closes before the pong arrives.
+    pub(crate) fn probe(&self) -> Option<oneshot::Receiver<()>> {
+        let shared = self.shared.upgrade()?;
+        let mut locked = shared.lock().unwrap();
+
+        if !locked.is_ping_sent() {
+            locked.send_ping();
+            if !locked.is_ping_sent() {
+                return None;
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        locked.probes.push(tx);
+        Some(rx)
+    }
+}
+
 // ===== impl Ponger =====
 
 impl Ponger {
@@ -308,6 +358,10 @@ impl Ponger {
                 let rtt = now - start;
                 trace!("recv pong");
 
+                for tx in locked.probes.drain(..) {
+                    let _ = tx.send(());
+                }
+
                 if let Some(ref mut ka) = self.keep_alive {
                     locked.update_last_read_at();
                     ka.maybe_schedule(is_idle, &locked);
@@ -328,12 +382,14 @@ impl Ponger {
             }
             Poll::Ready(Err(_e)) => {
                 debug!("pong error: {}", _e);
+                locked.probes.clear();
             }
             Poll::Pending => {
                 if let Some(ref mut ka) = self.keep_alive {
                     if let Err(KeepAliveTimedOut) = ka.maybe_timeout(cx) {
                         self.keep_alive = None;
                         locked.is_keep_alive_timed_out = true;
+                        locked.probes.clear();
                         return Poll::Ready(Ponged::KeepAliveTimedOut);
                     }
                 }
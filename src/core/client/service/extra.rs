@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use http::{Uri, Version};
 
@@ -41,6 +41,12 @@ impl ConnectExtra {
         Self { uri, options }
     }
 
+    /// Return the target [`Uri`].
+    #[inline]
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
     /// Return the negotiated [`AlpnProtocol`].
     pub fn alpn_protocol(&self) -> Option<AlpnProtocol> {
         match self
@@ -78,4 +84,10 @@ impl ConnectExtra {
     pub fn tcp_options(&self) -> Option<&TcpConnectOptions> {
         self.options.as_ref().map(RequestOptions::tcp_connect_opts)
     }
+
+    /// Return the forced remote address, if any.
+    #[inline]
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.options.as_ref().and_then(RequestOptions::remote_addr)
+    }
 }
@@ -10,7 +10,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{self, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use bytes::Bytes;
@@ -18,6 +18,8 @@ use futures_util::future::{Either, FutureExt, TryFutureExt};
 use http::{HeaderValue, Method, Request, Response, Uri, Version, header::HOST};
 use http_body::Body;
 use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "cancel-token")]
+use tokio_util::sync::CancellationToken;
 use tower::util::Oneshot;
 
 use self::{
@@ -25,6 +27,8 @@ use self::{
     extra::{ConnectExtra, Identifier},
 };
 use super::pool::Ver;
+#[cfg(feature = "cancel-token")]
+use crate::core::ext::RequestCancelToken;
 use crate::{
     core::{
         client::{
@@ -32,11 +36,13 @@ use crate::{
             common::{Exec, Lazy, lazy},
             conn::{self, TrySendError as ConnTrySendError},
             connect::{Alpn, Connected, Connection},
+            limit::{ConnectionLimits, ConnectionPermit},
+            metrics::{PoolEventCallback, PoolMetrics, PoolStats},
             options::{RequestOptions, http1::Http1Options, http2::Http2Options},
             pool,
         },
         error::BoxError,
-        ext::{RequestConfig, RequestLayerOptions},
+        ext::{RequestConfig, RequestLayerOptions, RequestTraceId},
         rt::{ArcTimer, Executor, Timer},
     },
     hash::{HASHER, HashMemo},
@@ -45,6 +51,9 @@ use crate::{
 
 type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
+/// How long a reused connection has to pass its liveness check.
+const CHECK_ON_REUSE_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Parameters required to initiate a new connection.
 ///
 /// [`ConnectRequest`] holds the target URI and all connection-specific options
@@ -55,6 +64,9 @@ type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 pub struct ConnectRequest {
     uri: Uri,
     extra: Arc<HashMemo<ConnectExtra>>,
+    trace_id: Option<Arc<str>>,
+    #[cfg(feature = "cancel-token")]
+    cancel_token: Option<CancellationToken>,
 }
 
 // ===== impl ConnectRequest =====
@@ -62,12 +74,19 @@ pub struct ConnectRequest {
 impl ConnectRequest {
     /// Create a new [`ConnectRequest`] with the given URI and options.
     #[inline]
-    fn new(uri: Uri, options: Option<RequestOptions>) -> ConnectRequest {
+    fn new(
+        uri: Uri,
+        options: Option<RequestOptions>,
+        trace_id: Option<Arc<str>>,
+    ) -> ConnectRequest {
         let extra = ConnectExtra::new(uri.clone(), options);
         let extra = HashMemo::with_hasher(extra, HASHER);
         ConnectRequest {
             uri,
             extra: Arc::new(extra),
+            trace_id,
+            #[cfg(feature = "cancel-token")]
+            cancel_token: None,
         }
     }
 
@@ -94,6 +113,21 @@ impl ConnectRequest {
     pub(crate) fn extra(&self) -> &ConnectExtra {
         self.extra.as_ref().as_ref()
     }
+
+    /// Returns the ID the request was tagged with for log correlation.
+    ///
+    /// Unlike the [`ConnectExtra`], it is not part of the connection's identity.
+    #[inline]
+    pub(crate) fn trace_id(&self) -> Option<&Arc<str>> {
+        self.trace_id.as_ref()
+    }
+
+    /// Returns the token cancelling the request, and the DNS lookup made for it.
+    #[cfg(feature = "cancel-token")]
+    #[inline]
+    pub(crate) fn cancel_token(&self) -> Option<&CancellationToken> {
+        self.cancel_token.as_ref()
+    }
 }
 
 /// A HttpClient to make outgoing HTTP requests.
@@ -108,12 +142,16 @@ pub struct HttpClient<C, B> {
     h1_builder: conn::http1::Builder,
     h2_builder: conn::http2::Builder<Exec>,
     pool: pool::Pool<PoolClient<B>, Identifier>,
+    metrics: PoolMetrics,
+    limits: ConnectionLimits,
+    timer: Option<ArcTimer>,
 }
 
 #[derive(Clone, Copy)]
 struct Config {
     retry_canceled_requests: bool,
     set_host: bool,
+    check_on_reuse: bool,
     ver: Ver,
 }
 
@@ -182,7 +220,13 @@ where
             }
         }
 
-        let connect_req = ConnectRequest::new(uri, options);
+        let trace_id = RequestConfig::<RequestTraceId>::remove(req.extensions_mut());
+        let connect_req = ConnectRequest::new(uri, options, trace_id);
+        #[cfg(feature = "cancel-token")]
+        let connect_req = ConnectRequest {
+            cancel_token: RequestConfig::<RequestCancelToken>::remove(req.extensions_mut()),
+            ..connect_req
+        };
         ResponseFuture::new(this.send_request(req, connect_req))
     }
 
@@ -315,7 +359,21 @@ where
     ) -> Result<pool::Pooled<PoolClient<B>, Identifier>, Error> {
         loop {
             match self.one_connection_for(req.clone()).await {
-                Ok(pooled) => return Ok(pooled),
+                Ok(mut pooled) => {
+                    if self.config.check_on_reuse
+                        && pooled.is_reused()
+                        && !self.check_alive(&mut pooled).await
+                    {
+                        // Make sure the dead connection is not handed out again
+                        pooled.conn_info.poison();
+                        trace!("pooled connection failed its liveness check, trying again");
+                        continue;
+                    }
+                    if pooled.is_reused() {
+                        self.metrics.reused(req.uri().host().unwrap_or_default());
+                    }
+                    return Ok(pooled);
+                }
                 Err(ClientConnectError::Normal(err)) => return Err(err),
                 Err(ClientConnectError::CheckoutIsClosed(reason)) => {
                     if !self.config.retry_canceled_requests {
@@ -332,14 +390,32 @@ where
         }
     }
 
+    /// Checks that an idle connection taken from the pool is still alive.
+    async fn check_alive(&self, pooled: &mut PoolClient<B>) -> bool {
+        let check = std::pin::pin!(pooled.is_alive());
+        match self.timer {
+            Some(ref timer) => {
+                let timeout = timer.sleep(CHECK_ON_REUSE_TIMEOUT);
+                match futures_util::future::select(check, timeout).await {
+                    Either::Left((alive, _)) => alive,
+                    Either::Right(_) => false,
+                }
+            }
+            None => check.await,
+        }
+    }
+
     async fn one_connection_for(
         &self,
         req: ConnectRequest,
     ) -> Result<pool::Pooled<PoolClient<B>, Identifier>, ClientConnectError> {
+        let host = req.uri().host().unwrap_or_default().to_owned();
+
         // Return a single connection if pooling is not enabled
         if !self.pool.is_enabled() {
+            let permit = self.connection_permit(&host).await;
             return self
-                .connect_to(req)
+                .connect_to(req, permit)
                 .await
                 .map_err(ClientConnectError::Normal);
         }
@@ -354,8 +430,31 @@ where
         // - If a new connection is started, but the Checkout wins after (an idle connection became
         //   available first), the started connection future is spawned into the runtime to
         //   complete, and then be inserted into the pool as an idle connection.
-        let checkout = self.pool.checkout(req.identify());
-        let connect = self.connect_to(req);
+        let mut checkout = self.pool.checkout(req.identify());
+
+        // When a connection limit is reached, wait for a connection to be
+        // allowed, unless a connection to the host is returned to the pool first.
+        let permit = if self.limits.is_limited() {
+            let permit = std::pin::pin!(self.connection_permit(&host));
+            match futures_util::future::select(&mut checkout, permit).await {
+                Either::Left((Ok(checked_out), _)) => return Ok(checked_out),
+                Either::Left((Err(err), permit)) if err.is_canceled() => {
+                    let permit = permit.await;
+                    return self
+                        .connect_to(req, permit)
+                        .await
+                        .map_err(ClientConnectError::Normal);
+                }
+                Either::Left((Err(err), _)) => {
+                    return Err(ClientConnectError::Normal(e!(Connect, err)));
+                }
+                Either::Right((permit, _)) => permit,
+            }
+        } else {
+            None
+        };
+
+        let connect = self.connect_to(req, permit);
         let is_ver_h2 = self.config.ver == Ver::Http2;
 
         // The order of the `select` is depended on below...
@@ -420,15 +519,51 @@ where
         }
     }
 
+    /// Waits until the connection limits allow a new connection to `host`.
+    async fn connection_permit(&self, host: &str) -> Option<ConnectionPermit> {
+        if !self.limits.is_limited() {
+            return None;
+        }
+
+        let mut acquire = std::pin::pin!(self.limits.acquire(host));
+        let Some(idle) = self.pool.idle_notify() else {
+            return Some(acquire.await);
+        };
+
+        // Idle connections hold permits too. Close the ones in the way while
+        // waiting, including those returned to the pool after the wait started.
+        loop {
+            let mut notified = std::pin::pin!(idle.notified());
+            notified.as_mut().enable();
+            self.close_idle_in_the_way(host);
+
+            match futures_util::future::select(acquire.as_mut(), notified).await {
+                Either::Left((permit, _)) => return Some(permit),
+                Either::Right(_) => trace!("connection became idle, checking the limits again"),
+            }
+        }
+    }
+
+    /// Closes the least recently used idle connections until a new
+    /// connection to `host` is no longer held back by them.
+    fn close_idle_in_the_way(&self, host: &str) {
+        while self.limits.is_host_exhausted(host)
+            && self.pool.close_oldest_idle(|key| is_host(key, host)) > 0
+        {}
+        while self.limits.is_total_exhausted() && self.pool.close_oldest_idle(|_| true) > 0 {}
+    }
+
     fn connect_to(
         &self,
         req: ConnectRequest,
+        permit: Option<ConnectionPermit>,
     ) -> impl Lazy<Output = Result<pool::Pooled<PoolClient<B>, Identifier>, Error>>
     + Send
     + Unpin
     + 'static {
         let executor = self.exec.clone();
         let pool = self.pool.clone();
+        let metrics = self.metrics.clone();
 
         let h1_builder = self.h1_builder.clone();
         let h2_builder = self.h2_builder.clone();
@@ -438,7 +573,11 @@ where
         };
         let is_ver_h2 = ver == Ver::Http2;
         let connector = self.connector.clone();
+        let permit = permit.map(Arc::new);
         lazy(move || {
+            let started = Instant::now();
+            let host = req.uri().host().unwrap_or_default().to_owned();
+
             // Try to take a "connecting lock".
             //
             // If the pool_key is for HTTP/2, and there is already a
@@ -588,11 +727,13 @@ where
                                 }
                             };
 
+                            metrics.connected(&host, started.elapsed(), is_h2);
                             Ok(pool.pooled(
                                 connecting,
                                 PoolClient {
                                     conn_info: connected,
                                     tx,
+                                    _permit: permit,
                                 },
                             ))
                         }))
@@ -658,6 +799,19 @@ impl<C: Clone, B> Clone for HttpClient<C, B> {
             h2_builder: self.h2_builder.clone(),
             connector: self.connector.clone(),
             pool: self.pool.clone(),
+            metrics: self.metrics.clone(),
+            limits: self.limits.clone(),
+            timer: self.timer.clone(),
+        }
+    }
+}
+
+impl<C, B> HttpClient<C, B> {
+    /// Returns a handle to the connection pool of this client.
+    pub(crate) fn connection_pool(&self) -> ConnectionPool<B> {
+        ConnectionPool {
+            pool: self.pool.clone(),
+            metrics: self.metrics.clone(),
         }
     }
 }
@@ -668,10 +822,67 @@ impl<C, B> fmt::Debug for HttpClient<C, B> {
     }
 }
 
+/// A handle to the connection pool of a [`HttpClient`], kept outside of the
+/// service stack so connections can be closed on demand.
+pub(crate) struct ConnectionPool<B> {
+    pool: pool::Pool<PoolClient<B>, Identifier>,
+    metrics: PoolMetrics,
+}
+
+// ===== impl ConnectionPool =====
+
+impl<B> ConnectionPool<B> {
+    /// Closes the pooled connections to `host`, returning how many idle ones
+    /// were closed.
+    ///
+    /// Connections to `host` in use are closed once their request completes,
+    /// instead of being returned to the pool.
+    pub(crate) fn close_connections(&self, host: &str) -> usize {
+        self.pool.close_matching(|key| is_host(key, host))
+    }
+
+    /// Returns the connection counts of each host.
+    pub(crate) fn stats(&self) -> PoolStats {
+        let connections = self
+            .pool
+            .connection_counts()
+            .into_iter()
+            .map(|(key, idle, active)| {
+                let extra: &ConnectExtra = key.as_ref().as_ref();
+                let host = extra.uri().host().unwrap_or_default().to_owned();
+                (host, idle, active)
+            });
+        self.metrics.stats(connections)
+    }
+}
+
+impl<B> Clone for ConnectionPool<B> {
+    fn clone(&self) -> ConnectionPool<B> {
+        ConnectionPool {
+            pool: self.pool.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// Returns whether the connection `key` is for `host`, ignoring IPv6 brackets.
+fn is_host(key: &Identifier, host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let extra: &ConnectExtra = key.as_ref().as_ref();
+    extra.uri().host().is_some_and(|uri_host| {
+        uri_host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .eq_ignore_ascii_case(host)
+    })
+}
+
 /// A pooled HTTP connection that can send requests
 struct PoolClient<B> {
     conn_info: Connected,
     tx: PoolTx<B>,
+    // Released once every handle to the connection is dropped.
+    _permit: Option<Arc<ConnectionPermit>>,
 }
 
 enum PoolTx<B> {
@@ -713,6 +924,14 @@ impl<B> PoolClient<B> {
             PoolTx::Http2(ref tx) => tx.is_ready(),
         }
     }
+
+    async fn is_alive(&mut self) -> bool {
+        match self.tx {
+            PoolTx::Http1(ref mut tx) => tx.is_alive().await,
+
+            PoolTx::Http2(ref mut tx) => tx.is_alive().await,
+        }
+    }
 }
 
 impl<B: Body + 'static> PoolClient<B> {
@@ -743,16 +962,19 @@ where
             PoolTx::Http1(tx) => pool::Reservation::Unique(PoolClient {
                 conn_info: self.conn_info,
                 tx: PoolTx::Http1(tx),
+                _permit: self._permit,
             }),
 
             PoolTx::Http2(tx) => {
                 let b = PoolClient {
                     conn_info: self.conn_info.clone(),
                     tx: PoolTx::Http2(tx.clone()),
+                    _permit: self._permit.clone(),
                 };
                 let a = PoolClient {
                     conn_info: self.conn_info,
                     tx: PoolTx::Http2(tx),
+                    _permit: self._permit,
                 };
                 pool::Reservation::Shared(a, b)
             }
@@ -803,6 +1025,9 @@ pub struct Builder {
     h2_builder: conn::http2::Builder<Exec>,
     pool_config: pool::Config,
     pool_timer: Option<ArcTimer>,
+    pool_event_callback: Option<PoolEventCallback>,
+    max_connections_per_host: Option<usize>,
+    max_total_connections: Option<usize>,
 }
 
 // ===== impl Builder =====
@@ -818,6 +1043,7 @@ impl Builder {
             client_config: Config {
                 retry_canceled_requests: true,
                 set_host: true,
+                check_on_reuse: false,
                 ver: Ver::Auto,
             },
             exec: exec.clone(),
@@ -830,12 +1056,16 @@ impl Builder {
                 max_pool_size: None,
             },
             pool_timer: None,
+            pool_event_callback: None,
+            max_connections_per_host: None,
+            max_total_connections: None,
         }
     }
     /// Set an optional timeout for idle sockets being kept-alive.
     /// A `Timer` is required for this to take effect. See `Builder::pool_timer`
     ///
     /// Pass `None` to disable timeout.
+    /// Pass `Duration::ZERO` to disable pooling, closing connections after each request.
     ///
     /// Default is 90 seconds.
     #[inline]
@@ -960,6 +1190,48 @@ impl Builder {
         self
     }
 
+    /// Set whether to check that an idle connection is still alive before reusing it.
+    ///
+    /// HTTP/1 connections are read once more, without blocking, to notice an EOF
+    /// sent by the server while idle. HTTP/2 connections are sent a PING, and the
+    /// PONG is awaited. A connection failing the check, or not passing it within
+    /// one second, is discarded and another one is used. The timeout requires a
+    /// `Timer`, see `Builder::pool_timer`.
+    ///
+    /// Default is `false`.
+    #[inline]
+    pub fn check_connection_on_reuse(mut self, val: bool) -> Self {
+        self.client_config.check_on_reuse = val;
+        self.h1_builder.probe(val);
+        self.h2_builder.probe(val);
+        self
+    }
+
+    /// Sets a callback receiving the events of the connection pool.
+    #[inline]
+    pub fn pool_event_callback(mut self, callback: Option<PoolEventCallback>) -> Self {
+        self.pool_event_callback = callback;
+        self
+    }
+
+    /// Sets the maximum number of connections open to each host.
+    ///
+    /// Default is `None` (no limit).
+    #[inline]
+    pub fn max_connections_per_host(mut self, max: Option<usize>) -> Self {
+        self.max_connections_per_host = max;
+        self
+    }
+
+    /// Sets the maximum number of connections open across hosts.
+    ///
+    /// Default is `None` (no limit).
+    #[inline]
+    pub fn max_total_connections(mut self, max: Option<usize>) -> Self {
+        self.max_total_connections = max;
+        self
+    }
+
     /// Combine the configuration of this builder with a connector to create a `HttpClient`.
     pub fn build<C, B>(self, connector: C) -> HttpClient<C, B>
     where
@@ -979,7 +1251,13 @@ impl Builder {
             h1_builder: self.h1_builder,
             h2_builder: self.h2_builder,
             connector,
-            pool: pool::Pool::new(self.pool_config, exec, timer),
+            pool: pool::Pool::new(self.pool_config, exec, timer.clone()),
+            metrics: PoolMetrics::new(self.pool_event_callback),
+            limits: ConnectionLimits::new(
+                self.max_connections_per_host,
+                self.max_total_connections,
+            ),
+            timer,
         }
     }
 }
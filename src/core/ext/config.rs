@@ -135,6 +135,22 @@ impl RequestConfigValue for RequestLayerOptions {
     type Value = crate::core::client::options::RequestOptions;
 }
 
+#[derive(Clone, Copy)]
+pub(crate) struct RequestTraceId;
+
+impl RequestConfigValue for RequestTraceId {
+    type Value = std::sync::Arc<str>;
+}
+
+#[cfg(feature = "cancel-token")]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestCancelToken;
+
+#[cfg(feature = "cancel-token")]
+impl RequestConfigValue for RequestCancelToken {
+    type Value = tokio_util::sync::CancellationToken;
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct RequestOrigHeaderMap;
 
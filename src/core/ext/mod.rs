@@ -3,7 +3,12 @@
 mod config;
 mod h1_reason_phrase;
 
+#[cfg(feature = "cancel-token")]
+pub(crate) use self::config::RequestCancelToken;
 pub(crate) use self::{
-    config::{RequestConfig, RequestConfigValue, RequestLayerOptions, RequestOrigHeaderMap},
+    config::{
+        RequestConfig, RequestConfigValue, RequestLayerOptions, RequestOrigHeaderMap,
+        RequestTraceId,
+    },
     h1_reason_phrase::ReasonPhrase,
 };
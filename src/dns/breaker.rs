@@ -0,0 +1,274 @@
+//! Spreading lookups over several upstream resolvers, skipping failing ones
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use super::{Addrs, DnsResolveError, IntoResolve, Name, Resolve, Resolving, fallback::labeled};
+use crate::{error::BoxError, sync::Mutex};
+
+/// Consecutive failures after which an upstream is taken out of rotation
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an upstream stays out of rotation before it is probed again
+const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// A resolver that rotates lookups over several upstreams, with a circuit
+/// breaker for each.
+///
+/// Lookups go to the upstreams in turn. When an upstream fails, the next one is
+/// tried. After [`failure_threshold`](CircuitBreakerResolver::failure_threshold)
+/// consecutive failures, its breaker opens and the upstream receives no more
+/// queries. Once [`open_duration`](CircuitBreakerResolver::open_duration) has
+/// passed, the next lookup probes it first: success puts it back in rotation,
+/// failure keeps it out for another period.
+///
+/// If every breaker is open, all upstreams are tried anyway rather than failing
+/// without a query.
+///
+/// Each upstream is typically a
+/// [`HickoryDnsResolver::with_name_server`](super::HickoryDnsResolver::with_name_server),
+/// so that each name server is tracked separately.
+#[derive(Clone)]
+pub struct CircuitBreakerResolver {
+    upstreams: Vec<Upstream>,
+    health: Arc<Mutex<Vec<Health>>>,
+    next: Arc<AtomicUsize>,
+    failure_threshold: u32,
+    open_duration: Duration,
+    slow_threshold: Option<Duration>,
+}
+
+#[derive(Clone)]
+struct Upstream {
+    label: Arc<str>,
+    resolver: Arc<dyn Resolve>,
+}
+
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    /// Set while the breaker is open; once passed, the upstream may be probed.
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreakerResolver {
+    /// Creates a resolver without upstreams.
+    pub fn new() -> CircuitBreakerResolver {
+        CircuitBreakerResolver {
+            upstreams: Vec::new(),
+            health: Arc::new(Mutex::new(Vec::new())),
+            next: Arc::new(AtomicUsize::new(0)),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: DEFAULT_OPEN_DURATION,
+            slow_threshold: None,
+        }
+    }
+
+    /// Adds an upstream resolver, named `name` in errors.
+    pub fn upstream<R>(mut self, name: &str, resolver: R) -> CircuitBreakerResolver
+    where
+        R: IntoResolve,
+    {
+        self.upstreams.push(Upstream {
+            label: Arc::from(name),
+            resolver: resolver.into_resolve(),
+        });
+        self.health.lock().push(Health::default());
+        self
+    }
+
+    /// Sets how many consecutive failures open an upstream's breaker.
+    ///
+    /// Default is 3.
+    pub fn failure_threshold(mut self, failures: u32) -> CircuitBreakerResolver {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Sets how long an open breaker keeps its upstream out of rotation.
+    ///
+    /// Default is 30 seconds.
+    pub fn open_duration(mut self, duration: Duration) -> CircuitBreakerResolver {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Counts lookups slower than `threshold` as failures.
+    ///
+    /// The result of a slow lookup is still used. Default is to only count errors.
+    pub fn slow_threshold(mut self, threshold: Duration) -> CircuitBreakerResolver {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Returns the names of the upstreams whose breaker is currently open.
+    pub fn open_upstreams(&self) -> Vec<String> {
+        let health = self.health.lock();
+        self.upstreams
+            .iter()
+            .zip(health.iter())
+            .filter(|(_, health)| health.open_until.is_some())
+            .map(|(upstream, _)| upstream.label.to_string())
+            .collect()
+    }
+
+    /// Returns the upstreams to try, in order.
+    ///
+    /// Upstreams due for a probe come first, so that they are actually queried,
+    /// followed by the healthy ones in rotation.
+    fn pick(&self) -> Vec<usize> {
+        let len = self.upstreams.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let mut health = self.health.lock();
+
+        let mut probes = Vec::new();
+        let mut closed = Vec::new();
+        for i in (0..len).map(|offset| (start + offset) % len) {
+            match health[i].open_until {
+                None => closed.push(i),
+                Some(until) if until <= now => {
+                    // Re-arm the breaker, so a single lookup probes at a time
+                    health[i].open_until = Some(now + self.open_duration);
+                    probes.push(i);
+                }
+                Some(_) => {}
+            }
+        }
+
+        probes.append(&mut closed);
+        if probes.is_empty() {
+            // Every breaker is open, trying them is better than not resolving at all
+            probes = (0..len).collect();
+            probes.sort_by_key(|&i| health[i].open_until);
+        }
+        probes
+    }
+
+    fn record(&self, i: usize, success: bool) {
+        let mut health = self.health.lock();
+        let health = &mut health[i];
+        if success {
+            health.failures = 0;
+            health.open_until = None;
+            return;
+        }
+
+        health.failures = health.failures.saturating_add(1);
+        // A failed probe opens the breaker again right away
+        if health.failures >= self.failure_threshold || health.open_until.is_some() {
+            debug!(
+                "DNS upstream {} failing, opening its breaker",
+                self.upstreams[i].label
+            );
+            health.open_until = Some(Instant::now() + self.open_duration);
+        }
+    }
+
+    async fn resolve_with_breaker(self, name: Name) -> Result<Addrs, BoxError> {
+        let mut errors = Vec::new();
+        for i in self.pick() {
+            let upstream = &self.upstreams[i];
+            let start = Instant::now();
+            let result = upstream.resolver.resolve(name.clone()).await;
+            let slow = self
+                .slow_threshold
+                .is_some_and(|threshold| start.elapsed() > threshold);
+            self.record(i, result.is_ok() && !slow);
+
+            match result {
+                Ok(addrs) => return Ok(addrs),
+                Err(err) => {
+                    let err = labeled(name.as_str(), &upstream.label, err);
+                    debug!("DNS upstream failed for {}: {}", name, err);
+                    errors.push(err);
+                }
+            }
+        }
+
+        Err(DnsResolveError::chained(name.as_str(), errors).into())
+    }
+}
+
+impl Default for CircuitBreakerResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolve for CircuitBreakerResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().resolve_with_breaker(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::mock::MockResolver;
+
+    #[tokio::test]
+    async fn failing_upstream_is_dropped_and_readmitted() {
+        let flaky = MockResolver::default();
+        let healthy = MockResolver::default();
+        flaky.set_failing(true);
+
+        let resolver = CircuitBreakerResolver::new()
+            .upstream("flaky", flaky.clone())
+            .upstream("healthy", healthy.clone())
+            .failure_threshold(2)
+            .open_duration(Duration::from_millis(100));
+
+        // Every other lookup starts at the flaky upstream and falls through
+        for _ in 0..4 {
+            assert!(resolver.resolve("example.com".into()).await.is_ok());
+        }
+        assert_eq!(flaky.lookups(), 2);
+        assert_eq!(resolver.open_upstreams(), ["flaky"]);
+
+        // The open breaker keeps the flaky upstream out of rotation
+        for _ in 0..4 {
+            assert!(resolver.resolve("example.com".into()).await.is_ok());
+        }
+        assert_eq!(flaky.lookups(), 2);
+        assert_eq!(healthy.lookups(), 8);
+
+        // Once recovered, the next lookup probes it and puts it back in rotation
+        flaky.set_failing(false);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(resolver.resolve("example.com".into()).await.is_ok());
+        assert_eq!(flaky.lookups(), 3);
+        assert!(resolver.open_upstreams().is_empty());
+
+        for _ in 0..4 {
+            assert!(resolver.resolve("example.com".into()).await.is_ok());
+        }
+        assert_eq!(flaky.lookups(), 5);
+    }
+
+    #[tokio::test]
+    async fn reports_every_failed_upstream() {
+        let first = MockResolver::default();
+        let second = MockResolver::default();
+        first.set_failing(true);
+        second.set_failing(true);
+
+        let resolver = CircuitBreakerResolver::new()
+            .upstream("first", first)
+            .upstream("second", second);
+
+        let err = match resolver.resolve("example.com".into()).await {
+            Ok(_) => panic!("every upstream should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "failed to resolve example.com: first: timeout; second: timeout"
+        );
+    }
+}
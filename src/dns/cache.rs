@@ -4,12 +4,26 @@
 //! It significantly improves performance by avoiding redundant DNS queries.
 
 use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+    future::Future,
     net::SocketAddr,
     sync::{Arc, LazyLock},
     time::{Duration, Instant},
 };
 
-use crate::hash::{HashMap, HASHER};
+use futures_channel::oneshot;
+
+use super::{
+    DnsResolveError,
+    canonical::normalize_host,
+    lru::LruEntries,
+    refresh::{DEFAULT_MAX_BACKGROUND_REFRESHES, RefreshLimiter},
+    secondary::SecondaryCache,
+    zone::{self, ParseError},
+};
+use crate::error::BoxError;
+use crate::hash::{HashMap, HashSet, HASHER};
 use crate::sync::Mutex;
 
 /// Default TTL for cached DNS entries (60 seconds)
@@ -18,36 +32,342 @@ const DEFAULT_DNS_TTL: Duration = Duration::from_secs(60);
 /// Maximum number of entries in the cache
 const DEFAULT_MAX_ENTRIES: usize = 1000;
 
+/// Window over which evictions-per-insert are measured to detect thrashing
+const THRASH_WINDOW: Duration = Duration::from_secs(60);
+
+/// Minimum number of inserts within a window before thrashing is evaluated
+const THRASH_MIN_INSERTS: u64 = 100;
+
+/// Evictions-per-insert ratio above which the cache is considered thrashing
+const THRASH_EVICTION_RATIO: f64 = 0.5;
+
+/// Prefix of the metric names rendered by [`DnsCache::prometheus_metrics`]
+const DEFAULT_METRICS_PREFIX: &str = "wreq_dns_cache";
+
+/// Returns the current time, read from Tokio's clock so that tests can pause it
+fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+/// A definitive negative answer from the name servers, see [`DnsCache::insert_negative`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NegativeKind {
+    /// The name does not exist (`NXDOMAIN`)
+    NxDomain,
+    /// The name exists but has no usable address records (`NODATA`)
+    NoData,
+}
+
+/// What a cache entry records about a host
+#[derive(Clone, Debug)]
+enum Answer {
+    Addrs(Vec<SocketAddr>),
+    Negative(NegativeKind),
+}
+
 /// A cached DNS resolution result with expiration time
 #[derive(Clone, Debug)]
 struct CachedEntry {
-    addrs: Vec<SocketAddr>,
+    answer: Answer,
     expires_at: Instant,
+    /// The cache generation the entry was inserted in
+    generation: u64,
 }
 
 impl CachedEntry {
-    fn new(addrs: Vec<SocketAddr>, ttl: Duration) -> Self {
+    fn new(answer: Answer, ttl: Duration, generation: u64) -> Self {
+        Self {
+            answer,
+            expires_at: now() + ttl,
+            generation,
+        }
+    }
+
+    /// Returns the cached addresses, `None` for a negative entry
+    fn addrs(&self) -> Option<&[SocketAddr]> {
+        match self.answer {
+            Answer::Addrs(ref addrs) => Some(addrs),
+            Answer::Negative(_) => None,
+        }
+    }
+
+    /// Returns whether the entry expired, or was inserted before `generation`
+    fn is_expired(&self, generation: u64) -> bool {
+        self.is_expired_at(now(), generation)
+    }
+
+    fn is_expired_at(&self, at: Instant, generation: u64) -> bool {
+        self.generation < generation || at >= self.expires_at
+    }
+
+    fn is_stale_within(&self, max_stale: Duration) -> bool {
+        self.expires_at
+            .checked_add(max_stale)
+            .is_none_or(|until| now() < until)
+    }
+
+    fn remaining_ttl(&self) -> Duration {
+        self.expires_at.saturating_duration_since(now())
+    }
+}
+
+/// A point-in-time snapshot of the DNS cache counters
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct DnsCacheStats {
+    /// Total number of lookups answered from the cache
+    pub hits: u64,
+    /// Total number of lookups that found no live entry
+    pub misses: u64,
+    /// Total number of entries removed after their TTL expired
+    pub expirations: u64,
+    /// Total number of entries inserted
+    pub inserts: u64,
+    /// Total number of entries evicted to make room for new ones
+    pub evictions: u64,
+    /// Number of entries currently cached, including expired ones not yet removed
+    pub size: usize,
+    /// Maximum number of entries the cache holds
+    pub max_entries: usize,
+    /// Evictions per insert over the current fixed measurement window
+    pub eviction_rate: f64,
+    /// Whether the eviction rate exceeded the thrashing threshold, meaning the
+    /// working set is larger than `max_entries`
+    pub thrashing: bool,
+}
+
+impl DnsCacheStats {
+    /// Returns the share of lookups answered from the cache, between 0 and 1
+    ///
+    /// Returns 0 before the first lookup.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// Tracks evictions-per-insert to detect cache thrashing
+///
+/// Counts are kept per fixed window of [`THRASH_WINDOW`], which starts over with
+/// the first insert after it has elapsed, rather than sliding over the last minute.
+struct ThrashMonitor {
+    window_start: Instant,
+    window_inserts: u64,
+    window_evictions: u64,
+    thrashing: bool,
+    warned: bool,
+}
+
+impl ThrashMonitor {
+    fn new() -> Self {
         Self {
-            addrs,
-            expires_at: Instant::now() + ttl,
+            window_start: now(),
+            window_inserts: 0,
+            window_evictions: 0,
+            thrashing: false,
+            warned: false,
+        }
+    }
+
+    fn record_insert(&mut self, evicted: bool) {
+        if self.window_start.elapsed() >= THRASH_WINDOW {
+            self.window_start = now();
+            self.window_inserts = 0;
+            self.window_evictions = 0;
+        }
+
+        self.window_inserts += 1;
+        if evicted {
+            self.window_evictions += 1;
+        }
+
+        if self.window_inserts >= THRASH_MIN_INSERTS {
+            self.thrashing = self.eviction_rate() > THRASH_EVICTION_RATIO;
+            if self.thrashing && !self.warned {
+                warn!(
+                    "DNS cache is thrashing ({:.2} evictions per insert), consider raising max_entries",
+                    self.eviction_rate()
+                );
+                self.warned = true;
+            }
         }
     }
 
-    fn is_expired(&self) -> bool {
-        Instant::now() >= self.expires_at
+    fn eviction_rate(&self) -> f64 {
+        if self.window_inserts == 0 {
+            0.0
+        } else {
+            self.window_evictions as f64 / self.window_inserts as f64
+        }
     }
 }
 
+/// Callback invoked on cache lifecycle transitions
+type LifecycleHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Callback invoked for each evicted entry
+type EvictHook = Arc<dyn Fn(&str, EvictReason) + Send + Sync>;
+
+/// Callback invoked when a host's addresses change, with the old and new sets
+type AddrChangeHook = Arc<dyn Fn(&str, &[SocketAddr], &[SocketAddr]) + Send + Sync>;
+
+/// Why an entry was removed from the cache
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvictReason {
+    /// Removed to make room for a new entry
+    Capacity,
+    /// Removed after its TTL expired
+    Expired,
+    /// Removed through [`DnsCache::remove`] or [`DnsCache::clear`]
+    Manual,
+    /// Removed because connecting to its addresses failed
+    Unreachable,
+}
+
+/// Which entry is evicted to make room when the cache is full
+///
+/// Expired entries are always removed first; the policy only picks among live ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvictionPolicy {
+    /// Evict the entry that was looked up or inserted least recently
+    #[default]
+    LeastRecentlyUsed,
+    /// Evict the entry closest to expiry, which would need resolving again soonest anyway
+    ShortestRemainingTtl,
+}
+
+/// What a lookup of a host would get from the cache, see [`DnsCache::classify`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CacheDecision {
+    /// A live entry with addresses would be returned
+    Hit,
+    /// Only an expired entry is cached, still within the serve-stale window
+    Stale,
+    /// A live entry recording that the host has no addresses would be returned
+    NegativeHit,
+    /// Nothing usable is cached for the host
+    Miss,
+}
+
+/// Predicate deciding whether a resolved answer may be cached
+type AdmissionFilter = Arc<dyn Fn(&str, &[SocketAddr]) -> bool + Send + Sync>;
+
+/// Result of a shared lookup, handed to callers waiting on the same host
+type SharedLookup = Result<Vec<SocketAddr>, String>;
+
 /// DNS cache with TTL and LRU eviction
 #[derive(Clone)]
 pub struct DnsCache {
     inner: Arc<Mutex<DnsCacheInner>>,
     default_ttl: Duration,
+    min_ttl: Duration,
+    max_ttl: Duration,
+    refresh: RefreshLimiter,
+    min_addrs: usize,
+    admission: Option<AdmissionFilter>,
+    metrics_prefix: Arc<str>,
+    secondary: Option<Arc<dyn SecondaryCache>>,
+    negative_ttl: Option<Duration>,
+    refresh_ahead: Option<RefreshAhead>,
+}
+
+/// Settings of [`DnsCache::with_refresh_ahead`]
+#[derive(Clone, Copy, Debug)]
+struct RefreshAhead {
+    ahead: Duration,
+    serve_stale_for: Duration,
 }
 
 struct DnsCacheInner {
-    cache: HashMap<String, CachedEntry>,
+    cache: LruEntries<CachedEntry>,
     max_entries: usize,
+    eviction_policy: EvictionPolicy,
+    hits: u64,
+    misses: u64,
+    expirations: u64,
+    inserts: u64,
+    evictions: u64,
+    thrash: ThrashMonitor,
+    on_first_entry: Option<LifecycleHook>,
+    on_empty: Option<LifecycleHook>,
+    on_evict: Option<EvictHook>,
+    on_addr_change: Option<AddrChangeHook>,
+    frozen: bool,
+    /// Bumped to invalidate every entry inserted before, see [`DnsCache::mark_all_stale`]
+    generation: u64,
+    pending: HashMap<String, Vec<oneshot::Sender<SharedLookup>>>,
+    /// Hosts with a background refresh requested, see [`DnsCache::get_refresh_ahead`]
+    refreshing: HashSet<String>,
+}
+
+/// Clears a host's in-flight marker if its lookup is dropped before completing
+struct InFlightGuard<'a> {
+    cache: &'a DnsCache,
+    host: &'a str,
+    done: bool,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            // Dropping the senders wakes the waiters, which then resolve on their own
+            self.cache.inner.lock().pending.remove(self.host);
+        }
+    }
+}
+
+impl DnsCacheInner {
+    /// Returns the hook to fire, if the cache crossed the empty/non-empty boundary
+    fn transition(&self, was_empty: bool) -> Option<LifecycleHook> {
+        match (was_empty, self.cache.is_empty()) {
+            (true, false) => self.on_first_entry.clone(),
+            (false, true) => self.on_empty.clone(),
+            _ => None,
+        }
+    }
+
+    /// Removes expired entries, recording them if an eviction hook is set
+    fn remove_expired(&mut self, evictions: &mut Vec<(String, EvictReason)>) {
+        let track = self.on_evict.is_some();
+        let generation = self.generation;
+        let before = self.cache.len();
+        self.cache.retain(|host, entry| {
+            if !entry.is_expired(generation) {
+                return true;
+            }
+            if track {
+                evictions.push((host.clone(), EvictReason::Expired));
+            }
+            false
+        });
+        self.expirations += (before - self.cache.len()) as u64;
+    }
+
+    /// Returns the entry to evict to make room, according to the eviction policy
+    fn eviction_candidate(&self) -> Option<String> {
+        match self.eviction_policy {
+            EvictionPolicy::LeastRecentlyUsed => self.cache.oldest().cloned(),
+            EvictionPolicy::ShortestRemainingTtl => self
+                .cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(host, _)| host.clone()),
+        }
+    }
+
+    /// Counts a lookup as a hit or a miss
+    fn record_lookup(&mut self, hit: bool) {
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
 }
 
 impl DnsCache {
@@ -57,65 +377,858 @@ impl DnsCache {
     }
 
     /// Creates a new DNS cache with custom TTL and max entries
+    ///
+    /// Use [`DnsCache::builder`] to also bound the TTLs taken from DNS answers.
     pub fn with_config(default_ttl: Duration, max_entries: usize) -> Self {
+        DnsCache::builder()
+            .default_ttl(default_ttl)
+            .max_entries(max_entries)
+            .build()
+    }
+
+    /// Returns a handle to the process-wide cache, which resolvers use unless
+    /// given a cache of their own
+    pub fn global() -> DnsCache {
+        GLOBAL_DNS_CACHE.clone()
+    }
+
+    /// Creates a [`DnsCacheBuilder`] to configure a [`DnsCache`]
+    pub fn builder() -> DnsCacheBuilder {
+        DnsCacheBuilder {
+            default_ttl: DEFAULT_DNS_TTL,
+            min_ttl: Duration::ZERO,
+            max_ttl: Duration::MAX,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            capacity: 0,
+            eviction_policy: EvictionPolicy::default(),
+            metrics_prefix: Arc::from(DEFAULT_METRICS_PREFIX),
+            refresh_ahead: None,
+            min_addrs: 1,
+            admission: None,
+            max_background_refreshes: DEFAULT_MAX_BACKGROUND_REFRESHES,
+            secondary: None,
+            negative_ttl: None,
+        }
+    }
+
+    fn from_builder(builder: DnsCacheBuilder) -> Self {
+        let capacity = builder.capacity.min(builder.max_entries);
         Self {
             inner: Arc::new(Mutex::new(DnsCacheInner {
-                cache: HashMap::with_hasher(HASHER),
-                max_entries,
+                cache: LruEntries::with_capacity(capacity),
+                max_entries: builder.max_entries,
+                eviction_policy: builder.eviction_policy,
+                hits: 0,
+                misses: 0,
+                expirations: 0,
+                inserts: 0,
+                evictions: 0,
+                thrash: ThrashMonitor::new(),
+                on_first_entry: None,
+                on_empty: None,
+                on_evict: None,
+                on_addr_change: None,
+                frozen: false,
+                generation: 0,
+                pending: HashMap::with_hasher(HASHER),
+                refreshing: HashSet::with_hasher(HASHER),
             })),
-            default_ttl,
+            default_ttl: builder.default_ttl,
+            min_ttl: builder.min_ttl,
+            max_ttl: builder.max_ttl.max(builder.min_ttl),
+            refresh: RefreshLimiter::new(builder.max_background_refreshes),
+            min_addrs: builder.min_addrs,
+            admission: builder.admission,
+            metrics_prefix: builder.metrics_prefix,
+            secondary: builder.secondary,
+            negative_ttl: builder.negative_ttl,
+            refresh_ahead: builder.refresh_ahead,
         }
     }
 
+    /// Returns whether an answer passes the admission rules
+    fn admits(&self, host: &str, addrs: &[SocketAddr]) -> bool {
+        !addrs.is_empty()
+            && addrs.len() >= self.min_addrs
+            && self
+                .admission
+                .as_ref()
+                .is_none_or(|filter| filter(host, addrs))
+    }
+
+    /// Refreshes entries in the background shortly before they expire
+    ///
+    /// A lookup through a resolver supporting it, such as the hickory resolver, of
+    /// an entry expiring within `refresh_ahead` still gets the cached addresses,
+    /// while a background Tokio task resolves the host again. Once expired, an
+    /// entry keeps being served for up to `serve_stale_for` until the refresh
+    /// replaces it, so the first request after expiry does not wait for the name
+    /// servers. Refreshes are capped by [`DnsCacheBuilder::max_background_refreshes`].
+    ///
+    /// Default is to let entries expire and resolve them again on the next lookup.
+    /// For a cache built with a [`DnsCacheBuilder`], such as a per-client one, see
+    /// [`DnsCacheBuilder::refresh_ahead`].
+    pub fn with_refresh_ahead(
+        mut self,
+        refresh_ahead: Duration,
+        serve_stale_for: Duration,
+    ) -> Self {
+        self.refresh_ahead = Some(RefreshAhead {
+            ahead: refresh_ahead,
+            serve_stale_for,
+        });
+        self
+    }
+
+    /// Spawns a background refresh task, unless too many are already running
+    ///
+    /// Returns `false` if the refresh was skipped.
+    pub(crate) fn spawn_refresh<F>(&self, fut: F) -> bool
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.refresh.try_spawn(fut)
+    }
+
     /// Gets cached addresses for a hostname if available and not expired
+    ///
+    /// Expired entries are kept around (until evicted or cleaned up) so that they
+    /// can still be served through [`DnsCache::get_stale`]. Failed lookups recorded
+    /// by a resolver are not returned.
     pub fn get(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let host = &*cache_key(host);
         let mut inner = self.inner.lock();
+        let generation = inner.generation;
 
         if let Some(entry) = inner.cache.get(host) {
-            if !entry.is_expired() {
-                trace!("DNS cache hit for {}", host);
-                return Some(entry.addrs.clone());
-            } else {
+            if entry.is_expired(generation) {
                 trace!("DNS cache entry expired for {}", host);
-                inner.cache.remove(host);
+            } else if let Some(addrs) = entry.addrs() {
+                trace!("DNS cache hit for {}", host);
+                let addrs = addrs.to_vec();
+                inner.record_lookup(true);
+                return Some(addrs);
             }
         }
 
         trace!("DNS cache miss for {}", host);
+        inner.record_lookup(false);
         None
     }
 
+    /// Gets cached addresses for a hostname, for a resolver refreshing entries ahead
+    /// of expiry
+    ///
+    /// Like [`DnsCache::get`], unless refresh-ahead is enabled: then an entry that
+    /// expires soon, or expired no longer than the serve-stale window ago, is
+    /// returned along with `true`, asking the caller to refresh it with
+    /// [`DnsCache::refresh_in_background`]. A refresh is only asked for once until
+    /// it completes.
+    pub(crate) fn get_refresh_ahead(&self, host: &str) -> Option<(Vec<SocketAddr>, bool)> {
+        let Some(ahead) = self.refresh_ahead else {
+            return self.get(host).map(|addrs| (addrs, false));
+        };
+
+        let host = &*cache_key(host);
+        let mut inner = self.inner.lock();
+        let generation = inner.generation;
+        let now = now();
+        let found = inner.cache.get(host).and_then(|entry| {
+            // Negative entries are left to expire, they are not worth refreshing
+            let addrs = entry.addrs()?;
+            let fresh = !entry.is_expired_at(now, generation);
+            let servable = fresh
+                || entry
+                    .expires_at
+                    .checked_add(ahead.serve_stale_for)
+                    .is_none_or(|until| now < until);
+            let refresh = !fresh || entry.remaining_ttl() <= ahead.ahead;
+            servable.then(|| (addrs.to_vec(), refresh))
+        });
+        inner.record_lookup(found.is_some());
+
+        match found {
+            Some((addrs, true)) => {
+                let refresh = inner.refreshing.insert(host.to_owned());
+                if refresh {
+                    trace!("DNS cache entry for {} due for refresh", host);
+                }
+                Some((addrs, refresh))
+            }
+            found => found,
+        }
+    }
+
+    /// Resolves `host` again with `resolve` in a background Tokio task, replacing
+    /// its entry
+    ///
+    /// Called when [`DnsCache::get_refresh_ahead`] asks for a refresh. Concurrent
+    /// lookups of the host join the refresh instead of resolving on their own. The
+    /// refresh is skipped when too many are already running.
+    pub(crate) fn refresh_in_background<F, Fut>(&self, host: &str, resolve: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut:
+            Future<Output = Result<(Vec<SocketAddr>, Option<Duration>), BoxError>> + Send + 'static,
+    {
+        let host = &*cache_key(host);
+        let cache = self.clone();
+        let owned = host.to_owned();
+        let spawned = self.spawn_refresh(async move {
+            trace!("refreshing DNS cache entry for {} in the background", owned);
+            if let Err(err) = cache.get_or_resolve_with_ttl(&owned, resolve).await {
+                debug!("background DNS refresh for {} failed: {}", owned, err);
+            }
+            cache.inner.lock().refreshing.remove(&owned);
+        });
+        if !spawned {
+            self.inner.lock().refreshing.remove(host);
+        }
+    }
+
+    /// Gets cached addresses for several hostnames under a single lock acquisition
+    ///
+    /// Results are returned in the order of `hosts`, with `None` for hosts that
+    /// are missing or expired.
+    pub fn get_many(&self, hosts: &[&str]) -> Vec<(String, Option<Vec<SocketAddr>>)> {
+        let mut inner = self.inner.lock();
+        let generation = inner.generation;
+        hosts
+            .iter()
+            .map(|&host| {
+                let addrs = inner
+                    .cache
+                    .get(&cache_key(host))
+                    .filter(|entry| !entry.is_expired(generation))
+                    .and_then(|entry| entry.addrs().map(<[_]>::to_vec));
+                inner.record_lookup(addrs.is_some());
+                (host.to_owned(), addrs)
+            })
+            .collect()
+    }
+
+    /// Reports what a lookup of `host` would get from the cache right now
+    ///
+    /// An expired entry is [`CacheDecision::Stale`] if it expired no more than
+    /// `max_stale` ago, as with [`DnsCache::get_stale`], and a miss otherwise.
+    ///
+    /// This is a pure read for tests and diagnostics: it neither removes expired
+    /// entries nor counts towards the hit and miss statistics.
+    pub fn classify(&self, host: &str, max_stale: Duration) -> CacheDecision {
+        let inner = self.inner.lock();
+        match inner.cache.peek(&cache_key(host)) {
+            None => CacheDecision::Miss,
+            Some(entry) if entry.is_expired(inner.generation) => {
+                if entry.addrs().is_some() && entry.is_stale_within(max_stale) {
+                    CacheDecision::Stale
+                } else {
+                    CacheDecision::Miss
+                }
+            }
+            Some(CachedEntry {
+                answer: Answer::Negative(_),
+                ..
+            }) => CacheDecision::NegativeHit,
+            Some(_) => CacheDecision::Hit,
+        }
+    }
+
+    /// Gets cached addresses for a hostname as they would be seen at `at`
+    ///
+    /// Like [`DnsCache::get`], but expiry is evaluated against the supplied instant
+    /// instead of the current time, which allows simulating how the cache evolves.
+    /// This is read-only: it neither removes nor refreshes entries.
+    pub fn get_at(&self, host: &str, at: Instant) -> Option<Vec<SocketAddr>> {
+        let inner = self.inner.lock();
+        let entry = inner.cache.peek(&cache_key(host))?;
+        if entry.is_expired_at(at, inner.generation) {
+            return None;
+        }
+        entry.addrs().map(<[_]>::to_vec)
+    }
+
+    /// Returns every entry that would be live at `at`, sorted by hostname
+    pub fn dump_at(&self, at: Instant) -> Vec<(String, Vec<SocketAddr>)> {
+        let inner = self.inner.lock();
+        let mut live: Vec<_> = inner
+            .cache
+            .iter()
+            .filter(|(_, entry)| !entry.is_expired_at(at, inner.generation))
+            .filter_map(|(host, entry)| Some((host.clone(), entry.addrs()?.to_vec())))
+            .collect();
+        live.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        live
+    }
+
+    /// Gets cached addresses for a hostname along with their remaining TTL
+    pub fn get_with_ttl(&self, host: &str) -> Option<(Vec<SocketAddr>, Duration)> {
+        let host = &*cache_key(host);
+        let mut inner = self.inner.lock();
+        let generation = inner.generation;
+        let live = inner
+            .cache
+            .get(host)
+            .filter(|entry| !entry.is_expired(generation))
+            .and_then(|entry| Some((entry.addrs()?.to_vec(), entry.remaining_ttl())));
+        inner.record_lookup(live.is_some());
+
+        if live.is_some() {
+            trace!("DNS cache hit for {}", host);
+        }
+        live
+    }
+
+    /// Gets cached addresses for a hostname, including an expired entry as long as
+    /// it expired no more than `max_stale` ago.
+    ///
+    /// Entries made stale by [`DnsCache::mark_all_stale`] are served until their
+    /// TTL runs out, plus `max_stale`.
+    ///
+    /// This backs serve-stale behavior ([RFC 8767]) when the upstream resolver is
+    /// unreachable.
+    ///
+    /// [RFC 8767]: https://www.rfc-editor.org/rfc/rfc8767
+    pub fn get_stale(&self, host: &str, max_stale: Duration) -> Option<Vec<SocketAddr>> {
+        let mut inner = self.inner.lock();
+        let entry = inner.cache.get(&cache_key(host))?;
+        let addrs = entry.addrs()?;
+
+        if entry.is_stale_within(max_stale) {
+            trace!("DNS cache serving stale entry for {}", host);
+            Some(addrs.to_vec())
+        } else {
+            None
+        }
+    }
+
     /// Inserts addresses into the cache with default TTL
     pub fn insert(&self, host: String, addrs: Vec<SocketAddr>) {
         self.insert_with_ttl(host, addrs, self.default_ttl);
     }
 
+    /// Returns a view of the cache scoped to `tenant`
+    ///
+    /// Lookups and inserts through the partition only see the entries of that
+    /// tenant, so one tenant's answers never leak into another's resolution, while
+    /// every partition shares this cache's memory budget, eviction and hooks. Entries
+    /// are keyed internally as `tenant/host`, which is also the name reported to the
+    /// hooks and by [`DnsCache::dump_at`]. Hostnames cannot contain a `/`, so
+    /// partitioned entries never collide with unpartitioned ones.
+    pub fn partition(&self, tenant: &str) -> DnsCachePartition {
+        DnsCachePartition {
+            cache: self.clone(),
+            tenant: Arc::from(tenant),
+        }
+    }
+
+    /// Preloads the cache from a zone file or a `host ip[,ip]` list
+    ///
+    /// Only A and AAAA records are loaded; other record types are skipped. Every
+    /// host is cached for `ttl`, regardless of the TTLs in the zone. The whole text
+    /// is parsed before anything is inserted, so a malformed line leaves the cache
+    /// untouched. Returns the number of hosts found.
+    ///
+    /// ```text
+    /// $ORIGIN example.com.
+    /// www   IN A    192.0.2.10
+    ///          AAAA 2001:db8::10
+    /// api.example.org 192.0.2.20,192.0.2.21
+    /// ```
+    pub fn load_zone(&self, text: &str, ttl: Duration) -> Result<usize, ParseError> {
+        let entries = zone::parse(text)?;
+        let count = entries.len();
+        for (host, addrs) in entries {
+            self.insert_with_ttl(host, addrs, ttl);
+        }
+        Ok(count)
+    }
+
+    /// Inserts an answer for the TTL its records carried, clamped to the bounds
+    /// set with [`DnsCacheBuilder::min_ttl`] and [`DnsCacheBuilder::max_ttl`]
+    pub(crate) fn insert_upstream(&self, host: String, addrs: Vec<SocketAddr>, ttl: Duration) {
+        let ttl = self.clamp_ttl(ttl);
+        self.insert_with_ttl(host, addrs, ttl);
+    }
+
+    /// Bounds a TTL taken from a DNS answer
+    pub(crate) fn clamp_ttl(&self, ttl: Duration) -> Duration {
+        ttl.clamp(self.min_ttl, self.max_ttl)
+    }
+
     /// Inserts addresses into the cache with custom TTL
+    ///
+    /// The TTL is used as given, without the upstream TTL bounds. Answers rejected
+    /// by the admission rules are silently dropped.
     pub fn insert_with_ttl(&self, host: String, addrs: Vec<SocketAddr>, ttl: Duration) {
+        if !self.admits(&host, &addrs) {
+            trace!("DNS answer for {} not admitted into cache", host);
+            return;
+        }
+        self.insert_entry(host, Answer::Addrs(addrs), ttl);
+    }
+
+    /// Records a negative answer for `host`, for the TTL set with
+    /// [`DnsCacheBuilder::negative_ttl`]
+    ///
+    /// Until the entry expires, resolvers fail lookups of `host` with the same
+    /// kind of error without querying again. Negative entries are never returned
+    /// by the address getters. Does nothing unless negative caching is enabled.
+    pub(crate) fn insert_negative(&self, host: String, kind: NegativeKind) {
+        if let Some(ttl) = self.negative_ttl {
+            self.insert_negative_with_ttl(host, kind, ttl);
+        }
+    }
+
+    /// Records a negative answer for `host`, for `ttl`
+    ///
+    /// Negative entries bypass the admission rules, which only apply to answers.
+    pub(crate) fn insert_negative_with_ttl(&self, host: String, kind: NegativeKind, ttl: Duration) {
+        trace!("Negatively caching DNS lookup for {} ({:?})", host, kind);
+        self.insert_entry(host, Answer::Negative(kind), ttl);
+    }
+
+    /// Gets the live negative answer cached for `host`, if any
+    pub(crate) fn get_negative(&self, host: &str) -> Option<NegativeKind> {
+        let host = &*cache_key(host);
+        let mut inner = self.inner.lock();
+        let generation = inner.generation;
+        let entry = inner.cache.get(host)?;
+        let Answer::Negative(kind) = entry.answer else {
+            return None;
+        };
+        if entry.is_expired(generation) {
+            return None;
+        }
+        trace!("DNS cache negative hit for {}", host);
+        inner.record_lookup(true);
+        Some(kind)
+    }
+
+    fn insert_entry(&self, host: String, answer: Answer, ttl: Duration) {
+        let host = cache_key(&host).into_owned();
         let mut inner = self.inner.lock();
+        if inner.frozen {
+            trace!("DNS cache frozen, not caching {}", host);
+            return;
+        }
+        let was_empty = inner.cache.is_empty();
+        let mut evicted = false;
+        let mut evictions = Vec::new();
 
-        // Simple eviction strategy: remove oldest entries if cache is full
+        // Compare against the previous answer, expired or not, before it may be evicted
+        let addr_change = inner.on_addr_change.clone().and_then(|hook| {
+            let Answer::Addrs(ref addrs) = answer else {
+                return None;
+            };
+            let old = inner.cache.peek(&host)?.addrs()?.to_vec();
+            (!same_addrs(&old, addrs)).then(|| (hook, old, addrs.clone()))
+        });
+
+        // Make room if the cache is full
         if inner.cache.len() >= inner.max_entries {
             // Remove expired entries first
-            inner.cache.retain(|_, entry| !entry.is_expired());
+            inner.remove_expired(&mut evictions);
 
-            // If still full, remove one random entry (HashMap doesn't preserve insertion order)
+            // If still full, remove one entry picked by the eviction policy
             if inner.cache.len() >= inner.max_entries {
-                if let Some(key) = inner.cache.keys().next().cloned() {
+                if let Some(key) = inner.eviction_candidate() {
                     trace!("Evicting DNS cache entry for {}", key);
                     inner.cache.remove(&key);
+                    evicted = true;
+                    if inner.on_evict.is_some() {
+                        evictions.push((key, EvictReason::Capacity));
+                    }
                 }
             }
         }
 
+        inner.inserts += 1;
+        if evicted {
+            inner.evictions += 1;
+        }
+        inner.thrash.record_insert(evicted);
+
         trace!("Caching DNS result for {} (TTL: {:?})", host, ttl);
-        inner.cache.insert(host, CachedEntry::new(addrs, ttl));
+        let generation = inner.generation;
+        inner
+            .cache
+            .insert(host.clone(), CachedEntry::new(answer, ttl, generation));
+
+        let hook = inner.transition(was_empty);
+        let on_evict = inner.on_evict.clone();
+        drop(inner);
+        fire(hook);
+        fire_evictions(on_evict, evictions);
+        if let Some((on_addr_change, old, new)) = addr_change {
+            on_addr_change(&host, &old, &new);
+        }
+    }
+
+    /// Registers a callback invoked when the cache goes from empty to holding an entry
+    ///
+    /// The callback fires only on that transition, not on every insert, which makes it
+    /// a good place to start background maintenance such as a cleanup task.
+    pub fn on_first_entry<F>(&self, f: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.inner.lock().on_first_entry = Some(Arc::new(f));
+    }
+
+    /// Registers a callback invoked when the cache becomes empty
+    ///
+    /// The callback fires only when the last entry is removed, so background
+    /// maintenance started from [`DnsCache::on_first_entry`] can be stopped here.
+    pub fn on_empty<F>(&self, f: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.inner.lock().on_empty = Some(Arc::new(f));
+    }
+
+    /// Registers a callback invoked for every entry removed from the cache
+    ///
+    /// The callback receives the hostname and the [`EvictReason`]. It runs on the
+    /// thread that caused the eviction, often while resolving a request, so it
+    /// must be cheap and must not block.
+    pub fn on_evict<F>(&self, f: F)
+    where
+        F: Fn(&str, EvictReason) + Send + Sync + 'static,
+    {
+        self.inner.lock().on_evict = Some(Arc::new(f));
+    }
+
+    /// Registers a callback invoked when a host is cached with a different set of
+    /// addresses than it had before
+    ///
+    /// The callback receives the hostname, the previously cached addresses and the
+    /// new ones. It fires whenever a refreshed answer replaces an entry, expired or
+    /// not, whose addresses differ regardless of order, which helps spotting CDN
+    /// steering changes. Like [`DnsCache::on_evict`], it must be cheap and must not
+    /// block.
+    pub fn on_addr_change<F>(&self, f: F)
+    where
+        F: Fn(&str, &[SocketAddr], &[SocketAddr]) + Send + Sync + 'static,
+    {
+        self.inner.lock().on_addr_change = Some(Arc::new(f));
+    }
+
+    /// Resolves `host` with `resolve`, sharing a single lookup among concurrent callers
+    ///
+    /// The first caller for a host runs the lookup; callers arriving while it is in
+    /// flight wait for its result instead of querying the resolver again. The
+    /// secondary cache, if any, is consulted before `resolve`. Successful answers
+    /// are inserted into the cache with the default TTL. Callers are expected to
+    /// check the cache before calling this.
+    pub(crate) async fn get_or_resolve<F, Fut>(
+        &self,
+        host: &str,
+        resolve: F,
+    ) -> Result<Vec<SocketAddr>, BoxError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<SocketAddr>, BoxError>>,
+    {
+        self.get_or_resolve_with_ttl(host, move || async move {
+            resolve().await.map(|addrs| (addrs, None))
+        })
+        .await
+    }
+
+    /// Like [`DnsCache::get_or_resolve`], with `resolve` also returning the TTL of
+    /// the answer
+    ///
+    /// A TTL is clamped to the configured bounds; answers without one are cached
+    /// with the default TTL.
+    pub(crate) async fn get_or_resolve_with_ttl<F, Fut>(
+        &self,
+        host: &str,
+        resolve: F,
+    ) -> Result<Vec<SocketAddr>, BoxError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(Vec<SocketAddr>, Option<Duration>), BoxError>>,
+    {
+        self.get_or_resolve_cacheable(host, move || async move {
+            resolve().await.map(|(addrs, ttl)| (addrs, ttl, true))
+        })
+        .await
+    }
+
+    /// Like [`DnsCache::get_or_resolve_with_ttl`], with `resolve` also telling
+    /// whether the answer may be cached
+    ///
+    /// An answer that may not be cached is still shared with the callers waiting
+    /// on the lookup, but is neither inserted nor written through to the secondary
+    /// cache.
+    pub(crate) async fn get_or_resolve_cacheable<F, Fut>(
+        &self,
+        host: &str,
+        resolve: F,
+    ) -> Result<Vec<SocketAddr>, BoxError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(Vec<SocketAddr>, Option<Duration>, bool), BoxError>>,
+    {
+        let host = &*cache_key(host);
+        let waiter = {
+            let mut inner = self.inner.lock();
+            match inner.pending.get_mut(host) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    Some(rx)
+                }
+                None => {
+                    inner.pending.insert(host.to_owned(), Vec::new());
+                    None
+                }
+            }
+        };
+
+        if let Some(rx) = waiter {
+            trace!("joining in-flight DNS lookup for {}", host);
+            return match rx.await {
+                Ok(Ok(addrs)) => Ok(addrs),
+                Ok(Err(msg)) => Err(DnsResolveError::new(host, msg).into()),
+                // The leading lookup was dropped, resolve on our own
+                Err(_canceled) => resolve().await.map(|(addrs, ..)| addrs),
+            };
+        }
+
+        let mut guard = InFlightGuard {
+            cache: self,
+            host,
+            done: false,
+        };
+        let result = match self.get_secondary(host).await {
+            Some(addrs) => Ok((addrs, self.default_ttl, true)),
+            None => {
+                let result = resolve().await.map(|(addrs, ttl, cacheable)| {
+                    let ttl = ttl.map_or(self.default_ttl, |ttl| self.clamp_ttl(ttl));
+                    (addrs, ttl, cacheable)
+                });
+                if let Ok((ref addrs, ttl, true)) = result {
+                    self.set_secondary(host, addrs, ttl).await;
+                }
+                result
+            }
+        };
+
+        let result = result.map(|(addrs, ttl, cacheable)| {
+            if cacheable && !addrs.is_empty() {
+                self.insert_with_ttl(host.to_owned(), addrs.clone(), ttl);
+            }
+            addrs
+        });
+
+        let waiters = self.inner.lock().pending.remove(host);
+        guard.done = true;
+
+        for tx in waiters.into_iter().flatten() {
+            let shared = match result {
+                Ok(ref addrs) => Ok(addrs.clone()),
+                Err(ref err) => Err(err.to_string()),
+            };
+            let _ = tx.send(shared);
+        }
+
+        result
+    }
+
+    /// Looks `host` up in the secondary cache, if one is set
+    async fn get_secondary(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let addrs = self.secondary.as_ref()?.get(host).await?;
+        if addrs.is_empty() {
+            return None;
+        }
+        trace!("DNS secondary cache hit for {}", host);
+        Some(addrs)
+    }
+
+    /// Writes a resolved answer through to the secondary cache, if one is set
+    async fn set_secondary(&self, host: &str, addrs: &[SocketAddr], ttl: Duration) {
+        if let Some(ref secondary) = self.secondary {
+            if self.admits(host, addrs) {
+                secondary.set(host, addrs, ttl).await;
+            }
+        }
+    }
+
+    /// Returns the hosts that currently have a shared lookup in flight
+    ///
+    /// A host stuck in this list points at a hung resolver that is blocking every
+    /// caller waiting on it.
+    pub fn in_flight(&self) -> Vec<String> {
+        self.inner.lock().pending.keys().cloned().collect()
+    }
+
+    /// Removes the entry for a hostname, returning whether one was present
+    pub fn remove(&self, host: &str) -> bool {
+        self.evict(host, EvictReason::Manual)
+    }
+
+    /// Removes the entry for a hostname for the given reason
+    ///
+    /// Used with [`EvictReason::Unreachable`] to invalidate addresses that turned
+    /// out to be unreachable.
+    pub(crate) fn evict(&self, host: &str, reason: EvictReason) -> bool {
+        let host = &*cache_key(host);
+        let mut inner = self.inner.lock();
+        if inner.frozen && reason != EvictReason::Manual {
+            return false;
+        }
+        let was_empty = inner.cache.is_empty();
+        let removed = inner.cache.remove(host).is_some();
+
+        let hook = inner.transition(was_empty);
+        let on_evict = inner.on_evict.clone().filter(|_| removed);
+        drop(inner);
+        fire(hook);
+        if let Some(on_evict) = on_evict {
+            on_evict(host, reason);
+        }
+        removed
+    }
+
+    /// Marks every cached entry stale, without scanning the cache
+    ///
+    /// This bumps the cache generation: entries inserted before are treated as
+    /// expired by lookups, while entries inserted afterwards are fresh. Stale
+    /// entries can still be served through [`DnsCache::get_stale`], and are
+    /// removed like expired ones.
+    pub fn mark_all_stale(&self) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        trace!("DNS cache generation bumped to {}", inner.generation);
+    }
+
+    /// Replaces the cache contents with `entries`, cached for the default TTL
+    ///
+    /// Like [`DnsCache::mark_all_stale`] followed by inserting each entry, so the
+    /// previous contents are invalidated without being scanned.
+    pub fn replace_all<I>(&self, entries: I)
+    where
+        I: IntoIterator<Item = (String, Vec<SocketAddr>)>,
+    {
+        self.mark_all_stale();
+        for (host, addrs) in entries {
+            self.insert(host, addrs);
+        }
+    }
+
+    /// Freezes the cache contents, for inspection or during a sensitive operation
+    ///
+    /// While frozen, inserts are ignored and nothing is evicted: neither to make
+    /// room, nor by [`DnsCache::cleanup_expired`], nor because a host turned out
+    /// to be unreachable. Lookups keep honouring expiry, so an entry that expires
+    /// while frozen reads as a miss but stays in the cache. Explicit calls to
+    /// [`DnsCache::remove`] and [`DnsCache::clear`] still apply.
+    pub fn freeze(&self) {
+        self.inner.lock().frozen = true;
+    }
+
+    /// Resumes inserts and evictions after [`DnsCache::freeze`]
+    pub fn unfreeze(&self) {
+        self.inner.lock().frozen = false;
+    }
+
+    /// Returns whether the cache is frozen
+    pub fn is_frozen(&self) -> bool {
+        self.inner.lock().frozen
+    }
+
+    /// Returns a snapshot of the cache counters
+    pub fn stats(&self) -> DnsCacheStats {
+        let inner = self.inner.lock();
+        DnsCacheStats {
+            hits: inner.hits,
+            misses: inner.misses,
+            expirations: inner.expirations,
+            inserts: inner.inserts,
+            evictions: inner.evictions,
+            size: inner.cache.len(),
+            max_entries: inner.max_entries,
+            eviction_rate: inner.thrash.eviction_rate(),
+            thrashing: inner.thrash.thrashing,
+        }
+    }
+
+    /// Renders the cache counters in the Prometheus text exposition format
+    ///
+    /// Covers hits, misses, capacity evictions, expirations, the current number of
+    /// entries and the maximum number of entries, with metric names starting with
+    /// the prefix set by [`DnsCacheBuilder::metrics_prefix`].
+    pub fn prometheus_metrics(&self) -> String {
+        let inner = self.inner.lock();
+        let metrics = [
+            (
+                "hits_total",
+                "counter",
+                "Lookups answered from the cache.",
+                inner.hits,
+            ),
+            (
+                "misses_total",
+                "counter",
+                "Lookups that found no live entry.",
+                inner.misses,
+            ),
+            (
+                "evictions_total",
+                "counter",
+                "Entries evicted to make room for new ones.",
+                inner.evictions,
+            ),
+            (
+                "expirations_total",
+                "counter",
+                "Entries removed after their TTL expired.",
+                inner.expirations,
+            ),
+            (
+                "size",
+                "gauge",
+                "Entries currently cached.",
+                inner.cache.len() as u64,
+            ),
+            (
+                "capacity",
+                "gauge",
+                "Maximum number of cached entries.",
+                inner.max_entries as u64,
+            ),
+        ];
+        drop(inner);
+
+        let mut out = String::new();
+        for (name, kind, help, value) in metrics {
+            let prefix = &self.metrics_prefix;
+            let _ = writeln!(out, "# HELP {prefix}_{name} {help}");
+            let _ = writeln!(out, "# TYPE {prefix}_{name} {kind}");
+            let _ = writeln!(out, "{prefix}_{name} {value}");
+        }
+        out
     }
 
     /// Clears all entries from the cache
     #[allow(dead_code)]
     pub fn clear(&self) {
-        self.inner.lock().cache.clear();
+        let mut inner = self.inner.lock();
+        let was_empty = inner.cache.is_empty();
+        let evictions: Vec<_> = if inner.on_evict.is_some() {
+            let hosts = inner.cache.drain().map(|(host, _)| host);
+            hosts.map(|host| (host, EvictReason::Manual)).collect()
+        } else {
+            inner.cache.clear();
+            Vec::new()
+        };
+
+        let hook = inner.transition(was_empty);
+        let on_evict = inner.on_evict.clone();
+        drop(inner);
+        fire(hook);
+        fire_evictions(on_evict, evictions);
     }
 
     /// Returns the number of cached entries (including expired ones)
@@ -134,74 +1247,1184 @@ impl DnsCache {
     #[allow(dead_code)]
     pub fn cleanup_expired(&self) {
         let mut inner = self.inner.lock();
+        if inner.frozen {
+            return;
+        }
         let before = inner.cache.len();
-        inner.cache.retain(|_, entry| !entry.is_expired());
+        let mut evictions = Vec::new();
+        inner.remove_expired(&mut evictions);
         let removed = before - inner.cache.len();
         if removed > 0 {
             trace!("Cleaned up {} expired DNS cache entries", removed);
         }
+
+        let hook = inner.transition(before == 0);
+        let on_evict = inner.on_evict.clone();
+        drop(inner);
+        fire(hook);
+        fire_evictions(on_evict, evictions);
     }
 }
 
-impl Default for DnsCache {
-    fn default() -> Self {
-        Self::new()
+/// Runs a lifecycle hook outside of the cache lock, so it may use the cache itself
+fn fire(hook: Option<LifecycleHook>) {
+    if let Some(hook) = hook {
+        hook();
     }
 }
 
-/// Global DNS cache instance shared across all resolvers
-pub static GLOBAL_DNS_CACHE: LazyLock<DnsCache> = LazyLock::new(DnsCache::new);
+/// Returns whether two answers hold the same addresses, regardless of order
+fn same_addrs(a: &[SocketAddr], b: &[SocketAddr]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::net::{Ipv4Addr, SocketAddr};
+/// A tenant's view of a [`DnsCache`], created by [`DnsCache::partition`]
+#[derive(Clone)]
+pub struct DnsCachePartition {
+    cache: DnsCache,
+    tenant: Arc<str>,
+}
 
-    #[test]
-    fn test_cache_insert_and_get() {
-        let cache = DnsCache::new();
-        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+impl DnsCachePartition {
+    /// Returns the tenant this partition is scoped to
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
 
-        cache.insert("example.com".to_string(), addrs.clone());
+    /// Gets the tenant's cached addresses for a hostname, see [`DnsCache::get`]
+    pub fn get(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        self.cache.get(&self.key(host))
+    }
 
-        let cached = cache.get("example.com").unwrap();
-        assert_eq!(cached, addrs);
+    /// Gets the tenant's cached addresses for a hostname along with their remaining
+    /// TTL, see [`DnsCache::get_with_ttl`]
+    pub fn get_with_ttl(&self, host: &str) -> Option<(Vec<SocketAddr>, Duration)> {
+        self.cache.get_with_ttl(&self.key(host))
     }
 
-    #[test]
-    fn test_cache_expiration() {
-        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
-        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+    /// Inserts addresses for the tenant with the cache's default TTL
+    pub fn insert(&self, host: &str, addrs: Vec<SocketAddr>) {
+        self.cache.insert(self.key(host), addrs);
+    }
 
-        cache.insert("example.com".to_string(), addrs.clone());
+    /// Inserts addresses for the tenant with a custom TTL
+    pub fn insert_with_ttl(&self, host: &str, addrs: Vec<SocketAddr>, ttl: Duration) {
+        self.cache.insert_with_ttl(self.key(host), addrs, ttl);
+    }
 
-        // Should be cached
-        assert!(cache.get("example.com").is_some());
+    /// Removes the tenant's entry for a hostname, returning whether one was present
+    pub fn remove(&self, host: &str) -> bool {
+        self.cache.remove(&self.key(host))
+    }
+
+    fn key(&self, host: &str) -> String {
+        format!("{}/{host}", self.tenant)
+    }
+}
+
+/// Returns the key `host` is cached under: lowercased and without its trailing
+/// dot, so `Example.com.` and `example.com` share an entry. The tenant of a
+/// partitioned key is kept as it is.
+fn cache_key(host: &str) -> Cow<'_, str> {
+    match host.rsplit_once('/') {
+        Some((tenant, name)) => match normalize_host(name) {
+            Cow::Borrowed(key) if key.len() == name.len() => Cow::Borrowed(host),
+            key => Cow::Owned(format!("{tenant}/{key}")),
+        },
+        None => normalize_host(host),
+    }
+}
+
+/// Reports evicted entries outside of the cache lock
+fn fire_evictions(hook: Option<EvictHook>, evictions: Vec<(String, EvictReason)>) {
+    if let Some(hook) = hook {
+        for (host, reason) in evictions {
+            hook(&host, reason);
+        }
+    }
+}
+
+/// A builder to configure a [`DnsCache`]
+#[must_use]
+#[derive(Clone)]
+pub struct DnsCacheBuilder {
+    default_ttl: Duration,
+    min_ttl: Duration,
+    max_ttl: Duration,
+    max_entries: usize,
+    capacity: usize,
+    eviction_policy: EvictionPolicy,
+    metrics_prefix: Arc<str>,
+    refresh_ahead: Option<RefreshAhead>,
+    min_addrs: usize,
+    admission: Option<AdmissionFilter>,
+    max_background_refreshes: usize,
+    secondary: Option<Arc<dyn SecondaryCache>>,
+    negative_ttl: Option<Duration>,
+}
+
+impl DnsCacheBuilder {
+    /// Sets the TTL used for entries inserted without one. Defaults to 60 seconds.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Sets the shortest time an answer is cached for, even if its records carry a
+    /// lower TTL. Defaults to no minimum.
+    ///
+    /// Raising it spares the resolver hosts answered with very short TTLs.
+    pub fn min_ttl(mut self, ttl: Duration) -> Self {
+        self.min_ttl = ttl;
+        self
+    }
+
+    /// Sets the longest time an answer is cached for, even if its records carry a
+    /// higher TTL. Defaults to no maximum.
+    ///
+    /// Lowering it bounds how long a changed address can stay stale in the cache.
+    pub fn max_ttl(mut self, ttl: Duration) -> Self {
+        self.max_ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of cached hosts. Defaults to 1000.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets which entry is evicted when the cache is full. Defaults to
+    /// [`EvictionPolicy::LeastRecentlyUsed`].
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.eviction_policy = policy;
+        self
+    }
+
+    /// Sizes the cache up front to hold `capacity` hosts without reallocating
+    ///
+    /// Useful when the working set is known to be large, to avoid rehashing as
+    /// the cache fills up at startup. The capacity is capped at `max_entries`.
+    pub fn preallocate(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the prefix of the metric names rendered by
+    /// [`DnsCache::prometheus_metrics`]. Defaults to `wreq_dns_cache`.
+    pub fn metrics_prefix(mut self, prefix: &str) -> Self {
+        self.metrics_prefix = Arc::from(prefix);
+        self
+    }
+
+    /// Refreshes entries in the background shortly before they expire, see
+    /// [`DnsCache::with_refresh_ahead`]. Defaults to no refresh-ahead.
+    pub fn refresh_ahead(mut self, refresh_ahead: Duration, serve_stale_for: Duration) -> Self {
+        self.refresh_ahead = Some(RefreshAhead {
+            ahead: refresh_ahead,
+            serve_stale_for,
+        });
+        self
+    }
+
+    /// Only cache answers with at least `min` addresses
+    ///
+    /// Smaller answers are still returned to the caller but not cached, so the
+    /// next request resolves again. This guards against caching a partial answer
+    /// for a host that usually returns several addresses. Defaults to 1; empty
+    /// answers are never cached as addresses.
+    pub fn min_addrs_to_cache(mut self, min: usize) -> Self {
+        self.min_addrs = min;
+        self
+    }
+
+    /// Sets a filter deciding which answers are admitted into the cache
+    ///
+    /// The filter receives the hostname and its resolved addresses, and the answer
+    /// is only cached if it returns `true`. It is applied after the
+    /// [`DnsCacheBuilder::min_addrs_to_cache`] rule.
+    pub fn admission_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str, &[SocketAddr]) -> bool + Send + Sync + 'static,
+    {
+        self.admission = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets the maximum number of background refresh tasks that may run at once
+    ///
+    /// Refreshes requested while the limit is reached are skipped, leaving the
+    /// entry stale until a later access. The limit is shared by all clones of the
+    /// built cache. Defaults to 32.
+    pub fn max_background_refreshes(mut self, max: usize) -> Self {
+        self.max_background_refreshes = max;
+        self
+    }
+
+    /// Shares answers with other processes through a [`SecondaryCache`]
+    ///
+    /// On a local miss, the secondary store is consulted before the resolver runs,
+    /// and an answer found there is cached locally with the default TTL. Answers the
+    /// resolver returns are written through to the store, for the same TTL, before
+    /// the lookup completes. Entries added with [`DnsCache::insert`] are local and
+    /// not written through.
+    pub fn secondary_cache<C>(mut self, secondary: C) -> Self
+    where
+        C: SecondaryCache,
+    {
+        self.secondary = Some(Arc::new(secondary));
+        self
+    }
+
+    /// Caches negative answers for `ttl`
+    ///
+    /// The built-in resolvers remember that a name does not exist (`NXDOMAIN`) or
+    /// has no address records (`NODATA`), which spares the name servers a query
+    /// per request while a host is down or misspelled, and fail lookups with the
+    /// same [`DnsResolveError`] until `ttl` has passed. Other failures, such as
+    /// timeouts or `SERVFAIL`, are not cached. Negative entries are never
+    /// returned as addresses by [`DnsCache::get`] and the other getters. Keep
+    /// `ttl` short, so that a host is retried soon after it recovers. Default is
+    /// to not cache negative answers.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = Some(ttl);
+        self
+    }
+
+    /// Builds the [`DnsCache`]
+    pub fn build(self) -> DnsCache {
+        DnsCache::from_builder(self)
+    }
+}
+
+impl fmt::Debug for DnsCacheBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsCacheBuilder")
+            .field("default_ttl", &self.default_ttl)
+            .field("min_ttl", &self.min_ttl)
+            .field("max_ttl", &self.max_ttl)
+            .field("max_entries", &self.max_entries)
+            .field("eviction_policy", &self.eviction_policy)
+            .field("refresh_ahead", &self.refresh_ahead)
+            .field("min_addrs", &self.min_addrs)
+            .field("max_background_refreshes", &self.max_background_refreshes)
+            .field("negative_ttl", &self.negative_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DnsCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsCache")
+            .field("default_ttl", &self.default_ttl)
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Which [`DnsCache`] the resolvers of a [`Client`](crate::Client) use
+///
+/// Resolvers sharing a cache see each other's answers, so clients resolving
+/// differently, such as through other DNS-over-HTTPS endpoints, should not share
+/// one. See [`ClientBuilder::dns_cache`](crate::ClientBuilder::dns_cache).
+#[derive(Clone, Debug)]
+pub enum DnsCacheConfig {
+    /// A cache of the client's own, built from the given builder. This is the
+    /// default, with the [`DnsCache::builder`] settings.
+    PerClient(DnsCacheBuilder),
+    /// The given cache, shared by the clients given clones of the same handle
+    Shared(DnsCache),
+    /// The process-wide cache, see [`DnsCache::global`]
+    Global,
+}
+
+impl DnsCacheConfig {
+    /// Returns the cache this configuration designates, building it if needed
+    pub(crate) fn into_cache(self) -> DnsCache {
+        match self {
+            DnsCacheConfig::PerClient(builder) => builder.build(),
+            DnsCacheConfig::Shared(cache) => cache,
+            DnsCacheConfig::Global => DnsCache::global(),
+        }
+    }
+}
+
+impl Default for DnsCacheConfig {
+    fn default() -> Self {
+        DnsCacheConfig::PerClient(DnsCache::builder())
+    }
+}
+
+/// Returns the cache key of the answer for an `https` destination on the default
+/// port, which may come from an HTTPS record and carry its port and address hints.
+///
+/// Follows the port-prefix naming of [RFC 9460], so it never collides with the
+/// plain A/AAAA answer cached under the hostname.
+///
+/// [RFC 9460]: https://www.rfc-editor.org/rfc/rfc9460#section-2.3
+pub(crate) fn https_cache_key(host: &str) -> String {
+    format!("_443._https.{host}")
+}
+
+/// Global DNS cache instance shared across all resolvers
+pub static GLOBAL_DNS_CACHE: LazyLock<DnsCache> = LazyLock::new(DnsCache::new);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    #[test]
+    fn test_cache_insert_and_get() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("example.com".to_string(), addrs.clone());
+
+        let cached = cache.get("example.com").unwrap();
+        assert_eq!(cached, addrs);
+    }
+
+    #[test]
+    fn test_cache_keys_are_normalized() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("Example.COM.".to_string(), addrs.clone());
+        assert_eq!(cache.get("example.com"), Some(addrs.clone()));
+        assert_eq!(cache.get("EXAMPLE.com."), Some(addrs.clone()));
+        assert_eq!(cache.len(), 1);
+
+        // The tenant of a partition keeps its case
+        cache
+            .partition("Tenant")
+            .insert("WWW.example.com", addrs.clone());
+        assert_eq!(
+            cache.partition("Tenant").get("www.example.com."),
+            Some(addrs)
+        );
+        assert!(cache.partition("tenant").get("www.example.com").is_none());
+
+        assert!(cache.remove("example.com."));
+        assert!(cache.get("Example.com").is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_expiration() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("example.com".to_string(), addrs.clone());
+
+        // Should be cached
+        assert!(cache.get("example.com").is_some());
 
         // Wait for expiration
-        std::thread::sleep(Duration::from_millis(20));
+        tokio::time::advance(Duration::from_millis(20)).await;
 
         // Should be expired
         assert!(cache.get("example.com").is_none());
     }
 
+    #[test]
+    fn test_cache_get_with_ttl() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert_with_ttl(
+            "example.com".to_string(),
+            addrs.clone(),
+            Duration::from_secs(30),
+        );
+
+        let (cached, ttl) = cache.get_with_ttl("example.com").unwrap();
+        assert_eq!(cached, addrs);
+        assert!(ttl <= Duration::from_secs(30));
+        assert!(ttl > Duration::from_secs(25));
+    }
+
+    #[tokio::test]
+    async fn test_cache_upstream_ttl_is_clamped() {
+        let cache = DnsCache::builder()
+            .min_ttl(Duration::from_secs(30))
+            .max_ttl(Duration::from_secs(300))
+            .build();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 0)];
+
+        for (host, ttl, bound) in [
+            ("short.example", Some(1), 30),
+            ("long.example", Some(86400), 300),
+            ("within.example", Some(120), 120),
+            ("unknown.example", None, 60),
+        ] {
+            let answer = addrs.clone();
+            cache
+                .get_or_resolve_with_ttl(host, || async move {
+                    Ok((answer, ttl.map(Duration::from_secs)))
+                })
+                .await
+                .unwrap();
+
+            let (_, remaining) = cache.get_with_ttl(host).unwrap();
+            let bound = Duration::from_secs(bound);
+            assert!(remaining <= bound, "{host}: {remaining:?}");
+            assert!(remaining > bound / 2, "{host}: {remaining:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_negative_entries() {
+        // Failures are not cached unless a negative TTL is set
+        let cache = DnsCache::new();
+        cache.insert_negative("down.example".to_string(), NegativeKind::NxDomain);
+        assert_eq!(
+            cache.classify("down.example", Duration::ZERO),
+            CacheDecision::Miss
+        );
+
+        // Negative entries bypass the admission rules and expire on their own TTL
+        let cache = DnsCache::builder()
+            .min_addrs_to_cache(2)
+            .negative_ttl(Duration::from_millis(10))
+            .build();
+        cache.insert_negative("down.example".to_string(), NegativeKind::NxDomain);
+        assert_eq!(
+            cache.classify("down.example", Duration::ZERO),
+            CacheDecision::NegativeHit
+        );
+        assert_eq!(
+            cache.get_negative("down.example"),
+            Some(NegativeKind::NxDomain)
+        );
+
+        // They are kept apart from the addresses
+        assert!(cache.get("down.example").is_none());
+        assert!(cache.get_stale("down.example", Duration::MAX).is_none());
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+        assert!(cache.get_negative("down.example").is_none());
+
+        // An empty answer is not cached as addresses
+        cache.insert("empty.example".to_string(), Vec::new());
+        assert_eq!(
+            cache.classify("empty.example", Duration::ZERO),
+            CacheDecision::Miss
+        );
+    }
+
+    #[test]
+    fn test_cache_min_addrs_to_cache() {
+        let cache = DnsCache::builder().min_addrs_to_cache(2).build();
+        let one = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        let two = vec![
+            SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80),
+            SocketAddr::new(Ipv4Addr::new(127, 0, 0, 2).into(), 80),
+        ];
+
+        cache.insert("example.com".to_string(), one);
+        assert!(cache.get("example.com").is_none());
+        assert_eq!(cache.stats().inserts, 0);
+
+        cache.insert("example.com".to_string(), two.clone());
+        assert_eq!(cache.get("example.com"), Some(two));
+    }
+
+    #[tokio::test]
+    async fn test_cache_background_refreshes_are_capped_across_clones() {
+        let cache = DnsCache::builder().max_background_refreshes(1).build();
+        let clone = cache.clone();
+
+        assert!(cache.spawn_refresh(std::future::pending()));
+        assert!(!clone.spawn_refresh(std::future::pending()));
+    }
+
+    #[test]
+    fn test_cache_admission_filter() {
+        let cache = DnsCache::builder()
+            .admission_filter(|host, _| host != "internal.example")
+            .build();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("internal.example".to_string(), addrs.clone());
+        cache.insert("example.com".to_string(), addrs);
+        assert!(cache.get("internal.example").is_none());
+        assert!(cache.get("example.com").is_some());
+    }
+
+    #[test]
+    fn test_cache_load_zone() {
+        let cache = DnsCache::new();
+        let zone = "\
+$ORIGIN example.com.
+@    IN A    192.0.2.1
+www  IN A    192.0.2.10
+        AAAA 2001:db8::10
+api.example.org 192.0.2.20,192.0.2.21
+";
+
+        assert_eq!(cache.load_zone(zone, Duration::from_secs(30)).unwrap(), 3);
+        assert_eq!(
+            cache.get("www.example.com"),
+            Some(vec![
+                "192.0.2.10:0".parse().unwrap(),
+                "[2001:db8::10]:0".parse().unwrap(),
+            ])
+        );
+        assert_eq!(cache.get("example.com").map(|addrs| addrs.len()), Some(1));
+        assert_eq!(
+            cache.get("api.example.org").map(|addrs| addrs.len()),
+            Some(2)
+        );
+
+        let err = cache
+            .load_zone(
+                "ok.example.net 192.0.2.1\nbad.example.net A nope\n",
+                DEFAULT_DNS_TTL,
+            )
+            .unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert!(cache.get("ok.example.net").is_none());
+    }
+
+    #[test]
+    fn test_cache_freeze() {
+        let cache = DnsCache::with_config(DEFAULT_DNS_TTL, 1);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        cache.insert("kept.example.com".to_string(), addrs.clone());
+
+        cache.freeze();
+        assert!(cache.is_frozen());
+        cache.insert("ignored.example.com".to_string(), addrs.clone());
+        assert!(cache.get("ignored.example.com").is_none());
+        assert!(!cache.evict("kept.example.com", EvictReason::Unreachable));
+        assert_eq!(cache.get("kept.example.com"), Some(addrs.clone()));
+        assert_eq!(cache.len(), 1);
+
+        cache.unfreeze();
+        assert!(!cache.is_frozen());
+        cache.insert("resumed.example.com".to_string(), addrs.clone());
+        assert_eq!(cache.get("resumed.example.com"), Some(addrs));
+        // Inserting past capacity evicts again
+        assert!(cache.get("kept.example.com").is_none());
+    }
+
+    #[test]
+    fn test_cache_mark_all_stale() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        cache.insert("old.example.com".to_string(), addrs.clone());
+
+        cache.mark_all_stale();
+        assert!(cache.get("old.example.com").is_none());
+        assert_eq!(
+            cache.get_stale("old.example.com", Duration::ZERO),
+            Some(addrs.clone())
+        );
+
+        cache.insert("new.example.com".to_string(), addrs.clone());
+        assert_eq!(cache.get("new.example.com"), Some(addrs.clone()));
+
+        cache.replace_all([("replaced.example.com".to_string(), addrs.clone())]);
+        assert!(cache.get("new.example.com").is_none());
+        assert_eq!(cache.get("replaced.example.com"), Some(addrs));
+    }
+
+    #[test]
+    fn test_cache_get_many() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("a.example.com".to_string(), addrs.clone());
+        cache.insert("b.example.com".to_string(), addrs.clone());
+        cache.insert_with_ttl(
+            "expired.example.com".to_string(),
+            addrs.clone(),
+            Duration::ZERO,
+        );
+
+        let results = cache.get_many(&[
+            "a.example.com",
+            "missing.example.com",
+            "expired.example.com",
+            "b.example.com",
+        ]);
+        assert_eq!(
+            results,
+            vec![
+                ("a.example.com".to_string(), Some(addrs.clone())),
+                ("missing.example.com".to_string(), None),
+                ("expired.example.com".to_string(), None),
+                ("b.example.com".to_string(), Some(addrs)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_get_at() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        let now = Instant::now();
+
+        cache.insert_with_ttl(
+            "example.com".to_string(),
+            addrs.clone(),
+            Duration::from_secs(30),
+        );
+        cache.insert_with_ttl(
+            "short.example.com".to_string(),
+            addrs.clone(),
+            Duration::from_secs(5),
+        );
+
+        let before = now + Duration::from_secs(10);
+        let after = now + Duration::from_secs(31);
+
+        assert_eq!(cache.get_at("example.com", before), Some(addrs.clone()));
+        assert!(cache.get_at("example.com", after).is_none());
+        assert!(cache.get_at("short.example.com", before).is_none());
+
+        assert_eq!(
+            cache.dump_at(now),
+            vec![
+                ("example.com".to_string(), addrs.clone()),
+                ("short.example.com".to_string(), addrs.clone()),
+            ]
+        );
+        assert_eq!(
+            cache.dump_at(before),
+            vec![("example.com".to_string(), addrs)]
+        );
+        assert!(cache.dump_at(after).is_empty());
+
+        // Querying the future leaves the entries in place
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_preallocate() {
+        let cache = DnsCache::builder()
+            .max_entries(500)
+            .preallocate(500)
+            .build();
+        assert!(cache.inner.lock().cache.capacity() >= 500);
+
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        for i in 0..500 {
+            cache.insert(format!("host-{i}.example.com"), addrs.clone());
+        }
+
+        assert_eq!(cache.len(), 500);
+        assert_eq!(cache.stats().evictions, 0);
+        assert!((0..500).all(|i| cache.get(&format!("host-{i}.example.com")).is_some()));
+
+        // Capacity beyond max_entries is not allocated
+        let cache = DnsCache::builder()
+            .max_entries(10)
+            .preallocate(10_000)
+            .build();
+        assert!(cache.inner.lock().cache.capacity() < 10_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_on_evict() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 2);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let log = evicted.clone();
+        cache.on_evict(move |host, reason| log.lock().unwrap().push((host.to_owned(), reason)));
+
+        // Filling past capacity evicts one of the live entries
+        cache.insert_with_ttl("a.example.com".to_string(), addrs.clone(), DEFAULT_DNS_TTL);
+        cache.insert_with_ttl("b.example.com".to_string(), addrs.clone(), DEFAULT_DNS_TTL);
+        cache.insert_with_ttl("c.example.com".to_string(), addrs.clone(), DEFAULT_DNS_TTL);
+        {
+            let evicted = evicted.lock().unwrap();
+            assert_eq!(evicted.len(), 1);
+            assert_eq!(evicted[0].1, EvictReason::Capacity);
+        }
+
+        cache.clear();
+        cache.insert("short.example.com".to_string(), addrs);
+        evicted.lock().unwrap().clear();
+        tokio::time::advance(Duration::from_millis(20)).await;
+        cache.cleanup_expired();
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![("short.example.com".to_string(), EvictReason::Expired)]
+        );
+
+        // Removing a missing entry is not an eviction
+        assert!(!cache.remove("short.example.com"));
+        assert_eq!(evicted.lock().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_cache_miss() {
         let cache = DnsCache::new();
         assert!(cache.get("nonexistent.com").is_none());
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_get_stale() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("example.com".to_string(), addrs.clone());
+        tokio::time::advance(Duration::from_millis(20)).await;
+
+        // Expired for regular lookups, but still within the stale window
+        assert!(cache.get("example.com").is_none());
+        assert_eq!(
+            cache.get_stale("example.com", Duration::from_secs(60)),
+            Some(addrs.clone())
+        );
+        // A window too large to add to the expiry never closes
+        assert_eq!(cache.get_stale("example.com", Duration::MAX), Some(addrs));
+
+        // Outside of the stale window
+        assert!(
+            cache
+                .get_stale("example.com", Duration::from_millis(1))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_cache_thrashing_detected() {
+        let cache = DnsCache::with_config(Duration::from_secs(60), 10);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        for i in 0..1000 {
+            cache.insert(format!("host-{i}.example.com"), addrs.clone());
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.inserts, 1000);
+        assert_eq!(stats.evictions, 990);
+        assert!(stats.eviction_rate > THRASH_EVICTION_RATIO);
+        assert!(stats.thrashing);
+    }
+
+    #[test]
+    fn test_cache_not_thrashing_within_capacity() {
+        let cache = DnsCache::with_config(Duration::from_secs(60), 1000);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        for i in 0..500 {
+            cache.insert(format!("host-{i}.example.com"), addrs.clone());
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 0);
+        assert!(!stats.thrashing);
+    }
+
+    #[test]
+    fn test_cache_lifecycle_hooks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        let first = Arc::new(AtomicUsize::new(0));
+        let empty = Arc::new(AtomicUsize::new(0));
+
+        let counter = first.clone();
+        cache.on_first_entry(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let counter = empty.clone();
+        cache.on_empty(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        cache.insert("a.example.com".to_string(), addrs.clone());
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+
+        cache.insert("b.example.com".to_string(), addrs.clone());
+        cache.insert("a.example.com".to_string(), addrs);
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+        assert_eq!(empty.load(Ordering::SeqCst), 0);
+
+        cache.clear();
+        assert_eq!(empty.load(Ordering::SeqCst), 1);
+
+        // Clearing an already empty cache is not a transition
+        cache.clear();
+        assert_eq!(empty.load(Ordering::SeqCst), 1);
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_in_flight_lookup() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        let (release, released) = oneshot::channel::<()>();
+
+        let lookup = {
+            let cache = cache.clone();
+            let addrs = addrs.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_resolve("slow.example.com", || async move {
+                        let _ = released.await;
+                        Ok(addrs)
+                    })
+                    .await
+            })
+        };
+
+        while cache.in_flight().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(cache.in_flight(), vec!["slow.example.com".to_string()]);
+
+        // A concurrent caller joins the in-flight lookup instead of resolving
+        let joined = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_resolve("slow.example.com", || {
+                        std::future::ready(Err("lookup should be shared".into()))
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+
+        release.send(()).unwrap();
+        assert_eq!(lookup.await.unwrap().unwrap(), addrs);
+        assert_eq!(joined.await.unwrap().unwrap(), addrs);
+
+        assert!(cache.in_flight().is_empty());
+        assert_eq!(cache.get("slow.example.com"), Some(addrs));
+    }
+
+    #[tokio::test]
+    async fn test_cache_refresh_ahead() {
+        let cache =
+            DnsCache::new().with_refresh_ahead(Duration::from_secs(5), Duration::from_secs(5));
+        let old = vec![SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 0)];
+        let new = vec![SocketAddr::new(Ipv4Addr::new(192, 0, 2, 2).into(), 0)];
+
+        // A fresh entry far from expiry is not refreshed
+        cache.insert_with_ttl(
+            "far.example".to_string(),
+            old.clone(),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            cache.get_refresh_ahead("far.example"),
+            Some((old.clone(), false))
+        );
+
+        // An expired entry is still served, and refreshed only once
+        cache.insert_with_ttl("soon.example".to_string(), old.clone(), Duration::ZERO);
+        assert!(cache.get("soon.example").is_none());
+        assert_eq!(
+            cache.get_refresh_ahead("soon.example"),
+            Some((old.clone(), true))
+        );
+        assert_eq!(
+            cache.get_refresh_ahead("soon.example"),
+            Some((old.clone(), false))
+        );
+
+        let answer = new.clone();
+        cache.refresh_in_background("soon.example", move || async move {
+            Ok((answer, Some(Duration::from_secs(60))))
+        });
+        for _ in 0..100 {
+            if cache.get("soon.example").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(cache.get_refresh_ahead("soon.example"), Some((new, false)));
+        assert!(cache.inner.lock().refreshing.is_empty());
+    }
+
     #[test]
-    fn test_cache_cleanup() {
+    fn test_builder_refresh_ahead() {
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 0)];
+
+        // Not refreshed ahead unless asked for
+        let cache = DnsCache::builder().build();
+        cache.insert_with_ttl("soon.example".to_string(), addrs.clone(), Duration::ZERO);
+        assert_eq!(cache.get_refresh_ahead("soon.example"), None);
+
+        let cache = DnsCache::builder()
+            .refresh_ahead(Duration::from_secs(5), Duration::from_secs(5))
+            .build();
+        cache.insert_with_ttl("soon.example".to_string(), addrs.clone(), Duration::ZERO);
+        assert_eq!(cache.get_refresh_ahead("soon.example"), Some((addrs, true)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_cleanup() {
         let cache = DnsCache::with_config(Duration::from_millis(10), 100);
         let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
 
         cache.insert("example.com".to_string(), addrs);
         assert_eq!(cache.len(), 1);
 
-        std::thread::sleep(Duration::from_millis(20));
+        tokio::time::advance(Duration::from_millis(20)).await;
         cache.cleanup_expired();
 
         assert_eq!(cache.len(), 0);
     }
+
+    #[test]
+    fn test_cache_stats_size_and_hit_rate() {
+        let cache = DnsCache::with_config(Duration::from_secs(60), 10);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+
+        cache.insert("a.example".to_string(), addrs.clone());
+        cache.insert("b.example".to_string(), addrs);
+        for host in ["a.example", "a.example", "b.example", "missing.example"] {
+            cache.get(host);
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.size, stats.max_entries), (2, 10));
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+        assert_eq!(stats.hit_rate(), 0.75);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_prometheus_metrics() {
+        let cache = DnsCache::builder()
+            .default_ttl(Duration::from_millis(10))
+            .max_entries(2)
+            .metrics_prefix("app_dns")
+            .build();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert("a.example".to_string(), addrs.clone());
+        cache.insert("b.example".to_string(), addrs.clone());
+        assert!(cache.get("a.example").is_some());
+        assert!(cache.get("missing.example").is_none());
+
+        // Both entries expire and are cleaned up to make room
+        tokio::time::advance(Duration::from_millis(20)).await;
+        cache.insert_with_ttl("c.example".to_string(), addrs, Duration::from_secs(60));
+
+        let metrics = cache.prometheus_metrics();
+        for line in [
+            "# TYPE app_dns_hits_total counter",
+            "app_dns_hits_total 1",
+            "app_dns_misses_total 1",
+            "app_dns_evictions_total 0",
+            "app_dns_expirations_total 2",
+            "# TYPE app_dns_size gauge",
+            "app_dns_size 1",
+            "app_dns_capacity 2",
+        ] {
+            assert!(
+                metrics.lines().any(|l| l == line),
+                "missing {line:?} in\n{metrics}"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_classify() {
+        let cache = DnsCache::new();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        cache.insert_with_ttl(
+            "live.example".to_string(),
+            addrs.clone(),
+            Duration::from_secs(60),
+        );
+        cache.insert_with_ttl("old.example".to_string(), addrs, Duration::from_millis(10));
+        cache.insert_negative_with_ttl(
+            "none.example".to_string(),
+            NegativeKind::NoData,
+            Duration::from_secs(60),
+        );
+        tokio::time::advance(Duration::from_millis(20)).await;
+
+        assert_eq!(
+            cache.classify("live.example", Duration::ZERO),
+            CacheDecision::Hit
+        );
+        assert_eq!(
+            cache.classify("old.example", Duration::from_secs(60)),
+            CacheDecision::Stale
+        );
+        // Past the serve-stale window, an expired entry is as good as none
+        assert_eq!(
+            cache.classify("old.example", Duration::from_millis(5)),
+            CacheDecision::Miss
+        );
+        assert_eq!(
+            cache.classify("none.example", Duration::ZERO),
+            CacheDecision::NegativeHit
+        );
+        assert_eq!(
+            cache.classify("unknown.example", Duration::ZERO),
+            CacheDecision::Miss
+        );
+
+        // Classifying changed nothing
+        assert_eq!(cache.len(), 3);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_on_addr_change() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        cache.on_addr_change({
+            let changes = changes.clone();
+            move |host, old, new| {
+                changes
+                    .lock()
+                    .push((host.to_owned(), old.to_vec(), new.to_vec()))
+            }
+        });
+
+        // A resolver steered from one address to two, then answering them in another order
+        let a = SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 0);
+        let b = SocketAddr::new(Ipv4Addr::new(192, 0, 2, 2).into(), 0);
+        for answer in [vec![a], vec![a], vec![a, b], vec![b, a]] {
+            cache
+                .get_or_resolve("cdn.example", || async move { Ok(answer) })
+                .await
+                .unwrap();
+            tokio::time::advance(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(
+            *changes.lock(),
+            [("cdn.example".to_owned(), vec![a], vec![a, b])]
+        );
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = DnsCache::with_config(Duration::from_secs(60), 3);
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        for host in ["a.example", "b.example", "c.example"] {
+            cache.insert(host.to_string(), addrs.clone());
+        }
+        // Looking a host up keeps it from being evicted next
+        assert!(cache.get("a.example").is_some());
+        cache.insert("d.example".to_string(), addrs.clone());
+        assert!(cache.get("b.example").is_none());
+
+        // Reads that don't count as a use leave the order alone
+        assert_eq!(
+            cache.classify("c.example", Duration::ZERO),
+            CacheDecision::Hit
+        );
+        assert!(cache.get_at("c.example", Instant::now()).is_some());
+        cache.insert("e.example".to_string(), addrs);
+        assert!(cache.get("c.example").is_none());
+
+        for host in ["a.example", "d.example", "e.example"] {
+            assert!(cache.get(host).is_some(), "{host} was evicted");
+        }
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_cache_evicts_shortest_remaining_ttl() {
+        let cache = DnsCache::builder()
+            .max_entries(3)
+            .eviction_policy(EvictionPolicy::ShortestRemainingTtl)
+            .build();
+        let addrs = vec![SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 80)];
+
+        for (host, secs) in [
+            ("long.example", 600),
+            ("short.example", 5),
+            ("mid.example", 60),
+        ] {
+            cache.insert_with_ttl(host.to_string(), addrs.clone(), Duration::from_secs(secs));
+        }
+        cache.insert_with_ttl("new.example".to_string(), addrs, Duration::from_secs(30));
+
+        assert_eq!(cache.len(), 3);
+        assert!(cache.get("short.example").is_none());
+        for host in ["long.example", "mid.example", "new.example"] {
+            assert!(cache.get(host).is_some(), "{host} was evicted");
+        }
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_secondary_write_through() {
+        use crate::dns::{InMemorySecondaryCache, SecondaryCache};
+
+        let secondary = Arc::new(InMemorySecondaryCache::new());
+        let cache = DnsCache::builder()
+            .secondary_cache(secondary.clone())
+            .build();
+        let shared = vec![SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 0)];
+        let resolved = vec![SocketAddr::new(Ipv4Addr::new(10, 0, 0, 2).into(), 0)];
+
+        // A local miss is answered by the secondary, without resolving
+        secondary
+            .set("shared.example", &shared, Duration::from_secs(60))
+            .await;
+        let addrs = cache
+            .get_or_resolve("shared.example", || {
+                std::future::ready(Err("secondary should answer".into()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(addrs, shared);
+        assert_eq!(cache.get("shared.example"), Some(shared));
+
+        // Resolved answers are written through to the secondary
+        let answer = resolved.clone();
+        cache
+            .get_or_resolve("fresh.example", || async move { Ok(answer) })
+            .await
+            .unwrap();
+        assert_eq!(secondary.get("fresh.example").await, Some(resolved.clone()));
+        assert_eq!(cache.get("fresh.example"), Some(resolved));
+        assert_eq!(secondary.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_partitions_do_not_interfere() {
+        let cache = DnsCache::with_config(Duration::from_secs(60), 2);
+        let a = cache.partition("tenant-a");
+        let b = cache.partition("tenant-b");
+        let addr_a = vec![SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 0)];
+        let addr_b = vec![SocketAddr::new(Ipv4Addr::new(10, 0, 0, 2).into(), 0)];
+
+        a.insert("api.example.com", addr_a.clone());
+        assert_eq!(b.get("api.example.com"), None);
+        assert_eq!(cache.get("api.example.com"), None);
+
+        b.insert("api.example.com", addr_b.clone());
+        assert_eq!(a.get("api.example.com"), Some(addr_a.clone()));
+        assert_eq!(b.get("api.example.com"), Some(addr_b));
+
+        // Partitions share the memory budget of the cache
+        assert_eq!(cache.len(), 2);
+
+        assert!(b.remove("api.example.com"));
+        assert_eq!(a.get("api.example.com"), Some(addr_a));
+        assert_eq!(b.tenant(), "tenant-b");
+    }
 }
@@ -0,0 +1,165 @@
+//! Caching the answers of any resolver in a [`DnsCache`]
+
+use std::{net::SocketAddr, sync::Arc};
+
+use super::{
+    Addrs, DnsResolveError, IntoResolve, Name, Resolve, Resolving,
+    cache::{DnsCache, NegativeKind},
+};
+use crate::error::BoxError;
+
+/// A resolver that caches the answers of another resolver in a [`DnsCache`].
+///
+/// The built-in resolvers cache their lookups on their own; wrap a custom
+/// resolver, such as one querying a service registry, to give it the same
+/// treatment. Lookups are answered from the cache while an entry is live, and
+/// concurrent lookups of a host share a single query to the inner resolver.
+/// Failures the inner resolver reports as negative answers, with a
+/// [`DnsResolveError`] for which [`DnsResolveError::is_nxdomain`] or
+/// [`DnsResolveError::is_no_addresses`] returns `true`, are cached too when the
+/// cache has a negative TTL; other failures are not. Entries are refreshed ahead
+/// of expiry when the cache has refresh-ahead enabled.
+///
+/// Answers are cached in the global DNS cache unless given another with
+/// [`CachingResolver::with_cache`], such as the one shared with clients through
+/// [`DnsCacheConfig::Shared`](super::DnsCacheConfig::Shared).
+#[derive(Clone)]
+pub struct CachingResolver {
+    inner: Arc<dyn Resolve>,
+    cache: DnsCache,
+}
+
+impl CachingResolver {
+    /// Wraps `inner`, caching its answers in the global DNS cache.
+    pub fn new<R>(inner: R) -> CachingResolver
+    where
+        R: IntoResolve,
+    {
+        CachingResolver {
+            inner: inner.into_resolve(),
+            cache: DnsCache::global(),
+        }
+    }
+
+    /// Caches answers in `cache` instead of the global DNS cache.
+    pub fn with_cache(mut self, cache: DnsCache) -> CachingResolver {
+        self.cache = cache;
+        self
+    }
+
+    /// Returns the cache answers are kept in.
+    #[inline]
+    pub fn cache(&self) -> &DnsCache {
+        &self.cache
+    }
+
+    /// Resolves `name` with the inner resolver, bypassing the cache
+    async fn lookup_uncached(&self, name: Name) -> Result<Vec<SocketAddr>, BoxError> {
+        Ok(self.inner.resolve(name).await?.collect())
+    }
+
+    async fn lookup(self, name: Name) -> Result<Addrs, BoxError> {
+        let host = name.as_str();
+        if let Some(kind) = self.cache.get_negative(host) {
+            return Err(DnsResolveError::negative(host, kind).into());
+        }
+        if let Some((addrs, refresh)) = self.cache.get_refresh_ahead(host) {
+            trace!("Using cached DNS result for {}", host);
+            if refresh {
+                let resolver = self.clone();
+                let name = name.clone();
+                self.cache.refresh_in_background(host, move || async move {
+                    resolver
+                        .lookup_uncached(name)
+                        .await
+                        .map(|addrs| (addrs, None))
+                });
+            }
+            return Ok(Box::new(addrs.into_iter()));
+        }
+
+        let addrs = self
+            .cache
+            .get_or_resolve(host, || self.lookup_uncached(name.clone()))
+            .await
+            .inspect_err(|err| {
+                let negative = err
+                    .downcast_ref::<DnsResolveError>()
+                    .and_then(DnsResolveError::negative_kind);
+                if let Some(kind) = negative {
+                    self.cache.insert_negative(host.to_owned(), kind);
+                }
+            })?;
+        if addrs.is_empty() {
+            self.cache
+                .insert_negative(host.to_owned(), NegativeKind::NoData);
+            return Err(DnsResolveError::no_addresses(host).into());
+        }
+        Ok(Box::new(addrs.into_iter()))
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().lookup(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::dns::mock::MockResolver;
+
+    #[tokio::test]
+    async fn caches_answers_of_the_inner_resolver() {
+        let mock = MockResolver::default();
+        let cache = DnsCache::new();
+        let resolver = CachingResolver::new(mock.clone()).with_cache(cache.clone());
+
+        for _ in 0..3 {
+            let addrs: Vec<_> = resolver
+                .resolve("svc.internal".into())
+                .await
+                .unwrap()
+                .collect();
+            assert_eq!(addrs.len(), 1);
+        }
+        assert_eq!(mock.lookups(), 1);
+        assert!(cache.get("svc.internal").is_some());
+    }
+
+    #[tokio::test]
+    async fn caches_negative_answers_with_a_negative_ttl() {
+        let mock = MockResolver::nxdomain();
+        let cache = DnsCache::builder()
+            .negative_ttl(Duration::from_secs(5))
+            .build();
+        let resolver = CachingResolver::new(mock.clone()).with_cache(cache);
+
+        for _ in 0..3 {
+            let err = resolver
+                .resolve("down.internal".into())
+                .await
+                .err()
+                .unwrap();
+            assert!(err.downcast_ref::<DnsResolveError>().unwrap().is_nxdomain());
+        }
+        assert_eq!(mock.lookups(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_other_failures() {
+        let mock = MockResolver::failing();
+        let cache = DnsCache::builder()
+            .negative_ttl(Duration::from_secs(5))
+            .build();
+        let resolver = CachingResolver::new(mock.clone()).with_cache(cache);
+
+        for _ in 0..3 {
+            assert!(resolver.resolve("flaky.internal".into()).await.is_err());
+        }
+        assert_eq!(mock.lookups(), 3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Normalization of hostnames into the form used as lookup keys
+
+use std::{borrow::Cow, error::Error as StdError, fmt};
+
+/// The longest a single label may be, in bytes ([RFC 1035] section 2.3.4).
+///
+/// [RFC 1035]: https://www.rfc-editor.org/rfc/rfc1035
+const MAX_LABEL_LEN: usize = 63;
+
+/// The longest a name may be in its dotted form, without the trailing dot.
+const MAX_NAME_LEN: usize = 253;
+
+/// A hostname that cannot be canonicalized.
+#[derive(Debug)]
+pub struct InvalidHostError {
+    host: Box<str>,
+    reason: &'static str,
+}
+
+impl InvalidHostError {
+    fn new(host: &str, reason: &'static str) -> InvalidHostError {
+        InvalidHostError {
+            host: host.into(),
+            reason,
+        }
+    }
+
+    /// Returns the hostname that was rejected.
+    #[inline]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+}
+
+impl fmt::Display for InvalidHostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hostname `{}`: {}", self.host, self.reason)
+    }
+}
+
+impl StdError for InvalidHostError {}
+
+/// Returns the canonical form of `host`: lowercased, without its trailing dot.
+///
+/// This is the form the resolvers and the cache key hosts by, so `Example.COM.`
+/// and `example.com` name the same entry. Fails if the name is empty, has an
+/// empty label, a label longer than 63 bytes, or is longer than 253 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use wreq::dns::canonicalize_host;
+///
+/// assert_eq!(canonicalize_host("WWW.Example.com.").unwrap(), "www.example.com");
+/// assert!(canonicalize_host("a..b").is_err());
+/// ```
+pub fn canonicalize_host(host: &str) -> Result<String, InvalidHostError> {
+    let name = host.strip_suffix('.').unwrap_or(host);
+
+    if name.is_empty() {
+        return Err(InvalidHostError::new(host, "empty name"));
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(InvalidHostError::new(host, "name longer than 253 bytes"));
+    }
+    for label in name.split('.') {
+        if label.is_empty() {
+            return Err(InvalidHostError::new(host, "empty label"));
+        }
+        if label.len() > MAX_LABEL_LEN {
+            return Err(InvalidHostError::new(host, "label longer than 63 bytes"));
+        }
+    }
+
+    Ok(name.to_ascii_lowercase())
+}
+
+/// Returns `host` lowercased and without its trailing dot, like
+/// [`canonicalize_host`] but keeping names that fail validation as they are.
+pub(crate) fn normalize_host(host: &str) -> Cow<'_, str> {
+    let name = host.strip_suffix('.').unwrap_or(host);
+    if name.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercases_mixed_case() {
+        assert_eq!(
+            canonicalize_host("WwW.ExAmple.COM").unwrap(),
+            "www.example.com"
+        );
+        assert_eq!(canonicalize_host("localhost").unwrap(), "localhost");
+    }
+
+    #[test]
+    fn strips_trailing_dot() {
+        assert_eq!(canonicalize_host("example.com.").unwrap(), "example.com");
+        assert!(canonicalize_host(".").is_err());
+        assert!(canonicalize_host("example.com..").is_err());
+    }
+
+    #[test]
+    fn rejects_over_long_label() {
+        let label = "a".repeat(64);
+        let host = format!("{label}.example.com");
+
+        let err = canonicalize_host(&host).unwrap_err();
+        assert_eq!(err.host(), host);
+        assert!(err.to_string().contains("label longer than 63 bytes"));
+
+        let longest = format!("{}.example.com", "a".repeat(63));
+        assert!(canonicalize_host(&longest).is_ok());
+    }
+}
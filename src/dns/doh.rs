@@ -0,0 +1,299 @@
+//! DNS resolution over HTTPS ([RFC 8484](https://www.rfc-editor.org/rfc/rfc8484))
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use hickory_resolver::proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name as DnsName, RData, RecordType},
+};
+use http::Uri;
+
+use super::{Addrs, DnsResolveError, Name, Resolve, Resolving, cache::DnsCache};
+use crate::{
+    Client, IntoUri,
+    error::BoxError,
+    header::{ACCEPT, CONTENT_TYPE},
+};
+
+/// Media type of DNS messages in requests and responses
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// A resolver that sends its queries to a DNS-over-HTTPS endpoint.
+///
+/// Each lookup POSTs an A and an AAAA query in DNS wire format to the endpoint,
+/// such as `https://1.1.1.1/dns-query` or `https://dns.google/dns-query`, which
+/// keeps lookups working where plaintext UDP DNS is filtered. Addresses found are
+/// cached for the smallest TTL of their A and AAAA records, clamped to the TTL
+/// bounds of the cache. Names that do not exist (`NXDOMAIN`) or have no address
+/// records are cached for the negative TTL of the cache, if it has one (see
+/// [`DnsCacheBuilder::negative_ttl`](super::DnsCacheBuilder::negative_ttl));
+/// other failures, like HTTP errors or `SERVFAIL`, are not cached.
+/// The cache is the global DNS cache unless given another with
+/// [`DohResolver::with_cache`]; with
+/// [`ClientBuilder::doh_url`](crate::ClientBuilder::doh_url) it is the one of the
+/// client being built.
+///
+/// The queries are sent with a [`Client`] of their own, created with the default
+/// settings unless given with [`DohResolver::with_client`]. That client resolves
+/// the endpoint's host with the hickory resolver, which queries the name servers
+/// of the system configuration over plaintext DNS, so give the endpoint as an IP
+/// address where that is filtered too.
+///
+/// Use it with [`ClientBuilder::doh_url`](crate::ClientBuilder::doh_url), or with
+/// [`ClientBuilder::dns_resolver`](crate::ClientBuilder::dns_resolver) to combine it
+/// with other resolvers.
+#[derive(Clone)]
+pub struct DohResolver {
+    inner: Arc<Inner>,
+    cache: DnsCache,
+}
+
+struct Inner {
+    url: Uri,
+    client: Client,
+}
+
+impl DohResolver {
+    /// Creates a resolver querying the endpoint at `url`.
+    pub fn new<U: IntoUri>(url: U) -> crate::Result<DohResolver> {
+        DohResolver::with_client(url, Client::builder().build()?)
+    }
+
+    /// Creates a resolver querying the endpoint at `url` with `client`.
+    ///
+    /// `client` must not itself resolve hosts with this resolver.
+    pub fn with_client<U: IntoUri>(url: U, client: Client) -> crate::Result<DohResolver> {
+        Ok(DohResolver {
+            inner: Arc::new(Inner {
+                url: url.into_uri()?,
+                client,
+            }),
+            cache: DnsCache::global(),
+        })
+    }
+
+    /// Caches lookups in `cache` instead of the global DNS cache.
+    pub fn with_cache(mut self, cache: DnsCache) -> DohResolver {
+        self.cache = cache;
+        self
+    }
+
+    /// Returns the URL of the endpoint queries are sent to.
+    #[inline]
+    pub fn url(&self) -> &Uri {
+        &self.inner.url
+    }
+
+    async fn lookup(self, name: Name) -> Result<Addrs, BoxError> {
+        let host = name.as_str();
+        if let Some(kind) = self.cache.get_negative(host) {
+            return Err(DnsResolveError::negative(host, kind).into());
+        }
+        if let Some((addrs, _)) = self.cache.get_with_ttl(host) {
+            trace!("Using cached DNS result for {}", host);
+            return Ok(Box::new(addrs.into_iter()));
+        }
+
+        match self.lookup_uncached(host).await {
+            Ok((addrs, ttl)) => {
+                self.cache
+                    .insert_upstream(host.to_owned(), addrs.clone(), ttl);
+                Ok(Box::new(addrs.into_iter()))
+            }
+            Err(err) => {
+                if let Some(kind) = err.negative_kind() {
+                    self.cache.insert_negative(host.to_owned(), kind);
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Queries the addresses of `host`, along with the smallest TTL of their
+    /// records.
+    async fn lookup_uncached(
+        &self,
+        host: &str,
+    ) -> Result<(Vec<SocketAddr>, Duration), DnsResolveError> {
+        let dns_name = DnsName::from_ascii(host).map_err(|err| DnsResolveError::new(host, err))?;
+        let (v4, v6) = futures_util::future::join(
+            self.query(dns_name.clone(), RecordType::A),
+            self.query(dns_name, RecordType::AAAA),
+        )
+        .await;
+
+        let mut addrs = Vec::new();
+        let mut ttl = None::<Duration>;
+        for reply in [v4, v6] {
+            match reply.map_err(|err| DnsResolveError::new(host, err))? {
+                Reply::Addrs(found, found_ttl) => {
+                    if !found.is_empty() {
+                        addrs.extend(found);
+                        ttl = Some(ttl.map_or(found_ttl, |ttl| ttl.min(found_ttl)));
+                    }
+                }
+                Reply::NxDomain => {
+                    debug!("DNS-over-HTTPS lookup for {} found no such domain", host);
+                    return Err(DnsResolveError::nxdomain(host));
+                }
+            }
+        }
+
+        let Some(ttl) = ttl else {
+            debug!("DNS-over-HTTPS lookup for {} returned no addresses", host);
+            return Err(DnsResolveError::no_addresses(host));
+        };
+        Ok((addrs, ttl))
+    }
+
+    /// Sends a query for the `record_type` records of `name`.
+    async fn query(&self, name: DnsName, record_type: RecordType) -> Result<Reply, BoxError> {
+        let res = self
+            .inner
+            .client
+            .post(self.inner.url.clone())
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .header(ACCEPT, DNS_MESSAGE)
+            .body(build_query(name, record_type).to_vec()?)
+            .send()
+            .await?
+            .error_for_status()?;
+        parse_response(&res.bytes().await?)
+    }
+}
+
+/// The answer to a single query
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// The addresses answered, possibly none, and their smallest TTL
+    Addrs(Vec<SocketAddr>, Duration),
+    /// The name does not exist
+    NxDomain,
+}
+
+/// Builds the query for the `record_type` records of `name`.
+fn build_query(name: DnsName, record_type: RecordType) -> Message {
+    // The ID is left at 0 so that HTTP caches can share responses (RFC 8484 4.1)
+    let mut query = Message::new();
+    query
+        .set_id(0)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name, record_type));
+    query
+}
+
+/// Parses a DNS response in wire format.
+fn parse_response(bytes: &[u8]) -> Result<Reply, BoxError> {
+    let response = Message::from_vec(bytes)?;
+
+    match response.response_code() {
+        ResponseCode::NoError => {}
+        ResponseCode::NXDomain => return Ok(Reply::NxDomain),
+        code => return Err(format!("DNS-over-HTTPS query failed: {code}").into()),
+    }
+
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for record in response.answers() {
+        let ip = match record.data() {
+            RData::A(a) => a.0.into(),
+            RData::AAAA(aaaa) => aaaa.0.into(),
+            _ => continue,
+        };
+        addrs.push(SocketAddr::new(ip, 0));
+        ttl = ttl.min(record.ttl());
+    }
+
+    Ok(Reply::Addrs(addrs, Duration::from_secs(ttl.into())))
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().lookup(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use hickory_resolver::proto::rr::{
+        Record,
+        rdata::{A, AAAA, CNAME},
+    };
+
+    use super::*;
+
+    fn response(code: ResponseCode, answers: Vec<Record>) -> Vec<u8> {
+        let name = DnsName::from_ascii("example.com.").unwrap();
+        let mut response = build_query(name, RecordType::A);
+        response
+            .set_message_type(MessageType::Response)
+            .set_response_code(code)
+            .add_answers(answers);
+        response.to_vec().unwrap()
+    }
+
+    #[test]
+    fn query_is_a_recursive_query_with_id_zero() {
+        let name = DnsName::from_ascii("example.com.").unwrap();
+        let query = build_query(name.clone(), RecordType::AAAA);
+        let query = Message::from_vec(&query.to_vec().unwrap()).unwrap();
+
+        assert_eq!(query.id(), 0);
+        assert_eq!(query.message_type(), MessageType::Query);
+        assert_eq!(query.op_code(), OpCode::Query);
+        assert!(query.recursion_desired());
+        assert_eq!(query.queries(), [Query::query(name, RecordType::AAAA)]);
+    }
+
+    #[test]
+    fn parses_addresses_with_their_smallest_ttl() {
+        let name = DnsName::from_ascii("example.com.").unwrap();
+        let target = DnsName::from_ascii("cdn.example.com.").unwrap();
+        let bytes = response(
+            ResponseCode::NoError,
+            vec![
+                Record::from_rdata(name.clone(), 300, RData::CNAME(CNAME(target.clone()))),
+                Record::from_rdata(target.clone(), 60, RData::A(A::new(192, 0, 2, 1))),
+                Record::from_rdata(target, 120, RData::AAAA(AAAA::from(Ipv6Addr::LOCALHOST))),
+            ],
+        );
+
+        assert_eq!(
+            parse_response(&bytes).unwrap(),
+            Reply::Addrs(
+                vec![
+                    SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 0),
+                    SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0),
+                ],
+                Duration::from_secs(60),
+            )
+        );
+    }
+
+    #[test]
+    fn parses_nxdomain() {
+        let bytes = response(ResponseCode::NXDomain, Vec::new());
+        assert_eq!(parse_response(&bytes).unwrap(), Reply::NxDomain);
+    }
+
+    #[test]
+    fn parses_empty_answer() {
+        let bytes = response(ResponseCode::NoError, Vec::new());
+        assert!(matches!(
+            parse_response(&bytes).unwrap(),
+            Reply::Addrs(addrs, _) if addrs.is_empty()
+        ));
+    }
+
+    #[test]
+    fn rejects_failed_queries() {
+        let bytes = response(ResponseCode::ServFail, Vec::new());
+        let err = parse_response(&bytes).unwrap_err();
+        assert!(err.to_string().starts_with("DNS-over-HTTPS query failed"));
+        assert!(parse_response(b"not a dns message").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{error::Error as StdError, fmt};
+
+use super::cache::NegativeKind;
+use crate::error::BoxError;
+
+/// An error that occurred while resolving a hostname.
+///
+/// When several resolvers were tried, as with [`FallbackResolver`](super::FallbackResolver),
+/// the error of each one is kept in [`DnsResolveError::chain`], labeled with the
+/// resolver that produced it.
+#[derive(Debug)]
+pub struct DnsResolveError {
+    host: Box<str>,
+    resolver: Option<Box<str>>,
+    cause: Cause,
+}
+
+#[derive(Debug)]
+enum Cause {
+    Error(BoxError),
+    Chain(Vec<DnsResolveError>),
+    NoAddresses,
+    NxDomain,
+}
+
+impl DnsResolveError {
+    pub(crate) fn new<E>(host: &str, source: E) -> DnsResolveError
+    where
+        E: Into<BoxError>,
+    {
+        DnsResolveError {
+            host: host.into(),
+            resolver: None,
+            cause: Cause::Error(source.into()),
+        }
+    }
+
+    /// Combines the errors of every resolver that was tried for `host`.
+    pub(crate) fn chained(host: &str, errors: Vec<DnsResolveError>) -> DnsResolveError {
+        DnsResolveError {
+            host: host.into(),
+            resolver: None,
+            cause: Cause::Chain(errors),
+        }
+    }
+
+    /// The lookup succeeded but returned no usable addresses.
+    pub(crate) fn no_addresses(host: &str) -> DnsResolveError {
+        DnsResolveError {
+            host: host.into(),
+            resolver: None,
+            cause: Cause::NoAddresses,
+        }
+    }
+
+    /// The name does not exist (`NXDOMAIN`).
+    pub(crate) fn nxdomain(host: &str) -> DnsResolveError {
+        DnsResolveError {
+            host: host.into(),
+            resolver: None,
+            cause: Cause::NxDomain,
+        }
+    }
+
+    /// The error for a definitive negative answer, as remembered by the cache.
+    pub(crate) fn negative(host: &str, kind: NegativeKind) -> DnsResolveError {
+        match kind {
+            NegativeKind::NxDomain => DnsResolveError::nxdomain(host),
+            NegativeKind::NoData => DnsResolveError::no_addresses(host),
+        }
+    }
+
+    /// Returns the negative answer this error stands for, if it is one.
+    ///
+    /// Only such answers are worth caching; other failures, like timeouts, may
+    /// not happen again.
+    pub(crate) fn negative_kind(&self) -> Option<NegativeKind> {
+        match self.cause {
+            Cause::NxDomain => Some(NegativeKind::NxDomain),
+            Cause::NoAddresses => Some(NegativeKind::NoData),
+            Cause::Error(_) | Cause::Chain(_) => None,
+        }
+    }
+
+    /// Labels the error with the resolver that produced it.
+    pub(crate) fn with_resolver(mut self, resolver: impl Into<Box<str>>) -> DnsResolveError {
+        self.resolver = Some(resolver.into());
+        self
+    }
+
+    /// Returns the hostname that failed to resolve.
+    #[inline]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the label of the resolver that failed, if known.
+    #[inline]
+    pub fn resolver(&self) -> Option<&str> {
+        self.resolver.as_deref()
+    }
+
+    /// Returns `true` if the name exists but the response held no usable
+    /// addresses, for example only record types that cannot be connected to.
+    ///
+    /// This differs from a lookup that failed, such as `NXDOMAIN`.
+    #[inline]
+    pub fn is_no_addresses(&self) -> bool {
+        matches!(self.cause, Cause::NoAddresses)
+    }
+
+    /// Returns `true` if the name servers answered that the name does not exist
+    /// (`NXDOMAIN`).
+    #[inline]
+    pub fn is_nxdomain(&self) -> bool {
+        matches!(self.cause, Cause::NxDomain)
+    }
+
+    /// Returns the errors of each resolver that was tried, in order.
+    ///
+    /// Empty unless the error combines the failures of several resolvers.
+    #[inline]
+    pub fn chain(&self) -> &[DnsResolveError] {
+        match self.cause {
+            Cause::Chain(ref errors) => errors,
+            Cause::Error(_) | Cause::NoAddresses | Cause::NxDomain => &[],
+        }
+    }
+
+    fn fmt_cause(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cause {
+            Cause::Error(ref err) => write!(f, "{err}"),
+            Cause::NoAddresses => f.write_str("no addresses returned"),
+            Cause::NxDomain => f.write_str("no such domain"),
+            Cause::Chain(ref errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    if let Some(resolver) = err.resolver() {
+                        write!(f, "{resolver}: ")?;
+                    }
+                    err.fmt_cause(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for DnsResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to resolve {}", self.host)?;
+        if let Cause::Chain(_) | Cause::NoAddresses | Cause::NxDomain = self.cause {
+            f.write_str(": ")?;
+            self.fmt_cause(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for DnsResolveError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self.cause {
+            Cause::Error(ref err) => Some(&**err),
+            // Every cause is already part of the message
+            Cause::Chain(_) | Cause::NoAddresses | Cause::NxDomain => None,
+        }
+    }
+}
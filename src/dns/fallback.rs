@@ -0,0 +1,132 @@
+//! Resolving through a secondary resolver when the primary one fails
+
+use std::sync::Arc;
+
+use super::{Addrs, DnsResolveError, IntoResolve, Name, Resolve, Resolving};
+use crate::error::BoxError;
+
+/// A resolver that falls back to a secondary resolver when the primary fails.
+///
+/// If both fail, the returned [`DnsResolveError`] reports the error of each
+/// resolver, for example
+/// `failed to resolve example.com: primary(hickory): timeout; secondary(doh): nxdomain`.
+#[derive(Clone)]
+pub struct FallbackResolver {
+    primary: Arc<dyn Resolve>,
+    secondary: Arc<dyn Resolve>,
+    primary_label: Arc<str>,
+    secondary_label: Arc<str>,
+}
+
+impl FallbackResolver {
+    /// Creates a resolver trying `primary` first, then `secondary`.
+    pub fn new<P, S>(primary: P, secondary: S) -> FallbackResolver
+    where
+        P: IntoResolve,
+        S: IntoResolve,
+    {
+        FallbackResolver {
+            primary: primary.into_resolve(),
+            secondary: secondary.into_resolve(),
+            primary_label: Arc::from("primary"),
+            secondary_label: Arc::from("secondary"),
+        }
+    }
+
+    /// Names the primary and secondary resolvers in error messages.
+    ///
+    /// With names `"hickory"` and `"doh"`, failures are reported as
+    /// `primary(hickory)` and `secondary(doh)`.
+    pub fn with_names(mut self, primary: &str, secondary: &str) -> FallbackResolver {
+        self.primary_label = format!("primary({primary})").into();
+        self.secondary_label = format!("secondary({secondary})").into();
+        self
+    }
+
+    async fn resolve_with_fallback(self, name: Name) -> Result<Addrs, BoxError> {
+        let primary = match self.primary.resolve(name.clone()).await {
+            Ok(addrs) => return Ok(addrs),
+            Err(err) => labeled(name.as_str(), &self.primary_label, err),
+        };
+
+        debug!("primary resolver failed for {}: {}", name, primary);
+        let secondary = match self.secondary.resolve(name.clone()).await {
+            Ok(addrs) => return Ok(addrs),
+            Err(err) => labeled(name.as_str(), &self.secondary_label, err),
+        };
+
+        Err(DnsResolveError::chained(name.as_str(), vec![primary, secondary]).into())
+    }
+}
+
+impl Resolve for FallbackResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().resolve_with_fallback(name))
+    }
+}
+
+/// Attributes a resolver error to `label`, keeping the structure of nested errors.
+pub(super) fn labeled(host: &str, label: &str, err: BoxError) -> DnsResolveError {
+    match err.downcast::<DnsResolveError>() {
+        Ok(err) => (*err).with_resolver(label),
+        Err(err) => DnsResolveError::new(host, err).with_resolver(label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    struct Failing(&'static str);
+
+    impl Resolve for Failing {
+        fn resolve(&self, _: Name) -> Resolving {
+            let err: BoxError = self.0.into();
+            Box::pin(std::future::ready(Err(err)))
+        }
+    }
+
+    struct Static(SocketAddr);
+
+    impl Resolve for Static {
+        fn resolve(&self, _: Name) -> Resolving {
+            let addrs: Addrs = Box::new(std::iter::once(self.0));
+            Box::pin(std::future::ready(Ok(addrs)))
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_secondary() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let resolver = FallbackResolver::new(Failing("timeout"), Static(addr));
+
+        let addrs: Vec<_> = resolver
+            .resolve("example.com".into())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(addrs, vec![addr]);
+    }
+
+    #[tokio::test]
+    async fn reports_every_failed_resolver() {
+        let resolver = FallbackResolver::new(Failing("timeout"), Failing("nxdomain"))
+            .with_names("hickory", "doh");
+
+        let err = match resolver.resolve("example.com".into()).await {
+            Ok(_) => panic!("both resolvers should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "failed to resolve example.com: primary(hickory): timeout; secondary(doh): nxdomain"
+        );
+        assert_eq!(err.host(), "example.com");
+
+        let labels: Vec<_> = err.chain().iter().map(|e| e.resolver()).collect();
+        assert_eq!(labels, [Some("primary(hickory)"), Some("secondary(doh)")]);
+    }
+}
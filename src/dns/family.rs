@@ -0,0 +1,124 @@
+//! Restricting and ordering resolved addresses by IP version
+
+use std::{net::SocketAddr, sync::Arc};
+
+use super::{Addrs, DnsResolveError, Name, Resolve, Resolving};
+use crate::error::BoxError;
+
+/// The IP versions a client connects over.
+///
+/// See [`ClientBuilder::ip_version`](crate::ClientBuilder::ip_version).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IpVersion {
+    /// Only connect over IPv4, such as behind a broken IPv6 network.
+    V4Only,
+    /// Only connect over IPv6.
+    V6Only,
+    /// Connect over both, trying IPv4 addresses first.
+    Prefer4,
+    /// Connect over both, trying IPv6 addresses first.
+    Prefer6,
+}
+
+impl IpVersion {
+    /// Drops the addresses of an excluded version and puts the preferred version
+    /// first, keeping the order within each version
+    pub(crate) fn apply<I>(self, addrs: I) -> Vec<SocketAddr>
+    where
+        I: Iterator<Item = SocketAddr>,
+    {
+        let prefer_v4 = match self {
+            IpVersion::V4Only => return addrs.filter(SocketAddr::is_ipv4).collect(),
+            IpVersion::V6Only => return addrs.filter(SocketAddr::is_ipv6).collect(),
+            IpVersion::Prefer4 => true,
+            IpVersion::Prefer6 => false,
+        };
+        let (mut preferred, rest): (Vec<_>, Vec<_>) =
+            addrs.partition(|addr| addr.is_ipv4() == prefer_v4);
+        preferred.extend(rest);
+        preferred
+    }
+
+    /// Returns the hickory lookup strategy querying only the needed record types
+    #[cfg(feature = "hickory-dns")]
+    pub(crate) fn lookup_strategy(self) -> hickory_resolver::config::LookupIpStrategy {
+        use hickory_resolver::config::LookupIpStrategy;
+
+        match self {
+            IpVersion::V4Only => LookupIpStrategy::Ipv4Only,
+            IpVersion::V6Only => LookupIpStrategy::Ipv6Only,
+            IpVersion::Prefer4 | IpVersion::Prefer6 => LookupIpStrategy::Ipv4AndIpv6,
+        }
+    }
+}
+
+/// A resolver applying an [`IpVersion`] to the answers of another, including
+/// those served from a cache.
+#[derive(Clone)]
+pub(crate) struct IpVersionResolver {
+    inner: Arc<dyn Resolve>,
+    version: IpVersion,
+}
+
+impl IpVersionResolver {
+    pub(crate) fn new(inner: Arc<dyn Resolve>, version: IpVersion) -> Self {
+        IpVersionResolver { inner, version }
+    }
+
+    async fn lookup(self, name: Name) -> Result<Addrs, BoxError> {
+        let addrs = self.version.apply(self.inner.resolve(name.clone()).await?);
+        if addrs.is_empty() {
+            debug!("no {:?} address for {}", self.version, name);
+            return Err(DnsResolveError::no_addresses(name.as_str()).into());
+        }
+        Ok(Box::new(addrs.into_iter()))
+    }
+}
+
+impl Resolve for IpVersionResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().lookup(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn test_ip_version_apply() {
+        let v4: SocketAddr = (Ipv4Addr::new(192, 0, 2, 1), 0).into();
+        let v6: SocketAddr = (Ipv6Addr::LOCALHOST, 0).into();
+        let v4_other: SocketAddr = (Ipv4Addr::new(192, 0, 2, 2), 0).into();
+        let addrs = [v6, v4, v4_other];
+
+        let apply = |version: IpVersion| version.apply(addrs.into_iter());
+        assert_eq!(apply(IpVersion::V4Only), [v4, v4_other]);
+        assert_eq!(apply(IpVersion::V6Only), [v6]);
+        assert_eq!(apply(IpVersion::Prefer4), [v4, v4_other, v6]);
+        assert_eq!(apply(IpVersion::Prefer6), [v6, v4, v4_other]);
+    }
+
+    #[tokio::test]
+    async fn test_ip_version_resolver_fails_without_allowed_addresses() {
+        struct OnlyV6;
+
+        impl Resolve for OnlyV6 {
+            fn resolve(&self, _name: Name) -> Resolving {
+                let addr = SocketAddr::from((Ipv6Addr::LOCALHOST, 0));
+                let addrs: Addrs = Box::new(std::iter::once(addr));
+                Box::pin(std::future::ready(Ok(addrs)))
+            }
+        }
+
+        let resolver = IpVersionResolver::new(Arc::new(OnlyV6), IpVersion::V4Only);
+        let err = match resolver.resolve("v6.example".into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_no_addresses());
+    }
+}
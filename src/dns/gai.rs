@@ -10,12 +10,15 @@ use std::{
 use tokio::task::JoinHandle;
 use tower::Service;
 
-use super::{Addrs, Name, Resolve, Resolving, cache::GLOBAL_DNS_CACHE};
+use super::{
+    Addrs, DnsResolveError, Name, Resolve, Resolving,
+    cache::{DnsCache, NegativeKind},
+};
 
 /// A resolver using blocking `getaddrinfo` calls in a threadpool.
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct GaiResolver {
-    _priv: (),
+    cache: DnsCache,
 }
 
 /// An iterator of IP addresses returned from `getaddrinfo`.
@@ -36,9 +39,28 @@ pub(crate) struct SocketAddrs {
 // ==== impl GaiResolver ====
 
 impl GaiResolver {
-    /// Creates a new [`GaiResolver`].
+    /// Creates a new [`GaiResolver`], caching lookups in the global DNS cache.
     pub fn new() -> Self {
-        GaiResolver { _priv: () }
+        GaiResolver {
+            cache: DnsCache::global(),
+        }
+    }
+
+    /// Caches lookups in `cache` instead of the global DNS cache.
+    ///
+    /// Failed lookups are not cached: `getaddrinfo` errors do not tell a name that
+    /// does not exist apart from an unreachable name server. A lookup without
+    /// addresses is cached for the negative TTL of `cache`, if it has one, see
+    /// [`DnsCacheBuilder::negative_ttl`](super::DnsCacheBuilder::negative_ttl).
+    pub fn with_cache(mut self, cache: DnsCache) -> Self {
+        self.cache = cache;
+        self
+    }
+}
+
+impl Default for GaiResolver {
+    fn default() -> Self {
+        GaiResolver::new()
     }
 }
 
@@ -70,7 +92,10 @@ impl Resolve for GaiResolver {
             let hostname = name.as_str().to_string();
 
             // Check cache first
-            if let Some(cached_addrs) = GLOBAL_DNS_CACHE.get(&hostname) {
+            if let Some(kind) = this.cache.get_negative(&hostname) {
+                return Err(DnsResolveError::negative(&hostname, kind).into());
+            }
+            if let Some(cached_addrs) = this.cache.get(&hostname) {
                 trace!("Using cached DNS result for {}", hostname);
                 return Ok(Box::new(GaiAddrs {
                     inner: SocketAddrs::new(cached_addrs),
@@ -79,17 +104,18 @@ impl Resolve for GaiResolver {
 
             // Cache miss - perform actual DNS lookup
             debug!("DNS cache miss, resolving {} via getaddrinfo", hostname);
-            let result = this.call(name).await;
-
-            // Cache the result if successful
-            if let Ok(ref addrs) = result {
-                let socket_addrs: Vec<_> = addrs.inner.iter.as_slice().to_vec();
-                if !socket_addrs.is_empty() {
-                    GLOBAL_DNS_CACHE.insert(hostname, socket_addrs);
-                }
+            let addrs = this.call(name).await?;
+
+            // Cache the result, or its absence if negative caching is enabled
+            let socket_addrs: Vec<_> = addrs.inner.iter.as_slice().to_vec();
+            if socket_addrs.is_empty() {
+                this.cache
+                    .insert_negative(hostname.clone(), NegativeKind::NoData);
+                return Err(DnsResolveError::no_addresses(&hostname).into());
             }
+            this.cache.insert(hostname, socket_addrs);
 
-            result.map(|addrs| Box::new(addrs) as Addrs).map_err(Into::into)
+            Ok(Box::new(addrs) as Addrs)
         })
     }
 }
@@ -156,7 +182,7 @@ impl SocketAddrs {
     }
 
     #[inline]
-    fn filter(self, predicate: impl FnMut(&SocketAddr) -> bool) -> SocketAddrs {
+    pub(crate) fn filter(self, predicate: impl FnMut(&SocketAddr) -> bool) -> SocketAddrs {
         SocketAddrs::new(self.iter.filter(predicate).collect())
     }
 
@@ -185,6 +211,21 @@ impl SocketAddrs {
         }
     }
 
+    /// Moves the addresses of one family to the front, keeping their order
+    pub(crate) fn prefer_family(self, ipv6: bool) -> SocketAddrs {
+        let (mut preferred, rest) = self
+            .iter
+            .partition::<Vec<_>, _>(|addr| addr.is_ipv6() == ipv6);
+        preferred.extend(rest);
+        SocketAddrs::new(preferred)
+    }
+
+    /// Returns whether there are both IPv4 and IPv6 addresses
+    pub(crate) fn is_dual_stack(&self) -> bool {
+        let addrs = self.iter.as_slice();
+        addrs.iter().any(SocketAddr::is_ipv4) && addrs.iter().any(SocketAddr::is_ipv6)
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.iter.as_slice().is_empty()
     }
@@ -258,6 +299,20 @@ mod tests {
         assert!(fallback.is_empty());
     }
 
+    #[test]
+    fn test_ip_addrs_prefer_family() {
+        let v4_addr: SocketAddr = (Ipv4Addr::new(127, 0, 0, 1), 80).into();
+        let v6_addr: SocketAddr = (Ipv6Addr::LOCALHOST, 80).into();
+        let v4_other: SocketAddr = (Ipv4Addr::new(127, 0, 0, 2), 80).into();
+
+        let addrs = SocketAddrs::new(vec![v6_addr, v4_addr, v4_other]);
+        assert!(addrs.is_dual_stack());
+        let addrs: Vec<_> = addrs.prefer_family(false).collect();
+        assert_eq!(addrs, [v4_addr, v4_other, v6_addr]);
+
+        assert!(!SocketAddrs::new(vec![v4_addr, v4_other]).is_dual_stack());
+    }
+
     #[test]
     fn test_name_from_str() {
         const DOMAIN: &str = "test.example.com";
@@ -1,25 +1,451 @@
 //! DNS resolution via the [hickory-resolver](https://github.com/hickory-dns/hickory-dns) crate
 
-use std::{net::SocketAddr, sync::LazyLock};
+use std::{
+    borrow::Cow,
+    error::Error as StdError,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, LazyLock, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
 
 use hickory_resolver::{
-    TokioResolver,
-    config::{LookupIpStrategy, ResolverConfig},
-    lookup_ip::LookupIpIntoIter,
-    name_server::TokioConnectionProvider,
+    ResolveError, TokioResolver,
+    config::{
+        LookupIpStrategy, NameServerConfig, NameServerConfigGroup, ResolveHosts, ResolverConfig,
+        ResolverOpts,
+    },
+    name_server::{NameServerPool, TokioConnectionProvider},
+    proto::{
+        ProtoError, ProtoErrorKind,
+        op::{Query, ResponseCode},
+        rr::{
+            Name as DnsName, RData, RecordType,
+            rdata::svcb::{SVCB, SvcParamValue},
+        },
+        xfer::{DnsHandle, DnsRequestOptions, FirstAnswer, Protocol, RetryDnsHandle},
+    },
+    system_conf,
+};
+
+use super::{
+    Addrs, DnsResolveError, IpVersion, Name, Resolve, Resolving,
+    cache::{self, DnsCache, NegativeKind},
+    canonical::canonicalize_host,
+    rfc6724,
+};
+use crate::{
+    error::BoxError,
+    hash::{HASHER, HashMap},
+    sync::Mutex,
 };
 
-use super::{Addrs, Name, Resolve, Resolving, cache::GLOBAL_DNS_CACHE};
+/// How long reverse (PTR) lookup results are cached
+const PTR_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Maximum number of cached reverse lookups
+const PTR_CACHE_MAX_ENTRIES: usize = 256;
+
+/// Location of the system hosts file
+#[cfg(windows)]
+const HOSTS_FILE: &str = r"C:\Windows\System32\drivers\etc\hosts";
+#[cfg(not(windows))]
+const HOSTS_FILE: &str = "/etc/hosts";
+
+/// The system hosts file, read on first use by a layered resolver
+static HOSTS: LazyLock<Arc<HostsFile>> =
+    LazyLock::new(|| Arc::new(HostsFile::new(PathBuf::from(HOSTS_FILE))));
+
+/// The transport used to query name servers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DnsProtocol {
+    /// Only query over UDP.
+    Udp,
+    /// Only query over TCP.
+    Tcp,
+    /// Query over UDP, retrying over TCP when a response is truncated.
+    #[default]
+    UdpThenTcp,
+}
+
+impl DnsProtocol {
+    /// Restricts the name servers of `config` to this protocol.
+    fn apply(self, config: &ResolverConfig) -> ResolverConfig {
+        let protocol = match self {
+            DnsProtocol::Udp => Protocol::Udp,
+            DnsProtocol::Tcp => Protocol::Tcp,
+            DnsProtocol::UdpThenTcp => return config.clone(),
+        };
+
+        let mut addrs: Vec<SocketAddr> = Vec::new();
+        for ns in config.name_servers() {
+            if !addrs.contains(&ns.socket_addr) {
+                addrs.push(ns.socket_addr);
+            }
+        }
+
+        let name_servers: Vec<NameServerConfig> = addrs
+            .into_iter()
+            .map(|addr| NameServerConfig::new(addr, protocol))
+            .collect();
+
+        ResolverConfig::from_parts(
+            config.domain().cloned(),
+            config.search().to_vec(),
+            NameServerConfigGroup::from(name_servers),
+        )
+    }
+}
+
+/// How to pick the single address returned by
+/// [`HickoryDnsResolver::with_single_addr_selection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleAddrStrategy {
+    /// The numerically lowest address, IPv4 before IPv6.
+    LowestIp,
+    /// The first address, in the order addresses are resolved.
+    FirstReturned,
+    /// An address picked by a hash of the hostname, so that a host sticks to the
+    /// same address for as long as its address set does not change.
+    HashOfHost,
+}
+
+impl SingleAddrStrategy {
+    /// Picks one address of `addrs`, which must not be empty.
+    fn select(self, host: &str, addrs: &[SocketAddr]) -> SocketAddr {
+        let addr = match self {
+            SingleAddrStrategy::LowestIp => addrs.iter().copied().min_by_key(|addr| addr.ip()),
+            SingleAddrStrategy::FirstReturned => addrs.first().copied(),
+            SingleAddrStrategy::HashOfHost => {
+                // Independent of the answer order, and stable across processes (FNV-1a)
+                let mut sorted = addrs.to_vec();
+                sorted.sort_unstable_by_key(|addr| addr.ip());
+                let hash = host.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+                    (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+                });
+                sorted
+                    .get((hash % sorted.len().max(1) as u64) as usize)
+                    .copied()
+            }
+        };
+        addr.expect("addresses should not be empty")
+    }
+}
+
+/// The sources consulted, in order, by a resolver built with
+/// [`HickoryDnsResolver::with_layers`].
+#[derive(Debug, Clone)]
+struct Layers {
+    /// Static addresses, keyed by canonical hostname.
+    overrides: Arc<HashMap<String, Vec<SocketAddr>>>,
+    /// The hosts file consulted after the overrides, if any.
+    hosts_file: Option<Arc<HostsFile>>,
+    /// How often the hosts file is checked for changes. `None` reads it once.
+    hosts_reload: Option<Duration>,
+    /// Whether the name servers are queried when no local layer knows the host.
+    upstream: bool,
+}
+
+impl Layers {
+    /// Returns the addresses of the first local layer that knows `host`.
+    async fn lookup_local(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let host = canonicalize_host(host).ok()?;
+        if let Some(addrs) = self.overrides.get(&host).filter(|addrs| !addrs.is_empty()) {
+            return Some(addrs.clone());
+        }
+        if let Some(ref hosts_file) = self.hosts_file {
+            if let Some(addrs) = hosts_file.hosts(self.hosts_reload).await.get(&host) {
+                return Some(addrs.clone());
+            }
+        }
+        None
+    }
+}
+
+/// A hosts file, read on first use and re-read when watched and changed.
+struct HostsFile {
+    path: PathBuf,
+    read: Mutex<Option<HostsRead>>,
+}
+
+/// The addresses last read from a hosts file.
+struct HostsRead {
+    hosts: Arc<HashMap<String, Vec<SocketAddr>>>,
+    /// Modification time of the file when it was read
+    modified: Option<SystemTime>,
+    /// When the modification time was last checked
+    checked: Instant,
+}
+
+impl HostsFile {
+    fn new(path: PathBuf) -> HostsFile {
+        HostsFile {
+            path,
+            read: Mutex::new(None),
+        }
+    }
+
+    /// Returns the addresses in the file by canonical name.
+    ///
+    /// With `reload`, the modification time of the file is checked at most once
+    /// per `reload`, and the file is read again when it changed. The file is
+    /// accessed on the blocking thread pool, without holding the lock.
+    async fn hosts(&self, reload: Option<Duration>) -> Arc<HashMap<String, Vec<SocketAddr>>> {
+        let known = {
+            let mut read = self.read.lock();
+            match (read.as_mut(), reload) {
+                (Some(read), None) => return read.hosts.clone(),
+                (Some(read), Some(interval)) if read.checked.elapsed() < interval => {
+                    return read.hosts.clone();
+                }
+                (Some(read), Some(_)) => {
+                    read.checked = Instant::now();
+                    Some((read.hosts.clone(), read.modified))
+                }
+                (None, _) => None,
+            }
+        };
+
+        let path = self.path.clone();
+        let unchanged = known.as_ref().map(|(_, modified)| *modified);
+        let loaded = tokio::task::spawn_blocking(move || {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if unchanged == Some(modified) {
+                return (modified, None);
+            }
+            let hosts = match std::fs::read_to_string(&path) {
+                Ok(text) => parse_hosts(&text),
+                Err(_err) => {
+                    debug!("error reading hosts file {}: {}", path.display(), _err);
+                    HashMap::with_hasher(HASHER)
+                }
+            };
+            (modified, Some(hosts))
+        })
+        .await;
+
+        let (modified, hosts) = match (loaded, known) {
+            (Ok((_, None)), Some((hosts, _))) => return hosts,
+            (Ok((modified, Some(hosts))), _) => (modified, hosts),
+            // The read was cancelled along with the runtime
+            (_, Some((hosts, _))) => return hosts,
+            (_, None) => return Arc::new(HashMap::with_hasher(HASHER)),
+        };
+        if unchanged.is_some() {
+            debug!("hosts file {} changed, reloaded", self.path.display());
+        }
+
+        let hosts = Arc::new(hosts);
+        *self.read.lock() = Some(HostsRead {
+            hosts: hosts.clone(),
+            modified,
+            checked: Instant::now(),
+        });
+        hosts
+    }
+}
+
+impl std::fmt::Debug for HostsFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostsFile")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// How the underlying hickory resolver is built.
+#[derive(Debug, Clone, Default)]
+struct ResolverSetup {
+    /// Transport used to query name servers.
+    protocol: DnsProtocol,
+    /// The only name server to query, instead of the system ones.
+    name_server: Option<SocketAddr>,
+    /// The name the certificate of `name_server` is checked against, when it is
+    /// queried over TLS.
+    #[cfg(feature = "dns-over-tls")]
+    tls_server_name: Option<Arc<str>>,
+    /// Attempts per name server and the timeout of each attempt.
+    attempts: Option<(u32, Duration)>,
+    /// The IP versions looked up, both unless set.
+    ip_version: Option<IpVersion>,
+    /// Whether hickory skips its own lookup in the system hosts file, hosts files
+    /// being left to the layers.
+    skip_hosts_file: bool,
+}
+
+impl ResolverSetup {
+    /// Whether the name server is queried over an encrypted transport.
+    fn is_encrypted(&self) -> bool {
+        #[cfg(feature = "dns-over-tls")]
+        {
+            self.tls_server_name.is_some()
+        }
+        #[cfg(not(feature = "dns-over-tls"))]
+        {
+            false
+        }
+    }
+
+    /// Applies the explicit attempt settings, if any, on top of `options`.
+    fn options(&self, mut options: ResolverOpts) -> ResolverOpts {
+        if let Some((attempts, timeout)) = self.attempts {
+            options.attempts = attempts as usize;
+            options.timeout = timeout;
+        }
+        if self.skip_hosts_file {
+            options.use_hosts_file = ResolveHosts::Never;
+        }
+        options
+    }
+}
+
+/// Returns the names a lookup of `host` queries in turn, completing it with the
+/// search domains of `config` the way hickory does.
+fn search_names(
+    config: &ResolverConfig,
+    ndots: usize,
+    host: &str,
+) -> Result<Vec<DnsName>, BoxError> {
+    let name = DnsName::from_ascii(host)?;
+    if name.is_fqdn() {
+        return Ok(vec![name]);
+    }
+
+    let searched = config
+        .domain()
+        .into_iter()
+        .chain(config.search())
+        .filter_map(|domain| name.clone().append_domain(domain).ok());
+    let names = if name.num_labels() as usize > ndots {
+        std::iter::once(name.clone()).chain(searched).collect()
+    } else {
+        searched.chain(std::iter::once(name.clone())).collect()
+    };
+    Ok(names)
+}
+
+/// Builds a resolver as described by `setup`.
+///
+/// Uses the system configuration, unless a single name server is given.
+fn build_resolver(setup: &ResolverSetup) -> TokioResolver {
+    let (config, options) = match setup.name_server {
+        #[cfg(feature = "dns-over-tls")]
+        Some(addr) if setup.tls_server_name.is_some() => {
+            let server_name = setup.tls_server_name.as_deref().unwrap_or_default();
+            let name_servers = NameServerConfigGroup::from_ips_tls(
+                &[addr.ip()],
+                addr.port(),
+                server_name.to_owned(),
+                true,
+            );
+            (
+                ResolverConfig::from_parts(None, Vec::new(), name_servers),
+                ResolverOpts::default(),
+            )
+        }
+        Some(addr) => {
+            let name_servers =
+                NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            (
+                ResolverConfig::from_parts(None, Vec::new(), name_servers),
+                ResolverOpts::default(),
+            )
+        }
+        None => match system_conf::read_system_conf() {
+            Ok(conf) => {
+                debug!("using system DNS configuration");
+                conf
+            }
+            Err(_err) => {
+                debug!("error reading DNS system conf: {}, using defaults", _err);
+                (ResolverConfig::default(), ResolverOpts::default())
+            }
+        },
+    };
+
+    // An encrypted name server is queried over its own transport only
+    let config = if setup.is_encrypted() {
+        config
+    } else {
+        setup.protocol.apply(&config)
+    };
+
+    let mut builder =
+        TokioResolver::builder_with_config(config, TokioConnectionProvider::default());
+    *builder.options_mut() = setup.options(options);
+    builder.options_mut().ip_strategy = setup
+        .ip_version
+        .map_or(LookupIpStrategy::Ipv4AndIpv6, IpVersion::lookup_strategy);
+    builder.build()
+}
+
+/// The name server pool of a resolver, queried directly when the authority of
+/// answers matters.
+#[derive(Default)]
+struct AuthorityHandle(OnceLock<RetryDnsHandle<NameServerPool<TokioConnectionProvider>>>);
+
+impl std::fmt::Debug for AuthorityHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthorityHandle")
+            .field("built", &self.0.get().is_some())
+            .finish()
+    }
+}
 
 /// Wrapper around an [`TokioResolver`], which implements the `Resolve` trait.
 #[derive(Debug, Clone)]
 pub struct HickoryDnsResolver {
-    /// Shared, lazily-initialized Tokio-based DNS resolver.
+    /// Tokio-based DNS resolver.
     ///
-    /// Backed by [`LazyLock`] to guarantee thread-safe, one-time creation.
-    /// On initialization, it attempts to load the system's DNS configuration;
-    /// if unavailable, it falls back to sensible default settings.
-    resolver: &'static LazyLock<TokioResolver>,
+    /// The default resolver is shared and lazily created on first use. It
+    /// loads the system's DNS configuration, or falls back to sensible default
+    /// settings if unavailable.
+    resolver: Arc<TokioResolver>,
+
+    /// How a dedicated resolver is built, when the defaults are changed.
+    setup: ResolverSetup,
+
+    /// Handle querying the name servers of `resolver` directly, for answers whose
+    /// authority is checked. Built on first use and rebuilt with `resolver`.
+    authority: Arc<AuthorityHandle>,
+
+    /// How long past expiry a cached entry may still be served when the
+    /// upstream lookup fails. `None` disables serve-stale.
+    max_stale: Option<Duration>,
+
+    /// Whether HTTPS (SVCB) records are consulted before A/AAAA records.
+    https_records: bool,
+
+    /// Whether resolved addresses are sorted by RFC 6724 preference.
+    rfc6724_sorting: bool,
+
+    /// Whether resolved addresses are reordered to alternate between subnets.
+    subnet_diversity: bool,
+
+    /// How long a lookup without usable addresses is cached. `None` disables
+    /// caching such results.
+    no_addresses_ttl: Option<Duration>,
+
+    /// Reduce each result to a single address picked by this strategy.
+    single_addr: Option<SingleAddrStrategy>,
+
+    /// Local sources consulted before the cache and the name servers.
+    layers: Option<Layers>,
+
+    /// Whether answers for `authoritative_hosts` are only cached when authoritative.
+    cache_only_authoritative: bool,
+
+    /// Canonical names of the hosts the authoritative-only rule applies to.
+    authoritative_hosts: Arc<[String]>,
+
+    /// Cache of forward lookups, the global one unless set otherwise.
+    cache: DnsCache,
+
+    /// Cache of reverse lookups, kept apart from the forward A/AAAA cache.
+    ptr_cache: Arc<PtrCache>,
 }
 
 impl HickoryDnsResolver {
@@ -28,44 +454,827 @@ impl HickoryDnsResolver {
     /// overriden to look up for both IPv4 and IPv6 addresses
     /// to work with "happy eyeballs" algorithm.
     pub fn new() -> HickoryDnsResolver {
-        static RESOLVER: LazyLock<TokioResolver> = LazyLock::new(|| {
-            let mut builder = match TokioResolver::builder_tokio() {
-                Ok(resolver) => {
-                    debug!("using system DNS configuration");
-                    resolver
-                }
-                Err(_err) => {
-                    debug!("error reading DNS system conf: {}, using defaults", _err);
-                    TokioResolver::builder_with_config(
-                        ResolverConfig::default(),
-                        TokioConnectionProvider::default(),
-                    )
-                }
-            };
-            builder.options_mut().ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
-            builder.build()
-        });
+        static RESOLVER: LazyLock<Arc<TokioResolver>> =
+            LazyLock::new(|| Arc::new(build_resolver(&ResolverSetup::default())));
 
         HickoryDnsResolver {
-            resolver: &RESOLVER,
+            resolver: RESOLVER.clone(),
+            setup: ResolverSetup::default(),
+            authority: Arc::default(),
+            max_stale: None,
+            https_records: false,
+            rfc6724_sorting: false,
+            subnet_diversity: false,
+            no_addresses_ttl: None,
+            single_addr: None,
+            layers: None,
+            cache_only_authoritative: false,
+            authoritative_hosts: Arc::from([]),
+            cache: DnsCache::global(),
+            ptr_cache: Arc::new(PtrCache::new(PTR_CACHE_TTL)),
+        }
+    }
+
+    /// Cache lookups in `cache` instead of the global DNS cache.
+    ///
+    /// Resolvers sharing a cache see each other's answers, so give resolvers
+    /// querying different name servers caches of their own. Reverse lookups are
+    /// cached apart, by this resolver only, within the TTL bounds of `cache`.
+    pub fn with_cache(mut self, cache: DnsCache) -> HickoryDnsResolver {
+        self.ptr_cache = Arc::new(PtrCache::new(cache.clamp_ttl(PTR_CACHE_TTL)));
+        self.cache = cache;
+        self
+    }
+
+    /// Choose the transport used to query name servers.
+    ///
+    /// Use [`DnsProtocol::Tcp`] on networks that block UDP port 53, or
+    /// [`DnsProtocol::Udp`] to avoid TCP connection overhead. This creates a
+    /// dedicated resolver instead of sharing the default one.
+    ///
+    /// Default is [`DnsProtocol::UdpThenTcp`].
+    pub fn with_protocol(mut self, protocol: DnsProtocol) -> HickoryDnsResolver {
+        self.setup.protocol = protocol;
+        self.rebuild();
+        self
+    }
+
+    /// Only look up the addresses of the IP versions `version` connects over.
+    ///
+    /// With [`IpVersion::V4Only`] or [`IpVersion::V6Only`], only A or AAAA
+    /// records are queried. Addresses of the other version already cached by
+    /// another resolver are still returned; a client built with
+    /// [`ClientBuilder::ip_version`](crate::ClientBuilder::ip_version) filters
+    /// those too. This creates a dedicated resolver instead of sharing the
+    /// default one.
+    pub fn with_ip_version(mut self, version: IpVersion) -> HickoryDnsResolver {
+        self.setup.ip_version = Some(version);
+        self.rebuild();
+        self
+    }
+
+    /// Query only the name server at `addr`, instead of the system ones.
+    ///
+    /// Combined with [`CircuitBreakerResolver`](super::CircuitBreakerResolver),
+    /// this allows tracking the health of each upstream separately. This creates
+    /// a dedicated resolver instead of sharing the default one.
+    pub fn with_name_server(mut self, addr: SocketAddr) -> HickoryDnsResolver {
+        self.setup.name_server = Some(addr);
+        #[cfg(feature = "dns-over-tls")]
+        {
+            self.setup.tls_server_name = None;
+        }
+        self.rebuild();
+        self
+    }
+
+    /// Query only the name server at `addr` over DNS-over-TLS ([RFC 7858]),
+    /// instead of the system ones in plaintext.
+    ///
+    /// The server's certificate is verified against `server_name`, such as
+    /// `cloudflare-dns.com` for `1.1.1.1:853`. Lookups are cached like any other.
+    /// The transport chosen with [`with_protocol`](Self::with_protocol) does not
+    /// apply. This creates a dedicated resolver instead of sharing the default one.
+    ///
+    /// [RFC 7858]: https://www.rfc-editor.org/rfc/rfc7858
+    #[cfg(feature = "dns-over-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-tls")))]
+    pub fn with_dns_over_tls<S>(mut self, addr: SocketAddr, server_name: S) -> HickoryDnsResolver
+    where
+        S: Into<String>,
+    {
+        self.setup.name_server = Some(addr);
+        self.setup.tls_server_name = Some(Arc::from(server_name.into()));
+        self.rebuild();
+        self
+    }
+
+    /// Query each name server up to `per_server` times, waiting up to `timeout`
+    /// for each attempt, before failing.
+    ///
+    /// This replaces hickory's `attempts` and `timeout` resolver options, which
+    /// otherwise come from the system configuration (`options attempts:` and
+    /// `options timeout:` in `/etc/resolv.conf`). This creates a dedicated
+    /// resolver instead of sharing the default one.
+    pub fn with_attempts(mut self, per_server: u32, timeout: Duration) -> HickoryDnsResolver {
+        self.setup.attempts = Some((per_server.max(1), timeout));
+        self.rebuild();
+        self
+    }
+
+    /// Serve expired cache entries when the upstream lookup fails ([RFC 8767]).
+    ///
+    /// If resolving a host errors and the cache holds an entry that expired no
+    /// more than `max_stale` ago, that entry is returned instead of the error.
+    /// A later successful lookup replaces the stale entry.
+    ///
+    /// [RFC 8767]: https://www.rfc-editor.org/rfc/rfc8767
+    pub fn with_max_stale(mut self, max_stale: Duration) -> HickoryDnsResolver {
+        self.max_stale = Some(max_stale);
+        self
+    }
+
+    /// Consult HTTPS records ([RFC 9460]) before falling back to A/AAAA records.
+    ///
+    /// When a host publishes an HTTPS record, its target, port and
+    /// `ipv4hint`/`ipv6hint` parameters are used to connect directly, saving an
+    /// address lookup. Records are only consulted for `https` URLs on the default
+    /// port, and the derived addresses are cached apart from the A/AAAA answer for
+    /// the same host, which other URLs keep using.
+    ///
+    /// Default is `false`.
+    ///
+    /// [RFC 9460]: https://www.rfc-editor.org/rfc/rfc9460
+    pub fn with_https_records(mut self, enabled: bool) -> HickoryDnsResolver {
+        self.https_records = enabled;
+        self
+    }
+
+    /// Sort resolved addresses by [RFC 6724] destination preference before caching.
+    ///
+    /// The system resolver orders addresses using the local routing table, but
+    /// hickory returns them as received. With this enabled, unreachable address
+    /// families are moved last and addresses whose scope and label match the
+    /// local source address are preferred, which helps on dual-stack hosts.
+    ///
+    /// Default is `false`.
+    ///
+    /// [RFC 6724]: https://www.rfc-editor.org/rfc/rfc6724
+    pub fn with_rfc6724_sorting(mut self, enabled: bool) -> HickoryDnsResolver {
+        self.rfc6724_sorting = enabled;
+        self
+    }
+
+    /// Reorder resolved addresses so that consecutive ones come from different subnets.
+    ///
+    /// Addresses are grouped by their /24 (IPv4) or /48 (IPv6) prefix, and the
+    /// groups are interleaved, keeping the order of the addresses within each
+    /// group. Connection attempts then move to another failure domain after each
+    /// failed address instead of trying every address of a subnet that is down.
+    /// Applied after [RFC 6724] sorting, if enabled.
+    ///
+    /// Default is `false`.
+    ///
+    /// [RFC 6724]: https://www.rfc-editor.org/rfc/rfc6724
+    pub fn with_subnet_diversity(mut self, enabled: bool) -> HickoryDnsResolver {
+        self.subnet_diversity = enabled;
+        self
+    }
+
+    /// Cache lookups that succeed without usable addresses for `ttl`.
+    ///
+    /// Such lookups fail with a [`DnsResolveError`] for which
+    /// [`DnsResolveError::is_no_addresses`] returns `true`. With this set, the
+    /// failure is remembered so that repeated requests do not query the name
+    /// servers again until `ttl` has passed.
+    ///
+    /// Default is to not cache them.
+    pub fn with_no_addresses_ttl(mut self, ttl: Duration) -> HickoryDnsResolver {
+        self.no_addresses_ttl = Some(ttl);
+        self
+    }
+
+    /// Return a single address, picked by `strategy`, instead of every resolved one.
+    ///
+    /// Useful for reproducible tests and sticky routing. The full address set is
+    /// still cached, only the result handed to the connector is reduced.
+    pub fn with_single_addr_selection(
+        mut self,
+        strategy: SingleAddrStrategy,
+    ) -> HickoryDnsResolver {
+        self.single_addr = Some(strategy);
+        self
+    }
+
+    /// Resolve through layers: `overrides` first, then the hosts file if
+    /// `hosts_file` is set, then the name servers if `upstream` is set.
+    ///
+    /// The first layer that returns addresses for a host wins, and the later ones
+    /// are not consulted. Addresses from the overrides and the hosts file are not
+    /// cached; a port of `0` is replaced by the port of the URL. The hosts file is
+    /// the system one unless set with
+    /// [`with_hosts_file`](HickoryDnsResolver::with_hosts_file), and is read once,
+    /// the first time it is needed, unless watched with
+    /// [`with_hosts_file_reload`](HickoryDnsResolver::with_hosts_file_reload).
+    /// Without `upstream`, hosts unknown to the local layers fail with a
+    /// [`DnsResolveError`] for which [`DnsResolveError::is_no_addresses`] returns
+    /// `true`.
+    ///
+    /// The name servers are then queried without hickory looking the host up in
+    /// the system hosts file itself, so without `hosts_file` that file is not
+    /// read at all. This creates a dedicated resolver instead of sharing the
+    /// default one.
+    pub fn with_layers<I, K>(
+        mut self,
+        overrides: I,
+        hosts_file: bool,
+        upstream: bool,
+    ) -> HickoryDnsResolver
+    where
+        I: IntoIterator<Item = (K, Vec<SocketAddr>)>,
+        K: AsRef<str>,
+    {
+        let overrides = overrides
+            .into_iter()
+            .filter_map(|(host, addrs)| Some((canonicalize_host(host.as_ref()).ok()?, addrs)))
+            .collect();
+        self.skip_system_hosts();
+        let previous = self.layers.take();
+        let (custom_hosts_file, hosts_reload) = previous
+            .map(|layers| (layers.hosts_file, layers.hosts_reload))
+            .unwrap_or_default();
+        self.layers = Some(Layers {
+            overrides: Arc::new(overrides),
+            hosts_file: hosts_file.then(|| custom_hosts_file.unwrap_or_else(|| HOSTS.clone())),
+            hosts_reload,
+            upstream,
+        });
+        self
+    }
+
+    /// Consult the hosts file at `path` instead of the system one.
+    ///
+    /// Enables the hosts file layer, after the overrides of
+    /// [`with_layers`](HickoryDnsResolver::with_layers) if set. Handy to pin the
+    /// hosts of a test environment, or to point tracking domains at `127.0.0.1`,
+    /// without touching `/etc/hosts`.
+    pub fn with_hosts_file<P>(mut self, path: P) -> HickoryDnsResolver
+    where
+        P: Into<PathBuf>,
+    {
+        self.layers_mut().hosts_file = Some(Arc::new(HostsFile::new(path.into())));
+        self
+    }
+
+    /// Watch the hosts file for changes, checking it at most once per `interval`.
+    ///
+    /// Lookups then compare the modification time of the file with the one it had
+    /// when read, and read it again when it changed, so edits are picked up without
+    /// rebuilding the resolver. A zero `interval` checks on every lookup that
+    /// reaches the hosts file layer. Has no effect unless the layer is enabled with
+    /// [`with_layers`](HickoryDnsResolver::with_layers) or
+    /// [`with_hosts_file`](HickoryDnsResolver::with_hosts_file).
+    pub fn with_hosts_file_reload(mut self, interval: Duration) -> HickoryDnsResolver {
+        self.layers_mut().hosts_reload = Some(interval);
+        self
+    }
+
+    /// Returns the layers, adding ones that only query the name servers if unset
+    fn layers_mut(&mut self) -> &mut Layers {
+        self.skip_system_hosts();
+        self.layers.get_or_insert_with(|| Layers {
+            overrides: Arc::new(HashMap::with_hasher(HASHER)),
+            hosts_file: None,
+            hosts_reload: None,
+            upstream: true,
+        })
+    }
+
+    /// Builds a dedicated resolver for the current setup.
+    fn rebuild(&mut self) {
+        self.resolver = Arc::new(build_resolver(&self.setup));
+        self.authority = Arc::default();
+    }
+
+    /// Leaves hosts files to the layers, rebuilding the resolver so hickory no
+    /// longer reads the system one.
+    fn skip_system_hosts(&mut self) {
+        if !self.setup.skip_hosts_file {
+            self.setup.skip_hosts_file = true;
+            self.rebuild();
+        }
+    }
+
+    /// Only cache answers for the hosts flagged with
+    /// [`with_authoritative_hosts`](HickoryDnsResolver::with_authoritative_hosts)
+    /// when they come from an authoritative server, with the AA bit set.
+    ///
+    /// Flagged hosts are then queried without hickory's own cache, and a
+    /// non-authoritative answer, from a cache or a forwarder, is used for the
+    /// request but not cached, so the next request queries again. Concurrent
+    /// lookups of a flagged host still share one query, and serve-stale still
+    /// applies when it fails.
+    ///
+    /// Default is `false`.
+    pub fn with_cache_only_authoritative(mut self, enabled: bool) -> HickoryDnsResolver {
+        self.cache_only_authoritative = enabled;
+        self
+    }
+
+    /// Flag the hosts that
+    /// [`with_cache_only_authoritative`](HickoryDnsResolver::with_cache_only_authoritative)
+    /// applies to.
+    pub fn with_authoritative_hosts<I, K>(mut self, hosts: I) -> HickoryDnsResolver
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        self.authoritative_hosts = hosts
+            .into_iter()
+            .filter_map(|host| canonicalize_host(host.as_ref()).ok())
+            .collect();
+        self
+    }
+
+    /// Whether only authoritative answers for `host` may be cached.
+    fn requires_authority(&self, host: &str) -> bool {
+        self.cache_only_authoritative
+            && canonicalize_host(host).is_ok_and(|host| self.authoritative_hosts.contains(&host))
+    }
+
+    /// Turn a non-empty lookup result into the addresses to connect to.
+    fn to_addrs(&self, host: &str, addrs: Vec<SocketAddr>) -> Addrs {
+        let addrs = match self.single_addr {
+            Some(strategy) => vec![strategy.select(host, &addrs)],
+            None => addrs,
+        };
+        Box::new(CachedSocketAddrs::new(addrs))
+    }
+
+    /// Apply the configured address ordering to a fresh lookup result.
+    fn sort(&self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        if self.rfc6724_sorting {
+            rfc6724::sort_addrs(&mut addrs);
+        }
+        if self.subnet_diversity {
+            addrs = interleave_subnets(addrs);
         }
+        addrs
+    }
+
+    /// Look up the HTTPS record of `host` and turn it into connectable addresses.
+    ///
+    /// Returns `None` when there is no usable service-mode record, so the caller
+    /// falls back to a regular address lookup.
+    async fn lookup_https(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let lookup = self.resolver.lookup(host, RecordType::HTTPS).await.ok()?;
+
+        // Lowest priority wins; priority 0 marks alias mode, which is not followed.
+        let svcb = lookup
+            .record_iter()
+            .filter_map(|record| match record.data() {
+                RData::HTTPS(https) => Some(&https.0),
+                _ => None,
+            })
+            .filter(|svcb| svcb.svc_priority() > 0)
+            .min_by_key(|svcb| svcb.svc_priority())?;
+
+        let addrs = svcb_addrs(svcb);
+        if !addrs.is_empty() {
+            return Some(addrs);
+        }
+
+        // No address hints, resolve the service target instead
+        let port = svcb_port(svcb);
+        let target = svcb.target_name();
+        let lookup = if target.is_root() {
+            self.resolver.lookup_ip(host).await
+        } else {
+            self.resolver.lookup_ip(target.clone()).await
+        };
+
+        let addrs: Vec<SocketAddr> = lookup
+            .ok()?
+            .iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        (!addrs.is_empty()).then_some(addrs)
+    }
+
+    /// Look up the ECHConfigList published in the HTTPS record of `host`.
+    pub(crate) async fn lookup_ech_config(&self, host: &str) -> Option<Vec<u8>> {
+        let lookup = self.resolver.lookup(host, RecordType::HTTPS).await.ok()?;
+        lookup
+            .record_iter()
+            .filter_map(|record| match record.data() {
+                RData::HTTPS(https) => Some(&https.0),
+                _ => None,
+            })
+            .filter(|svcb| svcb.svc_priority() > 0)
+            .min_by_key(|svcb| svcb.svc_priority())
+            .and_then(svcb_ech_config)
+    }
+
+    /// Resolve `host` without consulting the cache, returning the addresses and
+    /// the remaining TTL of their records.
+    ///
+    /// With `https`, HTTPS records are tried first, then A/AAAA records. Addresses
+    /// from HTTPS records carry no TTL.
+    async fn lookup_uncached(
+        &self,
+        host: &str,
+        https: bool,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>), BoxError> {
+        if https {
+            if let Some(addrs) = self.lookup_https(host).await {
+                trace!("Using HTTPS record addresses for {}", host);
+                return Ok((self.sort(addrs), None));
+            }
+        }
+
+        debug!("DNS cache miss, resolving {}", host);
+        let lookup = self.resolver.lookup_ip(host).await?;
+        let ttl = lookup
+            .valid_until()
+            .saturating_duration_since(Instant::now());
+        let addrs = lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+        Ok((self.sort(addrs), Some(ttl)))
+    }
+
+    /// Resolve `host` with A and AAAA queries sent to the name servers, bypassing
+    /// the cache, and report whether every answer was authoritative (the AA bit
+    /// was set).
+    ///
+    /// The queries go through the same name servers, transport, attempts and search
+    /// domains as regular lookups. The returned addresses carry port `0`.
+    pub async fn lookup_with_authority(
+        &self,
+        host: &str,
+    ) -> Result<(Vec<SocketAddr>, bool), DnsResolveError> {
+        self.query_authority(host)
+            .await
+            .map(|(addrs, _, authoritative)| (addrs, authoritative))
+            .map_err(|err| DnsResolveError::new(host, err))
+    }
+
+    /// Queries the addresses of `host`, returning them along with the lowest TTL
+    /// of the answers and whether every answer was authoritative.
+    async fn query_authority(
+        &self,
+        host: &str,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>, bool), BoxError> {
+        let config = self.resolver.config();
+        let options = self.resolver.options();
+        let handle = self.authority.0.get_or_init(|| {
+            let pool = NameServerPool::from_config(
+                NameServerConfigGroup::from(config.name_servers().to_vec()),
+                Arc::new(options.clone()),
+                TokioConnectionProvider::default(),
+            );
+            RetryDnsHandle::new(pool, options.attempts)
+        });
+
+        let record_types: &[RecordType] = match options.ip_strategy {
+            LookupIpStrategy::Ipv4Only => &[RecordType::A],
+            LookupIpStrategy::Ipv6Only => &[RecordType::AAAA],
+            _ => &[RecordType::A, RecordType::AAAA],
+        };
+
+        let mut last_err = None;
+        for name in search_names(config, options.ndots, host)? {
+            let mut addrs = Vec::new();
+            let mut ttl = None::<u32>;
+            let mut authoritative = true;
+            for &record_type in record_types {
+                let mut request = DnsRequestOptions::default();
+                request.recursion_desired = true;
+                let response = match handle
+                    .lookup(Query::query(name.clone(), record_type), request)
+                    .first_answer()
+                    .await
+                {
+                    Ok(response) => response,
+                    // The name does not exist, the other type will not have records either
+                    Err(err) if err.is_nx_domain() => return Err(err.into()),
+                    // No record of this type, the other one may still have some
+                    Err(err) if err.is_no_records_found() => continue,
+                    Err(err) => {
+                        last_err = Some(err);
+                        continue;
+                    }
+                };
+
+                authoritative &= response.header().authoritative();
+                for record in response.answers() {
+                    let ip: IpAddr = match record.data() {
+                        RData::A(a) => a.0.into(),
+                        RData::AAAA(aaaa) => aaaa.0.into(),
+                        _ => continue,
+                    };
+                    addrs.push(SocketAddr::new(ip, 0));
+                    ttl = Some(ttl.map_or(record.ttl(), |ttl| ttl.min(record.ttl())));
+                }
+            }
+
+            if !addrs.is_empty() {
+                let ttl = ttl.map(|ttl| Duration::from_secs(ttl.into()));
+                return Ok((self.sort(addrs), ttl, authoritative));
+            }
+        }
+
+        match last_err {
+            Some(err) => Err(err.into()),
+            None => Ok((Vec::new(), None, true)),
+        }
+    }
+
+    /// Build the error for a negative answer, caching it under `key` if configured.
+    ///
+    /// Lookups without usable addresses are cached for the `no_addresses_ttl` if
+    /// set, everything else for the negative TTL of the cache.
+    fn negative(&self, host: &str, key: &str, kind: NegativeKind) -> DnsResolveError {
+        debug!("DNS lookup for {} got a negative answer: {:?}", host, kind);
+        match (kind, self.no_addresses_ttl) {
+            (NegativeKind::NoData, Some(ttl)) => {
+                self.cache
+                    .insert_negative_with_ttl(key.to_owned(), kind, ttl)
+            }
+            _ => self.cache.insert_negative(key.to_owned(), kind),
+        }
+        DnsResolveError::negative(host, kind)
+    }
+
+    /// Look up the hostname of `ip` with a PTR query, for logging.
+    ///
+    /// Results, including failed lookups, are kept for a few minutes in a small
+    /// cache of this resolver and its clones, separate from the forward A/AAAA
+    /// cache. Returns `None` if the address has no PTR record or the lookup
+    /// failed.
+    pub async fn reverse(&self, ip: IpAddr) -> Option<String> {
+        self.ptr_cache
+            .get_or_lookup(ip, || async {
+                let lookup = self.resolver.reverse_lookup(ip).await.ok()?;
+                let ptr = lookup.iter().next()?;
+                Some(ptr.0.to_utf8().trim_end_matches('.').to_owned())
+            })
+            .await
+    }
+
+    /// Resolve `host` and return its addresses together with their TTL.
+    ///
+    /// The TTL is the smallest TTL among the returned records. On a cache hit,
+    /// the remaining TTL of the cached entry is returned instead. Fresh results
+    /// are cached for that TTL, within the TTL bounds of the cache.
+    ///
+    /// HTTPS records are not consulted, so addresses from the name servers carry
+    /// port `0`. A cached entry is returned as stored, including the port of
+    /// addresses inserted into the cache with one.
+    pub async fn lookup_with_ttl(
+        &self,
+        host: &str,
+    ) -> Result<(Vec<SocketAddr>, Duration), DnsResolveError> {
+        if let Some(kind) = self.cache.get_negative(host) {
+            return Err(DnsResolveError::negative(host, kind));
+        }
+        if let Some(cached) = self.cache.get_with_ttl(host) {
+            trace!("Using cached DNS result for {}", host);
+            return Ok(cached);
+        }
+
+        let lookup = match self.resolver.lookup_ip(host).await {
+            Ok(lookup) => lookup,
+            Err(err) => {
+                return Err(match negative_kind(&err) {
+                    Some(kind) => self.negative(host, host, kind),
+                    None => DnsResolveError::new(host, err),
+                });
+            }
+        };
+
+        let ttl = lookup
+            .valid_until()
+            .saturating_duration_since(Instant::now());
+        let addrs = self.sort(lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect());
+
+        if addrs.is_empty() {
+            return Err(self.negative(host, host, NegativeKind::NoData));
+        }
+        self.cache
+            .insert_upstream(host.to_owned(), addrs.clone(), ttl);
+
+        Ok((addrs, ttl))
     }
 }
 
-struct SocketAddrs {
-    iter: LookupIpIntoIter,
+impl Default for HickoryDnsResolver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Wrapper for cached socket addresses
+///
+/// Cached addresses keep their port, which is `0` unless it was learned from an
+/// HTTPS record.
 struct CachedSocketAddrs {
-    iter: std::vec::IntoIter<std::net::IpAddr>,
+    iter: std::vec::IntoIter<SocketAddr>,
+}
+
+impl CachedSocketAddrs {
+    fn new(addrs: Vec<SocketAddr>) -> Self {
+        CachedSocketAddrs {
+            iter: addrs.into_iter(),
+        }
+    }
 }
 
 impl Iterator for CachedSocketAddrs {
     type Item = SocketAddr;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|ip_addr| SocketAddr::new(ip_addr, 0))
+        self.iter.next()
+    }
+}
+
+/// Cache of reverse lookup results, including negative ones
+struct PtrCache {
+    entries: Mutex<HashMap<IpAddr, (Option<String>, Instant)>>,
+    ttl: Duration,
+}
+
+impl PtrCache {
+    fn new(ttl: Duration) -> Self {
+        PtrCache {
+            entries: Mutex::new(HashMap::with_hasher(HASHER)),
+            ttl,
+        }
+    }
+
+    /// Returns the cached name of `ip`, running `lookup` on a miss.
+    async fn get_or_lookup<F, Fut>(&self, ip: IpAddr, lookup: F) -> Option<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Option<String>>,
+    {
+        if let Some((name, expires_at)) = self.entries.lock().get(&ip) {
+            if Instant::now() < *expires_at {
+                trace!("PTR cache hit for {}", ip);
+                return name.clone();
+            }
+        }
+
+        let name = lookup().await;
+
+        let mut entries = self.entries.lock();
+        if entries.len() >= PTR_CACHE_MAX_ENTRIES {
+            let now = Instant::now();
+            entries.retain(|_, (_, expires_at)| now < *expires_at);
+            if entries.len() >= PTR_CACHE_MAX_ENTRIES {
+                if let Some(key) = entries.keys().next().copied() {
+                    entries.remove(&key);
+                }
+            }
+        }
+        entries.insert(ip, (name.clone(), Instant::now() + self.ttl));
+        name
+    }
+}
+
+/// Interleaves `addrs` by /24 (IPv4) or /48 (IPv6) subnet.
+///
+/// Subnets take turns in the order they first appear, and each keeps the
+/// relative order of its addresses.
+fn interleave_subnets(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    #[derive(PartialEq)]
+    enum Subnet {
+        V4([u8; 3]),
+        V6([u16; 3]),
+    }
+
+    fn subnet(ip: IpAddr) -> Subnet {
+        match ip {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                Subnet::V4([a, b, c])
+            }
+            IpAddr::V6(ip) => {
+                let [a, b, c, ..] = ip.segments();
+                Subnet::V6([a, b, c])
+            }
+        }
+    }
+
+    let mut groups: Vec<(Subnet, Vec<SocketAddr>)> = Vec::new();
+    for addr in &addrs {
+        let key = subnet(addr.ip());
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(*addr),
+            None => groups.push((key, vec![*addr])),
+        }
+    }
+
+    let mut interleaved = Vec::with_capacity(addrs.len());
+    for i in 0.. {
+        let before = interleaved.len();
+        interleaved.extend(groups.iter().filter_map(|(_, group)| group.get(i)));
+        if interleaved.len() == before {
+            break;
+        }
+    }
+    interleaved
+}
+
+/// Parses a hosts file of `ip name [alias...]` lines into addresses by canonical name.
+///
+/// Comments and lines without a valid address are skipped. Addresses carry port `0`.
+fn parse_hosts(text: &str) -> HashMap<String, Vec<SocketAddr>> {
+    let mut hosts: HashMap<String, Vec<SocketAddr>> = HashMap::with_hasher(HASHER);
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(ip) = fields.next().and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+            continue;
+        };
+
+        let addr = SocketAddr::new(ip, 0);
+        for name in fields.filter_map(|name| canonicalize_host(name).ok()) {
+            let addrs = hosts.entry(name).or_default();
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+    }
+    hosts
+}
+
+/// Returns the `port` parameter of an SVCB record, or `0` if absent.
+fn svcb_port(svcb: &SVCB) -> u16 {
+    svcb.svc_params()
+        .iter()
+        .find_map(|(_, value)| match value {
+            SvcParamValue::Port(port) => Some(*port),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the `ech` parameter of an SVCB record, the ECHConfigList to
+/// encrypt the ClientHello with.
+fn svcb_ech_config(svcb: &SVCB) -> Option<Vec<u8>> {
+    svcb.svc_params().iter().find_map(|(_, value)| match value {
+        SvcParamValue::EchConfigList(configs) => Some(configs.0.clone()),
+        _ => None,
+    })
+}
+
+/// Collects the `ipv4hint` and `ipv6hint` addresses of an SVCB record.
+fn svcb_addrs(svcb: &SVCB) -> Vec<SocketAddr> {
+    let port = svcb_port(svcb);
+    let mut addrs = Vec::new();
+
+    for (_, value) in svcb.svc_params() {
+        match value {
+            SvcParamValue::Ipv4Hint(hint) => {
+                addrs.extend(hint.0.iter().map(|a| SocketAddr::new(a.0.into(), port)));
+            }
+            SvcParamValue::Ipv6Hint(hint) => {
+                addrs.extend(
+                    hint.0
+                        .iter()
+                        .map(|aaaa| SocketAddr::new(aaaa.0.into(), port)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    addrs
+}
+
+/// Returns the negative answer a failed lookup stands for, if it is one.
+///
+/// Only `NXDOMAIN` and `NOERROR` without records count; transport errors and
+/// other response codes like `SERVFAIL` may not happen again and are not cached.
+fn negative_kind(err: &(dyn StdError + 'static)) -> Option<NegativeKind> {
+    let err = match err.downcast_ref::<ResolveError>() {
+        Some(err) => err.proto()?,
+        None => err.downcast_ref::<ProtoError>()?,
+    };
+    match *err.kind() {
+        ProtoErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain,
+            ..
+        } => Some(NegativeKind::NxDomain),
+        ProtoErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NoError,
+            ..
+        } => Some(NegativeKind::NoData),
+        _ => None,
+    }
+}
+
+/// Falls back to a recently-expired cache entry after a failed lookup.
+///
+/// Returns the original error if serve-stale is disabled or no entry is
+/// within the `max_stale` window.
+fn serve_stale<E>(
+    cache: &DnsCache,
+    hostname: &str,
+    max_stale: Option<Duration>,
+    err: E,
+) -> Result<Addrs, BoxError>
+where
+    E: Into<BoxError>,
+{
+    let stale = max_stale
+        .and_then(|max_stale| cache.get_stale(hostname, max_stale))
+        .filter(|addrs| !addrs.is_empty());
+    match stale {
+        Some(stale_addrs) => {
+            debug!("DNS lookup failed for {}, serving stale entry", hostname);
+            Ok(Box::new(CachedSocketAddrs::new(stale_addrs)))
+        }
+        None => Err(err.into()),
     }
 }
 
@@ -75,43 +1284,815 @@ impl Resolve for HickoryDnsResolver {
         Box::pin(async move {
             let hostname = name.as_str();
 
-            // Check cache first
-            if let Some(cached_addrs) = GLOBAL_DNS_CACHE.get(hostname) {
+            // Answers that may come from an HTTPS record are cached apart, since they
+            // only apply to `https` on the default port
+            let https = resolver.https_records && name.is_https();
+            let key = if https {
+                Cow::Owned(cache::https_cache_key(hostname))
+            } else {
+                Cow::Borrowed(hostname)
+            };
+            let key = key.as_ref();
+
+            // Local layers take precedence over the cache and the name servers
+            if let Some(ref layers) = resolver.layers {
+                if let Some(addrs) = layers.lookup_local(hostname).await {
+                    trace!("Using local layer addresses for {}", hostname);
+                    return Ok(resolver.to_addrs(hostname, addrs));
+                }
+                if !layers.upstream {
+                    return Err(DnsResolveError::no_addresses(hostname).into());
+                }
+            }
+
+            // Check cache first, refreshing entries about to expire in the background
+            if let Some(kind) = resolver.cache.get_negative(key) {
+                trace!("Using cached negative DNS result for {}", hostname);
+                return Err(DnsResolveError::negative(hostname, kind).into());
+            }
+            if let Some((cached_addrs, refresh)) = resolver.cache.get_refresh_ahead(key) {
                 trace!("Using cached DNS result for {}", hostname);
-                let ip_addrs: Vec<std::net::IpAddr> = cached_addrs.into_iter().map(|addr| addr.ip()).collect();
-                let addrs: Addrs = Box::new(CachedSocketAddrs {
-                    iter: ip_addrs.into_iter(),
-                });
-                return Ok(addrs);
+                if refresh {
+                    let refresher = resolver.clone();
+                    let host = hostname.to_owned();
+                    resolver
+                        .cache
+                        .refresh_in_background(key, move || async move {
+                            refresher.lookup_uncached(&host, https).await
+                        });
+                }
+                return Ok(resolver.to_addrs(hostname, cached_addrs));
             }
 
-            // Cache miss - perform actual DNS lookup
-            debug!("DNS cache miss, resolving {}", hostname);
-            let lookup = resolver.resolver.lookup_ip(hostname).await?;
+            // Cache miss - share one lookup among concurrent callers for this host
+            let lookup = if resolver.requires_authority(hostname) {
+                let lookup = || async {
+                    let (addrs, ttl, authoritative) = resolver.query_authority(hostname).await?;
+                    if !authoritative {
+                        debug!(
+                            "DNS answer for {} is not authoritative, not caching",
+                            hostname
+                        );
+                    }
+                    Ok((addrs, ttl, authoritative))
+                };
+                resolver.cache.get_or_resolve_cacheable(key, lookup).await
+            } else {
+                resolver
+                    .cache
+                    .get_or_resolve_with_ttl(key, || resolver.lookup_uncached(hostname, https))
+                    .await
+            };
 
-            // Collect addresses for caching
-            let ip_addrs: Vec<_> = lookup.iter().collect();
-            let socket_addrs: Vec<SocketAddr> = ip_addrs.iter()
-                .map(|ip| SocketAddr::new(*ip, 0))
-                .collect();
+            let err = match lookup {
+                Ok(addrs) if addrs.is_empty() => {
+                    let err = resolver.negative(hostname, key, NegativeKind::NoData);
+                    return Err(err.into());
+                }
+                Ok(addrs) => return Ok(resolver.to_addrs(hostname, addrs)),
+                Err(err) => err,
+            };
 
-            // Cache the result
-            if !socket_addrs.is_empty() {
-                GLOBAL_DNS_CACHE.insert(hostname.to_string(), socket_addrs);
+            // A negative answer is final, anything else may be served stale
+            if let Some(kind) = negative_kind(&*err) {
+                return Err(resolver.negative(hostname, key, kind).into());
             }
-
-            let addrs: Addrs = Box::new(SocketAddrs {
-                iter: lookup.into_iter(),
-            });
-            Ok(addrs)
+            serve_stale(&resolver.cache, key, resolver.max_stale, err).map(|addrs| {
+                match resolver.single_addr {
+                    Some(_) => resolver.to_addrs(hostname, addrs.collect()),
+                    None => addrs,
+                }
+            })
         })
     }
 }
 
-impl Iterator for SocketAddrs {
-    type Item = SocketAddr;
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::{IpAddr, Ipv4Addr},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|ip_addr| SocketAddr::new(ip_addr, 0))
+    use hickory_resolver::proto::op::{Message, MessageType};
+    use tokio::net::UdpSocket;
+
+    use super::*;
+    use crate::dns::cache::{CacheDecision, GLOBAL_DNS_CACHE};
+
+    fn lookup_failed() -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, "resolver unreachable")
+    }
+
+    /// Serves DNS over UDP on a local port, sending each query the response
+    /// `respond` returns for it, or nothing for `None`. Returns the address of the
+    /// name server and the number of queries it received.
+    async fn name_server<F>(mut respond: F) -> (SocketAddr, Arc<AtomicUsize>)
+    where
+        F: FnMut(&Message) -> Option<Message> + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let Ok(query) = Message::from_vec(&buf[..len]) else {
+                    continue;
+                };
+                if let Some(response) = respond(&query) {
+                    let _ = socket.send_to(&response.to_vec().unwrap(), peer).await;
+                }
+            }
+        });
+        (addr, queries)
+    }
+
+    /// Starts the response to `query`, echoing its id and questions.
+    fn reply(query: &Message) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(query.op_code())
+            .set_recursion_desired(query.recursion_desired())
+            .set_recursion_available(true)
+            .add_queries(query.queries().to_vec());
+        response
+    }
+
+    #[tokio::test]
+    async fn lookup_with_ttl_returns_addrs_and_ttl() {
+        let (addrs, ttl) = HickoryDnsResolver::new()
+            .lookup_with_ttl("localhost")
+            .await
+            .expect("localhost should resolve");
+
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        assert!(ttl > Duration::ZERO);
+    }
+
+    #[test]
+    fn protocol_restricts_name_servers() {
+        let config = ResolverConfig::google();
+        let protocols = |protocol: DnsProtocol| {
+            protocol
+                .apply(&config)
+                .name_servers()
+                .iter()
+                .map(|ns| ns.protocol)
+                .collect::<Vec<_>>()
+        };
+
+        let udp = protocols(DnsProtocol::Udp);
+        assert!(!udp.is_empty());
+        assert!(udp.iter().all(|p| *p == Protocol::Udp));
+
+        let tcp = protocols(DnsProtocol::Tcp);
+        assert!(!tcp.is_empty());
+        assert!(tcp.iter().all(|p| *p == Protocol::Tcp));
+
+        let both = protocols(DnsProtocol::UdpThenTcp);
+        assert!(both.contains(&Protocol::Udp));
+        assert!(both.contains(&Protocol::Tcp));
+    }
+
+    #[tokio::test]
+    async fn reverse_lookup_is_cached() {
+        let cache = PtrCache::new(Duration::from_secs(60));
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let lookups = AtomicUsize::new(0);
+        let lookup = || async {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            Some("host.example.com".to_owned())
+        };
+
+        assert_eq!(
+            cache.get_or_lookup(ip, lookup).await.as_deref(),
+            Some("host.example.com")
+        );
+        assert_eq!(
+            cache.get_or_lookup(ip, lookup).await.as_deref(),
+            Some("host.example.com")
+        );
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        // The forward cache is left untouched
+        assert!(GLOBAL_DNS_CACHE.get("host.example.com").is_none());
+    }
+
+    #[tokio::test]
+    async fn reverse_is_cached_per_resolver() {
+        use hickory_resolver::proto::rr::{Record, rdata::PTR};
+
+        // A name server answering every PTR query with the same name
+        let (addr, queries) = name_server(|query| {
+            let question = query.queries().first()?;
+            let mut response = reply(query);
+            if question.query_type() == RecordType::PTR {
+                response.add_answer(Record::from_rdata(
+                    question.name().clone(),
+                    60,
+                    RData::PTR(PTR(DnsName::from_ascii("host.example.com.").unwrap())),
+                ));
+            }
+            Some(response)
+        })
+        .await;
+
+        let resolver = || {
+            HickoryDnsResolver::new()
+                .with_cache(DnsCache::new())
+                .with_name_server(addr)
+                .with_protocol(DnsProtocol::Udp)
+                .with_attempts(1, Duration::from_millis(200))
+        };
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        let first = resolver();
+        assert_eq!(first.reverse(ip).await.as_deref(), Some("host.example.com"));
+        let sent = queries.load(Ordering::SeqCst);
+        assert!(sent > 0);
+        assert_eq!(
+            first.clone().reverse(ip).await.as_deref(),
+            Some("host.example.com")
+        );
+        assert_eq!(queries.load(Ordering::SeqCst), sent);
+
+        // Another resolver has a cache of its own
+        assert_eq!(
+            resolver().reverse(ip).await.as_deref(),
+            Some("host.example.com")
+        );
+        assert!(queries.load(Ordering::SeqCst) > sent);
+    }
+
+    #[tokio::test]
+    async fn single_addr_lowest_ip() {
+        let host = "single-addr.example.com";
+        let addrs: Vec<SocketAddr> = [
+            "192.0.2.30:0",
+            "[2001:db8::1]:0",
+            "192.0.2.4:0",
+            "192.0.2.12:0",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        GLOBAL_DNS_CACHE.insert(host.to_owned(), addrs.clone());
+
+        let resolver =
+            HickoryDnsResolver::new().with_single_addr_selection(SingleAddrStrategy::LowestIp);
+        let resolved: Vec<_> = resolver.resolve(host.into()).await.unwrap().collect();
+        assert_eq!(resolved, ["192.0.2.4:0".parse::<SocketAddr>().unwrap()]);
+
+        // The full set stays cached
+        assert_eq!(GLOBAL_DNS_CACHE.get(host), Some(addrs.clone()));
+        GLOBAL_DNS_CACHE.remove(host);
+
+        assert_eq!(
+            SingleAddrStrategy::FirstReturned.select(host, &addrs),
+            addrs[0]
+        );
+        let mut reversed = addrs.clone();
+        reversed.reverse();
+        assert_eq!(
+            SingleAddrStrategy::HashOfHost.select(host, &addrs),
+            SingleAddrStrategy::HashOfHost.select(host, &reversed)
+        );
+    }
+
+    #[cfg(feature = "dns-over-tls")]
+    #[test]
+    fn dns_over_tls_queries_only_the_tls_name_server() {
+        let addr: SocketAddr = "1.1.1.1:853".parse().unwrap();
+        let resolver = HickoryDnsResolver::new()
+            .with_protocol(DnsProtocol::Udp)
+            .with_dns_over_tls(addr, "cloudflare-dns.com");
+
+        let name_servers = resolver.resolver.config().name_servers();
+        assert_eq!(name_servers.len(), 1);
+        assert_eq!(name_servers[0].socket_addr, addr);
+        assert_eq!(name_servers[0].protocol, Protocol::Tls);
+        assert_eq!(
+            name_servers[0].tls_dns_name.as_deref(),
+            Some("cloudflare-dns.com")
+        );
+    }
+
+    #[test]
+    fn attempts_override_resolver_options() {
+        let setup = ResolverSetup {
+            attempts: Some((3, Duration::from_millis(500))),
+            ..Default::default()
+        };
+        let options = setup.options(ResolverOpts::default());
+        assert_eq!(options.attempts, 3);
+        assert_eq!(options.timeout, Duration::from_millis(500));
+
+        let defaults = ResolverSetup::default().options(ResolverOpts::default());
+        assert_eq!(defaults.attempts, ResolverOpts::default().attempts);
+        assert_eq!(defaults.timeout, ResolverOpts::default().timeout);
+    }
+
+    #[test]
+    fn layers_leave_hosts_file_to_the_layer() {
+        let defaults = ResolverSetup::default().options(ResolverOpts::default());
+        assert_eq!(
+            defaults.use_hosts_file,
+            ResolverOpts::default().use_hosts_file
+        );
+
+        let resolver = HickoryDnsResolver::new().with_layers(
+            Vec::<(&str, Vec<SocketAddr>)>::new(),
+            false,
+            true,
+        );
+        let options = resolver.setup.options(ResolverOpts::default());
+        assert_eq!(options.use_hosts_file, ResolveHosts::Never);
+    }
+
+    #[tokio::test]
+    async fn with_attempts_retries_a_failed_attempt() {
+        use hickory_resolver::proto::rr::{Record, rdata::A};
+
+        // A name server that ignores the first query for each question
+        let mut seen = Vec::new();
+        let (addr, _) = name_server(move |query| {
+            let question = query.queries().first()?;
+            if !seen.contains(question) {
+                seen.push(question.clone());
+                return None;
+            }
+
+            let mut response = reply(query);
+            if question.query_type() == RecordType::A {
+                response.add_answer(Record::from_rdata(
+                    question.name().clone(),
+                    60,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+            }
+            Some(response)
+        })
+        .await;
+
+        let host = "retry.example.";
+        let resolver = HickoryDnsResolver::new()
+            .with_name_server(addr)
+            .with_protocol(DnsProtocol::Udp)
+            .with_attempts(2, Duration::from_millis(200));
+        let (addrs, _) = resolver.lookup_with_ttl(host).await.unwrap();
+        assert_eq!(addrs, ["192.0.2.1:0".parse::<SocketAddr>().unwrap()]);
+
+        GLOBAL_DNS_CACHE.remove(host);
+    }
+
+    #[tokio::test]
+    async fn non_authoritative_answer_is_not_cached() {
+        use hickory_resolver::proto::rr::{Record, rdata::A};
+
+        // A name server that is only authoritative for `auth.example.`
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        let (addr, _) = name_server(move |query| {
+            let question = query.queries().first()?;
+            if question.query_type() == RecordType::A {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+
+            let mut response = reply(query);
+            response.set_authoritative(question.name().to_ascii() == "auth.example.");
+            if question.query_type() == RecordType::A {
+                response.add_answer(Record::from_rdata(
+                    question.name().clone(),
+                    60,
+                    RData::A(A::new(192, 0, 2, 1)),
+                ));
+            }
+            Some(response)
+        })
+        .await;
+
+        let cache = DnsCache::new();
+        let resolver = HickoryDnsResolver::new()
+            .with_name_server(addr)
+            .with_protocol(DnsProtocol::Udp)
+            .with_cache(cache.clone())
+            .with_cache_only_authoritative(true)
+            .with_authoritative_hosts(["forwarded.example", "auth.example"]);
+        let expected = ["192.0.2.1:0".parse::<SocketAddr>().unwrap()];
+
+        let (addrs, authoritative) = resolver
+            .lookup_with_authority("forwarded.example.")
+            .await
+            .unwrap();
+        assert_eq!(addrs, expected);
+        assert!(!authoritative);
+        let handle: *const _ = resolver.authority.0.get().unwrap();
+
+        // The non-authoritative answer is used, but not cached, and concurrent
+        // lookups share a single query
+        queries.store(0, Ordering::SeqCst);
+        let (first, second) = tokio::join!(
+            resolver.resolve("forwarded.example".into()),
+            resolver.resolve("forwarded.example".into()),
+        );
+        assert_eq!(first.unwrap().collect::<Vec<_>>(), expected);
+        assert_eq!(second.unwrap().collect::<Vec<_>>(), expected);
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+        assert!(cache.get("forwarded.example").is_none());
+
+        // An authoritative answer is cached as usual
+        assert!(resolver.resolve("auth.example".into()).await.is_ok());
+        assert_eq!(cache.get("auth.example").as_deref(), Some(&expected[..]));
+
+        // Every lookup went through the same name server pool
+        assert!(std::ptr::eq(handle, resolver.authority.0.get().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn nxdomain_is_negatively_cached() {
+        // A name server answering that no name exists
+        let (addr, queries) = name_server(|query| {
+            let mut response = reply(query);
+            response.set_response_code(ResponseCode::NXDomain);
+            Some(response)
+        })
+        .await;
+
+        let host = "negative-cache.example.";
+        let cache = DnsCache::builder()
+            .negative_ttl(Duration::from_secs(5))
+            .build();
+        let resolver = HickoryDnsResolver::new()
+            .with_cache(cache.clone())
+            .with_name_server(addr)
+            .with_protocol(DnsProtocol::Udp)
+            .with_attempts(1, Duration::from_millis(200));
+
+        let err = match resolver.resolve(host.into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_nxdomain());
+        assert_eq!(
+            cache.classify(host, Duration::ZERO),
+            CacheDecision::NegativeHit
+        );
+        assert!(cache.get(host).is_none());
+        let sent = queries.load(Ordering::SeqCst);
+        assert!(sent > 0);
+
+        // The next lookup is answered by the cache with the same error, without
+        // querying again
+        let err = match resolver.resolve(host.into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_nxdomain());
+        assert!(!err.is_no_addresses());
+        assert_eq!(queries.load(Ordering::SeqCst), sent);
+    }
+
+    #[tokio::test]
+    async fn servfail_is_not_negatively_cached() {
+        // A name server failing every query
+        let (addr, queries) = name_server(|query| {
+            let mut response = reply(query);
+            response.set_response_code(ResponseCode::ServFail);
+            Some(response)
+        })
+        .await;
+
+        let host = "servfail.example.";
+        let cache = DnsCache::builder()
+            .negative_ttl(Duration::from_secs(5))
+            .build();
+        let resolver = HickoryDnsResolver::new()
+            .with_cache(cache.clone())
+            .with_name_server(addr)
+            .with_protocol(DnsProtocol::Udp)
+            .with_attempts(1, Duration::from_millis(200));
+
+        assert!(resolver.resolve(host.into()).await.is_err());
+        assert_eq!(cache.classify(host, Duration::ZERO), CacheDecision::Miss);
+        let sent = queries.load(Ordering::SeqCst);
+        assert!(sent > 0);
+
+        // The failure may be transient, so the next lookup queries again
+        assert!(resolver.resolve(host.into()).await.is_err());
+        assert!(queries.load(Ordering::SeqCst) > sent);
+    }
+
+    #[tokio::test]
+    async fn empty_lookup_is_no_addresses_error() {
+        // A name server answering every query without records
+        let (addr, queries) = name_server(|query| Some(reply(query))).await;
+
+        let host = "no-addresses.example.com";
+        let cache = DnsCache::new();
+        let resolver = HickoryDnsResolver::new()
+            .with_cache(cache.clone())
+            .with_name_server(addr)
+            .with_protocol(DnsProtocol::Udp)
+            .with_attempts(1, Duration::from_millis(200))
+            .with_no_addresses_ttl(Duration::from_secs(60));
+
+        let err = match resolver.resolve(host.into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_no_addresses());
+        assert!(!err.is_nxdomain());
+        assert_eq!(err.host(), host);
+        assert_eq!(
+            err.to_string(),
+            "failed to resolve no-addresses.example.com: no addresses returned"
+        );
+        let sent = queries.load(Ordering::SeqCst);
+        assert!(sent > 0);
+
+        // The result was negatively cached and is served without a new query
+        assert_eq!(
+            cache.classify(host, Duration::ZERO),
+            CacheDecision::NegativeHit
+        );
+        let err = match resolver.resolve(host.into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_no_addresses());
+        assert_eq!(queries.load(Ordering::SeqCst), sent);
+    }
+
+    #[test]
+    fn https_record_ech_config() {
+        use hickory_resolver::proto::rr::{
+            Name as DnsName,
+            rdata::svcb::{EchConfigList, SvcParamKey},
+        };
+
+        let svcb = SVCB::new(
+            1,
+            DnsName::root(),
+            vec![(
+                SvcParamKey::EchConfigList,
+                SvcParamValue::EchConfigList(EchConfigList(vec![0, 4, 0xfe, 0x0d, 0, 0])),
+            )],
+        );
+        assert_eq!(svcb_ech_config(&svcb), Some(vec![0, 4, 0xfe, 0x0d, 0, 0]));
+
+        let svcb = SVCB::new(1, DnsName::root(), Vec::new());
+        assert_eq!(svcb_ech_config(&svcb), None);
+    }
+
+    #[test]
+    fn https_record_hints_to_addrs() {
+        use hickory_resolver::proto::rr::{
+            Name as DnsName,
+            rdata::{
+                A, AAAA,
+                svcb::{IpHint, SvcParamKey},
+            },
+        };
+
+        let svcb = SVCB::new(
+            1,
+            DnsName::root(),
+            vec![
+                (SvcParamKey::Port, SvcParamValue::Port(8443)),
+                (
+                    SvcParamKey::Ipv4Hint,
+                    SvcParamValue::Ipv4Hint(IpHint(vec![A::new(192, 0, 2, 1)])),
+                ),
+                (
+                    SvcParamKey::Ipv6Hint,
+                    SvcParamValue::Ipv6Hint(IpHint(vec![AAAA::new(
+                        0x2001, 0xdb8, 0, 0, 0, 0, 0, 1,
+                    )])),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            svcb_addrs(&svcb),
+            vec![
+                "192.0.2.1:8443".parse::<SocketAddr>().unwrap(),
+                "[2001:db8::1]:8443".parse::<SocketAddr>().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn https_record_without_port_uses_zero() {
+        use hickory_resolver::proto::rr::{
+            Name as DnsName,
+            rdata::{
+                A,
+                svcb::{IpHint, SvcParamKey},
+            },
+        };
+
+        let svcb = SVCB::new(
+            1,
+            DnsName::root(),
+            vec![(
+                SvcParamKey::Ipv4Hint,
+                SvcParamValue::Ipv4Hint(IpHint(vec![A::new(192, 0, 2, 1)])),
+            )],
+        );
+
+        assert_eq!(
+            svcb_addrs(&svcb),
+            vec!["192.0.2.1:0".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn https_record_answers_are_cached_apart() {
+        let host = "svcb-key.example.com";
+        let cache = DnsCache::new();
+        let resolver = HickoryDnsResolver::new()
+            .with_https_records(true)
+            .with_cache(cache.clone());
+
+        let plain: SocketAddr = "192.0.2.1:0".parse().unwrap();
+        let hinted: SocketAddr = "192.0.2.2:8443".parse().unwrap();
+        cache.insert(host.to_owned(), vec![plain]);
+        cache.insert(cache::https_cache_key(host), vec![hinted]);
+
+        // Only `https` on the default port sees the answer derived from the record
+        let addrs: Vec<_> = resolver.resolve(host.into()).await.unwrap().collect();
+        assert_eq!(addrs, vec![plain]);
+        let name = Name::from(host).with_https(true);
+        let addrs: Vec<_> = resolver.resolve(name).await.unwrap().collect();
+        assert_eq!(addrs, vec![hinted]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serve_stale_on_lookup_error() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        cache.insert("example.com".to_owned(), vec![SocketAddr::new(ip, 0)]);
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+        assert!(cache.get("example.com").is_none());
+
+        let addrs = serve_stale(
+            &cache,
+            "example.com",
+            Some(Duration::from_secs(30)),
+            lookup_failed(),
+        )
+        .expect("stale entry should be served");
+        assert_eq!(addrs.map(|addr| addr.ip()).collect::<Vec<_>>(), vec![ip]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serve_stale_disabled_propagates_error() {
+        let cache = DnsCache::with_config(Duration::from_millis(10), 100);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        cache.insert("example.com".to_owned(), vec![SocketAddr::new(ip, 0)]);
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+
+        assert!(serve_stale(&cache, "example.com", None, lookup_failed()).is_err());
+        assert!(
+            serve_stale(
+                &cache,
+                "example.com",
+                Some(Duration::from_millis(1)),
+                lookup_failed()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn hosts_file_lines_by_name() {
+        let hosts = parse_hosts(
+            "# comment\n\
+             127.0.0.1 localhost Local.Test # trailing comment\n\
+             ::1       localhost\n\
+             not-an-ip ignored\n",
+        );
+
+        let ipv4 = "127.0.0.1:0".parse::<SocketAddr>().unwrap();
+        let ipv6 = "[::1]:0".parse::<SocketAddr>().unwrap();
+        assert_eq!(hosts["localhost"], [ipv4, ipv6]);
+        assert_eq!(hosts["local.test"], [ipv4]);
+        assert!(!hosts.contains_key("ignored"));
+    }
+
+    #[tokio::test]
+    async fn override_layer_short_circuits() {
+        // A name server that counts the queries it receives, and never answers
+        let (addr, queries) = name_server(|_| None).await;
+
+        // `localhost` is also in the hosts file, the override must win
+        let pinned = "192.0.2.7:0".parse::<SocketAddr>().unwrap();
+        let resolver = HickoryDnsResolver::new()
+            .with_name_server(addr)
+            .with_attempts(1, Duration::from_millis(100))
+            .with_layers([("LocalHost", vec![pinned])], true, true);
+
+        let resolved: Vec<_> = resolver
+            .resolve("localhost".into())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(resolved, [pinned]);
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+
+        // Without upstream, a host unknown to the local layers is not queried
+        let resolver = resolver.with_layers([("localhost", vec![pinned])], false, false);
+        let err = match resolver.resolve("unknown.example.".into()).await {
+            Ok(_) => panic!("lookup should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert!(err.is_no_addresses());
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn hosts_file_layer_reloads_when_changed() {
+        let path = std::env::temp_dir().join(format!("wreq-hosts-{}", std::process::id()));
+        let write = |text: &str, modified: SystemTime| {
+            std::fs::write(&path, text).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(modified).unwrap();
+        };
+        let resolve = |resolver: &HickoryDnsResolver| {
+            let resolving = resolver.resolve("tracker.example".into());
+            async move { resolving.await.ok().map(|addrs| addrs.collect::<Vec<_>>()) }
+        };
+
+        write("10.0.0.1 tracker.example\n", SystemTime::UNIX_EPOCH);
+        let resolver = HickoryDnsResolver::new()
+            .with_layers([("pinned.example", vec![])], true, false)
+            .with_hosts_file(&path)
+            .with_hosts_file_reload(Duration::ZERO);
+        let first = "10.0.0.1:0".parse::<SocketAddr>().unwrap();
+        assert_eq!(resolve(&resolver).await, Some(vec![first]));
+
+        // Redirect the host to localhost, as during a scraping run
+        write("127.0.0.1 tracker.example\n", SystemTime::now());
+        let localhost = "127.0.0.1:0".parse::<SocketAddr>().unwrap();
+        assert_eq!(resolve(&resolver).await, Some(vec![localhost]));
+
+        // Without reloading, the file is read once
+        let unwatched = HickoryDnsResolver::new()
+            .with_layers([("pinned.example", vec![])], true, false)
+            .with_hosts_file(&path);
+        assert_eq!(resolve(&unwatched).await, Some(vec![localhost]));
+        write("", SystemTime::UNIX_EPOCH);
+        assert_eq!(resolve(&unwatched).await, Some(vec![localhost]));
+        assert_eq!(resolve(&resolver).await, None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn subnet_diversity_alternates_subnets() {
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        let resolver = HickoryDnsResolver::new().with_subnet_diversity(true);
+
+        let sorted = resolver.sort(vec![
+            addr("192.0.2.1:0"),
+            addr("192.0.2.2:0"),
+            addr("192.0.2.3:0"),
+            addr("198.51.100.1:0"),
+            addr("198.51.100.2:0"),
+        ]);
+        assert_eq!(
+            sorted,
+            [
+                addr("192.0.2.1:0"),
+                addr("198.51.100.1:0"),
+                addr("192.0.2.2:0"),
+                addr("198.51.100.2:0"),
+                addr("192.0.2.3:0"),
+            ]
+        );
+
+        // IPv6 addresses are grouped by /48
+        let sorted = resolver.sort(vec![
+            addr("[2001:db8:1::1]:0"),
+            addr("[2001:db8:1:ff::2]:0"),
+            addr("[2001:db8:2::1]:0"),
+        ]);
+        assert_eq!(
+            sorted,
+            [
+                addr("[2001:db8:1::1]:0"),
+                addr("[2001:db8:2::1]:0"),
+                addr("[2001:db8:1:ff::2]:0"),
+            ]
+        );
     }
 }
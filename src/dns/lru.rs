@@ -0,0 +1,169 @@
+//! Map of cached hosts that tracks their least-recently-used order
+
+use std::collections::BTreeMap;
+
+use crate::hash::{HASHER, HashMap};
+
+/// A map from hostnames to values that remembers the order they were used in.
+///
+/// Inserting or getting a host makes it the most recently used, so the host
+/// returned by [`LruEntries::oldest`] is always the least recently used one.
+/// Every use is stamped with an increasing tick, and an ordered index from tick
+/// to host keeps promotion and eviction at `O(log n)`.
+pub(crate) struct LruEntries<V> {
+    entries: HashMap<String, Slot<V>>,
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+struct Slot<V> {
+    value: V,
+    /// Tick of the last use, the key of the host in `order`
+    used: u64,
+}
+
+impl<V> LruEntries<V> {
+    /// Creates an empty map with room for `capacity` hosts
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        LruEntries {
+            entries: HashMap::with_capacity_and_hasher(capacity, HASHER),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of `host`, marking it as the most recently used
+    pub(crate) fn get(&mut self, host: &str) -> Option<&V> {
+        let slot = self.entries.get_mut(host)?;
+        self.tick += 1;
+        if let Some(key) = self.order.remove(&slot.used) {
+            self.order.insert(self.tick, key);
+        }
+        slot.used = self.tick;
+        Some(&slot.value)
+    }
+
+    /// Returns the value of `host`, without changing the order
+    pub(crate) fn peek(&self, host: &str) -> Option<&V> {
+        self.entries.get(host).map(|slot| &slot.value)
+    }
+
+    /// Inserts `value` for `host` as the most recently used, returning the value
+    /// it replaces
+    pub(crate) fn insert(&mut self, host: String, value: V) -> Option<V> {
+        self.tick += 1;
+        self.order.insert(self.tick, host.clone());
+        let slot = Slot {
+            value,
+            used: self.tick,
+        };
+        let old = self.entries.insert(host, slot)?;
+        self.order.remove(&old.used);
+        Some(old.value)
+    }
+
+    pub(crate) fn remove(&mut self, host: &str) -> Option<V> {
+        let slot = self.entries.remove(host)?;
+        self.order.remove(&slot.used);
+        Some(slot.value)
+    }
+
+    /// Returns the least recently used host
+    pub(crate) fn oldest(&self) -> Option<&String> {
+        self.order.values().next()
+    }
+
+    /// Keeps only the hosts for which `keep` returns `true`
+    pub(crate) fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&String, &V) -> bool,
+    {
+        let order = &mut self.order;
+        self.entries.retain(|host, slot| {
+            let kept = keep(host, &slot.value);
+            if !kept {
+                order.remove(&slot.used);
+            }
+            kept
+        });
+    }
+
+    /// Iterates over the hosts and their values, in no particular order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.entries.iter().map(|(host, slot)| (host, &slot.value))
+    }
+
+    /// Removes every host, returning them in no particular order
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = (String, V)> + '_ {
+        self.order.clear();
+        self.entries.drain().map(|(host, slot)| (host, slot.value))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_get_promotes() {
+        let mut lru = LruEntries::with_capacity(0);
+        for host in ["a", "b", "c"] {
+            lru.insert(host.to_owned(), ());
+        }
+        assert_eq!(lru.oldest().map(String::as_str), Some("a"));
+
+        lru.get("a");
+        assert_eq!(lru.oldest().map(String::as_str), Some("b"));
+
+        // Peeking leaves the order alone
+        lru.peek("b");
+        assert_eq!(lru.oldest().map(String::as_str), Some("b"));
+    }
+
+    #[test]
+    fn test_lru_insert_replaces_and_promotes() {
+        let mut lru = LruEntries::with_capacity(0);
+        lru.insert("a".to_owned(), 1);
+        lru.insert("b".to_owned(), 2);
+
+        assert_eq!(lru.insert("a".to_owned(), 3), Some(1));
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.oldest().map(String::as_str), Some("b"));
+        assert_eq!(lru.peek("a"), Some(&3));
+    }
+
+    #[test]
+    fn test_lru_remove_and_retain_keep_order_in_sync() {
+        let mut lru = LruEntries::with_capacity(0);
+        for (i, host) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            lru.insert(host.to_owned(), i);
+        }
+
+        assert_eq!(lru.remove("a"), Some(0));
+        lru.retain(|_, &i| i != 1);
+        assert_eq!(lru.oldest().map(String::as_str), Some("c"));
+        assert_eq!(lru.order.len(), lru.len());
+
+        lru.clear();
+        assert!(lru.is_empty());
+        assert!(lru.oldest().is_none());
+    }
+}
@@ -0,0 +1,65 @@
+//! A mock upstream resolver for the tests of the resolver decorators
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use super::{Addrs, DnsResolveError, Name, Resolve, Resolving};
+use crate::error::BoxError;
+
+/// The address every successful lookup of [`MockResolver`] returns.
+pub(crate) const MOCK_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+/// An upstream counting its lookups, failing them with a timeout while
+/// `failing` is set, or with `NXDOMAIN` if `nxdomain` is set.
+#[derive(Clone, Default)]
+pub(crate) struct MockResolver {
+    failing: Arc<AtomicBool>,
+    nxdomain: bool,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl MockResolver {
+    /// Creates an upstream failing every lookup.
+    pub(crate) fn failing() -> MockResolver {
+        let mock = MockResolver::default();
+        mock.set_failing(true);
+        mock
+    }
+
+    /// Creates an upstream answering that no name exists.
+    pub(crate) fn nxdomain() -> MockResolver {
+        MockResolver {
+            nxdomain: true,
+            ..MockResolver::failing()
+        }
+    }
+
+    pub(crate) fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    /// Returns the number of lookups so far.
+    pub(crate) fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+}
+
+impl Resolve for MockResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        let result: Result<Addrs, BoxError> = if self.nxdomain {
+            Err(DnsResolveError::nxdomain(name.as_str()).into())
+        } else if self.failing.load(Ordering::SeqCst) {
+            Err("timeout".into())
+        } else {
+            Ok(Box::new(std::iter::once(MOCK_ADDR)))
+        };
+        Box::pin(std::future::ready(result))
+    }
+}
@@ -1,14 +1,51 @@
 //! DNS resolution
 
+pub(crate) mod breaker;
 pub(crate) mod cache;
+pub(crate) mod caching;
+pub(crate) mod canonical;
+#[cfg(feature = "hickory-dns")]
+pub(crate) mod doh;
+pub(crate) mod error;
+pub(crate) mod fallback;
+pub(crate) mod family;
 pub(crate) mod gai;
 #[cfg(feature = "hickory-dns")]
 pub(crate) mod hickory;
+pub(crate) mod lru;
+#[cfg(test)]
+pub(crate) mod mock;
+pub(crate) mod ratelimit;
+pub(crate) mod refresh;
 pub(crate) mod resolve;
+#[cfg(feature = "hickory-dns")]
+pub(crate) mod rfc6724;
+pub(crate) mod secondary;
+pub(crate) mod zone;
 
+pub use breaker::CircuitBreakerResolver;
+pub use cache::{
+    CacheDecision, DnsCache, DnsCacheBuilder, DnsCacheConfig, DnsCachePartition, DnsCacheStats,
+    EvictReason, EvictionPolicy,
+};
+pub use caching::CachingResolver;
+pub use canonical::{InvalidHostError, canonicalize_host};
+#[cfg(feature = "hickory-dns")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hickory-dns")))]
+pub use doh::DohResolver;
+pub use error::DnsResolveError;
+pub use fallback::FallbackResolver;
+pub use family::IpVersion;
+#[cfg(feature = "hickory-dns")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hickory-dns")))]
+pub use hickory::{DnsProtocol, HickoryDnsResolver, SingleAddrStrategy};
+pub use ratelimit::RateLimitedResolver;
 pub use resolve::{Addrs, IntoResolve, Name, Resolve, Resolving};
+pub use secondary::{InMemorySecondaryCache, SecondaryCache, SecondaryFuture};
+pub use zone::ParseError;
 
 pub(crate) use self::{
+    family::IpVersionResolver,
     gai::{GaiResolver, SocketAddrs},
     resolve::{DnsResolverWithOverrides, DynResolver},
     sealed::{InternalResolve, resolve},
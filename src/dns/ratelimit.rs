@@ -0,0 +1,170 @@
+//! Capping the rate of lookups sent upstream for each host
+
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    Addrs, DnsResolveError, IntoResolve, Name, Resolve, Resolving, canonical::normalize_host,
+};
+use crate::{
+    error::BoxError,
+    hash::{HASHER, HashMap},
+    sync::Mutex,
+};
+
+/// Number of tracked hosts above which hosts whose window ended are forgotten
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// A resolver that allows at most a number of lookups per host within a window.
+///
+/// Lookups beyond the limit are not sent to the inner resolver until the host's
+/// window resets. They are answered with the last addresses the inner resolver
+/// returned for that host, or fail with a [`DnsResolveError`] if there are none,
+/// which protects both sides during a storm of cache misses for a broken host.
+///
+/// Windows are fixed: the first lookup of a host starts its window, and the count
+/// resets once `window` has passed. Hosts are counted case-insensitively and
+/// without their trailing dot, so `Example.COM` and `example.com.` share a window.
+#[derive(Clone)]
+pub struct RateLimitedResolver {
+    inner: Arc<dyn Resolve>,
+    max_lookups: u32,
+    window: Duration,
+    hosts: Arc<Mutex<HashMap<String, HostWindow>>>,
+}
+
+struct HostWindow {
+    started: Instant,
+    lookups: u32,
+    /// The last answer of the inner resolver, served while throttled
+    last: Option<Vec<SocketAddr>>,
+}
+
+impl RateLimitedResolver {
+    /// Wraps `inner`, allowing at most `max_lookups` lookups per host every `window`.
+    pub fn new<R>(inner: R, max_lookups: u32, window: Duration) -> RateLimitedResolver
+    where
+        R: IntoResolve,
+    {
+        RateLimitedResolver {
+            inner: inner.into_resolve(),
+            max_lookups: max_lookups.max(1),
+            window,
+            hosts: Arc::new(Mutex::new(HashMap::with_hasher(HASHER))),
+        }
+    }
+
+    /// Counts a lookup of `host`, returning `Err` with the last known answer, if
+    /// any, when the host is over its limit.
+    fn acquire(&self, host: &str) -> Result<(), Option<Vec<SocketAddr>>> {
+        let now = Instant::now();
+        let mut hosts = self.hosts.lock();
+
+        if hosts.len() >= PRUNE_THRESHOLD {
+            hosts.retain(|_, entry| now.duration_since(entry.started) < self.window);
+        }
+
+        let entry = hosts.entry(host.to_owned()).or_insert_with(|| HostWindow {
+            started: now,
+            lookups: 0,
+            last: None,
+        });
+        if now.duration_since(entry.started) >= self.window {
+            entry.started = now;
+            entry.lookups = 0;
+        }
+        if entry.lookups >= self.max_lookups {
+            return Err(entry.last.clone());
+        }
+        entry.lookups += 1;
+        Ok(())
+    }
+
+    async fn resolve_limited(self, name: Name) -> Result<Addrs, BoxError> {
+        let host = normalize_host(name.as_str());
+        if let Err(last) = self.acquire(&host) {
+            debug!("DNS lookup rate limit reached for {}", name);
+            return match last {
+                Some(addrs) => Ok(Box::new(addrs.into_iter())),
+                None => {
+                    Err(DnsResolveError::new(name.as_str(), "lookup rate limit exceeded").into())
+                }
+            };
+        }
+
+        let addrs: Vec<SocketAddr> = self.inner.resolve(name.clone()).await?.collect();
+        if let Some(entry) = self.hosts.lock().get_mut(&*host) {
+            entry.last = Some(addrs.clone());
+        }
+        Ok(Box::new(addrs.into_iter()))
+    }
+}
+
+impl Resolve for RateLimitedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(self.clone().resolve_limited(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::mock::{MOCK_ADDR, MockResolver};
+
+    #[tokio::test]
+    async fn host_is_throttled_within_window() {
+        let upstream = MockResolver::default();
+        let resolver = RateLimitedResolver::new(upstream.clone(), 2, Duration::from_millis(100));
+
+        for _ in 0..2 {
+            assert!(resolver.resolve("busy.example".into()).await.is_ok());
+        }
+        assert_eq!(upstream.lookups(), 2);
+
+        // Over the limit, the last answer is served without querying upstream
+        let addrs: Vec<_> = resolver
+            .resolve("busy.example".into())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(addrs, [MOCK_ADDR]);
+        assert_eq!(upstream.lookups(), 2);
+
+        // Other hosts have their own budget
+        assert!(resolver.resolve("quiet.example".into()).await.is_ok());
+        assert_eq!(upstream.lookups(), 3);
+
+        // Once the window resets, the host is allowed again
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(resolver.resolve("busy.example".into()).await.is_ok());
+        assert_eq!(upstream.lookups(), 4);
+    }
+
+    #[tokio::test]
+    async fn spellings_of_a_host_share_a_window() {
+        let upstream = MockResolver::default();
+        let resolver = RateLimitedResolver::new(upstream.clone(), 1, Duration::from_secs(60));
+
+        assert!(resolver.resolve("Example.COM".into()).await.is_ok());
+        assert!(resolver.resolve("example.com.".into()).await.is_ok());
+        assert!(resolver.resolve("example.com".into()).await.is_ok());
+        assert_eq!(upstream.lookups(), 1);
+    }
+
+    #[tokio::test]
+    async fn throttled_host_without_answer_fails() {
+        let upstream = MockResolver::failing();
+        let resolver = RateLimitedResolver::new(upstream.clone(), 1, Duration::from_secs(60));
+
+        assert!(resolver.resolve("broken.example".into()).await.is_err());
+        let err = match resolver.resolve("broken.example".into()).await {
+            Ok(_) => panic!("throttled lookup without a previous answer should fail"),
+            Err(err) => err.downcast::<DnsResolveError>().unwrap(),
+        };
+        assert_eq!(err.host(), "broken.example");
+        assert_eq!(upstream.lookups(), 1);
+    }
+}
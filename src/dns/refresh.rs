@@ -0,0 +1,111 @@
+//! Bounded spawning of background DNS refresh tasks
+
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Default maximum number of concurrent background refresh tasks
+pub(crate) const DEFAULT_MAX_BACKGROUND_REFRESHES: usize = 32;
+
+/// Caps the number of background refresh tasks running at once.
+///
+/// Refreshes beyond the cap are skipped rather than queued: the entry stays
+/// stale until a later access triggers another refresh.
+#[derive(Clone, Debug)]
+pub(crate) struct RefreshLimiter {
+    max: usize,
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Releases a refresh slot when the task finishes
+struct RefreshPermit(Arc<AtomicUsize>);
+
+impl RefreshLimiter {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Spawns `fut` on the Tokio runtime if a slot is free.
+    ///
+    /// Returns `false` if the cap is reached and the refresh was skipped.
+    pub(crate) fn try_spawn<F>(&self, fut: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let Some(permit) = self.try_acquire() else {
+            trace!("skipping DNS refresh, {} already in flight", self.max);
+            return false;
+        };
+
+        tokio::spawn(async move {
+            fut.await;
+            drop(permit);
+        });
+        true
+    }
+
+    /// Returns the number of refresh tasks currently running.
+    #[cfg(test)]
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    fn try_acquire(&self) -> Option<RefreshPermit> {
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| RefreshPermit(self.in_flight.clone()))
+    }
+}
+
+impl Drop for RefreshPermit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refreshes_beyond_cap_are_skipped() {
+        let limiter = RefreshLimiter::new(2);
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let rx = Arc::new(std::sync::Mutex::new(rx));
+
+        let blocked = || {
+            let rx = rx.clone();
+            async move {
+                while rx.lock().unwrap().try_recv().is_err() {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }
+        };
+
+        assert!(limiter.try_spawn(blocked()));
+        assert!(limiter.try_spawn(blocked()));
+        assert!(!limiter.try_spawn(blocked()));
+        assert_eq!(limiter.in_flight(), 2);
+
+        // Release the running refreshes, freeing their slots
+        tx.send(()).unwrap();
+        tx.send(()).unwrap();
+        while limiter.in_flight() > 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert!(limiter.try_spawn(async {}));
+    }
+}
@@ -17,13 +17,14 @@ use crate::core::BoxError;
 #[derive(Clone, Hash, Eq, PartialEq)]
 pub struct Name {
     host: Box<str>,
+    https: bool,
 }
 
 impl Name {
     /// Creates a new [`Name`] from a string slice.
     #[inline]
     pub fn new(host: Box<str>) -> Name {
-        Name { host }
+        Name { host, https: false }
     }
 
     /// View the hostname as a string slice.
@@ -31,6 +32,21 @@ impl Name {
     pub fn as_str(&self) -> &str {
         &self.host
     }
+
+    /// Marks the name as resolved for an `https` destination on the default port,
+    /// the only one HTTPS records apply to without a port prefix.
+    #[inline]
+    pub(crate) fn with_https(mut self, https: bool) -> Name {
+        self.https = https;
+        self
+    }
+
+    /// Returns whether the name is resolved for an `https` destination on the
+    /// default port.
+    #[inline]
+    pub(crate) fn is_https(&self) -> bool {
+        self.https
+    }
 }
 
 impl From<&str> for Name {
@@ -148,7 +164,8 @@ impl Service<Name> for DynResolver {
 ///
 /// This resolver first checks for manual hostname-to-IP mappings before
 /// falling back to the underlying DNS resolver. Useful for testing or
-/// bypassing DNS for specific domains.
+/// bypassing DNS for specific domains. Hostnames match regardless of case and
+/// of a trailing dot.
 pub(crate) struct DnsResolverWithOverrides {
     dns_resolver: Arc<dyn Resolve>,
     overrides: Arc<HashMap<Cow<'static, str>, Vec<SocketAddr>>>,
@@ -160,6 +177,10 @@ impl DnsResolverWithOverrides {
         dns_resolver: Arc<dyn Resolve>,
         overrides: HashMap<Cow<'static, str>, Vec<SocketAddr>>,
     ) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|(host, addrs)| (normalize_override(host), addrs))
+            .collect();
         DnsResolverWithOverrides {
             dns_resolver,
             overrides: Arc::new(overrides),
@@ -167,9 +188,22 @@ impl DnsResolverWithOverrides {
     }
 }
 
+/// Lowercases `host` and strips a trailing dot, borrowing when it already is
+fn normalize_override<'a, H>(host: H) -> Cow<'a, str>
+where
+    H: Into<Cow<'a, str>>,
+{
+    let host = host.into();
+    let trimmed = host.strip_suffix('.').unwrap_or(&host);
+    if trimmed.len() == host.len() && !trimmed.bytes().any(|b| b.is_ascii_uppercase()) {
+        return host;
+    }
+    Cow::Owned(trimmed.to_ascii_lowercase())
+}
+
 impl Resolve for DnsResolverWithOverrides {
     fn resolve(&self, name: Name) -> Resolving {
-        match self.overrides.get(name.as_str()) {
+        match self.overrides.get(&*normalize_override(name.as_str())) {
             Some(dest) => {
                 let addrs: Addrs = Box::new(dest.clone().into_iter());
                 Box::pin(std::future::ready(Ok(addrs)))
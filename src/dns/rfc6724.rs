@@ -0,0 +1,260 @@
+//! Destination address ordering as described by [RFC 6724] section 6
+//!
+//! The system resolver applies this ordering itself, but hickory returns
+//! addresses in the order they were received.
+//!
+//! [RFC 6724]: https://www.rfc-editor.org/rfc/rfc6724
+
+use std::{
+    cmp::Ordering,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+/// Scope of link-local (and loopback) addresses
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+
+/// Scope of site-local addresses
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+
+/// Scope of global addresses
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// Default policy table from RFC 6724 section 2.1: prefix, prefix length,
+/// precedence and label. Longest matching prefix wins.
+const POLICY_TABLE: &[(Ipv6Addr, u8, u8, u8)] = &[
+    (Ipv6Addr::LOCALHOST, 128, 50, 0),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96, 35, 4),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 96, 1, 3),
+    (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 32, 5, 5),
+    (Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), 16, 30, 2),
+    (Ipv6Addr::new(0x3ffe, 0, 0, 0, 0, 0, 0, 0), 16, 1, 12),
+    (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10, 1, 11),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7, 3, 13),
+    (Ipv6Addr::UNSPECIFIED, 0, 40, 1),
+];
+
+/// Sorts `addrs` by RFC 6724 destination preference.
+///
+/// The source address for each destination is the one the local routing table
+/// would pick, found by connecting a UDP socket (no packets are sent).
+pub(crate) fn sort_addrs(addrs: &mut [SocketAddr]) {
+    sort_addrs_with(addrs, source_addr);
+}
+
+/// Sorts `addrs` using `source` to find the source address for a destination,
+/// `None` meaning the destination is unreachable.
+fn sort_addrs_with<F>(addrs: &mut [SocketAddr], source: F)
+where
+    F: Fn(IpAddr) -> Option<IpAddr>,
+{
+    if addrs.len() < 2 {
+        return;
+    }
+
+    let mut keyed: Vec<_> = addrs
+        .iter()
+        .map(|addr| {
+            let dst = to_ipv6(addr.ip());
+            (*addr, dst, source(addr.ip()).map(to_ipv6))
+        })
+        .collect();
+
+    // `sort_by` is stable, which implements rule 10: otherwise leave the order unchanged
+    keyed.sort_by(|(_, da, sa), (_, db, sb)| compare(da, sa.as_ref(), db, sb.as_ref()));
+
+    for (slot, (addr, ..)) in addrs.iter_mut().zip(keyed) {
+        *slot = addr;
+    }
+}
+
+/// Compares two destinations, `Less` meaning `a` is preferred.
+fn compare(a: &Ipv6Addr, sa: Option<&Ipv6Addr>, b: &Ipv6Addr, sb: Option<&Ipv6Addr>) -> Ordering {
+    let (sa, sb) = match (sa, sb) {
+        (Some(sa), Some(sb)) => (sa, sb),
+        // Rule 1: avoid unusable destinations
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => return Ordering::Equal,
+    };
+
+    let (policy_a, policy_b) = (policy(a), policy(b));
+
+    // Rule 2: prefer matching scope
+    let matching_scope = |dst: &Ipv6Addr, src: &Ipv6Addr| scope(dst) == scope(src);
+    prefer(matching_scope(a, sa), matching_scope(b, sb))
+        // Rule 5: prefer matching label
+        .then_with(|| prefer(policy_a.1 == policy(sa).1, policy_b.1 == policy(sb).1))
+        // Rule 6: prefer higher precedence
+        .then_with(|| policy_b.0.cmp(&policy_a.0))
+        // Rule 8: prefer smaller scope
+        .then_with(|| scope(a).cmp(&scope(b)))
+        // Rule 9: use longest matching prefix, for IPv6 only
+        .then_with(|| {
+            if a.to_ipv4_mapped().is_some() || b.to_ipv4_mapped().is_some() {
+                return Ordering::Equal;
+            }
+            common_prefix_len(b, sb).cmp(&common_prefix_len(a, sa))
+        })
+}
+
+/// Orders `true` before `false`.
+fn prefer(a: bool, b: bool) -> Ordering {
+    b.cmp(&a)
+}
+
+/// Returns the precedence and label of `addr` in the default policy table.
+fn policy(addr: &Ipv6Addr) -> (u8, u8) {
+    let bits = addr.to_bits();
+    POLICY_TABLE
+        .iter()
+        .find(|(prefix, len, ..)| {
+            *len == 0 || (bits ^ prefix.to_bits()) >> (128 - u32::from(*len)) == 0
+        })
+        .map_or((40, 1), |&(_, _, precedence, label)| (precedence, label))
+}
+
+/// Returns the scope of `addr`, per RFC 6724 section 3.1.
+fn scope(addr: &Ipv6Addr) -> u8 {
+    if let Some(v4) = addr.to_ipv4_mapped() {
+        return if v4.is_loopback() || v4.is_link_local() {
+            SCOPE_LINK_LOCAL
+        } else {
+            SCOPE_GLOBAL
+        };
+    }
+
+    let segments = addr.segments();
+    if addr.is_multicast() {
+        (segments[0] & 0x000f) as u8
+    } else if addr.is_loopback() || segments[0] & 0xffc0 == 0xfe80 {
+        SCOPE_LINK_LOCAL
+    } else if segments[0] & 0xffc0 == 0xfec0 {
+        SCOPE_SITE_LOCAL
+    } else {
+        SCOPE_GLOBAL
+    }
+}
+
+/// Returns the number of leading bits `a` and `b` share, up to the 64-bit prefix.
+fn common_prefix_len(a: &Ipv6Addr, b: &Ipv6Addr) -> u32 {
+    (a.to_bits() ^ b.to_bits()).leading_zeros().min(64)
+}
+
+/// Represents IPv4 addresses as IPv4-mapped IPv6 addresses, as the policy table expects.
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+/// Asks the routing table which source address would be used to reach `dst`.
+fn source_addr(dst: IpAddr) -> Option<IpAddr> {
+    let unspecified: IpAddr = match dst {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind((unspecified, 0)).ok()?;
+    socket.connect((dst, 443)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(addrs: &[&str], sources: &[(&str, &str)]) -> Vec<String> {
+        let mut addrs: Vec<SocketAddr> = addrs
+            .iter()
+            .map(|ip| SocketAddr::new(ip.parse().unwrap(), 443))
+            .collect();
+        let sources: Vec<(IpAddr, IpAddr)> = sources
+            .iter()
+            .map(|(dst, src)| (dst.parse().unwrap(), src.parse().unwrap()))
+            .collect();
+
+        sort_addrs_with(&mut addrs, |dst| {
+            sources.iter().find(|(d, _)| *d == dst).map(|(_, src)| *src)
+        });
+        addrs.iter().map(|addr| addr.ip().to_string()).collect()
+    }
+
+    #[test]
+    fn prefers_ipv6_when_both_families_are_reachable() {
+        let order = sorted(
+            &["198.51.100.1", "2001:db8:1::1"],
+            &[
+                ("198.51.100.1", "192.0.2.10"),
+                ("2001:db8:1::1", "2001:db8:1::10"),
+            ],
+        );
+        assert_eq!(order, ["2001:db8:1::1", "198.51.100.1"]);
+    }
+
+    #[test]
+    fn unreachable_destinations_go_last() {
+        let order = sorted(
+            &["2001:db8:1::1", "198.51.100.1"],
+            &[("198.51.100.1", "192.0.2.10")],
+        );
+        assert_eq!(order, ["198.51.100.1", "2001:db8:1::1"]);
+    }
+
+    #[test]
+    fn prefers_matching_label() {
+        // A global source prefers a global destination over a ULA one
+        let order = sorted(
+            &["fd00::1", "2a00:1450::1"],
+            &[
+                ("fd00::1", "2a00:1450::10"),
+                ("2a00:1450::1", "2a00:1450::10"),
+            ],
+        );
+        assert_eq!(order, ["2a00:1450::1", "fd00::1"]);
+    }
+
+    #[test]
+    fn prefers_smaller_scope() {
+        let order = sorted(
+            &["2a00:1450::1", "fe80::1"],
+            &[("2a00:1450::1", "2a00:1450::10"), ("fe80::1", "fe80::10")],
+        );
+        assert_eq!(order, ["fe80::1", "2a00:1450::1"]);
+    }
+
+    #[test]
+    fn prefers_longest_matching_prefix() {
+        let order = sorted(
+            &["2a00:2000::1", "2a00:1450::1"],
+            &[
+                ("2a00:2000::1", "2a00:1450::10"),
+                ("2a00:1450::1", "2a00:1450::10"),
+            ],
+        );
+        assert_eq!(order, ["2a00:1450::1", "2a00:2000::1"]);
+    }
+
+    #[test]
+    fn keeps_order_of_equal_destinations() {
+        let order = sorted(
+            &["198.51.100.2", "198.51.100.1"],
+            &[
+                ("198.51.100.1", "192.0.2.10"),
+                ("198.51.100.2", "192.0.2.10"),
+            ],
+        );
+        assert_eq!(order, ["198.51.100.2", "198.51.100.1"]);
+    }
+
+    #[test]
+    fn policy_table_lookup() {
+        assert_eq!(policy(&Ipv6Addr::LOCALHOST), (50, 0));
+        assert_eq!(
+            policy(&Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped()),
+            (35, 4)
+        );
+        assert_eq!(policy(&"2002::1".parse().unwrap()), (30, 2));
+        assert_eq!(policy(&"fd00::1".parse().unwrap()), (3, 13));
+        assert_eq!(policy(&"2a00:1450::1".parse().unwrap()), (40, 1));
+    }
+}
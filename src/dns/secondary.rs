@@ -0,0 +1,113 @@
+//! Shared second-level stores for DNS answers, such as Redis
+
+use std::{
+    future::{self, Future},
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    hash::{HASHER, HashMap},
+    sync::Mutex,
+};
+
+/// Future returned by the [`SecondaryCache`] methods.
+pub type SecondaryFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A store shared between processes that a [`DnsCache`](super::DnsCache) falls
+/// back to on a local miss, and writes resolved answers through to.
+///
+/// Implement this over an external store such as Redis or memcached to share DNS
+/// results across the instances of a deployment. Errors of the store are the
+/// implementation's to handle: a failed `get` should return `None`, so the host is
+/// resolved over the network, and a failed `set` should be ignored.
+///
+/// See [`DnsCacheBuilder::secondary_cache`](super::DnsCacheBuilder::secondary_cache).
+pub trait SecondaryCache: Send + Sync + 'static {
+    /// Returns the addresses stored for `host`, if any.
+    fn get<'a>(&'a self, host: &'a str) -> SecondaryFuture<'a, Option<Vec<SocketAddr>>>;
+
+    /// Stores the addresses of `host`, to be kept for `ttl`.
+    fn set<'a>(
+        &'a self,
+        host: &'a str,
+        addrs: &'a [SocketAddr],
+        ttl: Duration,
+    ) -> SecondaryFuture<'a, ()>;
+}
+
+impl<C: SecondaryCache> SecondaryCache for Arc<C> {
+    fn get<'a>(&'a self, host: &'a str) -> SecondaryFuture<'a, Option<Vec<SocketAddr>>> {
+        (**self).get(host)
+    }
+
+    fn set<'a>(
+        &'a self,
+        host: &'a str,
+        addrs: &'a [SocketAddr],
+        ttl: Duration,
+    ) -> SecondaryFuture<'a, ()> {
+        (**self).set(host, addrs, ttl)
+    }
+}
+
+/// An in-process [`SecondaryCache`], mostly useful for tests.
+///
+/// Entries are dropped once their TTL has passed.
+pub struct InMemorySecondaryCache {
+    entries: Mutex<HashMap<String, (Vec<SocketAddr>, Instant)>>,
+}
+
+impl InMemorySecondaryCache {
+    /// Creates an empty store.
+    pub fn new() -> InMemorySecondaryCache {
+        InMemorySecondaryCache {
+            entries: Mutex::new(HashMap::with_hasher(HASHER)),
+        }
+    }
+
+    /// Returns the number of hosts stored, expired ones included.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns `true` if no host is stored.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+impl Default for InMemorySecondaryCache {
+    fn default() -> Self {
+        InMemorySecondaryCache::new()
+    }
+}
+
+impl SecondaryCache for InMemorySecondaryCache {
+    fn get<'a>(&'a self, host: &'a str) -> SecondaryFuture<'a, Option<Vec<SocketAddr>>> {
+        let mut entries = self.entries.lock();
+        let addrs = match entries.get(host) {
+            Some((addrs, expires_at)) if Instant::now() < *expires_at => Some(addrs.clone()),
+            Some(_) => {
+                entries.remove(host);
+                None
+            }
+            None => None,
+        };
+        Box::pin(future::ready(addrs))
+    }
+
+    fn set<'a>(
+        &'a self,
+        host: &'a str,
+        addrs: &'a [SocketAddr],
+        ttl: Duration,
+    ) -> SecondaryFuture<'a, ()> {
+        self.entries
+            .lock()
+            .insert(host.to_owned(), (addrs.to_vec(), Instant::now() + ttl));
+        Box::pin(future::ready(()))
+    }
+}
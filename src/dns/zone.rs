@@ -0,0 +1,277 @@
+//! Parsing of zone files and `host ip[,ip]` lists for preloading the DNS cache
+
+use std::{
+    error::Error as StdError,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use super::canonical::{InvalidHostError, canonicalize_host};
+use crate::hash::{HASHER, HashMap};
+
+/// Record types that may appear in a zone file, but carry no addresses
+const OTHER_TYPES: &[&str] = &[
+    "CAA", "CNAME", "DNSKEY", "DS", "HINFO", "HTTPS", "MX", "NAPTR", "NS", "NSEC", "NSEC3", "PTR",
+    "RRSIG", "SOA", "SRV", "SSHFP", "SVCB", "TLSA", "TXT",
+];
+
+/// Record classes
+const CLASSES: &[&str] = &["IN", "CH", "CS", "HS"];
+
+/// An error found while parsing a zone.
+#[derive(Debug)]
+pub struct ParseError {
+    line: usize,
+    message: Box<str>,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<Box<str>>) -> ParseError {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the line the error was found on, starting at 1.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl StdError for ParseError {}
+
+/// Parses the A and AAAA records of `text`, grouped by host in order of appearance.
+///
+/// Accepts both the standard zone file format ([RFC 1035] section 5), with
+/// `$ORIGIN`, relative names and parentheses, and lines of the form
+/// `host ip[,ip]`. Hostnames are lowercased and have their trailing dot removed.
+/// Addresses carry port `0`.
+///
+/// [RFC 1035]: https://www.rfc-editor.org/rfc/rfc1035
+pub(crate) fn parse(text: &str) -> Result<Vec<(String, Vec<SocketAddr>)>, ParseError> {
+    let mut entries: Vec<(String, Vec<SocketAddr>)> = Vec::new();
+    let mut index = HashMap::with_hasher(HASHER);
+    let mut add = |host: String, ip: IpAddr| {
+        let i = *index.entry(host.clone()).or_insert_with(|| {
+            entries.push((host, Vec::new()));
+            entries.len() - 1
+        });
+        let addr = SocketAddr::new(ip, 0);
+        if !entries[i].1.contains(&addr) {
+            entries[i].1.push(addr);
+        }
+    };
+
+    let mut origin: Option<String> = None;
+    let mut owner: Option<String> = None;
+    let mut depth = 0usize;
+
+    for (i, raw) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw.split(';').next().unwrap_or_default();
+
+        // Continuation of a multi-line record, which never holds addresses
+        if depth > 0 {
+            depth = nest(depth, line).ok_or_else(|| ParseError::new(line_no, "unbalanced `)`"))?;
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(&first) = tokens.peek() else {
+            continue;
+        };
+
+        if first.starts_with('$') {
+            tokens.next();
+            match first.to_ascii_uppercase().as_str() {
+                "$ORIGIN" => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| ParseError::new(line_no, "`$ORIGIN` without a name"))?;
+                    origin = Some(canonicalize_host(name).map_err(|err| invalid(line_no, err))?);
+                }
+                "$TTL" => {}
+                _ => {
+                    return Err(ParseError::new(
+                        line_no,
+                        format!("unsupported directive `{first}`"),
+                    ));
+                }
+            }
+            continue;
+        }
+
+        depth = nest(0, line).ok_or_else(|| ParseError::new(line_no, "unbalanced `)`"))?;
+
+        // A line starting with whitespace continues the previous owner
+        if !line.starts_with(char::is_whitespace) {
+            let name = tokens.next().unwrap_or_default();
+
+            let rest: Vec<&str> = tokens.clone().collect();
+            if let [ips] = rest[..] {
+                if let Some(ips) = parse_ip_list(ips) {
+                    let host = canonicalize_host(name).map_err(|err| invalid(line_no, err))?;
+                    for ip in ips {
+                        add(host.clone(), ip);
+                    }
+                    owner = Some(host);
+                    continue;
+                }
+            }
+
+            owner = Some(absolute(name, origin.as_deref()).map_err(|err| invalid(line_no, err))?);
+        }
+
+        let host = owner
+            .clone()
+            .ok_or_else(|| ParseError::new(line_no, "record without an owner name"))?;
+
+        // TTL and class may appear in either order before the type
+        while let Some(&token) = tokens.peek() {
+            if token.bytes().all(|b| b.is_ascii_digit())
+                || CLASSES.contains(&token.to_ascii_uppercase().as_str())
+            {
+                tokens.next();
+            } else {
+                break;
+            }
+        }
+
+        let ty = tokens
+            .next()
+            .ok_or_else(|| ParseError::new(line_no, "missing record type"))?
+            .to_ascii_uppercase();
+        match ty.as_str() {
+            "A" => {
+                let ip = tokens
+                    .next()
+                    .and_then(|ip| ip.parse::<Ipv4Addr>().ok())
+                    .ok_or_else(|| ParseError::new(line_no, "invalid A record address"))?;
+                add(host, ip.into());
+            }
+            "AAAA" => {
+                let ip = tokens
+                    .next()
+                    .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+                    .ok_or_else(|| ParseError::new(line_no, "invalid AAAA record address"))?;
+                add(host, ip.into());
+            }
+            ty if OTHER_TYPES.contains(&ty) || ty.starts_with("TYPE") => {}
+            _ => {
+                return Err(ParseError::new(
+                    line_no,
+                    format!("unknown record type `{ty}`"),
+                ));
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(ParseError::new(text.lines().count(), "unclosed `(`"));
+    }
+
+    Ok(entries)
+}
+
+/// Tracks the parenthesis depth across `line`, `None` if it closes more than it opens.
+fn nest(depth: usize, line: &str) -> Option<usize> {
+    line.chars().try_fold(depth, |depth, c| match c {
+        '(' => Some(depth + 1),
+        ')' => depth.checked_sub(1),
+        _ => Some(depth),
+    })
+}
+
+/// Parses a comma separated list of IP addresses.
+fn parse_ip_list(list: &str) -> Option<Vec<IpAddr>> {
+    list.split(',').map(|ip| ip.parse().ok()).collect()
+}
+
+/// Resolves a zone owner name against the current origin.
+fn absolute(name: &str, origin: Option<&str>) -> Result<String, InvalidHostError> {
+    match (name, origin) {
+        ("@", Some(origin)) => Ok(origin.to_owned()),
+        (name, Some(origin)) if !name.ends_with('.') => {
+            canonicalize_host(&format!("{name}.{origin}"))
+        }
+        (name, _) => canonicalize_host(name),
+    }
+}
+
+fn invalid(line: usize, err: InvalidHostError) -> ParseError {
+    ParseError::new(line, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(ips: &[&str]) -> Vec<SocketAddr> {
+        ips.iter()
+            .map(|ip| SocketAddr::new(ip.parse().unwrap(), 0))
+            .collect()
+    }
+
+    #[test]
+    fn parses_zone_file() {
+        let zone = "\
+$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA ns.example.com. admin.example.com. (
+                2024010101 ; serial
+                7200 3600 1209600 3600 )
+        IN  NS  ns.example.com.
+@           A   192.0.2.1
+www  300 IN A   192.0.2.10
+            AAAA 2001:db8::10
+mail.other.org. IN A 198.51.100.1
+alias       CNAME www
+";
+        let entries = parse(zone).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("example.com".to_owned(), addrs(&["192.0.2.1"])),
+                (
+                    "www.example.com".to_owned(),
+                    addrs(&["192.0.2.10", "2001:db8::10"])
+                ),
+                ("mail.other.org".to_owned(), addrs(&["198.51.100.1"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_host_ip_list() {
+        let entries =
+            parse("Example.com 192.0.2.1,2001:db8::1\napi.example.com. 192.0.2.2\n").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "example.com".to_owned(),
+                    addrs(&["192.0.2.1", "2001:db8::1"])
+                ),
+                ("api.example.com".to_owned(), addrs(&["192.0.2.2"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_line_of_malformed_record() {
+        let err = parse("ok.example.com 192.0.2.1\n\nbad.example.com A 999.0.0.1\n").unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert_eq!(err.to_string(), "line 3: invalid A record address");
+
+        let err = parse("host.example.com 192.0.2\n").unwrap_err();
+        assert_eq!(err.line(), 1);
+    }
+}
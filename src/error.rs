@@ -1,8 +1,8 @@
-use std::{error::Error as StdError, fmt, io};
+use std::{error::Error as StdError, fmt, io, sync::Arc};
 
 use http::Uri;
 
-use crate::{StatusCode, core::ext::ReasonPhrase, util::Escape};
+use crate::{ConnectAttempt, StatusCode, core::ext::ReasonPhrase, util::Escape};
 
 /// A `Result` alias where the `Err` case is `wreq::Error`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -79,6 +79,18 @@ impl Error {
     pub(crate) fn uri_bad_scheme(uri: Uri) -> Error {
         Error::new(Kind::Builder, Some(BadScheme)).with_uri(uri)
     }
+
+    /// Copies `err` for another caller of the same request, keeping its kind and URI
+    /// and sharing its source.
+    pub(crate) fn shared(err: &Arc<Error>) -> Error {
+        Error {
+            inner: Box::new(Inner {
+                kind: err.inner.kind.clone(),
+                source: Some(Box::new(Shared(err.clone()))),
+                uri: err.inner.uri.clone(),
+            }),
+        }
+    }
 }
 
 impl Error {
@@ -171,6 +183,25 @@ impl Error {
         false
     }
 
+    /// Returns true if the request was canceled through its cancellation token.
+    ///
+    /// See [`RequestBuilder::cancel_token`](crate::RequestBuilder::cancel_token).
+    #[cfg(feature = "cancel-token")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cancel-token")))]
+    pub fn is_canceled(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<Canceled>() {
+                return true;
+            }
+
+            source = err.source();
+        }
+
+        false
+    }
+
     /// Returns true if the error is related to the request
     pub fn is_request(&self) -> bool {
         matches!(self.inner.kind, Kind::Request)
@@ -195,6 +226,25 @@ impl Error {
         false
     }
 
+    /// Returns the addresses tried before connecting failed, with the outcome of each.
+    ///
+    /// `None` if the error is not a connect error, or the attempts were not recorded.
+    pub fn connect_attempts(&self) -> Option<&[ConnectAttempt]> {
+        use crate::core::client::connect::ConnectError;
+
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<ConnectError>() {
+                return err.attempts();
+            }
+
+            source = err.source();
+        }
+
+        None
+    }
+
     /// Returns true if the error is related to a connection reset.
     pub fn is_connection_reset(&self) -> bool {
         let mut source = self.source();
@@ -221,6 +271,24 @@ impl Error {
         matches!(self.inner.kind, Kind::Tls)
     }
 
+    /// Returns true if the certificates presented by the server matched none of
+    /// the pins set with [`ClientBuilder::cert_pin`](crate::ClientBuilder::cert_pin).
+    ///
+    /// Such an error is also a connect error.
+    pub fn is_cert_pin_mismatch(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<CertPinMismatch>() {
+                return true;
+            }
+
+            source = err.source();
+        }
+
+        false
+    }
+
     /// Returns true if the error is related to decoding the response's body
     pub fn is_decode(&self) -> bool {
         matches!(self.inner.kind, Kind::Decode)
@@ -338,7 +406,7 @@ impl StdError for Error {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum Kind {
     Builder,
     Request,
@@ -352,6 +420,22 @@ pub(crate) enum Kind {
     WebSocket,
 }
 
+/// The source of an error handed to every caller of a shared request.
+#[derive(Debug)]
+struct Shared(Arc<Error>);
+
+impl fmt::Display for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("error of a shared request")
+    }
+}
+
+impl StdError for Shared {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct TimedOut;
 
@@ -363,6 +447,41 @@ impl fmt::Display for TimedOut {
 
 impl StdError for TimedOut {}
 
+#[cfg(feature = "cancel-token")]
+#[derive(Debug)]
+pub(crate) struct Canceled;
+
+#[cfg(feature = "cancel-token")]
+impl fmt::Display for Canceled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("operation canceled")
+    }
+}
+
+#[cfg(feature = "cancel-token")]
+impl StdError for Canceled {}
+
+#[derive(Debug)]
+pub(crate) struct CertPinMismatch {
+    host: String,
+}
+
+impl CertPinMismatch {
+    pub(crate) fn new(host: &str) -> CertPinMismatch {
+        CertPinMismatch {
+            host: host.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for CertPinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no certificate of {} matches its pins", self.host)
+    }
+}
+
+impl StdError for CertPinMismatch {}
+
 #[derive(Debug)]
 pub(crate) struct BadScheme;
 
@@ -4,6 +4,7 @@
 //! number of types used for interacting with `HeaderMap`. These types allow representing both
 //! HTTP/1 and HTTP/2 headers.
 
+pub use crate::util::{parse_content_range, parse_retry_after, range_header};
 pub use http::header::*;
 pub use name::OrigHeaderName;
 use sealed::Sealed;
@@ -123,6 +124,38 @@ impl OrigHeaderMap {
 }
 
 impl OrigHeaderMap {
+    /// Moves the given header names to the front of this map, in the given order.
+    ///
+    /// Names already present keep their original casing. Entries not listed in
+    /// `order` follow in their existing order.
+    pub(crate) fn reorder(&mut self, order: &[HeaderName]) {
+        let mut ordered = HeaderMap::with_capacity(self.0.len().max(order.len()));
+
+        for name in order {
+            if ordered.contains_key(name) {
+                continue;
+            }
+
+            let mut found = false;
+            for orig_name in self.0.get_all(name) {
+                ordered.append(name.clone(), orig_name.clone());
+                found = true;
+            }
+
+            if !found {
+                ordered.append(name.clone(), OrigHeaderName::Standard(name.clone()));
+            }
+        }
+
+        for (name, orig_name) in self.0.iter() {
+            if !order.contains(name) {
+                ordered.append(name.clone(), orig_name.clone());
+            }
+        }
+
+        self.0 = ordered;
+    }
+
     /// Sorts headers by this map, preserving original casing.
     /// Headers in the map come first, others follow.
     pub(crate) fn sort_headers(&self, headers: &mut HeaderMap) {
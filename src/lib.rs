@@ -233,6 +233,10 @@
 //! Custom Certificate Store verification supports Root CA certificates, peer certificates, and
 //! self-signed certificate SSL pinning.
 //!
+//! Root certificates of a private CA can also be trusted alongside the built-in ones with
+//! `ClientBuilder::add_root_certificate`, and a client certificate set with
+//! `ClientBuilder::identity` for mutual TLS.
+//!
 //! ## Optional Features
 //!
 //! The following are a list of [Cargo features][cargo-features] that can be
@@ -251,9 +255,11 @@
 //! - **ws**: Provides websocket support.
 //! - **hickory-dns**: Enables a hickory-dns async resolver instead of default threadpool using
 //!   `getaddrinfo`.
+//! - **dns-over-tls**: Allows the hickory-dns resolver to query name servers over TLS.
 //! - **webpki-roots** *(enabled by default)*: Use the webpki-roots crate for root certificates.
 //! - **system-proxy**: Enable system proxy support.
 //! - **tracing**: Enable tracing logging support.
+//! - **cancel-token**: Cancel requests with a `tokio_util` `CancellationToken`.
 //!
 //! [client]: ./struct.Client.html
 //! [response]: ./struct.Response.html
@@ -276,6 +282,7 @@ mod proxy;
 mod sync;
 mod util;
 
+pub mod cache;
 #[cfg(feature = "cookies")]
 pub mod cookie;
 pub mod dns;
@@ -288,19 +295,24 @@ pub use http::{Method, StatusCode, Uri, Version};
 #[cfg(unix)]
 use libc as _;
 
+pub use self::client::middleware;
 #[cfg(feature = "multipart")]
 pub use self::client::multipart;
+#[cfg(feature = "stream")]
+pub use self::client::sse;
 #[cfg(feature = "ws")]
 pub use self::client::ws;
 pub use self::{
     client::{
-        Body, Client, ClientBuilder, Emulation, EmulationBuilder, EmulationFactory, Request,
-        RequestBuilder, Response, Upgraded, http1, http2,
+        Body, Client, ClientBuilder, ConnectAttempt, ConnectOutcome, ConnectivityReport, Emulation,
+        EmulationBuilder, EmulationFactory, FieldDiff, FingerprintConfig, HostPoolStats, PoolEvent,
+        PoolStats, Request, RequestBuilder, Response, TlsFingerprint, Upgraded, Validators, http1,
+        http2,
     },
     error::{Error, Result},
     ext::{Extension, ResponseBuilderExt, ResponseExt},
     into_uri::IntoUri,
-    proxy::{NoProxy, Proxy},
+    proxy::{NoProxy, Proxy, ProxySelector},
 };
 
 fn _assert_impls() {
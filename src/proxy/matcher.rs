@@ -12,9 +12,11 @@
 //! An [`Intercept`] includes the destination for the proxy, and any parsed
 //! authentication to be used.
 
-use std::net::IpAddr;
+#[cfg(windows)]
+use std::ffi::OsStr;
 #[cfg(unix)]
-use std::{path::Path, sync::Arc};
+use std::path::Path;
+use std::{net::IpAddr, sync::Arc};
 
 use bytes::Bytes;
 use http::{
@@ -34,9 +36,12 @@ use crate::ext::UriExt;
 pub struct Matcher {
     http: Option<Intercept>,
     https: Option<Intercept>,
+    chain: Option<Arc<[Intercept]>>,
     no: NoProxy,
     #[cfg(unix)]
     unix: Option<Arc<Path>>,
+    #[cfg(windows)]
+    named_pipe: Option<Arc<OsStr>>,
 }
 
 /// A matched proxy,
@@ -59,8 +64,11 @@ pub struct Builder {
     pub(super) http: String,
     pub(super) https: String,
     pub(super) no: String,
+    pub(super) chain: Vec<Intercept>,
     #[cfg(unix)]
     pub(super) unix: Option<Arc<Path>>,
+    #[cfg(windows)]
+    pub(super) named_pipe: Option<Arc<OsStr>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -77,6 +85,9 @@ pub enum Auth {
 struct NoProxy {
     ips: IpMatcher,
     domains: DomainMatcher,
+    /// Rules only matching destinations on a given port, such as
+    /// `example.com:8080`.
+    ports: Vec<(u16, NoProxy)>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
@@ -123,11 +134,22 @@ impl Matcher {
         if let Some(unix) = &self.unix {
             return Some(Intercepted::Unix(unix.clone()));
         }
+        #[cfg(windows)]
+        if let Some(pipe) = &self.named_pipe {
+            return Some(Intercepted::NamedPipe(pipe.clone()));
+        }
 
         // TODO(perf): don't need to check `no` if below doesn't match...
-        if self.no.contains(dst.host()?) {
+        if self.no.matches(dst.host()?, dst.port_or_default()) {
             return None;
         }
+        if let Some(chain) = self
+            .chain
+            .as_ref()
+            .filter(|_| dst.is_http() || dst.is_https())
+        {
+            return Some(Intercepted::Chain(chain.clone()));
+        }
         if dst.is_http() {
             return self.http.clone().map(Intercepted::Proxy);
         }
@@ -143,6 +165,13 @@ impl Matcher {
 // ===== impl Intercept =====
 
 impl Intercept {
+    /// Creates an intercept for a proxy that is one hop of a chain.
+    pub(super) fn hop(uri: &Uri, extra: Extra) -> Option<Intercept> {
+        let mut intercept = parse_env_uri(&uri.to_string())?;
+        intercept.extra = extra;
+        Some(intercept)
+    }
+
     #[inline]
     pub(crate) fn uri(&self) -> &Uri {
         &self.uri
@@ -169,6 +198,11 @@ impl Intercept {
         None
     }
 
+    #[inline]
+    pub(crate) fn http2(&self) -> bool {
+        self.extra.http2
+    }
+
     #[inline]
     #[cfg(feature = "socks")]
     pub(crate) fn raw_auth(&self) -> Option<(Bytes, Bytes)> {
@@ -190,8 +224,11 @@ impl Builder {
             http: get_first_env(&["HTTP_PROXY", "http_proxy"]),
             https: get_first_env(&["HTTPS_PROXY", "https_proxy"]),
             no: get_first_env(&["NO_PROXY", "no_proxy"]),
+            chain: Vec::new(),
             #[cfg(unix)]
             unix: None,
+            #[cfg(windows)]
+            named_pipe: None,
         }
     }
 
@@ -261,6 +298,12 @@ impl Builder {
         self
     }
 
+    /// Set the chain of proxies to tunnel through for all destinations.
+    pub fn chain(mut self, hops: Vec<Intercept>) -> Self {
+        self.chain = hops;
+        self
+    }
+
     // / Set the unix socket target proxy for all destinations.
     #[cfg(unix)]
     pub fn unix<S>(mut self, val: S) -> Self
@@ -271,15 +314,25 @@ impl Builder {
         self
     }
 
+    /// Set the named pipe target proxy for all destinations.
+    #[cfg(windows)]
+    pub fn named_pipe(mut self, pipe: Arc<OsStr>) -> Self {
+        self.named_pipe = Some(pipe);
+        self
+    }
+
     /// Construct a [`Matcher`] using the configured values.
     pub(super) fn build(self, extra: Extra) -> Matcher {
         if self.is_cgi {
             return Matcher {
                 http: None,
                 https: None,
+                chain: None,
                 no: NoProxy::empty(),
                 #[cfg(unix)]
                 unix: None,
+                #[cfg(windows)]
+                named_pipe: None,
             };
         }
 
@@ -302,9 +355,12 @@ impl Builder {
         Matcher {
             http: http.or_else(|| all.clone()),
             https: https.or(all),
+            chain: (!self.chain.is_empty()).then(|| self.chain.into()),
             no: NoProxy::from_string(&self.no),
             #[cfg(unix)]
             unix: self.unix,
+            #[cfg(windows)]
+            named_pipe: self.named_pipe,
         }
     }
 }
@@ -394,6 +450,7 @@ impl NoProxy {
         NoProxy {
             ips: IpMatcher(Vec::new()),
             domains: DomainMatcher(Vec::new()),
+            ports: Vec::new(),
         }
     }
 
@@ -405,10 +462,12 @@ impl NoProxy {
     /// * Entries are expected to be comma-separated (whitespace between entries is ignored)
     /// * IP addresses (both IPv4 and IPv6) are allowed, as are optional subnet masks (by adding
     ///   /size, for example "`192.168.1.0/24`").
-    /// * An entry "`*`" matches all hostnames (this is the only wildcard allowed)
+    /// * An entry "`*`" matches all hostnames, and a leading "`*.`" is the same as a leading dot
     /// * Any other entry is considered a domain name (and may contain a leading dot, for example
     ///   `google.com` and `.google.com` are equivalent) and would match both that domain AND all
-    ///   subdomains.
+    ///   subdomains. Domains are matched case-insensitively, ignoring a trailing dot.
+    /// * An entry may end with a port, for example "`google.com:8080`" or "`[::1]:8080`", to
+    ///   only match destinations on that port.
     ///
     /// For example, if `"NO_PROXY=google.com, 192.168.1.0/24"` was set, all of the following would
     /// match (and therefore would bypass the proxy):
@@ -418,31 +477,57 @@ impl NoProxy {
     ///
     /// The URI `http://notgoogle.com/` would not match.
     pub fn from_string(no_proxy_list: &str) -> Self {
-        let mut ips = Vec::new();
-        let mut domains = Vec::new();
-        let parts = no_proxy_list.split(',').map(str::trim);
-        for part in parts {
-            match part.parse::<IpNet>() {
-                // If we can parse an IP net or address, then use it, otherwise, assume it is a
-                // domain
-                Ok(ip) => ips.push(Ip::Network(ip)),
-                Err(_) => match part.parse::<IpAddr>() {
-                    Ok(addr) => ips.push(Ip::Address(addr)),
-                    Err(_) => {
-                        if !part.trim().is_empty() {
-                            domains.push(part.to_owned())
+        let mut no_proxy = NoProxy::empty();
+        for part in no_proxy_list.split(',').map(str::trim) {
+            match split_port(part) {
+                (rule, Some(port)) => {
+                    let index = match no_proxy.ports.iter().position(|(p, _)| *p == port) {
+                        Some(index) => index,
+                        None => {
+                            no_proxy.ports.push((port, NoProxy::empty()));
+                            no_proxy.ports.len() - 1
                         }
-                    }
-                },
+                    };
+                    no_proxy.ports[index].1.push(rule);
+                }
+                (rule, None) => no_proxy.push(rule),
             }
         }
-        NoProxy {
-            ips: IpMatcher(ips),
-            domains: DomainMatcher(domains),
+        no_proxy
+    }
+
+    /// Adds an IP address, IP network or domain rule, without a port.
+    fn push(&mut self, rule: &str) {
+        match rule.parse::<IpNet>() {
+            // If we can parse an IP net or address, then use it, otherwise, assume it is a
+            // domain
+            Ok(ip) => self.ips.0.push(Ip::Network(ip)),
+            Err(_) => match rule.parse::<IpAddr>() {
+                Ok(addr) => self.ips.0.push(Ip::Address(addr)),
+                Err(_) => {
+                    let domain = rule.trim_end_matches('.').to_ascii_lowercase();
+                    let domain = match domain.strip_prefix("*.") {
+                        Some(parent) => format!(".{parent}"),
+                        None => domain,
+                    };
+                    if !domain.is_empty() {
+                        self.domains.0.push(domain)
+                    }
+                }
+            },
         }
     }
 
-    /// Return true if this matches the host (domain or IP).
+    /// Return true if this matches the host (domain or IP) on the given port.
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        self.contains(host)
+            || self
+                .ports
+                .iter()
+                .any(|(p, rules)| *p == port && rules.contains(host))
+    }
+
+    /// Return true if this matches the host (domain or IP), whatever its port.
     pub fn contains(&self, host: &str) -> bool {
         // According to RFC3986, raw IPv6 hosts will be wrapped in []. So we need to strip those off
         // the end in order to parse correctly
@@ -455,11 +540,35 @@ impl NoProxy {
         match host.parse::<IpAddr>() {
             // If we can parse an IP addr, then use it, otherwise, assume it is a domain
             Ok(ip) => self.ips.contains(ip),
-            Err(_) => self.domains.contains(host),
+            Err(_) => self
+                .domains
+                .contains(&host.trim_end_matches('.').to_ascii_lowercase()),
         }
     }
 }
 
+/// Splits the port off a `no_proxy` entry, such as `example.com:8080` or
+/// `[::1]:8080`.
+fn split_port(entry: &str) -> (&str, Option<u16>) {
+    // A bare IPv6 address or network is full of colons, but has no port
+    if entry.parse::<IpNet>().is_ok() || entry.parse::<IpAddr>().is_ok() {
+        return (entry, None);
+    }
+    if let Some((addr, rest)) = entry
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        return (addr, rest.strip_prefix(':').and_then(|p| p.parse().ok()));
+    }
+    match entry.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (entry, None),
+        },
+        None => (entry, None),
+    }
+}
+
 impl IpMatcher {
     fn contains(&self, addr: IpAddr) -> bool {
         for ip in &self.0 {
@@ -620,6 +729,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_no_proxy_ports_and_wildcards() {
+        let no_proxy = NoProxy::from_string(
+            "*.Internal.corp, api.test:8080, 10.0.0.1:3128, [::1]:8443, 192.168.0.0/16, \
+             fe80::/10, *:9999, example.org.",
+        );
+
+        let should_match = [
+            // a `*.` wildcard matches the domain and its subdomains, in any case
+            ("internal.corp", 443),
+            ("a.b.INTERNAL.corp", 80),
+            // a trailing dot of the host is ignored
+            ("svc.internal.corp.", 80),
+            // a trailing dot of the rule is ignored
+            ("example.org", 80),
+            ("www.example.org", 443),
+            // rules with a port only match that port
+            ("api.test", 8080),
+            ("v1.api.test", 8080),
+            ("10.0.0.1", 3128),
+            ("[::1]", 8443),
+            // networks match every port
+            ("192.168.4.2", 1),
+            ("[fe80::1]", 443),
+            // `*` with a port matches every host on that port
+            ("anything.at.all", 9999),
+        ];
+
+        for (host, port) in should_match {
+            assert!(
+                no_proxy.matches(host, port),
+                "should match {host:?} on port {port}"
+            );
+        }
+
+        let should_not_match = [
+            ("notinternal.corp", 443),
+            ("api.test", 443),
+            ("api.test", 80),
+            ("10.0.0.1", 80),
+            ("[::1]", 443),
+            ("192.169.0.1", 80),
+            ("[fe00::1]", 443),
+            ("anything.at.all", 9998),
+            ("example.org.evil", 80),
+        ];
+
+        for (host, port) in should_not_match {
+            assert!(
+                !no_proxy.matches(host, port),
+                "should not match {host:?} on port {port}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_proxy_entries_with_ports() {
+        assert_eq!(split_port("example.com:8080"), ("example.com", Some(8080)));
+        assert_eq!(split_port("example.com"), ("example.com", None));
+        assert_eq!(split_port("10.0.0.1:80"), ("10.0.0.1", Some(80)));
+        assert_eq!(split_port("[::1]:443"), ("::1", Some(443)));
+        assert_eq!(split_port("[::1]"), ("::1", None));
+        assert_eq!(split_port("::1"), ("::1", None));
+        assert_eq!(split_port("2001:db8::/32"), ("2001:db8::/32", None));
+        assert_eq!(split_port("example.com:http"), ("example.com:http", None));
+    }
+
     macro_rules! p {
         ($($n:ident = $v:expr,)*) => ({Builder {
             $($n: $v.into(),)*
@@ -630,9 +806,14 @@ mod tests {
     fn intercept(p: &Matcher, u: &str) -> Intercept {
         match p.intercept(&u.parse().unwrap()).unwrap() {
             Intercepted::Proxy(intercept) => intercept,
+            Intercepted::Chain(hops) => unreachable!("should not intercept a chain: {hops:?}"),
             Intercepted::Unix(path) => {
                 unreachable!("should not intercept unix socket: {path:?}")
             }
+            #[cfg(windows)]
+            Intercepted::NamedPipe(pipe) => {
+                unreachable!("should not intercept named pipe: {pipe:?}")
+            }
         }
     }
 
@@ -660,6 +841,27 @@ mod tests {
         assert_eq!("http://y.ep", intercept(&p, "http://example.com").uri());
     }
 
+    #[test]
+    fn test_chain_proxy() {
+        let hops = ["http://gate.way", "socks5://resi.dential"]
+            .iter()
+            .map(|uri| Intercept::hop(&uri.parse().unwrap(), Extra::default()).unwrap())
+            .collect();
+        let p = Matcher::builder()
+            .chain(hops)
+            .no("direct.tld")
+            .build(Extra::default());
+
+        let Some(Intercepted::Chain(hops)) = p.intercept(&"https://example.com".parse().unwrap())
+        else {
+            panic!("should intercept a chain");
+        };
+        assert_eq!(hops[0].uri(), "http://gate.way");
+        assert_eq!(hops[1].uri(), "socks5://resi.dential:1080");
+
+        assert!(p.intercept(&"http://direct.tld".parse().unwrap()).is_none());
+    }
+
     #[test]
     fn test_parse_no_scheme_defaults_to_http() {
         let p = p! {
@@ -745,4 +947,38 @@ mod tests {
         test_parse_socks("socks5://localhost:8887");
         test_parse_socks("socks5h://localhost:8887");
     }
+
+    #[test]
+    fn test_no_proxy_applies_to_every_proxy_scheme() {
+        for proxy in [
+            "http://pro.xy",
+            "https://pro.xy",
+            "socks5://pro.xy",
+            "socks5h://pro.xy",
+        ] {
+            let p = p! {
+                all = proxy,
+                no = "direct.tld, 10.0.0.0/8, local.tld:8080",
+            };
+
+            for dst in [
+                "http://direct.tld",
+                "https://sub.direct.tld",
+                "https://10.1.2.3",
+                "http://local.tld:8080",
+            ] {
+                assert!(
+                    p.intercept(&dst.parse().unwrap()).is_none(),
+                    "{dst} should bypass {proxy}"
+                );
+            }
+
+            for dst in ["https://other.tld", "http://local.tld", "http://11.0.0.1"] {
+                assert!(
+                    p.intercept(&dst.parse().unwrap()).is_some(),
+                    "{dst} should use {proxy}"
+                );
+            }
+        }
+    }
 }
@@ -2,13 +2,21 @@ mod matcher;
 #[cfg(unix)]
 mod uds;
 
-use std::hash::{Hash, Hasher};
 #[cfg(unix)]
-use std::{path::Path, sync::Arc};
+pub(crate) use self::uds::IntoUnixSocket;
+
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::path::Path;
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
 use http::{HeaderMap, Uri, header::HeaderValue};
 
-use crate::{IntoUri, ext::UriExt};
+use crate::{Error, IntoUri, ext::UriExt};
 
 // # Internals
 //
@@ -72,8 +80,11 @@ pub struct NoProxy {
 #[derive(Clone, PartialEq, Eq)]
 pub(crate) enum Intercepted {
     Proxy(matcher::Intercept),
+    Chain(Arc<[matcher::Intercept]>),
     #[cfg(unix)]
     Unix(Arc<Path>),
+    #[cfg(windows)]
+    NamedPipe(Arc<OsStr>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -88,14 +99,18 @@ enum Intercept {
     All(Uri),
     Http(Uri),
     Https(Uri),
+    Chain(Vec<matcher::Intercept>),
     #[cfg(unix)]
     Unix(Arc<Path>),
+    #[cfg(windows)]
+    NamedPipe(Arc<OsStr>),
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 struct Extra {
     auth: Option<HeaderValue>,
     misc: Option<HeaderMap>,
+    http2: bool,
 }
 
 /// Trait used for converting into a proxy scheme. This trait supports
@@ -113,6 +128,50 @@ impl<S: IntoUri> IntoProxy for S {
     }
 }
 
+/// Picks the proxy of each request, to rotate through a pool of proxies
+/// without building a new [`Client`](crate::Client), and losing its
+/// connections, for each one.
+///
+/// Set with [`ClientBuilder::proxy_selector`](crate::ClientBuilder::proxy_selector).
+/// Closures taking the request URI implement this trait.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use wreq::{Client, Proxy, Uri};
+///
+/// let proxies = [
+///     Proxy::all("http://proxy-1:8080").unwrap(),
+///     Proxy::all("http://proxy-2:8080").unwrap(),
+/// ];
+/// let next = AtomicUsize::new(0);
+///
+/// let client = Client::builder()
+///     .proxy_selector(move |_: &Uri| {
+///         let i = next.fetch_add(1, Ordering::Relaxed);
+///         Some(proxies[i % proxies.len()].clone())
+///     })
+///     .build()
+///     .unwrap();
+/// ```
+pub trait ProxySelector: Send + Sync + 'static {
+    /// Returns the proxy to send a request to `uri` through, or `None` to use
+    /// the proxies of the client.
+    fn select(&self, uri: &Uri) -> Option<Proxy>;
+}
+
+impl<F> ProxySelector for F
+where
+    F: Fn(&Uri) -> Option<Proxy> + Send + Sync + 'static,
+{
+    #[inline]
+    fn select(&self, uri: &Uri) -> Option<Proxy> {
+        self(uri)
+    }
+}
+
 // ===== impl Proxy =====
 
 impl Proxy {
@@ -192,11 +251,96 @@ impl Proxy {
         Ok(Proxy::new(Intercept::Unix(unix.unix_socket())))
     }
 
+    /// Proxy all traffic to the passed Windows named pipe, such as
+    /// `\\.\pipe\docker_engine`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # #[cfg(windows)]
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = wreq::Client::builder()
+    ///     .proxy(wreq::Proxy::named_pipe(r"\\.\pipe\docker_engine")?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    #[cfg(windows)]
+    pub fn named_pipe<P: AsRef<OsStr>>(pipe: P) -> crate::Result<Proxy> {
+        Ok(Proxy::new(Intercept::NamedPipe(Arc::from(pipe.as_ref()))))
+    }
+
+    /// Proxy all traffic through a chain of proxies.
+    ///
+    /// The client connects to the first proxy, and through it to each next
+    /// one, before tunneling to the destination through the last. Each proxy
+    /// is reached with an HTTP `CONNECT` or a SOCKS handshake, using its own
+    /// credentials and custom headers, so the TLS handshake with the origin
+    /// happens end to end through every hop.
+    ///
+    /// Only the first proxy of the chain can be an HTTPS proxy, and none can
+    /// be a Unix socket. Rules such as [`Proxy::http`] of the chained proxies
+    /// are ignored; use [`Proxy::no_proxy`] on the chain to exclude hosts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = wreq::Client::builder()
+    ///     .proxy(wreq::Proxy::chain([
+    ///         wreq::Proxy::all("http://gateway.corp:3128")?,
+    ///         wreq::Proxy::all("http://residential.prox:8080")?.basic_auth("user", "pass"),
+    ///     ])?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn chain<I>(proxies: I) -> crate::Result<Proxy>
+    where
+        I: IntoIterator<Item = Proxy>,
+    {
+        let mut hops = Vec::new();
+        for proxy in proxies {
+            match proxy.intercept {
+                Intercept::All(uri) | Intercept::Http(uri) | Intercept::Https(uri) => {
+                    let hop = matcher::Intercept::hop(&uri, proxy.extra)
+                        .ok_or_else(|| Error::builder("unsupported scheme of a chained proxy"))?;
+                    hops.push(hop);
+                }
+                Intercept::Chain(chain) => hops.extend(chain),
+                #[cfg(unix)]
+                Intercept::Unix(_) => {
+                    return Err(Error::builder("a Unix socket cannot be chained"));
+                }
+                #[cfg(windows)]
+                Intercept::NamedPipe(_) => {
+                    return Err(Error::builder("a named pipe cannot be chained"));
+                }
+            }
+        }
+
+        if hops.is_empty() {
+            return Err(Error::builder("a proxy chain needs at least one proxy"));
+        }
+        if hops.iter().skip(1).any(|hop| hop.uri().is_https()) {
+            return Err(Error::builder(
+                "only the first proxy of a chain can be an HTTPS proxy",
+            ));
+        }
+
+        Ok(Proxy::new(Intercept::Chain(hops)))
+    }
+
     fn new(intercept: Intercept) -> Proxy {
         Proxy {
             extra: Extra {
                 auth: None,
                 misc: None,
+                http2: false,
             },
             intercept,
             no_proxy: None,
@@ -224,11 +368,18 @@ impl Proxy {
                 uri.set_userinfo(username, Some(password));
                 self.extra.auth = Some(header);
             }
+            Intercept::Chain(_) => {
+                // Each proxy of a chain carries its own credentials.
+            }
             #[cfg(unix)]
             Intercept::Unix(_) => {
                 // For Unix sockets, we don't set the auth header.
                 // This is a no-op, but keeps the API consistent.
             }
+            #[cfg(windows)]
+            Intercept::NamedPipe(_) => {
+                // Neither for named pipes.
+            }
         }
 
         self
@@ -273,16 +424,52 @@ impl Proxy {
             Intercept::All(_) | Intercept::Http(_) | Intercept::Https(_) => {
                 self.extra.misc = Some(headers);
             }
+            Intercept::Chain(_) => {
+                // Each proxy of a chain carries its own custom headers.
+            }
             #[cfg(unix)]
             Intercept::Unix(_) => {
                 // For Unix sockets, we don't set custom headers.
                 // This is a no-op, but keeps the API consistent.
             }
+            #[cfg(windows)]
+            Intercept::NamedPipe(_) => {
+                // Neither for named pipes.
+            }
         }
 
         self
     }
 
+    /// Speak HTTP/2 to this proxy for the `CONNECT` tunnels to HTTPS
+    /// destinations.
+    ///
+    /// Every tunnel through the proxy is then a stream of a single HTTP/2
+    /// connection, instead of a connection of its own, which avoids opening
+    /// many connections to the proxy and the head-of-line blocking between
+    /// them. An HTTPS proxy must agree on `h2` with ALPN, while HTTP/2 is
+    /// assumed of an HTTP proxy. The connection is closed once no tunnel was
+    /// opened through it for the client's
+    /// [`pool_idle_timeout`](crate::ClientBuilder::pool_idle_timeout).
+    ///
+    /// Requests to HTTP destinations are still forwarded over HTTP/1.1, and
+    /// SOCKS proxies or the proxies of a [`Proxy::chain`] ignore this option.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let proxy = wreq::Proxy::https("https://example.prox:4545")?.http2();
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn http2(mut self) -> Proxy {
+        self.extra.http2 = true;
+        self
+    }
+
     /// Adds a `No Proxy` exclusion list to this Proxy
     ///
     /// # Example
@@ -353,6 +540,16 @@ impl Proxy {
                     .no(no_proxy)
                     .build(extra)
             }
+            Intercept::Chain(hops) => {
+                // The destination is always tunneled to, so the request itself
+                // never carries proxy auth or headers.
+                maybe_has_http_auth = false;
+                maybe_has_http_custom_headers = false;
+                matcher::Matcher::builder()
+                    .chain(hops)
+                    .no(no_proxy)
+                    .build(extra)
+            }
             #[cfg(unix)]
             Intercept::Unix(unix) => {
                 maybe_has_http_auth = false;
@@ -362,6 +559,15 @@ impl Proxy {
                     .no(no_proxy)
                     .build(extra)
             }
+            #[cfg(windows)]
+            Intercept::NamedPipe(pipe) => {
+                maybe_has_http_auth = false;
+                maybe_has_http_custom_headers = false;
+                matcher::Matcher::builder()
+                    .named_pipe(pipe)
+                    .no(no_proxy)
+                    .build(extra)
+            }
         };
 
         Matcher {
@@ -396,10 +602,14 @@ impl NoProxy {
     /// * Entries are expected to be comma-separated (whitespace between entries is ignored)
     /// * IP addresses (both IPv4 and IPv6) are allowed, as are optional subnet masks (by adding
     ///   /size, for example "`192.168.1.0/24`").
-    /// * An entry "`*`" matches all hostnames (this is the only wildcard allowed)
+    /// * An entry "`*`" matches all hostnames, and a leading "`*.`" is the same as a leading dot
     /// * Any other entry is considered a domain name (and may contain a leading dot, for example
     ///   `google.com` and `.google.com` are equivalent) and would match both that domain AND all
-    ///   subdomains.
+    ///   subdomains. Domains are matched case-insensitively, ignoring a trailing dot.
+    /// * An entry may end with a port, for example "`google.com:8080`" or "`[::1]:8080`", to
+    ///   only match destinations on that port.
+    ///
+    /// The same rules apply whatever the scheme of the proxy, HTTP, HTTPS or SOCKS.
     ///
     /// For example, if `"NO_PROXY=google.com, 192.168.1.0/24"` was set, all the following would
     /// match (and therefore would bypass the proxy):
@@ -522,6 +732,7 @@ impl Hash for Extra {
         } else {
             1u8.hash(state);
         }
+        self.http2.hash(state);
     }
 }
 
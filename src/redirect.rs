@@ -4,7 +4,12 @@
 //! maximum redirect chain of 10 hops. To customize this behavior, a
 //! `redirect::Policy` can be used with a `ClientBuilder`.
 
-use std::{error::Error as StdError, fmt, sync::Arc};
+use std::{
+    error::Error as StdError,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytes::Bytes;
 use http::{Extensions, HeaderMap, HeaderValue, StatusCode, Uri, uri::Authority};
@@ -17,7 +22,7 @@ use crate::{
     core::ext::RequestConfig,
     error::{BoxError, Error},
     ext::{Extension, UriExt},
-    header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, REFERER, WWW_AUTHENTICATE},
+    header::{AUTHORIZATION, COOKIE, HOST, PROXY_AUTHORIZATION, REFERER, WWW_AUTHENTICATE},
 };
 
 /// A type that controls the policy on how to handle the following of redirects.
@@ -306,6 +311,19 @@ impl fmt::Display for TooManyRedirects {
 
 impl StdError for TooManyRedirects {}
 
+#[derive(Debug)]
+struct RedirectTimeExceeded;
+
+// ===== impl RedirectTimeExceeded =====
+
+impl fmt::Display for RedirectTimeExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("redirect time budget exceeded")
+    }
+}
+
+impl StdError for RedirectTimeExceeded {}
+
 /// A redirect policy handler for HTTP clients.
 ///
 /// `FollowRedirectPolicy` manages how HTTP redirects are handled by the client,
@@ -324,6 +342,8 @@ pub(crate) struct FollowRedirectPolicy {
     https_only: bool,
     history: bool,
     history_entries: Option<Vec<History>>,
+    max_time: Option<Duration>,
+    started: Option<Instant>,
 }
 
 // ===== impl FollowRedirectPolicy =====
@@ -338,6 +358,8 @@ impl FollowRedirectPolicy {
             https_only: false,
             history: false,
             history_entries: None,
+            max_time: None,
+            started: None,
         }
     }
 
@@ -358,6 +380,13 @@ impl FollowRedirectPolicy {
         self.history = history;
         self
     }
+
+    /// Limits the time spent following a redirect chain, counted from the first
+    /// redirect response.
+    pub(crate) const fn with_max_time(mut self, max_time: Option<Duration>) -> Self {
+        self.max_time = max_time;
+        self
+    }
 }
 
 impl policy::Policy<Body, BoxError> for FollowRedirectPolicy {
@@ -392,6 +421,16 @@ impl policy::Policy<Body, BoxError> for FollowRedirectPolicy {
                     .into());
                 }
 
+                // Validate the time spent following the chain so far.
+                if let Some(max_time) = self.max_time {
+                    let started = *self.started.get_or_insert_with(Instant::now);
+                    if started.elapsed() > max_time {
+                        return Err(
+                            Error::redirect(RedirectTimeExceeded, previous_uri.clone()).into()
+                        );
+                    }
+                }
+
                 // Record redirect history.
                 if self.history {
                     self.history_entries
@@ -472,6 +511,8 @@ fn remove_sensitive_headers(headers: &mut HeaderMap, next: &Uri, previous: &[Uri
             || next.port() != previous.port()
             || next.scheme() != previous.scheme();
         if cross_host {
+            // A Host override names the original server, not the redirect target
+            headers.remove(HOST);
             headers.remove(AUTHORIZATION);
             headers.remove(COOKIE);
             headers.remove("cookie2");
@@ -543,12 +584,13 @@ mod tests {
 
     #[test]
     fn test_remove_sensitive_headers() {
-        use http::header::{ACCEPT, AUTHORIZATION, COOKIE, HeaderValue};
+        use http::header::{ACCEPT, AUTHORIZATION, COOKIE, HOST, HeaderValue};
 
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
         headers.insert(AUTHORIZATION, HeaderValue::from_static("let me in"));
         headers.insert(COOKIE, HeaderValue::from_static("foo=bar"));
+        headers.insert(HOST, HeaderValue::from_static("vhost.internal"));
 
         let next = Uri::try_from("http://initial-domain.com/path").unwrap();
         let mut prev = vec![Uri::try_from("http://initial-domain.com/new_path").unwrap()];
@@ -560,6 +602,7 @@ mod tests {
         prev.push(Uri::try_from("http://new-domain.com/path").unwrap());
         filtered_headers.remove(AUTHORIZATION);
         filtered_headers.remove(COOKIE);
+        filtered_headers.remove(HOST);
 
         remove_sensitive_headers(&mut headers, &next, &prev);
         assert_eq!(headers, filtered_headers);
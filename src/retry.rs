@@ -35,8 +35,20 @@
 //!
 //! Some common properties to check include if the request method is
 //! idempotent, or if the response status code indicates a transient error.
+//! [`Policy::classify_transient`] sets a classifier checking both.
+//!
+//! # Backoff
+//!
+//! Retries are sent right away, unless the policy has a [`Policy::backoff`], which
+//! waits longer before each retry and honors the `Retry-After` header of `429 Too
+//! Many Requests` and `503 Service Unavailable` responses.
+//!
+//! # Bodies
+//!
+//! A request is only retried if its body can be sent again, such as one built from
+//! bytes or a string. Requests with a streaming body are never retried.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use http::Request;
 
@@ -51,6 +63,14 @@ pub struct Policy {
     pub(crate) classifier: Classifier,
     pub(crate) max_retries_per_request: u32,
     pub(crate) scope: Scoped,
+    pub(crate) backoff: Option<Backoff>,
+}
+
+/// Bounds of the exponential backoff between retries.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Backoff {
+    pub(crate) base: Duration,
+    pub(crate) max: Duration,
 }
 
 impl Policy {
@@ -101,6 +121,7 @@ impl Policy {
             classifier: Classifier::Never,
             max_retries_per_request: 2,
             scope: Scoped::Dyn(Arc::new(ScopeFn(func))),
+            backoff: None,
         }
     }
 
@@ -154,6 +175,45 @@ impl Policy {
         self
     }
 
+    /// Wait before each retry, with exponential backoff and jitter.
+    ///
+    /// The wait before the `n`th retry is drawn at random between half and all of
+    /// `base * 2^(n - 1)`, capped at `max`, so that clients failing together do not
+    /// retry in lockstep. When a `429 Too Many Requests` or `503 Service
+    /// Unavailable` response has a `Retry-After` header, its wait is used instead,
+    /// also capped at `max`.
+    ///
+    /// Default is to retry right away.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use wreq::retry::Policy;
+    ///
+    /// let policy = Policy::default()
+    ///     .classify_transient()
+    ///     .max_retries_per_request(4)
+    ///     .backoff(Duration::from_millis(100), Duration::from_secs(10));
+    /// ```
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff = Some(Backoff { base, max });
+        self
+    }
+
+    /// Retry requests that failed for reasons likely to be transient.
+    ///
+    /// Requests that failed to connect, and protocol NACKs such as an HTTP/2
+    /// `REFUSED_STREAM`, are retried whatever their method, as the server never
+    /// processed them. Requests answered with `429 Too Many Requests` or a `5xx`
+    /// status are only retried if their method is idempotent: `GET`, `HEAD`,
+    /// `OPTIONS`, `TRACE`, `PUT` or `DELETE`.
+    pub fn classify_transient(mut self) -> Self {
+        self.classifier = Classifier::Transient;
+        self
+    }
+
     /// Provide a classifier to determine if a request should be retried.
     ///
     /// # Example
@@ -186,6 +246,7 @@ impl Default for Policy {
             classifier: Classifier::ProtocolNacks,
             max_retries_per_request: 2,
             scope: Scoped::Unscoped,
+            backoff: None,
         }
     }
 }
@@ -5,6 +5,7 @@ mod macros;
 mod cache;
 mod cert_compression;
 mod ext;
+mod pq;
 mod service;
 
 use std::{
@@ -19,14 +20,20 @@ use std::{
 use boring2::{
     error::ErrorStack,
     ex_data::Index,
-    ssl::{Ssl, SslConnector, SslMethod, SslOptions, SslSessionCacheMode},
+    ssl::{
+        ConnectConfiguration, HandshakeError, Ssl, SslConnector, SslMethod, SslOptions, SslRef,
+        SslSessionCacheMode,
+    },
 };
 use cache::{SessionCache, SessionKey};
 use http::Uri;
+use pq::PostQuantumFallback;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio_boring2::SslStream;
+use tokio_boring2::{SslStream, SslStreamBuilder};
 use tower::Service;
 
+#[cfg(feature = "hickory-dns")]
+use crate::dns::hickory::HickoryDnsResolver;
 use crate::{
     Error,
     core::client::{
@@ -36,8 +43,8 @@ use crate::{
     error::BoxError,
     sync::Mutex,
     tls::{
-        AlpnProtocol, AlpsProtocol, CertStore, Identity, KeyLog, TlsOptions, TlsVersion,
-        conn::ext::SslConnectorBuilderExt,
+        AlpnProtocol, AlpsProtocol, CertPins, CertStore, EchConfigs, EchMode, Identity, KeyLog,
+        TlsOptions, TlsVersion, conn::ext::SslConnectorBuilderExt,
     },
 };
 
@@ -162,6 +169,9 @@ struct Inner {
     ssl: SslConnector,
     cache: Option<Arc<Mutex<SessionCache<Identifier>>>>,
     config: HandshakeConfig,
+    cert_pins: Option<Arc<CertPins>>,
+    ech: Option<Arc<EchConfigs>>,
+    post_quantum_fallback: Option<PostQuantumFallback>,
 }
 
 /// A builder for creating a `TlsConnector`.
@@ -171,11 +181,17 @@ pub struct TlsConnectorBuilder {
     alpn_protocol: Option<AlpnProtocol>,
     max_version: Option<TlsVersion>,
     min_version: Option<TlsVersion>,
+    permute_extensions: Option<bool>,
     tls_sni: bool,
     verify_hostname: bool,
     identity: Option<Identity>,
     cert_store: Option<CertStore>,
     cert_verification: bool,
+    cert_pins: Option<Arc<CertPins>>,
+    ech: Option<EchMode>,
+    #[cfg(feature = "hickory-dns")]
+    ech_resolver: Option<HickoryDnsResolver>,
+    post_quantum: Option<bool>,
     keylog: Option<KeyLog>,
 }
 
@@ -215,15 +231,58 @@ where
 
 impl Inner {
     fn setup_ssl(&self, uri: Uri) -> Result<Ssl, BoxError> {
-        let cfg = self.ssl.configure()?;
         let host = uri.host().ok_or("URI missing host")?;
         let host = Self::normalize_host(host);
+        let cfg = self.connector(Some(host)).configure()?;
         let ssl = cfg.into_ssl(host)?;
         Ok(ssl)
     }
 
     fn setup_ssl2(&self, req: ConnectRequest) -> Result<Ssl, BoxError> {
-        let mut cfg = self.ssl.configure()?;
+        let uri = req.uri().clone();
+        let host = uri.host().ok_or("URI missing host")?;
+        let host = Self::normalize_host(host);
+
+        let mut cfg = self.configure(Some(host), req.extra().alpn_protocol())?;
+
+        if let Some(ref cache) = self.cache {
+            let key = SessionKey(req.identify());
+
+            // If the session cache is enabled, we try to retrieve the session
+            // associated with the key. If it exists, we set it in the SSL configuration.
+            if let Some(session) = cache.lock().get(&key) {
+                #[allow(unsafe_code)]
+                unsafe { cfg.set_session(&session) }?;
+
+                if self.config.no_ticket {
+                    cfg.set_options(SslOptions::NO_TICKET)?;
+                }
+            }
+
+            let idx = key_index()?;
+            cfg.set_ex_data(idx, key);
+        }
+
+        let ssl = cfg.into_ssl(host)?;
+        Ok(ssl)
+    }
+
+    /// Returns the connector for connections to `host`.
+    fn connector(&self, host: Option<&str>) -> &SslConnector {
+        match (self.post_quantum_fallback.as_ref(), host) {
+            (Some(fallback), Some(host)) => fallback.connector(host).unwrap_or(&self.ssl),
+            _ => &self.ssl,
+        }
+    }
+
+    /// Applies the handshake config for connections to `host`, with `alpn`
+    /// taking precedence over the configured ALPN protocols.
+    fn configure(
+        &self,
+        host: Option<&str>,
+        alpn: Option<AlpnProtocol>,
+    ) -> Result<ConnectConfiguration, BoxError> {
+        let mut cfg = self.connector(host).configure()?;
 
         // Use server name indication
         cfg.set_use_server_name_indication(self.config.tls_sni);
@@ -253,7 +312,7 @@ impl Inner {
         }
 
         // Set ALPN protocols
-        if let Some(alpn) = req.extra().alpn_protocol() {
+        if let Some(alpn) = alpn {
             // If ALPN is set in the request, it takes precedence over the connector configuration.
             cfg.set_alpn_protos(&alpn.encode())?;
         } else {
@@ -264,30 +323,88 @@ impl Inner {
             }
         }
 
-        let uri = req.uri().clone();
-        let host = uri.host().ok_or("URI missing host")?;
-        let host = Self::normalize_host(host);
-
-        if let Some(ref cache) = self.cache {
-            let key = SessionKey(req.identify());
-
-            // If the session cache is enabled, we try to retrieve the session
-            // associated with the key. If it exists, we set it in the SSL configuration.
-            if let Some(session) = cache.lock().get(&key) {
-                #[allow(unsafe_code)]
-                unsafe { cfg.set_session(&session) }?;
+        Ok(cfg)
+    }
+
+    /// Runs the handshake over `io`, offering the ECH configuration of the host
+    /// of `uri` when Encrypted Client Hello is enabled and there is one.
+    ///
+    /// Returns `None` when the handshake should be retried on a new connection:
+    /// the server rejected the ECH configuration and sent fresh ones, or failed
+    /// the handshake before sending its certificate while a post-quantum key
+    /// share was offered. The next handshake with the host offers the fresh ECH
+    /// configuration, or no post-quantum key share.
+    ///
+    /// The HTTPS record of the host is only looked up with `resolve_ech`, so that
+    /// connections through a proxy don't resolve the target locally.
+    async fn handshake<IO>(
+        &self,
+        mut ssl: Ssl,
+        uri: &Uri,
+        io: IO,
+        resolve_ech: bool,
+    ) -> Result<Option<SslStream<IO>>, BoxError>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin + Debug + Send + Sync + 'static,
+    {
+        let ech = match (self.ech.as_deref(), uri.host()) {
+            (Some(ech), Some(host)) => Some((ech, Self::normalize_host(host))),
+            _ => None,
+        };
+
+        let configs = match ech {
+            Some((ech, host)) => ech.get(host, resolve_ech).await,
+            None => None,
+        };
+        if let Some(ref configs) = configs {
+            ssl.set_ech_config_list(configs)?;
+        }
 
-                if self.config.no_ticket {
-                    cfg.set_options(SslOptions::NO_TICKET)?;
-                }
+        let err = match SslStreamBuilder::new(ssl, io).connect().await {
+            Ok(stream) => {
+                self.verify_pins(uri, stream.ssl())?;
+                return Ok(Some(stream));
             }
+            Err(err) => err,
+        };
+
+        let retry_configs = err
+            .ssl()
+            .and_then(SslRef::get_ech_retry_configs)
+            .map(<[u8]>::to_vec);
+        if let (Some((ech, host)), Some(retry_configs), Some(_)) = (ech, retry_configs, &configs) {
+            debug!("server of {} rejected ECH, retrying with its configs", host);
+            ech.set_retry_configs(host, &retry_configs);
+            return Ok(None);
+        }
 
-            let idx = key_index()?;
-            cfg.set_ex_data(idx, key);
+        // A server or middlebox choking on the larger ClientHello drops the
+        // connection before the server sends its certificate, without an alert
+        let oversized_hello = err
+            .ssl()
+            .is_some_and(|ssl| ssl.peer_certificate().is_none())
+            && pq::is_dropped_handshake(err.code(), err.as_io_error());
+        match (self.post_quantum_fallback.as_ref(), uri.host()) {
+            (Some(fallback), Some(host))
+                if oversized_hello && fallback.reject(Self::normalize_host(host)) =>
+            {
+                debug!(
+                    "handshake with {} failed, retrying without post-quantum key share",
+                    host
+                );
+                Ok(None)
+            }
+            _ => Err(err.into()),
         }
+    }
 
-        let ssl = cfg.into_ssl(host)?;
-        Ok(ssl)
+    /// Checks the certificates presented by the server of `uri` against the
+    /// pins of its host.
+    fn verify_pins(&self, uri: &Uri, ssl: &SslRef) -> Result<(), BoxError> {
+        match (self.cert_pins.as_deref(), uri.host()) {
+            (Some(pins), Some(host)) => pins.verify(Self::normalize_host(host), ssl),
+            _ => Ok(()),
+        }
     }
 
     /// If `host` is an IPv6 address, we must strip away the square brackets that surround
@@ -352,6 +469,43 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Sets the public keys pinned for each host.
+    #[inline(always)]
+    pub fn cert_pins(mut self, pins: CertPins) -> Self {
+        self.cert_pins = (!pins.is_empty()).then(|| Arc::new(pins));
+        self
+    }
+
+    /// Sets whether Encrypted Client Hello is used, taking precedence over the
+    /// ECH GREASE setting of the TLS options.
+    #[inline(always)]
+    pub fn ech(mut self, mode: Option<EchMode>) -> Self {
+        self.ech = mode;
+        self
+    }
+
+    /// Sets the resolver looking up the ECH configurations published in HTTPS
+    /// records. Without one, only the configurations sent by servers are used.
+    #[cfg(feature = "hickory-dns")]
+    #[inline(always)]
+    pub fn ech_resolver(mut self, resolver: Option<HickoryDnsResolver>) -> Self {
+        self.ech_resolver = resolver;
+        self
+    }
+
+    /// Sets whether a hybrid post-quantum key share is offered, adding
+    /// `X25519MLKEM768` to or removing post-quantum groups from the curves list
+    /// of the TLS options.
+    ///
+    /// When enabled, a server failing the handshake before sending its
+    /// certificate is retried without the post-quantum key share, which is
+    /// then left out for that host.
+    #[inline(always)]
+    pub fn post_quantum(mut self, enabled: Option<bool>) -> Self {
+        self.post_quantum = enabled;
+        self
+    }
+
     /// Sets the minimum TLS version to use.
     #[inline(always)]
     pub fn min_version<T>(mut self, version: T) -> Self
@@ -372,6 +526,14 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Sets whether extensions are permuted on every handshake, taking
+    /// precedence over the TLS options.
+    #[inline(always)]
+    pub fn permute_extensions(mut self, enabled: Option<bool>) -> Self {
+        self.permute_extensions = enabled;
+        self
+    }
+
     /// Sets the Server Name Indication (SNI) flag.
     #[inline(always)]
     pub fn tls_sni(mut self, enabled: bool) -> Self {
@@ -388,6 +550,26 @@ impl TlsConnectorBuilder {
 
     /// Build the `TlsConnector` with the provided configuration.
     pub fn build(&self, opts: &TlsOptions) -> crate::Result<TlsConnector> {
+        // Add or remove the post-quantum key share
+        let opts = match self.post_quantum {
+            Some(enabled) => {
+                let mut opts = opts.clone();
+                let curves = pq::curves_list(opts.curves_list.as_deref(), enabled);
+                opts.curves_list = Some(Cow::Owned(curves));
+                Cow::Owned(opts)
+            }
+            None => Cow::Borrowed(opts),
+        };
+
+        // The same connector without it, for servers rejecting the larger ClientHello
+        let post_quantum_fallback = match self.post_quantum {
+            Some(true) => {
+                let fallback = self.clone().post_quantum(Some(false)).build(&opts)?;
+                Some(PostQuantumFallback::new(fallback.inner.ssl))
+            }
+            _ => None,
+        };
+
         // Replace the default configuration with the provided one
         let max_tls_version = opts.max_tls_version.or(self.max_version);
         let min_tls_version = opts.min_tls_version.or(self.min_version);
@@ -458,7 +640,9 @@ impl TlsConnectorBuilder {
         set_option!(opts, grease_enabled, connector, set_grease_enabled);
 
         // Set TLS permute extensions options
-        set_option!(opts, permute_extensions, connector, set_permute_extensions);
+        if let Some(permute) = self.permute_extensions.or(opts.permute_extensions) {
+            connector.set_permute_extensions(permute);
+        }
 
         // Set TLS curves list
         set_option_ref_try!(opts, curves_list, connector, set_curves_list);
@@ -519,7 +703,11 @@ impl TlsConnectorBuilder {
             .alpn_protocols(alpn_protocols)
             .alps_protocols(opts.alps_protocols.clone())
             .alps_use_new_codepoint(opts.alps_use_new_codepoint)
-            .enable_ech_grease(opts.enable_ech_grease)
+            .enable_ech_grease(match self.ech {
+                Some(EchMode::Enable | EchMode::Grease) => true,
+                Some(EchMode::Disable) => false,
+                None => opts.enable_ech_grease,
+            })
             .tls_sni(self.tls_sni)
             .verify_hostname(self.verify_hostname)
             .random_aes_hw_override(opts.random_aes_hw_override)
@@ -542,11 +730,21 @@ impl TlsConnectorBuilder {
             cache
         });
 
+        let ech = (self.ech == Some(EchMode::Enable)).then(|| {
+            let ech = EchConfigs::new();
+            #[cfg(feature = "hickory-dns")]
+            let ech = ech.with_resolver(self.ech_resolver.clone());
+            Arc::new(ech)
+        });
+
         Ok(TlsConnector {
             inner: Inner {
                 ssl: connector.build(),
                 cache,
                 config,
+                cert_pins: self.cert_pins.clone(),
+                ech,
+                post_quantum_fallback,
             },
         })
     }
@@ -555,6 +753,20 @@ impl TlsConnectorBuilder {
 // ===== impl TlsConnector =====
 
 impl TlsConnector {
+    /// Returns the ClientHello records this connector would send to `host`.
+    ///
+    /// The handshake is driven against an in-memory stream, so nothing is sent
+    /// over the network. No session is resumed.
+    pub(crate) fn client_hello(&self, host: &str) -> Result<Vec<u8>, BoxError> {
+        let ssl = self.inner.configure(None, None)?.into_ssl(host)?;
+        match ssl.connect(ClientHelloCapture(Vec::new())) {
+            // The capture never answers, so the handshake stops after the ClientHello
+            Err(HandshakeError::WouldBlock(mid)) => Ok(mid.get_ref().0.clone()),
+            Err(err) => Err(err.to_string().into()),
+            Ok(_) => Err("handshake completed without a server".into()),
+        }
+    }
+
     /// Creates a new `TlsConnectorBuilder` with the given configuration.
     pub fn builder() -> TlsConnectorBuilder {
         const DEFAULT_SESSION_CACHE_CAPACITY: usize = 8;
@@ -565,9 +777,15 @@ impl TlsConnector {
             alpn_protocol: None,
             min_version: None,
             max_version: None,
+            permute_extensions: None,
             identity: None,
             cert_store: None,
             cert_verification: true,
+            cert_pins: None,
+            ech: None,
+            #[cfg(feature = "hickory-dns")]
+            ech_resolver: None,
+            post_quantum: None,
             tls_sni: true,
             verify_hostname: true,
             keylog: None,
@@ -575,6 +793,27 @@ impl TlsConnector {
     }
 }
 
+/// Records everything written to it and never yields data to read.
+#[derive(Debug)]
+struct ClientHelloCapture(Vec<u8>);
+
+impl io::Read for ClientHelloCapture {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+impl io::Write for ClientHelloCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// A stream which may be wrapped with TLS.
 pub enum MaybeHttpsStream<T> {
     /// A raw HTTP stream.
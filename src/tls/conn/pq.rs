@@ -0,0 +1,183 @@
+//! Hybrid post-quantum key shares.
+
+use std::{
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use boring2::ssl::{ErrorCode, SslConnector};
+use schnellru::ByLength;
+
+use crate::{
+    hash::{HASHER, LruMap},
+    sync::Mutex,
+};
+
+/// The hybrid post-quantum group sent by Chrome since version 131.
+const X25519_MLKEM768: &str = "X25519MLKEM768";
+
+/// The groups BoringSSL knows as hybrid post-quantum ones.
+const POST_QUANTUM_GROUPS: &[&str] = &[
+    X25519_MLKEM768,
+    "X25519Kyber768Draft00",
+    "X25519Kyber768",
+    "P256Kyber768Draft00",
+];
+
+/// The groups offered when a toggle is set without a curves list.
+const DEFAULT_GROUPS: &str = "X25519:P-256:P-384";
+
+/// How many hosts rejecting post-quantum key shares are remembered.
+const REJECTIONS_CAPACITY: u32 = 256;
+
+/// How long a host is sent no post-quantum key share after rejecting one, so
+/// that a fixed server or network path gets offered one again.
+const REJECTION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Returns `curves` with a hybrid post-quantum group preferred when `enabled`,
+/// or with every one of them removed otherwise.
+pub(super) fn curves_list(curves: Option<&str>, enabled: bool) -> String {
+    let curves = curves.unwrap_or(DEFAULT_GROUPS);
+    let groups = curves.split(':').filter(|group| !group.is_empty());
+
+    let groups: Vec<&str> = if !enabled {
+        groups
+            .filter(|group| !POST_QUANTUM_GROUPS.contains(group))
+            .collect()
+    } else if has_post_quantum(curves) {
+        groups.collect()
+    } else {
+        std::iter::once(X25519_MLKEM768).chain(groups).collect()
+    };
+    groups.join(":")
+}
+
+/// Returns whether `curves` offers a hybrid post-quantum group.
+fn has_post_quantum(curves: &str) -> bool {
+    curves
+        .split(':')
+        .any(|group| POST_QUANTUM_GROUPS.contains(&group))
+}
+
+/// Returns whether a failed handshake looks like one dropped for a ClientHello
+/// too large for the network path: the connection was reset or closed, rather
+/// than the server answering with a TLS alert.
+pub(super) fn is_dropped_handshake(code: Option<ErrorCode>, io: Option<&io::Error>) -> bool {
+    match io {
+        Some(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        None => code == Some(ErrorCode::SYSCALL) || code == Some(ErrorCode::ZERO_RETURN),
+    }
+}
+
+/// A connector offering no post-quantum key share, for the hosts whose servers
+/// failed a handshake offering one, likely because the larger ClientHello
+/// spans several packets that some middlebox does not reassemble.
+#[derive(Clone)]
+pub(super) struct PostQuantumFallback {
+    ssl: SslConnector,
+    rejected: Arc<Mutex<LruMap<String, Instant>>>,
+    ttl: Duration,
+}
+
+impl PostQuantumFallback {
+    pub(super) fn new(ssl: SslConnector) -> PostQuantumFallback {
+        PostQuantumFallback {
+            ssl,
+            rejected: Arc::new(Mutex::new(LruMap::with_hasher(
+                ByLength::new(REJECTIONS_CAPACITY),
+                HASHER,
+            ))),
+            ttl: REJECTION_TTL,
+        }
+    }
+
+    /// Returns the fallback connector if the server of `host` rejected a
+    /// post-quantum key share recently.
+    pub(super) fn connector(&self, host: &str) -> Option<&SslConnector> {
+        self.is_rejected(host).then_some(&self.ssl)
+    }
+
+    /// Returns whether the server of `host` had not rejected a post-quantum
+    /// key share recently, and remembers that it did now.
+    pub(super) fn reject(&self, host: &str) -> bool {
+        if self.is_rejected(host) {
+            return false;
+        }
+        self.rejected.lock().insert(host.to_owned(), Instant::now());
+        true
+    }
+
+    fn is_rejected(&self, host: &str) -> bool {
+        let mut rejected = self.rejected.lock();
+        match rejected.get(host) {
+            Some(at) if at.elapsed() < self.ttl => true,
+            Some(_) => {
+                rejected.remove(host);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boring2::ssl::SslMethod;
+
+    use super::*;
+
+    #[test]
+    fn post_quantum_group_is_added_or_removed() {
+        assert_eq!(curves_list(None, true), "X25519MLKEM768:X25519:P-256:P-384");
+        assert_eq!(
+            curves_list(Some("X25519MLKEM768:X25519:P-256"), true),
+            "X25519MLKEM768:X25519:P-256"
+        );
+        assert_eq!(
+            curves_list(Some("X25519Kyber768Draft00:X25519:P-256"), false),
+            "X25519:P-256"
+        );
+        assert_eq!(curves_list(None, false), "X25519:P-256:P-384");
+        assert!(!has_post_quantum("X25519:P-256"));
+    }
+
+    #[test]
+    fn only_dropped_handshakes_fall_back() {
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(is_dropped_handshake(Some(ErrorCode::SYSCALL), Some(&reset)));
+        assert!(is_dropped_handshake(Some(ErrorCode::SYSCALL), None));
+
+        // A TLS alert means the server read the ClientHello
+        assert!(!is_dropped_handshake(Some(ErrorCode::SSL), None));
+        let other = io::Error::other("denied");
+        assert!(!is_dropped_handshake(
+            Some(ErrorCode::SYSCALL),
+            Some(&other)
+        ));
+    }
+
+    #[test]
+    fn rejections_expire() {
+        let ssl = SslConnector::builder(SslMethod::tls()).unwrap().build();
+        let fallback = PostQuantumFallback::new(ssl);
+
+        assert!(fallback.connector("example.com").is_none());
+        assert!(fallback.reject("example.com"));
+        assert!(!fallback.reject("example.com"));
+        assert!(fallback.connector("example.com").is_some());
+
+        let expired = PostQuantumFallback {
+            ttl: Duration::ZERO,
+            ..fallback
+        };
+        assert!(expired.connector("example.com").is_none());
+        assert!(expired.reject("example.com"));
+    }
+}
@@ -7,8 +7,7 @@ use std::{
 
 use http::{Uri, uri::Scheme};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_boring2::SslStreamBuilder;
-use tower::Service;
+use tower::{Service, ServiceExt};
 
 use super::{EstablishedConn, HttpsConnector, MaybeHttpsStream};
 use crate::{
@@ -21,7 +20,7 @@ type BoxFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
 
 impl<T, S> Service<Uri> for HttpsConnector<S>
 where
-    S: Service<Uri, Response = T> + Send,
+    S: Service<Uri, Response = T> + Send + Clone + 'static,
     S::Error: Into<BoxError>,
     S::Future: Unpin + Send + 'static,
     T: AsyncRead + AsyncWrite + Connection + Unpin + Debug + Sync + Send + 'static,
@@ -37,6 +36,7 @@ where
 
     fn call(&mut self, uri: Uri) -> Self::Future {
         let connect = self.http.call(uri.clone());
+        let http = self.http.clone();
         let inner = self.inner.clone();
 
         let f = async move {
@@ -47,13 +47,20 @@ where
                 return Ok(MaybeHttpsStream::Http(conn));
             }
 
-            let ssl = inner.setup_ssl(uri)?;
-            let stream = SslStreamBuilder::new(ssl, conn)
-                .connect()
-                .await
-                .map(MaybeHttpsStream::Https)?;
+            let ssl = inner.setup_ssl(uri.clone())?;
+            if let Some(stream) = inner.handshake(ssl, &uri, conn, true).await? {
+                return Ok(MaybeHttpsStream::Https(stream));
+            }
+
+            // The server rejected the ClientHello, retry with the adjusted one
+            let conn = http.oneshot(uri.clone()).await.map_err(Into::into)?;
+            let ssl = inner.setup_ssl(uri.clone())?;
+            let stream = inner
+                .handshake(ssl, &uri, conn, true)
+                .await?
+                .ok_or("server rejected the TLS handshake again")?;
 
-            Ok(stream)
+            Ok(MaybeHttpsStream::Https(stream))
         };
 
         Box::pin(f)
@@ -62,7 +69,7 @@ where
 
 impl<T, S> Service<ConnectRequest> for HttpsConnector<S>
 where
-    S: Service<Uri, Response = T> + Send,
+    S: Service<Uri, Response = T> + Send + Clone + 'static,
     S::Error: Into<BoxError>,
     S::Future: Unpin + Send + 'static,
     T: AsyncRead + AsyncWrite + Connection + Unpin + Debug + Sync + Send + 'static,
@@ -79,6 +86,7 @@ where
     fn call(&mut self, req: ConnectRequest) -> Self::Future {
         let uri = req.uri().clone();
         let connect = self.http.call(uri.clone());
+        let http = self.http.clone();
         let inner = self.inner.clone();
 
         let f = async move {
@@ -89,13 +97,20 @@ where
                 return Ok(MaybeHttpsStream::Http(conn));
             }
 
+            let ssl = inner.setup_ssl2(req.clone())?;
+            if let Some(stream) = inner.handshake(ssl, &uri, conn, true).await? {
+                return Ok(MaybeHttpsStream::Https(stream));
+            }
+
+            // The server rejected the ClientHello, retry with the adjusted one
+            let conn = http.oneshot(uri.clone()).await.map_err(Into::into)?;
             let ssl = inner.setup_ssl2(req)?;
-            let stream = SslStreamBuilder::new(ssl, conn)
-                .connect()
-                .await
-                .map(MaybeHttpsStream::Https)?;
+            let stream = inner
+                .handshake(ssl, &uri, conn, true)
+                .await?
+                .ok_or("server rejected the TLS handshake again")?;
 
-            Ok(stream)
+            Ok(MaybeHttpsStream::Https(stream))
         };
 
         Box::pin(f)
@@ -127,12 +142,18 @@ where
                 return Ok(MaybeHttpsStream::Http(conn.io));
             }
 
+            let uri = conn.req.uri().clone();
             let ssl = inner.setup_ssl2(conn.req)?;
-            SslStreamBuilder::new(ssl, conn.io)
-                .connect()
-                .await
-                .map(MaybeHttpsStream::Https)
-                .map_err(Into::into)
+            // The connection was established by the caller, so a rejected
+            // ClientHello cannot be retried here; the next handshake with the
+            // host uses the adjusted one. The connection may go through a
+            // proxy resolving the host itself, so it isn't looked up here.
+            let stream = inner
+                .handshake(ssl, &uri, conn.io, false)
+                .await?
+                .ok_or("server rejected the TLS handshake, retry the request")?;
+
+            Ok(MaybeHttpsStream::Https(stream))
         };
 
         Box::pin(fut)
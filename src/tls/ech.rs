@@ -0,0 +1,110 @@
+//! Encrypted Client Hello (ECH).
+
+use schnellru::ByLength;
+
+#[cfg(feature = "hickory-dns")]
+use crate::dns::hickory::HickoryDnsResolver;
+use crate::{
+    hash::{HASHER, LruMap},
+    sync::Mutex,
+};
+
+/// How many hosts the ECH configurations sent by servers are kept for.
+const RETRY_CONFIGS_CAPACITY: u32 = 256;
+
+/// Whether the TLS ClientHello is sent with [Encrypted Client Hello][ech].
+///
+/// Browsers such as Chrome send an ECH GREASE extension on every connection,
+/// so a ClientHello without one stands out.
+///
+/// [ech]: https://datatracker.ietf.org/doc/draft-ietf-tls-esni/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchMode {
+    /// Encrypts the ClientHello with the ECH configuration a host publishes in
+    /// its DNS HTTPS record, and sends ECH GREASE to hosts without one.
+    ///
+    /// When a server rejects the configuration and sends fresh ones, the
+    /// handshake is retried once on a new connection with them.
+    ///
+    /// HTTPS records are looked up with the client's resolver when it is the
+    /// hickory resolver of the `hickory-dns` feature. With another resolver,
+    /// such as DNS-over-HTTPS or a custom one, and for requests through a
+    /// proxy, the host is not looked up, and only configurations sent by
+    /// servers are used.
+    Enable,
+    /// Sends an ECH GREASE extension, without encrypting the ClientHello.
+    Grease,
+    /// Sends no ECH extension.
+    Disable,
+}
+
+/// Where the ECH configurations of hosts come from.
+pub(crate) struct EchConfigs {
+    #[cfg(feature = "hickory-dns")]
+    resolver: Option<HickoryDnsResolver>,
+    retry_configs: Mutex<LruMap<String, Vec<u8>>>,
+}
+
+// ===== impl EchConfigs =====
+
+impl EchConfigs {
+    pub(crate) fn new() -> EchConfigs {
+        EchConfigs {
+            #[cfg(feature = "hickory-dns")]
+            resolver: None,
+            retry_configs: Mutex::new(LruMap::with_hasher(
+                ByLength::new(RETRY_CONFIGS_CAPACITY),
+                HASHER,
+            )),
+        }
+    }
+
+    /// Looks up the ECH configurations of hosts with `resolver`.
+    #[cfg(feature = "hickory-dns")]
+    pub(crate) fn with_resolver(mut self, resolver: Option<HickoryDnsResolver>) -> EchConfigs {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Returns the ECHConfigList to offer to `host`: the one its server last
+    /// sent, or with `lookup`, the one in its HTTPS record.
+    pub(crate) async fn get(&self, host: &str, lookup: bool) -> Option<Vec<u8>> {
+        let retry_configs = self.retry_configs.lock().get(host).cloned();
+        if retry_configs.is_some() || !lookup {
+            return retry_configs;
+        }
+
+        #[cfg(feature = "hickory-dns")]
+        if let Some(ref resolver) = self.resolver {
+            return resolver.lookup_ech_config(host).await;
+        }
+        None
+    }
+
+    /// Keeps the ECHConfigList sent by the server of `host` after rejecting
+    /// the one offered.
+    pub(crate) fn set_retry_configs(&self, host: &str, configs: &[u8]) {
+        self.retry_configs
+            .lock()
+            .insert(host.to_owned(), configs.to_vec());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn retry_configs_take_precedence() {
+        let ech = EchConfigs::new();
+        ech.set_retry_configs("localhost", &[0, 1, 2]);
+        assert_eq!(ech.get("localhost", true).await, Some(vec![0, 1, 2]));
+        assert_eq!(ech.get("localhost", false).await, Some(vec![0, 1, 2]));
+    }
+
+    #[tokio::test]
+    async fn no_lookup_without_resolver() {
+        let ech = EchConfigs::new();
+        assert_eq!(ech.get("localhost", true).await, None);
+    }
+}
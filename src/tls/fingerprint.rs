@@ -0,0 +1,339 @@
+//! JA3 and JA4 fingerprints of a TLS ClientHello
+//!
+//! See <https://github.com/salesforce/ja3> and <https://github.com/FoxIO-LLC/ja4>.
+
+use std::fmt::Write;
+
+use boring2::hash::{MessageDigest, hash};
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// The fields of a ClientHello that make up its fingerprint
+#[derive(Debug, Default)]
+pub(crate) struct ClientHello {
+    version: u16,
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    groups: Vec<u16>,
+    point_formats: Vec<u8>,
+    signature_algorithms: Vec<u16>,
+    supported_versions: Vec<u16>,
+    alpn: Option<Vec<u8>>,
+}
+
+/// Reads big-endian fields from a byte slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.bytes(3)
+            .map(|b| (usize::from(b[0]) << 16) | (usize::from(b[1]) << 8) | usize::from(b[2]))
+    }
+
+    /// Reads a vector prefixed with a one byte length
+    fn vec8(&mut self) -> Option<Reader<'a>> {
+        let len = self.u8()?;
+        self.bytes(len.into()).map(Reader)
+    }
+
+    /// Reads a vector prefixed with a two byte length
+    fn vec16(&mut self) -> Option<Reader<'a>> {
+        let len = self.u16()?;
+        self.bytes(len.into()).map(Reader)
+    }
+
+    fn u16s(mut self) -> Option<Vec<u16>> {
+        let mut values = Vec::with_capacity(self.0.len() / 2);
+        while !self.0.is_empty() {
+            values.push(self.u16()?);
+        }
+        Some(values)
+    }
+}
+
+impl ClientHello {
+    /// Parses a ClientHello from the TLS records a client sent.
+    pub(crate) fn parse(records: &[u8]) -> Option<ClientHello> {
+        // The handshake message may span several records
+        let mut records = Reader(records);
+        let mut handshake = Vec::new();
+        while !records.0.is_empty() {
+            let content_type = records.u8()?;
+            let _legacy_version = records.u16()?;
+            let fragment = records.vec16()?;
+            if content_type == CONTENT_TYPE_HANDSHAKE {
+                handshake.extend_from_slice(fragment.0);
+            }
+        }
+
+        let mut handshake = Reader(&handshake);
+        if handshake.u8()? != HANDSHAKE_CLIENT_HELLO {
+            return None;
+        }
+        let len = handshake.u24()?;
+        let mut body = Reader(handshake.bytes(len)?);
+
+        let mut hello = ClientHello {
+            version: body.u16()?,
+            ..Default::default()
+        };
+        let _random = body.bytes(32)?;
+        let _session_id = body.vec8()?;
+        hello.ciphers = body.vec16()?.u16s()?;
+        let _compression_methods = body.vec8()?;
+
+        // Extensions are optional
+        if body.0.is_empty() {
+            return Some(hello);
+        }
+
+        let mut extensions = body.vec16()?;
+        while !extensions.0.is_empty() {
+            let ty = extensions.u16()?;
+            let mut data = extensions.vec16()?;
+            hello.extensions.push(ty);
+
+            match ty {
+                EXT_SUPPORTED_GROUPS => hello.groups = data.vec16()?.u16s()?,
+                EXT_EC_POINT_FORMATS => hello.point_formats = data.vec8()?.0.to_vec(),
+                EXT_SIGNATURE_ALGORITHMS => hello.signature_algorithms = data.vec16()?.u16s()?,
+                EXT_SUPPORTED_VERSIONS => hello.supported_versions = data.vec8()?.u16s()?,
+                EXT_ALPN => hello.alpn = data.vec16()?.vec8().map(|proto| proto.0.to_vec()),
+                _ => {}
+            }
+        }
+
+        Some(hello)
+    }
+
+    /// Returns the JA3 string, before hashing.
+    pub(crate) fn ja3_string(&self) -> String {
+        fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+            values.map(|v| v.to_string()).collect::<Vec<_>>().join("-")
+        }
+
+        format!(
+            "{},{},{},{},{}",
+            self.version,
+            join(self.ciphers.iter().filter(|v| !is_grease(**v))),
+            join(self.extensions.iter().filter(|v| !is_grease(**v))),
+            join(self.groups.iter().filter(|v| !is_grease(**v))),
+            join(self.point_formats.iter()),
+        )
+    }
+
+    /// Returns the JA3 fingerprint: the MD5 hash of the JA3 string.
+    pub(crate) fn ja3(&self) -> String {
+        hex(&digest(MessageDigest::md5(), self.ja3_string().as_bytes()))
+    }
+
+    /// Returns the JA4 fingerprint.
+    pub(crate) fn ja4(&self) -> String {
+        let ciphers: Vec<u16> = self
+            .ciphers
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect();
+        let extensions: Vec<u16> = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect();
+
+        let version = self
+            .supported_versions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+        let sni = if extensions.contains(&EXT_SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+
+        let a = format!(
+            "t{version}{sni}{:02}{:02}{}",
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn_chars(self.alpn.as_deref()),
+        );
+
+        let mut sorted = ciphers;
+        sorted.sort_unstable();
+        let b = truncated_hash(&hex_list(&sorted));
+
+        let mut sorted: Vec<u16> = extensions
+            .into_iter()
+            .filter(|v| *v != EXT_SERVER_NAME && *v != EXT_ALPN)
+            .collect();
+        sorted.sort_unstable();
+        let mut c = hex_list(&sorted);
+        if !self.signature_algorithms.is_empty() {
+            c.push('_');
+            c.push_str(&hex_list(&self.signature_algorithms));
+        }
+        let c = truncated_hash(&c);
+
+        format!("{a}_{b}_{c}")
+    }
+}
+
+/// GREASE values ([RFC 8701]) are random and excluded from fingerprints.
+///
+/// [RFC 8701]: https://www.rfc-editor.org/rfc/rfc8701
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// First and last character of the first ALPN value, as used by JA4.
+fn alpn_chars(alpn: Option<&[u8]>) -> String {
+    let Some(&[first, .., last] | &[first @ last]) = alpn else {
+        return "00".to_owned();
+    };
+
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        format!("{}{}", first as char, last as char)
+    } else {
+        let hex = hex(&[first, last]);
+        format!("{}{}", &hex[..1], &hex[3..])
+    }
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The first 12 hex characters of the SHA-256 of `input`, or zeros if it is empty.
+fn truncated_hash(input: &str) -> String {
+    if input.is_empty() {
+        return "000000000000".to_owned();
+    }
+    let mut hash = hex(&digest(MessageDigest::sha256(), input.as_bytes()));
+    hash.truncate(12);
+    hash
+}
+
+fn digest(md: MessageDigest, data: &[u8]) -> Vec<u8> {
+    // Hashing an in-memory buffer cannot fail
+    hash(md, data).map(|d| d.to_vec()).unwrap_or_default()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ClientHello offering two ciphers plus GREASE, SNI, supported groups,
+    /// point formats, signature algorithms, ALPN `h2` and TLS 1.3.
+    fn client_hello() -> Vec<u8> {
+        let mut extensions = Vec::new();
+        let mut ext = |ty: u16, data: &[u8]| {
+            extensions.extend_from_slice(&ty.to_be_bytes());
+            extensions.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            extensions.extend_from_slice(data);
+        };
+        ext(0x0a0a, &[]);
+        ext(0x0000, b"\x00\x0e\x00\x00\x0bexample.com");
+        ext(0x000a, &[0x00, 0x06, 0x2a, 0x2a, 0x00, 0x1d, 0x00, 0x17]);
+        ext(0x000b, &[0x01, 0x00]);
+        ext(0x000d, &[0x00, 0x04, 0x04, 0x03, 0x08, 0x04]);
+        ext(0x0010, b"\x00\x03\x02h2");
+        ext(0x002b, &[0x04, 0x03, 0x04, 0x03, 0x03]);
+
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x00, 0x06, 0x1a, 0x1a, 0x13, 0x01, 0xc0, 0x2b]);
+        body.extend_from_slice(&[0x01, 0x00]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&body);
+
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn ja3_of_known_client_hello() {
+        let hello = ClientHello::parse(&client_hello()).unwrap();
+
+        assert_eq!(
+            hello.ja3_string(),
+            "771,4865-49195,0-10-11-13-16-43,29-23,0"
+        );
+        assert_eq!(hello.ja3(), "87991a9b84cb5b4bc5f84c5ecad46032");
+    }
+
+    #[test]
+    fn ja4_of_known_client_hello() {
+        let hello = ClientHello::parse(&client_hello()).unwrap();
+
+        assert_eq!(hello.ja4(), "t13d0206h2_777cda164f4b_fb71836bce29");
+    }
+
+    #[test]
+    fn grease_values() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[test]
+    fn rejects_truncated_client_hello() {
+        let hello = client_hello();
+        assert!(ClientHello::parse(&hello[..hello.len() - 1]).is_none());
+    }
+}
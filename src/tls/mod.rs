@@ -5,30 +5,67 @@
 //! - Various parts of TLS can also be configured or even disabled on the `ClientBuilder`.
 
 pub(crate) mod conn;
+mod ech;
+pub(crate) mod fingerprint;
 mod keylog;
 mod options;
+mod pin;
 mod x509;
 
 pub use boring2::ssl::{CertificateCompressionAlgorithm, ExtensionType};
 
+pub(crate) use self::{ech::EchConfigs, pin::CertPins};
 pub use self::{
+    ech::EchMode,
     keylog::KeyLog,
     options::{TlsOptions, TlsOptionsBuilder},
+    pin::Sha256Pin,
     x509::{CertStore, CertStoreBuilder, Certificate, Identity},
 };
 
 /// Http extension carrying extra TLS layer information.
 /// Made available to clients on responses when `tls_info` is set.
+///
+/// Unlike the configured [`TlsOptions`], which describe what the client offers,
+/// this reports what was actually negotiated with the server.
 #[derive(Debug, Clone)]
 pub struct TlsInfo {
     pub(crate) peer_certificate: Option<Vec<u8>>,
+    pub(crate) version: Option<TlsVersion>,
+    pub(crate) cipher_suite: Option<&'static str>,
+    pub(crate) alpn_protocol: Option<Vec<u8>>,
 }
 
 impl TlsInfo {
+    /// Collects the negotiated parameters of an established connection.
+    pub(crate) fn from_ssl(ssl: &ssl::SslRef) -> TlsInfo {
+        TlsInfo {
+            peer_certificate: ssl.peer_certificate().and_then(|c| c.to_der().ok()),
+            version: ssl.version2().map(TlsVersion),
+            cipher_suite: ssl.current_cipher().map(|cipher| cipher.name()),
+            alpn_protocol: ssl.selected_alpn_protocol().map(<[u8]>::to_vec),
+        }
+    }
+
     /// Get the DER encoded leaf certificate of the peer.
     pub fn peer_certificate(&self) -> Option<&[u8]> {
         self.peer_certificate.as_deref()
     }
+
+    /// Get the negotiated TLS protocol version.
+    pub fn version(&self) -> Option<TlsVersion> {
+        self.version
+    }
+
+    /// Get the name of the negotiated cipher suite, e.g. `TLS_AES_128_GCM_SHA256`.
+    pub fn cipher_suite(&self) -> Option<&str> {
+        self.cipher_suite
+    }
+
+    /// Get the protocol selected through ALPN, e.g. `b"h2"`, if any.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
 }
 
 use boring2::ssl;
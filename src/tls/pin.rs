@@ -0,0 +1,184 @@
+//! Certificate pinning by the hash of the public key.
+
+use std::{fmt, str::FromStr};
+
+use boring2::{error::ErrorStack, ssl::SslRef, x509::X509Ref};
+
+use super::Certificate;
+use crate::{
+    Error,
+    error::{BoxError, CertPinMismatch},
+    hash::HashMap,
+};
+
+/// The SHA-256 hash of the SubjectPublicKeyInfo of a certificate, to pin the
+/// certificates a host may present with
+/// [`ClientBuilder::cert_pin`](crate::ClientBuilder::cert_pin).
+///
+/// Pinning the public key rather than the certificate keeps the pin valid when
+/// the certificate is renewed with the same key.
+///
+/// # Example
+///
+/// ```
+/// use wreq::tls::Sha256Pin;
+///
+/// let pin: Sha256Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+///     .parse()
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sha256Pin([u8; 32]);
+
+/// The pins configured for each host.
+#[derive(Clone, Default)]
+pub(crate) struct CertPins {
+    hosts: HashMap<String, Vec<Sha256Pin>>,
+}
+
+// ===== impl Sha256Pin =====
+
+impl Sha256Pin {
+    /// Creates a pin from the raw SHA-256 hash of a SubjectPublicKeyInfo.
+    #[inline]
+    pub const fn from_bytes(hash: [u8; 32]) -> Sha256Pin {
+        Sha256Pin(hash)
+    }
+
+    /// Parses a base64 encoded pin, with or without the `sha256/` prefix, as
+    /// printed by `openssl x509 -pubkey | openssl pkey -pubin -outform der |
+    /// openssl dgst -sha256 -binary | base64`.
+    pub fn from_base64(pin: &str) -> crate::Result<Sha256Pin> {
+        let encoded = pin.strip_prefix("sha256/").unwrap_or(pin);
+        let hash = boring2::base64::decode_block(encoded).map_err(Error::builder)?;
+        <[u8; 32]>::try_from(hash)
+            .map(Sha256Pin)
+            .map_err(|_| Error::builder("a SHA-256 pin must be 32 bytes long"))
+    }
+
+    /// Computes the pin of the public key of `cert`.
+    pub fn from_certificate(cert: &Certificate) -> crate::Result<Sha256Pin> {
+        cert.spki_sha256().map(Sha256Pin)
+    }
+
+    /// Returns the raw SHA-256 hash.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for Sha256Pin {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(pin: &str) -> crate::Result<Sha256Pin> {
+        Sha256Pin::from_base64(pin)
+    }
+}
+
+impl fmt::Display for Sha256Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256/{}", boring2::base64::encode_block(&self.0))
+    }
+}
+
+impl fmt::Debug for Sha256Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Returns the SHA-256 hash of the SubjectPublicKeyInfo of `cert`.
+pub(crate) fn spki_sha256(cert: &X509Ref) -> Result<[u8; 32], ErrorStack> {
+    let spki = cert.public_key()?.public_key_to_der()?;
+    Ok(boring2::sha::sha256(&spki))
+}
+
+// ===== impl CertPins =====
+
+impl CertPins {
+    /// Adds `pins` to the pins of `host`, which may start with `*.` to match
+    /// any single label.
+    pub(crate) fn add(&mut self, host: &str, pins: &[Sha256Pin]) {
+        self.hosts
+            .entry(host.trim_end_matches('.').to_ascii_lowercase())
+            .or_default()
+            .extend_from_slice(pins);
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Returns the pins of `host`, or of the wildcard pattern matching it.
+    fn get(&self, host: &str) -> Option<&[Sha256Pin]> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if let Some(pins) = self.hosts.get(&host) {
+            return Some(pins);
+        }
+        let (_, parent) = host.split_once('.')?;
+        self.hosts.get(&format!("*.{parent}")).map(Vec::as_slice)
+    }
+
+    /// Checks that a certificate presented by `host` over `ssl` has a pinned
+    /// public key, if any is pinned for `host`.
+    ///
+    /// The leaf is checked first, then each intermediate that signed the
+    /// certificate before it, so a certificate merely appended to the chain by
+    /// the server cannot satisfy a pin.
+    pub(crate) fn verify(&self, host: &str, ssl: &SslRef) -> Result<(), BoxError> {
+        let Some(pins) = self.get(host) else {
+            return Ok(());
+        };
+
+        let mut issued: Option<&X509Ref> = None;
+        for cert in ssl.peer_cert_chain().into_iter().flatten() {
+            let Ok(key) = cert.public_key() else {
+                break;
+            };
+            if issued.is_some_and(|issued| !issued.verify(&key).unwrap_or(false)) {
+                break;
+            }
+            if spki_sha256(cert).is_ok_and(|hash| pins.contains(&Sha256Pin(hash))) {
+                return Ok(());
+            }
+            issued = Some(cert);
+        }
+
+        Err(CertPinMismatch::new(host).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_parse_with_or_without_prefix() {
+        let prefixed: Sha256Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+            .parse()
+            .unwrap();
+        let bare = Sha256Pin::from_base64("47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=").unwrap();
+        assert_eq!(prefixed, bare);
+        assert_eq!(
+            prefixed.to_string(),
+            "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+        assert!(Sha256Pin::from_base64("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn pins_match_exact_and_wildcard_hosts() {
+        let pin = Sha256Pin::from_bytes([1; 32]);
+        let mut pins = CertPins::default();
+        pins.add("Example.com", &[pin]);
+        pins.add("*.example.org", &[pin]);
+
+        assert_eq!(pins.get("example.com."), Some(&[pin][..]));
+        assert_eq!(pins.get("api.example.org"), Some(&[pin][..]));
+        assert_eq!(pins.get("example.org"), None);
+        assert_eq!(pins.get("a.b.example.org"), None);
+    }
+}
@@ -88,6 +88,47 @@ impl Identity {
         Ok(Identity { pkey, cert, chain })
     }
 
+    /// Parses a PEM encoded private key and certificate chain, with the leaf
+    /// certificate first, such as `cat key.pem cert.pem chain.pem`.
+    ///
+    /// Unlike [`Identity::from_pkcs8_pem`], the key and certificates are read
+    /// from the same buffer, and the key may also be in the traditional RSA or
+    /// EC format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs;
+    /// # fn pem() -> Result<(), Box<dyn std::error::Error>> {
+    /// let buf = fs::read("identity.pem")?;
+    /// let identity = wreq::tls::Identity::from_pem(&buf)?;
+    /// # drop(identity);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pem(buf: &[u8]) -> crate::Result<Identity> {
+        let pkey = PKey::private_key_from_pem(buf).map_err(Error::tls)?;
+        let mut cert_chain = X509::stack_from_pem(buf).map_err(Error::tls)?.into_iter();
+        let cert = cert_chain.next().ok_or_else(|| {
+            Error::builder("at least one certificate must be provided to create an identity")
+        })?;
+        let chain = cert_chain.collect();
+        Ok(Identity { pkey, cert, chain })
+    }
+
+    /// Creates an identity from a DER encoded certificate and private key.
+    ///
+    /// The key may be PKCS #8, or in the traditional RSA or EC format.
+    pub fn from_der(cert: &[u8], key: &[u8]) -> crate::Result<Identity> {
+        let pkey = PKey::private_key_from_der(key).map_err(Error::tls)?;
+        let cert = X509::from_der(cert).map_err(Error::tls)?;
+        Ok(Identity {
+            pkey,
+            cert,
+            chain: Vec::new(),
+        })
+    }
+
     pub(crate) fn add_to_tls(
         &self,
         connector: &mut boring2::ssl::SslConnectorBuilder,
@@ -119,4 +160,14 @@ mod test {
     fn identity_from_pkcs8_pem_invalid() {
         Identity::from_pkcs8_pem(b"not pem", b"not key").unwrap_err();
     }
+
+    #[test]
+    fn identity_from_pem_invalid() {
+        Identity::from_pem(b"not pem").unwrap_err();
+    }
+
+    #[test]
+    fn identity_from_der_invalid() {
+        Identity::from_der(b"not der", b"not key").unwrap_err();
+    }
 }
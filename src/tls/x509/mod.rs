@@ -65,4 +65,10 @@ impl Certificate {
         let certs = X509::stack_from_pem(cert.as_ref()).map_err(Error::tls)?;
         Ok(certs.into_iter().map(Self).collect())
     }
+
+    /// Returns the SHA-256 hash of the certificate's SubjectPublicKeyInfo.
+    #[inline]
+    pub(crate) fn spki_sha256(&self) -> crate::Result<[u8; 32]> {
+        super::pin::spki_sha256(&self.0).map_err(Error::tls)
+    }
 }
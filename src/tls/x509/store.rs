@@ -73,6 +73,21 @@ impl CertStoreBuilder {
         self
     }
 
+    /// Adds the root certificates trusted by default: the Mozilla roots with
+    /// the `webpki-roots` feature, or those at the default locations otherwise,
+    /// see [`set_default_paths`](CertStoreBuilder::set_default_paths).
+    pub fn add_built_in_roots(self) -> Self {
+        #[cfg(feature = "webpki-roots")]
+        {
+            self.add_der_certs(webpki_root_certs::TLS_SERVER_ROOT_CERTS)
+        }
+
+        #[cfg(not(feature = "webpki-roots"))]
+        {
+            self.set_default_paths()
+        }
+    }
+
     /// Load certificates from their default locations.
     ///
     /// These locations are read from the `SSL_CERT_FILE` and `SSL_CERT_DIR`
@@ -209,7 +224,7 @@ impl Default for CertStore {
         #[cfg(feature = "webpki-roots")]
         static LOAD_CERTS: std::sync::LazyLock<CertStore> = std::sync::LazyLock::new(|| {
             CertStore::builder()
-                .add_der_certs(webpki_root_certs::TLS_SERVER_ROOT_CERTS)
+                .add_built_in_roots()
                 .build()
                 .expect("failed to load default cert store")
         });
@@ -217,7 +232,7 @@ impl Default for CertStore {
         #[cfg(not(feature = "webpki-roots"))]
         {
             CertStore::builder()
-                .set_default_paths()
+                .add_built_in_roots()
                 .build()
                 .expect("failed to load default cert store")
         }
@@ -1,10 +1,34 @@
-use std::{fmt, fmt::Write};
+use std::{
+    fmt,
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
 
 use bytes::Bytes;
 
 use crate::header::{Entry, HeaderMap, HeaderValue, OccupiedEntry};
 
+/// Encodes bytes as base64 for use in an `Authorization` header value.
+pub(crate) type Base64Encode = fn(&[u8]) -> String;
+
+/// The default base64 engine, backed by the TLS backend.
+pub(crate) fn base64_encode(input: &[u8]) -> String {
+    boring2::base64::encode_block(input)
+}
+
 pub(crate) fn basic_auth<U, P>(username: U, password: Option<P>) -> HeaderValue
+where
+    U: fmt::Display,
+    P: fmt::Display,
+{
+    basic_auth_with(base64_encode, username, password)
+}
+
+pub(crate) fn basic_auth_with<U, P>(
+    encode: Base64Encode,
+    username: U,
+    password: Option<P>,
+) -> HeaderValue
 where
     U: fmt::Display,
     P: fmt::Display,
@@ -17,7 +41,7 @@ where
             let _ = write!(buf_str, "{password}");
         }
 
-        let encoded = boring2::base64::encode_block(buf_str.as_bytes());
+        let encoded = encode(buf_str.as_bytes());
         buf.extend(encoded.into_bytes());
         buf
     };
@@ -28,6 +52,169 @@ where
     header
 }
 
+/// Builds a `Range` header value requesting bytes `start..=end`.
+///
+/// An `end` of `None` requests everything from `start` to the end of the
+/// resource (`bytes=start-`).
+///
+/// # Errors
+///
+/// Returns an error if `end` is before `start`.
+///
+/// # Example
+///
+/// ```
+/// use wreq::header::range_header;
+///
+/// assert_eq!(range_header(0, Some(99)).unwrap(), "bytes=0-99");
+/// assert_eq!(range_header(100, None).unwrap(), "bytes=100-");
+/// ```
+pub fn range_header(start: u64, end: Option<u64>) -> crate::Result<HeaderValue> {
+    let value = match end {
+        Some(end) if end < start => {
+            return Err(crate::Error::builder(format!(
+                "invalid range: end {end} is before start {start}"
+            )));
+        }
+        Some(end) => format!("bytes={start}-{end}"),
+        None => format!("bytes={start}-"),
+    };
+
+    HeaderValue::from_str(&value).map_err(crate::Error::builder)
+}
+
+/// Parses a `Content-Range` header value of the form `bytes start-end/total`.
+///
+/// Returns `(start, end, total)`, where `end` is inclusive and `total` is `None`
+/// when the complete length is unknown (`*`). Returns `None` for malformed values
+/// and for unsatisfied ranges (`bytes */total`).
+///
+/// # Example
+///
+/// ```
+/// use wreq::header::{HeaderValue, parse_content_range};
+///
+/// let value = HeaderValue::from_static("bytes 0-99/200");
+/// assert_eq!(parse_content_range(&value), Some((0, 99, Some(200))));
+/// ```
+pub fn parse_content_range(value: &HeaderValue) -> Option<(u64, u64, Option<u64>)> {
+    let value = value.to_str().ok()?.trim();
+    let (unit, spec) = value.split_once(' ')?;
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let (range, total) = spec.trim().split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let start = start.parse::<u64>().ok()?;
+    let end = end.parse::<u64>().ok()?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse::<u64>().ok()?),
+    };
+
+    if end < start || total.is_some_and(|total| end >= total) {
+        return None;
+    }
+
+    Some((start, end, total))
+}
+
+/// Parses a `Retry-After` header value into the time to wait from `now`.
+///
+/// Accepts both forms of the header: a number of seconds (`120`) and an HTTP-date
+/// (`Wed, 21 Oct 2015 07:28:00 GMT`), including the obsolete RFC 850 and asctime
+/// date formats. A date in the past yields [`Duration::ZERO`]. Returns `None` for
+/// malformed values.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+///
+/// use wreq::header::{HeaderValue, parse_retry_after};
+///
+/// let value = HeaderValue::from_static("120");
+/// assert_eq!(
+///     parse_retry_after(&value, SystemTime::now()),
+///     Some(Duration::from_secs(120))
+/// );
+/// ```
+pub fn parse_retry_after(value: &HeaderValue, now: SystemTime) -> Option<Duration> {
+    let value = value.to_str().ok()?.trim();
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return value.parse::<u64>().ok().map(Duration::from_secs);
+    }
+
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Parses an HTTP-date ([RFC 9110] section 5.6.7) in any of its three formats.
+///
+/// [RFC 9110]: https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let fields: Vec<&str> = value.split_ascii_whitespace().collect();
+    let (day, month, year, time) = match fields[..] {
+        // IMF-fixdate: `Sun, 06 Nov 1994 08:49:37 GMT`
+        [_, day, month, year, time, "GMT"] => (day, month, parse_year(year, 4)?, time),
+        // RFC 850: `Sunday, 06-Nov-94 08:49:37 GMT`
+        [_, date, time, "GMT"] => {
+            let mut parts = date.split('-');
+            let (day, month, year) = (parts.next()?, parts.next()?, parts.next()?);
+            // Two-digit years are read as 1970 to 2069, a fixed pivot rather than
+            // RFC 9110's "more than 50 years in the future" rule, which needs the
+            // current date
+            let year = parse_year(year, 2)?;
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+            (day, month, year, time)
+        }
+        // asctime: `Sun Nov  6 08:49:37 1994`
+        [_, month, day, time, year] => (day, month, parse_year(year, 4)?, time),
+        _ => return None,
+    };
+
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let day: u64 = day.parse().ok()?;
+
+    let mut hms = time.split(':').map(|n| n.parse::<u64>().ok());
+    let (hour, min, sec) = (hms.next()??, hms.next()??, hms.next()??);
+    if hms.next().is_some() || !(1..=31).contains(&day) || hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+    if year < 1970 {
+        return None;
+    }
+
+    // Days since the epoch of a proleptic Gregorian date, shifting the year to
+    // start in March so that leap days fall at its end
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y % 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146_097 + doe).checked_sub(719_468)?;
+
+    let secs = days * 86_400 + hour * 3_600 + min * 60 + sec;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Parses a year written with exactly `digits` digits, which keeps the date
+/// arithmetic of [`parse_http_date`] from overflowing.
+fn parse_year(year: &str, digits: usize) -> Option<u64> {
+    if year.len() != digits || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    year.parse().ok()
+}
+
 pub(crate) fn fast_random() -> u64 {
     use std::{
         cell::Cell,
@@ -52,6 +239,29 @@ pub(crate) fn fast_random() -> u64 {
     })
 }
 
+/// A seedable pseudo-random generator (SplitMix64), for randomness that has
+/// to be reproducible. Not suitable for cryptography.
+pub(crate) struct SeededRandom(u64);
+
+impl SeededRandom {
+    pub(crate) fn new(seed: u64) -> SeededRandom {
+        SeededRandom(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`, which must not be zero.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
 pub(crate) fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
     // IntoIter of HeaderMap yields (Option<HeaderName>, HeaderValue).
     // The first time a name is yielded, it will be Some(name), and if
@@ -120,3 +330,114 @@ impl fmt::Display for Escape<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_header_open_ended() {
+        assert_eq!(range_header(500, None).unwrap(), "bytes=500-");
+    }
+
+    #[test]
+    fn range_header_bounded() {
+        assert_eq!(range_header(0, Some(99)).unwrap(), "bytes=0-99");
+        assert_eq!(range_header(7, Some(7)).unwrap(), "bytes=7-7");
+        assert!(range_header(100, Some(99)).is_err());
+    }
+
+    #[test]
+    fn content_range_parse() {
+        let parse = |s: &'static str| parse_content_range(&HeaderValue::from_static(s));
+
+        assert_eq!(parse("bytes 0-99/200"), Some((0, 99, Some(200))));
+        assert_eq!(parse("bytes 100-199/*"), Some((100, 199, None)));
+
+        assert_eq!(parse("bytes */200"), None);
+        assert_eq!(parse("bytes 99-0/200"), None);
+        assert_eq!(parse("bytes 0-200/200"), None);
+        assert_eq!(parse("items 0-99/200"), None);
+        assert_eq!(parse("bytes 0-99"), None);
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let now = SystemTime::now();
+        let parse = |s: &'static str| parse_retry_after(&HeaderValue::from_static(s), now);
+
+        assert_eq!(parse("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("0"), Some(Duration::ZERO));
+        assert_eq!(parse("-5"), None);
+        assert_eq!(parse("soon"), None);
+    }
+
+    #[test]
+    fn retry_after_future_date() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        let now = date - Duration::from_secs(90);
+
+        for value in [
+            "Wed, 21 Oct 2015 07:28:00 GMT",
+            "Wednesday, 21-Oct-15 07:28:00 GMT",
+            "Wed Oct 21 07:28:00 2015",
+        ] {
+            let value = HeaderValue::from_static(value);
+            assert_eq!(
+                parse_retry_after(&value, now),
+                Some(Duration::from_secs(90)),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn retry_after_past_date_is_zero() {
+        let value = HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(
+            parse_retry_after(&value, SystemTime::now()),
+            Some(Duration::ZERO)
+        );
+
+        let value = HeaderValue::from_static("Sun, 06 Foo 1994 08:49:37 GMT");
+        assert_eq!(parse_retry_after(&value, SystemTime::now()), None);
+    }
+
+    #[test]
+    fn http_date_rejects_out_of_range_years() {
+        assert!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").is_some());
+        assert!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT").is_some());
+        assert!(parse_http_date("Sun Nov  6 08:49:37 1994").is_some());
+
+        assert!(parse_http_date("Sun, 06 Nov 18446744073709551615 08:49:37 GMT").is_none());
+        assert!(parse_http_date("Sun, 06 Nov 99999 08:49:37 GMT").is_none());
+        assert!(parse_http_date("Sunday, 06-Nov-1994 08:49:37 GMT").is_none());
+        assert!(parse_http_date("Sun Nov  6 08:49:37 +994").is_none());
+    }
+
+    #[test]
+    fn basic_auth_default_engine() {
+        let header = basic_auth("Aladdin", Some("open sesame"));
+        assert_eq!(header, "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+        assert!(header.is_sensitive());
+
+        let header = basic_auth_with(base64_encode, "Aladdin", Some("open sesame"));
+        assert_eq!(header, "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    }
+
+    #[test]
+    fn basic_auth_custom_engine() {
+        fn url_safe(input: &[u8]) -> String {
+            base64_encode(input)
+                .replace('+', "-")
+                .replace('/', "_")
+                .trim_end_matches('=')
+                .to_owned()
+        }
+
+        // "??>:" encodes to "Pz8+Og==" with the standard alphabet
+        let header = basic_auth_with(url_safe, "??>", None::<&str>);
+        assert_eq!(header, "Basic Pz8-Og");
+    }
+}
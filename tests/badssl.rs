@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use wreq::{
     Client, Extension,
-    tls::{AlpsProtocol, CertStore, TlsInfo, TlsOptions, TlsVersion},
+    tls::{AlpsProtocol, CertStore, Certificate, Sha256Pin, TlsInfo, TlsOptions, TlsVersion},
 };
 
 macro_rules! join {
@@ -47,6 +47,24 @@ async fn test_badssl_self_signed() {
 
     assert!(!text.is_empty());
 }
+
+#[tokio::test]
+async fn test_badssl_cert_pin_mismatch() {
+    let err = Client::builder()
+        .cert_pin("*.badssl.com", &[Sha256Pin::from_bytes([0; 32])])
+        .connect_timeout(Duration::from_secs(360))
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get("https://mozilla-modern.badssl.com/")
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_cert_pin_mismatch());
+    assert!(err.is_connect());
+}
+
 const CURVES_LIST: &str = join!(
     ":",
     "X25519",
@@ -225,3 +243,40 @@ async fn test_tls_self_signed_cert() {
     let res = client.get("https://www.google.com").send().await;
     assert!(res.is_err());
 }
+
+#[tokio::test]
+async fn test_add_root_certificate_keeps_built_in_roots() {
+    let client = Client::builder()
+        .cert_verification(false)
+        .connect_timeout(Duration::from_secs(360))
+        .tls_info(true)
+        .build()
+        .unwrap();
+
+    let resp = client
+        .get("https://self-signed.badssl.com/")
+        .send()
+        .await
+        .unwrap();
+
+    let peer_cert_der = resp
+        .extension::<TlsInfo>()
+        .and_then(|Extension(info)| info.peer_certificate())
+        .unwrap();
+
+    let client = Client::builder()
+        .add_root_certificate(Certificate::from_der(peer_cert_der).unwrap())
+        .connect_timeout(Duration::from_secs(360))
+        .build()
+        .unwrap();
+
+    let resp = client
+        .get("https://self-signed.badssl.com/")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let resp = client.get("https://www.google.com").send().await.unwrap();
+    assert!(resp.status().is_success());
+}
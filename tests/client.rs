@@ -14,7 +14,11 @@ use http_body_util::BodyExt;
 use pretty_env_logger::env_logger;
 use support::server;
 use tokio::io::AsyncWriteExt;
-use wreq::{Client, Extension, header::OrigHeaderMap, tls::TlsInfo};
+use wreq::{
+    Client, Extension,
+    header::OrigHeaderMap,
+    tls::{EchMode, TlsInfo, TlsVersion},
+};
 
 #[tokio::test]
 async fn auto_headers() {
@@ -436,6 +440,25 @@ async fn overridden_dns_resolution_with_gai_multiple() {
     assert_eq!("Hello", text);
 }
 
+#[tokio::test]
+async fn overridden_dns_resolution_keeps_host_and_ignores_case() {
+    let server = server::http(move |req| async move {
+        let host = req.headers()[header::HOST].to_str().unwrap().to_owned();
+        http::Response::new(host.into())
+    });
+
+    let url = format!("http://staging.example:{}/", server.addr().port());
+    let client = Client::builder()
+        .no_proxy()
+        .resolve_to_addr_slice("Staging.Example", &[server.addr()])
+        .build()
+        .expect("client builder");
+    let res = client.get(&url).send().await.expect("request");
+
+    let text = res.text().await.expect("Failed to get text");
+    assert_eq!(text, format!("staging.example:{}", server.addr().port()));
+}
+
 #[cfg(feature = "hickory-dns")]
 #[tokio::test]
 async fn overridden_dns_resolution_with_hickory_dns() {
@@ -495,6 +518,405 @@ async fn overridden_dns_resolution_with_hickory_dns_multiple() {
     assert_eq!("Hello", text);
 }
 
+#[cfg(feature = "hickory-dns")]
+#[tokio::test]
+async fn doh_url_resolves_hosts_over_https_endpoint() {
+    use hickory_resolver::proto::{
+        op::{Message, MessageType},
+        rr::{RData, Record, RecordType, rdata::A},
+    };
+
+    let server = server::http(move |req| async move {
+        if req.uri().path() != "/dns-query" {
+            return http::Response::new("Hello".into());
+        }
+
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/dns-message");
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        let query = Message::from_vec(&body).unwrap();
+
+        let mut answer = Message::new();
+        answer
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .add_queries(query.queries().to_vec());
+        let question = &query.queries()[0];
+        if question.query_type() == RecordType::A {
+            answer.add_answer(Record::from_rdata(
+                question.name().clone(),
+                60,
+                RData::A(A::new(127, 0, 0, 1)),
+            ));
+        }
+
+        http::Response::builder()
+            .header(CONTENT_TYPE, "application/dns-message")
+            .body(answer.to_vec().unwrap().into())
+            .unwrap()
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .doh_url(format!("http://{}/dns-query", server.addr()))
+        .build()
+        .expect("client builder");
+
+    let url = format!("http://doh.wreq.test:{}/hello", server.addr().port());
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+    assert_eq!(res.text().await.expect("text"), "Hello");
+}
+
+#[tokio::test]
+async fn request_resolve_to_keeps_host_header() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["host"], "forced.invalid");
+        http::Response::new("Hello".into())
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .build()
+        .expect("client builder");
+    let res = client
+        .get("http://forced.invalid/resolve_to")
+        .resolve_to(server.addr())
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+    let text = res.text().await.expect("Failed to get text");
+    assert_eq!("Hello", text);
+}
+
+#[tokio::test]
+async fn allowed_ports_rejects_other_ports() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+    let url = format!("http://{}/allowed_ports", server.addr());
+
+    let client = Client::builder()
+        .no_proxy()
+        .allowed_ports([80, 443])
+        .build()
+        .expect("client builder");
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_connect());
+
+    let client = Client::builder()
+        .no_proxy()
+        .allowed_ports([443, server.addr().port()])
+        .build()
+        .expect("client builder");
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn denied_ip_ranges_reject_resolved_addresses() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+    let url = format!("http://internal.test:{}/", server.addr().port());
+
+    // The name resolves into a denied range, whatever port is allowed
+    let client = Client::builder()
+        .no_proxy()
+        .resolve("internal.test", server.addr())
+        .allowed_ports([server.addr().port()])
+        .denied_ip_ranges(["10.0.0.0/8", "127.0.0.0/8"])
+        .build()
+        .expect("client builder");
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_connect());
+
+    // Both the port and the IP have to be allowed
+    let client = Client::builder()
+        .no_proxy()
+        .resolve("internal.test", server.addr())
+        .allowed_ports([server.addr().port()])
+        .denied_ip_ranges(["10.0.0.0/8", "::1"])
+        .build()
+        .expect("client builder");
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let err = Client::builder()
+        .denied_ip_ranges(["10.0.0.0/33"])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+/// Resolves every name to a fixed address, counting lookups.
+struct CountingResolver {
+    addr: std::net::SocketAddr,
+    lookups: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl wreq::dns::Resolve for CountingResolver {
+    fn resolve(&self, _: wreq::dns::Name) -> wreq::dns::Resolving {
+        self.lookups
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let addrs: wreq::dns::Addrs = Box::new(std::iter::once(self.addr));
+        Box::pin(std::future::ready(Ok(addrs)))
+    }
+}
+
+#[tokio::test]
+async fn connection_refused_triggers_reresolution() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    // Grab a free port and close it again, so connecting is refused
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .no_proxy()
+        .dns_resolver(CountingResolver {
+            addr,
+            lookups: lookups.clone(),
+        })
+        .build()
+        .unwrap();
+
+    let err = client
+        .get(format!("http://refused.invalid:{}/", addr.port()))
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_connect());
+    assert_eq!(lookups.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn tls_failure_does_not_trigger_reresolution() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    // A plain HTTP server makes the TLS handshake fail after connecting
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .no_proxy()
+        .dns_resolver(CountingResolver {
+            addr: server.addr(),
+            lookups: lookups.clone(),
+        })
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("https://tls.invalid:{}/", server.addr().port()))
+        .send()
+        .await;
+
+    assert!(res.is_err());
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn ip_version_filters_resolved_addresses() {
+    use wreq::dns::IpVersion;
+
+    let server = server::http(move |_req| async { http::Response::default() });
+    let url = format!("http://family.invalid:{}/", server.addr().port());
+    let client = |version| {
+        Client::builder()
+            .no_proxy()
+            .dns_resolver(CountingResolver {
+                addr: server.addr(),
+                lookups: Default::default(),
+            })
+            .ip_version(version)
+            .build()
+            .unwrap()
+    };
+
+    // The server only has an IPv4 address
+    let res = client(IpVersion::V4Only).get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let err = client(IpVersion::V6Only)
+        .get(&url)
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_connect());
+}
+
+#[tokio::test]
+async fn check_connectivity_succeeds_against_listener() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let client = Client::new();
+    let report = client
+        .check_connectivity(
+            "127.0.0.1",
+            server.addr().port(),
+            std::time::Duration::from_secs(5),
+        )
+        .await;
+
+    assert!(report.is_success(), "{:?}", report.error());
+    assert_eq!(report.addr(), Some(server.addr()));
+    assert!(report.dns_time().is_some());
+    assert!(report.connect_time().is_some());
+}
+
+#[tokio::test]
+async fn check_connectivity_reports_closed_port() {
+    // Grab a free port and close it again, so connecting is refused
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let client = Client::new();
+    let report = client
+        .check_connectivity("127.0.0.1", addr.port(), std::time::Duration::from_secs(5))
+        .await;
+
+    assert!(!report.is_success());
+    assert!(report.error().is_some());
+    assert_eq!(report.addr(), None);
+    assert!(report.dns_time().is_some());
+    assert!(report.connect_time().is_some());
+}
+
+#[tokio::test]
+async fn revalidate_with_reports_not_modified() {
+    let server = server::http(move |req| async move {
+        if req.headers().contains_key("if-none-match") {
+            assert_eq!(req.headers()["if-none-match"], "\"v1\"");
+            assert_eq!(
+                req.headers()["if-modified-since"],
+                "Wed, 21 Oct 2015 07:28:00 GMT"
+            );
+            return http::Response::builder()
+                .status(http::StatusCode::NOT_MODIFIED)
+                .body(Default::default())
+                .unwrap();
+        }
+
+        http::Response::builder()
+            .header("etag", "\"v1\"")
+            .header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+            .body("cached".into())
+            .unwrap()
+    });
+
+    let url = format!("http://{}/resource", server.addr());
+    let client = Client::new();
+
+    let res = client.get(&url).send().await.unwrap();
+    assert!(!res.is_not_modified());
+    let validators = res.validators();
+    assert_eq!(validators.etag().unwrap(), "\"v1\"");
+    let validators = validators.with_body(res.bytes().await.unwrap());
+
+    // The 304 is answered with the cached body
+    let res = client
+        .get(&url)
+        .revalidate_with(&validators)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.is_not_modified());
+    assert_eq!(res.text().await.unwrap(), "cached");
+}
+
+#[test]
+fn client_tls_fingerprints() {
+    let client = Client::builder().http1_only().build().unwrap();
+
+    let ja3 = client.ja3().unwrap();
+    assert_eq!(ja3.len(), 32);
+    assert!(ja3.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(client.ja3().unwrap(), ja3);
+
+    let ja4 = client.ja4().unwrap();
+    let parts: Vec<_> = ja4.split('_').collect();
+    assert_eq!(parts.len(), 3);
+    assert!(parts[0].starts_with("t13d"));
+    assert!(parts[0].ends_with("h1"));
+}
+
+#[test]
+fn client_tls_fingerprint_includes_http2() {
+    let client = Client::builder().build().unwrap();
+    let fingerprint = client.tls_fingerprint().unwrap();
+    assert_eq!(fingerprint.ja3(), client.ja3().unwrap());
+    assert_eq!(fingerprint.ja4(), client.ja4().unwrap());
+
+    let http2 = fingerprint.http2().unwrap();
+    let parts: Vec<_> = http2.split('|').collect();
+    assert_eq!(parts.len(), 4, "{http2}");
+    assert!(parts[0].contains("4:"), "{http2}");
+    assert_eq!(parts[3].split(',').count(), 4, "{http2}");
+
+    let client = Client::builder().http1_only().build().unwrap();
+    assert_eq!(client.tls_fingerprint().unwrap().http2(), None);
+}
+
+#[test]
+fn ech_mode_controls_ech_grease() {
+    let ja3 = |mode| {
+        let client = Client::builder().ech(mode).build().unwrap();
+        client.ja3().unwrap()
+    };
+
+    assert_ne!(ja3(EchMode::Grease), ja3(EchMode::Disable));
+    assert_eq!(ja3(EchMode::Enable), ja3(EchMode::Grease));
+}
+
+#[test]
+fn post_quantum_key_share_changes_supported_groups() {
+    let fingerprint = |enabled| {
+        let client = Client::builder()
+            .post_quantum_key_share(enabled)
+            .build()
+            .unwrap();
+        (client.ja3().unwrap(), client.ja4().unwrap())
+    };
+
+    let (pq_ja3, pq_ja4) = fingerprint(true);
+    let (classic_ja3, classic_ja4) = fingerprint(false);
+    // JA3 lists the supported groups, JA4 does not
+    assert_ne!(pq_ja3, classic_ja3);
+    assert_eq!(pq_ja4, classic_ja4);
+}
+
+#[test]
+fn shuffle_tls_extensions_changes_order_per_handshake() {
+    let orderings = |shuffle: bool| {
+        let client = Client::builder()
+            .shuffle_tls_extensions(shuffle)
+            .build()
+            .unwrap();
+        (0..8)
+            .map(|_| client.ja3().unwrap())
+            .collect::<std::collections::HashSet<_>>()
+    };
+
+    // JA3 keeps the extension order, so reordered extensions change it
+    assert!(orderings(true).len() > 1);
+    assert_eq!(orderings(false).len(), 1);
+}
+
 #[test]
 #[cfg(feature = "json")]
 fn add_json_default_content_type_if_not_set_manually() {
@@ -525,6 +947,58 @@ fn update_json_content_type_if_set_manually() {
     assert_eq!("application/json", req.headers().get(CONTENT_TYPE).unwrap());
 }
 
+#[test]
+#[cfg(feature = "json")]
+fn to_curl_renders_post_with_redacted_headers() {
+    let mut map = HashMap::new();
+    map.insert("name", "O'Brien");
+    let builder = Client::new()
+        .post("https://example.com/users?page=1")
+        .header("x-request-id", "abc 123")
+        .bearer_auth("secret-token")
+        .json(&map);
+
+    assert_eq!(
+        builder.to_curl(),
+        "curl -X POST 'https://example.com/users?page=1' \
+         -H 'x-request-id: abc 123' \
+         -H 'authorization: <redacted>' \
+         -H 'content-type: application/json' \
+         --data-raw '{\"name\":\"O'\\''Brien\"}'"
+    );
+
+    let unredacted = builder.to_curl_unredacted();
+    assert!(unredacted.contains("-H 'authorization: Bearer secret-token'"));
+}
+
+#[test]
+fn to_curl_keeps_empty_headers() {
+    let curl = Client::new()
+        .get("https://example.com/")
+        .header("x-empty", "")
+        .to_curl();
+
+    assert_eq!(curl, "curl 'https://example.com/' -H 'x-empty;'");
+}
+
+#[test]
+#[cfg(feature = "multipart")]
+fn to_curl_leaves_out_multipart_framing() {
+    let form = wreq::multipart::Form::new().text("name", "value");
+    let curl = Client::new()
+        .post("https://example.com/upload")
+        .multipart(form)
+        .to_curl();
+
+    assert_eq!(curl, "curl -X POST 'https://example.com/upload'");
+}
+
+#[test]
+fn to_curl_reports_build_errors() {
+    let curl = Client::new().get("not a url").to_curl();
+    assert!(curl.starts_with("# request could not be built: "));
+}
+
 #[tokio::test]
 async fn test_tls_info() {
     let resp = Client::builder()
@@ -552,6 +1026,58 @@ async fn test_tls_info() {
     assert!(tls_info.is_none());
 }
 
+#[tokio::test]
+async fn tls_info_reports_negotiated_parameters() {
+    use boring2::ssl::{self, AlpnError, SslAcceptor, SslFiletype, SslMethod};
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor
+        .set_certificate_file(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/support/server.cert"),
+            SslFiletype::ASN1,
+        )
+        .unwrap();
+    acceptor
+        .set_private_key_file(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/support/server.key"),
+            SslFiletype::ASN1,
+        )
+        .unwrap();
+    acceptor.set_alpn_select_callback(|_, client| {
+        ssl::select_next_proto(b"\x08http/1.1", client).ok_or(AlpnError::NOACK)
+    });
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let tls = tokio_boring2::accept(&acceptor, tcp).await.unwrap();
+        let svc = hyper::service::service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(String::new()))
+        });
+        let _ = hyper::server::conn::http1::Builder::new()
+            .serve_connection(hyper_util::rt::TokioIo::new(tls), svc)
+            .await;
+    });
+
+    let res = Client::builder()
+        .cert_verification(false)
+        .tls_info(true)
+        .build()
+        .unwrap()
+        .get(format!("https://{addr}/"))
+        .send()
+        .await
+        .unwrap();
+
+    let info = res.tls_info().expect("tls info");
+    assert_eq!(info.version(), Some(TlsVersion::TLS_1_3));
+    assert_eq!(info.alpn_protocol(), Some(&b"http/1.1"[..]));
+    assert!(info.cipher_suite().is_some());
+    assert!(info.peer_certificate().is_some());
+}
+
 #[tokio::test]
 async fn close_connection_after_idle_timeout() {
     let mut server = server::http(move |_| async move { http::Response::default() });
@@ -576,36 +1102,428 @@ async fn close_connection_after_idle_timeout() {
 }
 
 #[tokio::test]
-async fn http1_reason_phrase() {
-    let server = server::low_level_with_response(|_raw_request, client_socket| {
-        Box::new(async move {
-            client_socket
-                .write_all(b"HTTP/1.1 418 I'm not a teapot\r\nContent-Length: 0\r\n\r\n")
-                .await
-                .expect("response write_all failed");
-        })
-    });
+async fn zero_idle_timeout_disables_pooling() {
+    let mut server = server::http(move |_| async move { http::Response::default() });
 
-    let client = Client::new();
+    let client = Client::builder()
+        .pool_idle_timeout(std::time::Duration::ZERO)
+        .build()
+        .unwrap();
 
-    let res = client
-        .get(format!("http://{}", server.addr()))
-        .send()
-        .await
-        .expect("Failed to get");
+    let url = format!("http://{}", server.addr());
 
-    assert_eq!(
-        res.error_for_status().unwrap_err().to_string(),
-        format!(
-            "HTTP status client error (418 I'm not a teapot) for uri (http://{}/)",
-            server.addr()
-        )
-    );
-}
+    let res = client.get(&url).send().await.unwrap();
+    let first = res.local_addr();
+    assert!(first.is_some());
+    res.bytes().await.unwrap();
 
-#[tokio::test]
-async fn error_has_url() {
-    let u = "http://does.not.exist.local/ever";
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    assert!(
+        server
+            .events()
+            .iter()
+            .any(|e| matches!(e, server::Event::ConnectionClosed))
+    );
+
+    // A fresh connection is opened for the next request
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_ne!(res.local_addr(), first);
+}
+
+#[tokio::test]
+async fn clients_have_their_own_dns_cache_by_default() {
+    use wreq::dns::{DnsCache, DnsCacheConfig};
+
+    let server = server::http(move |_| async move { http::Response::default() });
+    let url = format!("http://localhost:{}/", server.addr().port());
+
+    let first = Client::builder().no_proxy().build().unwrap();
+    let second = Client::builder().no_proxy().build().unwrap();
+    first.get(&url).send().await.unwrap();
+    assert!(first.dns_cache().get("localhost").is_some());
+    assert!(second.dns_cache().get("localhost").is_none());
+
+    // Clients given the same cache share their answers
+    let shared = DnsCache::new();
+    let first = Client::builder()
+        .no_proxy()
+        .dns_cache(DnsCacheConfig::Shared(shared.clone()))
+        .build()
+        .unwrap();
+    let second = Client::builder()
+        .no_proxy()
+        .dns_cache(DnsCacheConfig::Shared(shared.clone()))
+        .build()
+        .unwrap();
+    first.get(&url).send().await.unwrap();
+    assert!(shared.get("localhost").is_some());
+    assert!(second.dns_cache().get("localhost").is_some());
+}
+
+#[tokio::test]
+async fn dns_cache_stats_count_lookups_of_the_client() {
+    let server = server::http(move |_| async move { http::Response::default() });
+    let url = format!("http://localhost:{}/", server.addr().port());
+
+    let client = Client::builder().no_proxy().build().unwrap();
+    assert_eq!(client.dns_cache_stats().size, 0);
+
+    client.get(&url).send().await.unwrap();
+    let stats = client.dns_cache_stats();
+    assert_eq!(stats.size, 1);
+    assert_eq!(stats.inserts, 1);
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hit_rate(), 0.0);
+}
+
+#[tokio::test]
+async fn close_connections_purges_pooled_connections_to_host() {
+    let mut server = server::http(move |_| async move { http::Response::default() });
+
+    let client = Client::new();
+    let url = format!("http://{}", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    let first = res.local_addr();
+    res.bytes().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // Other hosts are left alone
+    assert_eq!(client.close_connections("example.com"), 0);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.local_addr(), first);
+    res.bytes().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    assert_eq!(client.close_connections("127.0.0.1"), 1);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(
+        server
+            .events()
+            .iter()
+            .any(|e| matches!(e, server::Event::ConnectionClosed))
+    );
+
+    // The next request to the host opens a new connection
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_ne!(res.local_addr(), first);
+}
+
+#[tokio::test]
+async fn pool_stats_count_created_and_reused_connections() {
+    use std::sync::{Arc, Mutex};
+
+    use wreq::PoolEvent;
+
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let client = Client::builder()
+        .no_proxy()
+        .on_pool_event({
+            let events = events.clone();
+            move |event| {
+                let event = match event {
+                    PoolEvent::Connected { host, .. } => format!("connected {host}"),
+                    PoolEvent::Reused { host, .. } => format!("reused {host}"),
+                    _ => unreachable!(),
+                };
+                events.lock().unwrap().push(event);
+            }
+        })
+        .build()
+        .unwrap();
+    let url = format!("http://{}", server.addr());
+
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        res.bytes().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    let stats = client.pool_stats();
+    let host = stats.host("127.0.0.1").unwrap();
+    assert_eq!((host.idle(), host.active()), (1, 0));
+    assert_eq!((host.created(), host.reused()), (1, 1));
+    assert!(host.handshake_avg().is_some());
+    assert_eq!(stats.reuse_ratio(), 0.5);
+    assert_eq!(
+        *events.lock().unwrap(),
+        ["connected 127.0.0.1", "reused 127.0.0.1"]
+    );
+}
+
+#[tokio::test]
+async fn max_connections_per_host_queues_requests() {
+    let server = server::http(move |_| async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        http::Response::default()
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .max_connections_per_host(1)
+        .build()
+        .unwrap();
+    let url = format!("http://{}", server.addr());
+
+    let send = || async {
+        let res = client.get(&url).send().await.unwrap();
+        res.bytes().await.unwrap();
+    };
+    tokio::join!(send(), send(), send());
+
+    let stats = client.pool_stats();
+    let host = stats.host("127.0.0.1").unwrap();
+    assert_eq!((host.created(), host.reused()), (1, 2));
+}
+
+#[tokio::test]
+async fn basic_auth_uses_client_encoder() {
+    let server = server::http(move |req| async move {
+        // "??>:" encodes to "Pz8+Og==" with the standard alphabet
+        assert_eq!(req.headers()["authorization"], "Basic Pz8-Og");
+        http::Response::default()
+    });
+
+    fn url_safe(input: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let mut out = String::new();
+        for chunk in input.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+            for i in 0..=chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            }
+        }
+        out
+    }
+
+    let client = Client::builder()
+        .no_proxy()
+        .basic_auth_encoder(url_safe)
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/auth", server.addr()))
+        .basic_auth("??>", None::<&str>)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn max_total_connections_closes_connections_idled_while_waiting() {
+    let server = server::http(move |_| async move {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        http::Response::default()
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .resolve("other.test", server.addr())
+        .max_total_connections(1)
+        .build()
+        .unwrap();
+
+    let first = async {
+        let url = format!("http://{}", server.addr());
+        let res = client.get(url).send().await.unwrap();
+        res.bytes().await.unwrap();
+    };
+    // Starts waiting while the first connection is busy, which then goes
+    // idle in the pool instead of being closed.
+    let second = async {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let url = format!("http://other.test:{}", server.addr().port());
+        let res = client.get(url).send().await.unwrap();
+        res.bytes().await.unwrap();
+    };
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        tokio::join!(first, second)
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_header_order_on_the_wire() {
+    let server = server::low_level_with_response(|raw_request, client_socket| {
+        Box::new(async move {
+            let raw = String::from_utf8_lossy(raw_request).to_ascii_lowercase();
+            let pos = |name: &str| raw.find(&format!("\r\n{name}:")).unwrap();
+
+            assert!(pos("x-first") < pos("x-second"));
+            assert!(pos("x-second") < pos("x-third"));
+            assert!(pos("x-third") < pos("x-unlisted"));
+
+            client_socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .expect("response write_all failed");
+        })
+    });
+
+    let client = Client::builder().no_proxy().build().unwrap();
+
+    let res = client
+        .get(format!("http://{}/header_order", server.addr()))
+        .header("x-third", "3")
+        .header("x-unlisted", "0")
+        .header("x-second", "2")
+        .header("x-first", "1")
+        .header_order(&[
+            header::HeaderName::from_static("x-first"),
+            header::HeaderName::from_static("x-second"),
+            header::HeaderName::from_static("x-third"),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_header_order_with_replaced_headers() {
+    let server = server::low_level_with_response(|raw_request, client_socket| {
+        Box::new(async move {
+            let raw = String::from_utf8_lossy(raw_request).to_ascii_lowercase();
+            let pos = |name: &str| raw.find(&format!("\r\n{name}:")).unwrap();
+
+            // Default headers replaced by the request keep the requested position
+            assert!(raw.contains("\r\nx-replaced: new\r\n"));
+            assert!(!raw.contains("old"));
+            assert!(pos("x-replaced") < pos("x-default"));
+            assert!(pos("x-default") < pos("x-listed"));
+
+            client_socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .expect("response write_all failed");
+        })
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .default_headers({
+            let mut headers = HeaderMap::new();
+            headers.insert("x-default", HeaderValue::from_static("default"));
+            headers.insert("x-replaced", HeaderValue::from_static("old"));
+            headers
+        })
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/header_order", server.addr()))
+        .headers({
+            let mut headers = HeaderMap::new();
+            headers.insert("x-listed", HeaderValue::from_static("listed"));
+            headers.insert("x-replaced", HeaderValue::from_static("new"));
+            headers
+        })
+        .header_order(&[
+            header::HeaderName::from_static("x-replaced"),
+            header::HeaderName::from_static("x-default"),
+            header::HeaderName::from_static("x-listed"),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_header_order_keeps_emulation_orig_headers() {
+    let server = server::low_level_with_response(|raw_request, client_socket| {
+        Box::new(async move {
+            let raw = String::from_utf8_lossy(raw_request);
+            let pos = |name: &str| raw.find(&format!("\r\n{name}:")).unwrap();
+
+            // The listed header leads, the emulation's order and casing follow
+            assert!(pos("x-listed") < pos("X-Emulated-First"));
+            assert!(pos("X-Emulated-First") < pos("X-Emulated-Second"));
+
+            client_socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .expect("response write_all failed");
+        })
+    });
+
+    let emulation = wreq::Emulation::builder()
+        .headers({
+            let mut headers = HeaderMap::new();
+            headers.insert("x-emulated-second", HeaderValue::from_static("2"));
+            headers.insert("x-emulated-first", HeaderValue::from_static("1"));
+            headers
+        })
+        .orig_headers({
+            let mut orig_headers = OrigHeaderMap::new();
+            orig_headers.insert("X-Emulated-First");
+            orig_headers.insert("X-Emulated-Second");
+            orig_headers
+        })
+        .build();
+    let client = Client::builder()
+        .no_proxy()
+        .emulation(emulation)
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/header_order", server.addr()))
+        .header("x-listed", "listed")
+        .header_order(&[header::HeaderName::from_static("x-listed")])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn http1_reason_phrase() {
+    let server = server::low_level_with_response(|_raw_request, client_socket| {
+        Box::new(async move {
+            client_socket
+                .write_all(b"HTTP/1.1 418 I'm not a teapot\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .expect("response write_all failed");
+        })
+    });
+
+    let client = Client::new();
+
+    let res = client
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .expect("Failed to get");
+
+    assert_eq!(
+        res.error_for_status().unwrap_err().to_string(),
+        format!(
+            "HTTP status client error (418 I'm not a teapot) for uri (http://{}/)",
+            server.addr()
+        )
+    );
+}
+
+#[tokio::test]
+async fn error_has_url() {
+    let u = "http://does.not.exist.local/ever";
     let err = wreq::get(u).send().await.unwrap_err();
     assert_eq!(
         err.uri().map(ToString::to_string).as_deref(),
@@ -945,3 +1863,703 @@ async fn test_client_default_accept_encoding() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn connect_attempts_record_each_address() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    // Grab a free port and close it again, so connecting is refused
+    let closed = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    // Without a port in the URL, the resolved ports are used as is
+    let client = Client::builder()
+        .no_proxy()
+        .resolve_to_addrs("attempts.test", [closed, server.addr()])
+        .build()
+        .unwrap();
+    let res = client.get("http://attempts.test/").send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let attempts = res.connect_attempts().expect("attempts recorded");
+    let outcomes: Vec<_> = attempts.iter().map(|a| (a.addr(), a.outcome())).collect();
+    assert_eq!(
+        outcomes,
+        [
+            (closed, wreq::ConnectOutcome::Refused),
+            (server.addr(), wreq::ConnectOutcome::Connected),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn coalesce_requests_sends_one_upstream_request() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |_req| {
+            let hits = hits.clone();
+            async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                // Keep the request in flight long enough for every caller to join it
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                http::Response::new("shared".into())
+            }
+        }
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .coalesce_requests(true)
+        .build()
+        .unwrap();
+    let url = format!("http://{}/coalesce", server.addr());
+
+    let requests = (0..10).map(|_| async {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), wreq::StatusCode::OK);
+        res.text().await.unwrap()
+    });
+    let bodies = futures_util::future::join_all(requests).await;
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert!(bodies.iter().all(|body| body == "shared"));
+}
+
+#[tokio::test]
+async fn coalesce_requests_does_not_share_large_bodies() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    const LEN: usize = 2 * 1024 * 1024;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |_req| {
+            let hits = hits.clone();
+            async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                http::Response::new(vec![b'x'; LEN].into())
+            }
+        }
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .coalesce_requests(true)
+        .build()
+        .unwrap();
+    let url = format!("http://{}/coalesce", server.addr());
+
+    let requests = (0..2).map(|_| async {
+        client
+            .get(&url)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap()
+    });
+    let bodies = futures_util::future::join_all(requests).await;
+
+    // The waiting request goes upstream on its own
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert!(bodies.iter().all(|body| body.len() == LEN));
+}
+
+#[tokio::test]
+async fn http_cache_serves_fresh_responses_and_revalidates_stale_ones() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use wreq::cache::CacheConfig;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |req| {
+            let hits = hits.clone();
+            async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                let cache_control = match req.uri().path() {
+                    "/fresh" => "max-age=60",
+                    _ => "no-cache",
+                };
+                let not_modified = req.headers().get(header::IF_NONE_MATCH)
+                    == Some(&HeaderValue::from_static("\"v1\""));
+                http::Response::builder()
+                    .status(if not_modified { 304 } else { 200 })
+                    .header(CACHE_CONTROL, cache_control)
+                    .header(header::ETAG, "\"v1\"")
+                    .body(if not_modified { "" } else { "asset" }.into())
+                    .unwrap()
+            }
+        }
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .http_cache(CacheConfig::memory(16))
+        .build()
+        .unwrap();
+
+    // Fresh responses are served from the cache
+    let url = format!("http://{}/fresh", server.addr());
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), wreq::StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), "asset");
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // `no-cache` responses are revalidated, a 304 serving the stored body
+    let url = format!("http://{}/revalidate", server.addr());
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), wreq::StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), "asset");
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+    // A successful POST invalidates the stored response
+    let url = format!("http://{}/fresh", server.addr());
+    client.post(&url).send().await.unwrap();
+    client.get(&url).send().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn http_cache_skips_ranges_and_large_bodies_and_varies_on_sent_headers() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use wreq::cache::CacheConfig;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |req| {
+            let hits = hits.clone();
+            async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                let body = match req.uri().path() {
+                    "/vary" => req.headers()[header::ACCEPT_LANGUAGE]
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                    _ => "large body".to_owned(),
+                };
+                http::Response::builder()
+                    .header(CACHE_CONTROL, "max-age=60")
+                    .header(header::VARY, "accept-language")
+                    .body(body.into())
+                    .unwrap()
+            }
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static("en"));
+    let client = Client::builder()
+        .no_proxy()
+        .default_headers(headers)
+        .http_cache(CacheConfig::memory(16).max_body_size(4))
+        .build()
+        .unwrap();
+    let get = |path: &str, language: Option<&'static str>| {
+        let mut req = client.get(format!("http://{}{}", server.addr(), path));
+        if let Some(language) = language {
+            req = req.header(header::ACCEPT_LANGUAGE, language);
+        }
+        async move { req.send().await.unwrap().text().await.unwrap() }
+    };
+
+    // `Vary` is matched against the default headers the client sends
+    assert_eq!(get("/vary", None).await, "en");
+    assert_eq!(get("/vary", None).await, "en");
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert_eq!(get("/vary", Some("de")).await, "de");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // A range request is not answered from the cache
+    let res = client
+        .get(format!("http://{}/vary", server.addr()))
+        .header(header::RANGE, "bytes=0-0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "en");
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+    // A body above the size limit is returned whole, but not stored
+    assert_eq!(get("/large", None).await, "large body");
+    assert_eq!(get("/large", None).await, "large body");
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn host_header_overrides_url_authority() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["host"], "vhost.example");
+        http::Response::new("Hello".into())
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .resolve("app.test", server.addr())
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://app.test:{}/", server.addr().port()))
+        .host_header("vhost.example")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+}
+
+#[tokio::test]
+async fn check_connection_on_reuse_skips_server_closed_connection() {
+    let server = server::low_level_with_response(|_raw_request, client_socket| {
+        Box::new(async move {
+            client_socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .expect("response write_all failed");
+            // Close the connection while the client keeps it idle in its pool
+            client_socket.shutdown().await.expect("shutdown failed");
+        })
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .check_connection_on_reuse(true)
+        .build()
+        .unwrap();
+    let url = format!("http://{}/", server.addr());
+
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "ok");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn keep_alive_connection_resolves_once() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .no_proxy()
+        .dns_resolver(CountingResolver {
+            addr: server.addr(),
+            lookups: lookups.clone(),
+        })
+        .build()
+        .unwrap();
+    let url = format!("http://keep-alive.invalid:{}/", server.addr().port());
+
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.remote_addr(), Some(server.addr()));
+        assert_eq!(res.text().await.unwrap(), "Hello");
+        // Wait for the connection to be back in the pool, to be reused
+        while client.pool_stats().idle() == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn accept_language_from_locales() {
+    let server = server::http(move |req| async move {
+        let value = req.headers()["accept-language"].clone();
+        http::Response::new(value.to_str().unwrap().to_owned().into())
+    });
+    let url = format!("http://{}/", server.addr());
+
+    let client = Client::builder()
+        .no_proxy()
+        .accept_language(&[("en-US", 1.0), ("en", 0.9)])
+        .build()
+        .unwrap();
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "en-US,en;q=0.9");
+
+    // A header set on the request wins
+    let res = client
+        .get(&url)
+        .header("accept-language", "fr-FR")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "fr-FR");
+
+    let err = Client::builder()
+        .accept_language(&[("en-US", 1.5)])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[cfg(all(feature = "json", feature = "stream"))]
+#[tokio::test]
+async fn json_lines_across_chunks() {
+    use futures_util::StreamExt;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        id: u32,
+    }
+
+    let server = server::http(move |_req| async {
+        // Lines split mid-record, a blank line, and no trailing newline
+        let chunks = ["{\"id\":1}\n{\"i", "d\":2}\n\n{\"id\"", ":3}\r\n{\"id\":4}"];
+        let body = wreq::Body::wrap_stream(futures_util::stream::iter(
+            chunks.map(Ok::<_, std::convert::Infallible>),
+        ));
+        http::Response::new(body)
+    });
+
+    let records = Client::new()
+        .get(format!("http://{}/ndjson", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .json_lines::<Record>()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(records, (1..=4).map(|id| Record { id }).collect::<Vec<_>>());
+}
+
+#[cfg(feature = "stream")]
+#[tokio::test]
+async fn event_stream_reconnects_with_last_event_id() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use futures_util::StreamExt;
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let connections = connections.clone();
+        move |req| {
+            let connection = connections.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert_eq!(req.headers()["accept"], "text/event-stream");
+                let body = match connection {
+                    0 => {
+                        assert!(req.headers().get("last-event-id").is_none());
+                        // An event split across chunks, and one cut off by the disconnect
+                        let chunks = ["retry: 10\nid: 1\nda", "ta: one\n\n: ping\n\ndata: lost"];
+                        wreq::Body::wrap_stream(futures_util::stream::iter(
+                            chunks.map(Ok::<_, std::convert::Infallible>),
+                        ))
+                    }
+                    1 => {
+                        assert_eq!(req.headers()["last-event-id"], "1");
+                        "event: update\ndata: two\ndata: lines\n\n".into()
+                    }
+                    _ => {
+                        return http::Response::builder()
+                            .status(http::StatusCode::NO_CONTENT)
+                            .body(Default::default())
+                            .unwrap();
+                    }
+                };
+                http::Response::builder()
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .body(body)
+                    .unwrap()
+            }
+        }
+    });
+
+    let events = Client::new()
+        .get(format!("http://{}/events", server.addr()))
+        .event_stream()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+
+    let events: Vec<_> = events
+        .iter()
+        .map(|event| (event.event(), event.data(), event.id()))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            ("message", "one", Some("1")),
+            ("update", "two\nlines", Some("1")),
+        ]
+    );
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn trace_id_is_recorded_on_resolve_span() {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+    };
+    use tracing_subscriber::{Layer, layer::Context, prelude::*};
+
+    /// Records the `trace_id` of every `resolve` span.
+    #[derive(Clone, Default)]
+    struct ResolveSpans(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for ResolveSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            struct TraceId<'a>(&'a Mutex<Vec<String>>);
+
+            impl Visit for TraceId<'_> {
+                fn record_str(&mut self, field: &Field, value: &str) {
+                    if field.name() == "trace_id" {
+                        self.0.lock().unwrap().push(value.to_owned());
+                    }
+                }
+
+                fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+            }
+
+            if attrs.metadata().name() == "resolve" {
+                attrs.record(&mut TraceId(&self.0));
+            }
+        }
+    }
+
+    let spans = ResolveSpans::default();
+    let _guard = tracing_subscriber::registry()
+        .with(spans.clone())
+        .set_default();
+
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let client = Client::builder()
+        .no_proxy()
+        .resolve("traced.test", server.addr())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://traced.test:{}/", server.addr().port()))
+        .trace_id("req-42")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    assert_eq!(*spans.0.lock().unwrap(), ["req-42"]);
+}
+
+#[cfg(feature = "cancel-token")]
+#[tokio::test]
+async fn cancel_token_aborts_pending_resolution() {
+    use std::time::{Duration, Instant};
+
+    use tokio_util::sync::CancellationToken;
+
+    /// A resolver whose lookups never complete.
+    struct StalledResolver;
+
+    impl wreq::dns::Resolve for StalledResolver {
+        fn resolve(&self, _: wreq::dns::Name) -> wreq::dns::Resolving {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    let client = Client::builder()
+        .no_proxy()
+        .dns_resolver(StalledResolver)
+        .build()
+        .unwrap();
+
+    let token = CancellationToken::new();
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            token.cancel();
+        }
+    });
+
+    let start = Instant::now();
+    let err = client
+        .get("http://stalled.invalid/")
+        .cancel_token(token.clone())
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_canceled(), "{err:?}");
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // An already cancelled token fails the request up front
+    let err = client
+        .get("http://stalled.invalid/")
+        .cancel_token(token)
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_canceled(), "{err:?}");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn per_request_local_address_overrides_client_default() {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let server = server::http(move |_req| async { http::Response::default() });
+    let url = format!("http://{}/", server.addr());
+
+    let client = Client::builder()
+        .no_proxy()
+        .local_address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .build()
+        .unwrap();
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(
+        res.local_addr().unwrap().ip(),
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    );
+
+    // The whole 127.0.0.0/8 range is bound to the loopback interface on Linux;
+    // the pooled connection from 127.0.0.1 must not be reused
+    let bound = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+    let res = client.get(&url).local_address(bound).send().await.unwrap();
+    assert_eq!(res.local_addr().unwrap().ip(), bound);
+}
+
+#[tokio::test]
+async fn local_address_resolves_its_ip_version_only() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    struct DualStack(Vec<SocketAddr>);
+
+    impl wreq::dns::Resolve for DualStack {
+        fn resolve(&self, _: wreq::dns::Name) -> wreq::dns::Resolving {
+            let addrs: wreq::dns::Addrs = Box::new(self.0.clone().into_iter());
+            Box::pin(std::future::ready(Ok(addrs)))
+        }
+    }
+
+    let server = server::http(move |_req| async { http::Response::default() });
+    let v6 = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), server.addr().port());
+
+    // A socket bound to an IPv4 address only gets the IPv4 addresses
+    let client = Client::builder()
+        .no_proxy()
+        .local_address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .dns_resolver(DualStack(vec![v6, server.addr()]))
+        .build()
+        .unwrap();
+
+    let url = format!("http://dual.test:{}/", server.addr().port());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let attempts = res.connect_attempts().expect("attempts recorded");
+    let addrs: Vec<_> = attempts.iter().map(|a| a.addr()).collect();
+    assert_eq!(addrs, [server.addr()]);
+}
+
+#[tokio::test]
+async fn max_concurrent_connects_bounds_connects_in_progress() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    /// Resolves every name to a fixed address after a delay, tracking the
+    /// highest number of lookups in progress at once.
+    struct SlowResolver {
+        addr: std::net::SocketAddr,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl wreq::dns::Resolve for SlowResolver {
+        fn resolve(&self, _: wreq::dns::Name) -> wreq::dns::Resolving {
+            let addr = self.addr;
+            let in_flight = self.in_flight.clone();
+            let peak = self.peak.clone();
+            Box::pin(async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                let addrs: wreq::dns::Addrs = Box::new(std::iter::once(addr));
+                Ok(addrs)
+            })
+        }
+    }
+
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    // Returns the most connects in progress at once for 12 requests
+    let peak_connects = |limit: Option<usize>| {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut builder = Client::builder().no_proxy().dns_resolver(SlowResolver {
+            addr: server.addr(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        });
+        if let Some(limit) = limit {
+            builder = builder.max_concurrent_connects(limit);
+        }
+        let client = builder.build().unwrap();
+
+        // Distinct hosts, so that every request needs a connection of its own
+        let port = server.addr().port();
+        async move {
+            let requests =
+                (0..12).map(|i| client.get(format!("http://host{i}.test:{port}/")).send());
+            for res in futures::future::join_all(requests).await {
+                assert_eq!(res.unwrap().status(), wreq::StatusCode::OK);
+            }
+            peak.load(Ordering::SeqCst)
+        }
+    };
+
+    // Every connect overlaps the others, but only 3 are let through at once
+    assert!(peak_connects(None).await > 3);
+    assert_eq!(peak_connects(Some(3)).await, 3);
+}
@@ -1,6 +1,11 @@
 mod support;
+use std::sync::Arc;
+
 use support::server;
-use wreq::Client;
+use wreq::{
+    Client,
+    cookie::{CookieStore, FileCookieStore, Jar},
+};
 
 #[tokio::test]
 async fn cookie_response_accessor() {
@@ -216,3 +221,180 @@ async fn cookie_store_stores_response_cookie_with_manual_cookie() {
     let check_url = format!("http://{}/2", server.addr());
     let _ = client.get(&check_url).send().await.unwrap();
 }
+
+fn random_cookie_path(ext: &str) -> std::path::PathBuf {
+    let mut buf = std::env::temp_dir();
+    let rng = std::collections::hash_map::RandomState::new();
+    let n = rng.hash_one("cookies");
+    buf.push(format!("test-cookies-{}.{}", n, ext));
+    buf
+}
+
+#[tokio::test]
+async fn file_cookie_store_saves_on_drop_and_loads_again() {
+    let server = server::http(|req| async move {
+        if req.uri() == "/2" {
+            assert_eq!(req.headers()["cookie"], "key=val");
+        }
+
+        http::Response::builder()
+            .header("Set-Cookie", "key=val; HttpOnly")
+            .body(Default::default())
+            .unwrap()
+    });
+
+    let path = random_cookie_path("txt");
+    let store = Arc::new(FileCookieStore::open(&path).unwrap());
+    let client = Client::builder()
+        .cookie_provider(store.clone())
+        .build()
+        .unwrap();
+
+    let set_url = format!("http://{}/1", server.addr());
+    client.get(&set_url).send().await.unwrap();
+    drop(client);
+    drop(store);
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    let host = server.addr().ip().to_string();
+    assert!(saved.starts_with("# Netscape HTTP Cookie File\n"));
+    assert!(saved.contains(&format!(
+        "#HttpOnly_{}\tFALSE\t/\tFALSE\t0\tkey\tval\n",
+        host
+    )));
+
+    let store = Arc::new(FileCookieStore::open(&path).unwrap());
+    let client = Client::builder()
+        .cookie_provider(store.clone())
+        .build()
+        .unwrap();
+
+    let check_url = format!("http://{}/2", server.addr());
+    client.get(&check_url).send().await.unwrap();
+
+    drop(client);
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn client_save_cookies_writes_the_cookie_store() {
+    let server = server::http(|_req| async move {
+        http::Response::builder()
+            .header("Set-Cookie", "key=val; Domain=example.com; Secure")
+            .header(
+                "Set-Cookie",
+                "gone=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            )
+            .body(Default::default())
+            .unwrap()
+    });
+
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    let url = format!("http://{}/", server.addr());
+    client.get(&url).send().await.unwrap();
+
+    let path = random_cookie_path("txt");
+    client.save_cookies(&path).unwrap();
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains(".example.com\tTRUE\t/\tTRUE\t0\tkey\tval\n"));
+    assert!(!saved.contains("gone"));
+
+    let store = FileCookieStore::open(&path).unwrap();
+    let cookies = store
+        .jar()
+        .cookies(&"https://www.example.com/".parse().unwrap());
+    assert_eq!(cookies, ["key=val"]);
+
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+
+    let client = Client::new();
+    assert!(client.save_cookies(&path).is_err());
+}
+
+#[tokio::test]
+async fn request_cookie_jar_overrides_the_client_store() {
+    let server = server::http(|req| async move {
+        let cookie = req
+            .headers()
+            .get("cookie")
+            .map(|value| value.to_str().unwrap().to_owned());
+        match req.uri().path() {
+            "/alice" => http::Response::builder()
+                .header("Set-Cookie", "user=alice")
+                .body(Default::default())
+                .unwrap(),
+            "/bob" => http::Response::builder()
+                .header("Set-Cookie", "user=bob")
+                .body(Default::default())
+                .unwrap(),
+            _ => http::Response::new(cookie.unwrap_or_default().into()),
+        }
+    });
+
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    let alice = Arc::new(Jar::default());
+    let bob = Arc::new(Jar::default());
+
+    let url = |path: &str| format!("http://{}{}", server.addr(), path);
+    client
+        .get(url("/alice"))
+        .cookie_jar(alice.clone())
+        .send()
+        .await
+        .unwrap();
+    client
+        .get(url("/bob"))
+        .cookie_jar(bob.clone())
+        .send()
+        .await
+        .unwrap();
+
+    let seen = |jar: Option<Arc<Jar>>| {
+        let mut req = client.get(url("/check"));
+        if let Some(jar) = jar {
+            req = req.cookie_jar(jar);
+        }
+        async move { req.send().await.unwrap().text().await.unwrap() }
+    };
+    assert_eq!(seen(Some(alice)).await, "user=alice");
+    assert_eq!(seen(Some(bob)).await, "user=bob");
+    assert_eq!(seen(None).await, "");
+}
+
+#[tokio::test]
+async fn request_cookie_jar_bypasses_the_http_cache() {
+    let server = server::http(|req| async move {
+        let cookie = req
+            .headers()
+            .get("cookie")
+            .map(|value| value.to_str().unwrap().to_owned());
+        http::Response::builder()
+            .header("Cache-Control", "max-age=60")
+            .body(cookie.unwrap_or_default().into())
+            .unwrap()
+    });
+
+    let client = Client::builder()
+        .cookie_store(true)
+        .http_cache(wreq::cache::CacheConfig::memory(16))
+        .build()
+        .unwrap();
+    let url = format!("http://{}/account", server.addr());
+    let jar = |user: &str| {
+        let jar = Arc::new(Jar::default());
+        jar.add_cookie_str(&format!("user={user}"), url.as_str());
+        jar
+    };
+
+    // Stored for the client's own store, which holds no cookies
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "");
+
+    for user in ["alice", "bob"] {
+        let res = client.get(&url).cookie_jar(jar(user)).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), format!("user={user}"));
+    }
+}
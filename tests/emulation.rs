@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
 use wreq::{
     Client, Emulation,
@@ -159,6 +159,48 @@ fn emulation_template() -> Emulation {
         .build()
 }
 
+#[test]
+fn test_randomized_emulation() {
+    let fingerprint = |permute: bool, seed: Option<u64>| {
+        let tls = TlsOptions::builder()
+            .permute_extensions(permute)
+            .extension_permutation(&[
+                ExtensionType::SERVER_NAME,
+                ExtensionType::EXTENDED_MASTER_SECRET,
+                ExtensionType::SUPPORTED_GROUPS,
+                ExtensionType::EC_POINT_FORMATS,
+                ExtensionType::SESSION_TICKET,
+                ExtensionType::APPLICATION_LAYER_PROTOCOL_NEGOTIATION,
+                ExtensionType::STATUS_REQUEST,
+                ExtensionType::KEY_SHARE,
+                ExtensionType::SUPPORTED_VERSIONS,
+                ExtensionType::SIGNATURE_ALGORITHMS,
+                ExtensionType::PSK_KEY_EXCHANGE_MODES,
+                ExtensionType::CERT_COMPRESSION,
+            ])
+            .aes_hw_override(false)
+            .build();
+        let mut emulation = Emulation::builder().tls_options(tls).build();
+        if let Some(seed) = seed {
+            emulation = emulation.randomized_with_seed(seed);
+        }
+        let client = Client::builder().emulation(emulation).build().unwrap();
+        (client.ja3().unwrap(), client.ja4().unwrap())
+    };
+
+    // Extension orders are shuffled per client, which JA4 sorts away
+    let fingerprints: Vec<_> = (1..=8).map(|seed| fingerprint(true, Some(seed))).collect();
+    let ja3s: HashSet<_> = fingerprints.iter().map(|(ja3, _)| ja3).collect();
+    let ja4s: HashSet<_> = fingerprints.iter().map(|(_, ja4)| ja4).collect();
+    assert!(ja3s.len() > 1);
+    assert_eq!(ja4s.len(), 1);
+    assert_eq!(fingerprint(true, Some(3)), fingerprints[2]);
+
+    // A fixed extension order is left alone
+    let fixed = fingerprint(false, None);
+    assert!((1..=8).all(|seed| fingerprint(false, Some(seed)) == fixed));
+}
+
 #[tokio::test]
 async fn test_emulation() -> wreq::Result<()> {
     let client = Client::builder()
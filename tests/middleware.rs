@@ -0,0 +1,129 @@
+mod support;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use http::HeaderValue;
+use support::server;
+use wreq::{
+    Client, Request,
+    middleware::{Handling, Middleware, Next},
+};
+
+/// Adds a header to requests, and records the order it ran in.
+struct Tag {
+    name: &'static str,
+    order: Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+impl Middleware for Tag {
+    fn handle(&self, mut req: Request, next: Next) -> Handling {
+        self.order.lock().unwrap().push(self.name);
+        req.headers_mut()
+            .append("x-middleware", HeaderValue::from_static(self.name));
+        next.run(req)
+    }
+}
+
+/// Answers requests for `/cached` without sending them.
+struct ShortCircuit;
+
+impl Middleware for ShortCircuit {
+    fn handle(&self, req: Request, next: Next) -> Handling {
+        if req.uri().path() != "/cached" {
+            return next.run(req);
+        }
+        let res = http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body("from middleware")
+            .unwrap();
+        Box::pin(async move { Ok(wreq::Response::from(res)) })
+    }
+}
+
+/// Adds a header to every response.
+struct StampResponse;
+
+impl Middleware for StampResponse {
+    fn handle(&self, req: Request, next: Next) -> Handling {
+        Box::pin(async move {
+            let mut res = next.run(req).await?;
+            res.headers_mut()
+                .insert("x-stamped", HeaderValue::from_static("yes"));
+            Ok(res)
+        })
+    }
+}
+
+#[tokio::test]
+async fn middleware_runs_in_order_and_can_modify_requests_and_responses() {
+    let server = server::http(move |req| async move {
+        let tags: Vec<_> = req
+            .headers()
+            .get_all("x-middleware")
+            .iter()
+            .map(|value| value.to_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(tags, ["outer", "inner"]);
+        http::Response::default()
+    });
+
+    let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let client = Client::builder()
+        .no_proxy()
+        .with(StampResponse)
+        .with(Tag {
+            name: "outer",
+            order: order.clone(),
+        })
+        .with(Tag {
+            name: "inner",
+            order: order.clone(),
+        })
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(res.headers()["x-stamped"], "yes");
+    assert_eq!(*order.lock().unwrap(), ["outer", "inner"]);
+}
+
+#[tokio::test]
+async fn middleware_can_answer_without_sending() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |_req| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            async { http::Response::default() }
+        }
+    });
+
+    let client = Client::builder()
+        .no_proxy()
+        .with(ShortCircuit)
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/cached", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "from middleware");
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    client
+        .get(format!("http://{}/sent", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
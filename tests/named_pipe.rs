@@ -0,0 +1,106 @@
+#![cfg(windows)]
+
+use std::{hash::BuildHasher, time::Duration};
+
+use http_body_util::Full;
+use hyper::{Request, Response, body::Incoming, service::service_fn};
+use hyper_util::rt::TokioIo;
+use tokio::{
+    net::windows::named_pipe::{ClientOptions, ServerOptions},
+    task,
+};
+use wreq::{Client, Proxy};
+
+fn random_pipe_name() -> String {
+    // libstd uses system random to create each one
+    let rng = std::collections::hash_map::RandomState::new();
+    let n = rng.hash_one("named-pipe");
+    format!(r"\\.\pipe\test-named-pipe-{}", n)
+}
+
+/// Serves each connection to the pipe `name` with HTTP/1, answering with the
+/// path of the request.
+fn serve(name: String) {
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&name)
+        .unwrap();
+    tokio::spawn(async move {
+        loop {
+            server.connect().await.unwrap();
+            let connected = server;
+            server = ServerOptions::new().create(&name).unwrap();
+
+            let service = service_fn(|req: Request<Incoming>| async move {
+                let body = format!("{} over named pipe", req.uri().path());
+                Ok::<_, hyper::Error>(Response::new(Full::new(bytes::Bytes::from(body))))
+            });
+            task::spawn(
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(connected), service),
+            );
+        }
+    });
+}
+
+#[tokio::test]
+async fn test_client_named_pipe() {
+    let name = random_pipe_name();
+    serve(name.clone());
+
+    // The pipe is used even though the client has another proxy
+    let client = Client::builder()
+        .proxy(Proxy::http("http://127.0.0.1:1").unwrap())
+        .named_pipe(&name)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+
+    let resp = client
+        .get("http://localhost/v1.41/containers/json")
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    assert_eq!(body, "/v1.41/containers/json over named pipe");
+}
+
+#[tokio::test]
+async fn test_client_named_pipe_outlives_no_proxy() {
+    let name = random_pipe_name();
+    serve(name.clone());
+
+    let client = Client::builder()
+        .named_pipe(&name)
+        .no_proxy()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+
+    // Nor does the proxy of a request take its place
+    let resp = client
+        .get("http://localhost/info")
+        .proxy(Proxy::http("http://127.0.0.1:1").unwrap())
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    assert_eq!(body, "/info over named pipe");
+}
+
+#[tokio::test]
+async fn test_busy_named_pipe_gives_up() {
+    let name = random_pipe_name();
+    let _server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .max_instances(1)
+        .create(&name)
+        .unwrap();
+    // Take the only instance of the pipe
+    let _client = ClientOptions::new().open(&name).unwrap();
+
+    let client = Client::builder().named_pipe(&name).build().unwrap();
+
+    let err = client.get("http://localhost/").send().await.unwrap_err();
+    assert!(err.is_connect());
+}
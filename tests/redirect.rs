@@ -556,3 +556,62 @@ async fn test_redirect_applies_set_cookie_from_redirect() {
     assert_eq!(res.uri(), dst.as_str());
     assert_eq!(res.status(), wreq::StatusCode::OK);
 }
+
+/// Parses the hop number `n` ending a `/redirect/{n}` path.
+fn redirect_hop(uri: &http::Uri) -> u32 {
+    uri.path().rsplit('/').next().unwrap().parse().unwrap()
+}
+
+/// Starts a server redirecting `/redirect/{n}` to `/redirect/{n + 1}` after `delay`.
+fn endless_redirects(delay: std::time::Duration) -> server::Server {
+    server::http(move |req| async move {
+        let i = redirect_hop(req.uri());
+        tokio::time::sleep(delay).await;
+        http::Response::builder()
+            .status(302)
+            .header("location", format!("/redirect/{}", i + 1))
+            .body(Body::default())
+            .unwrap()
+    })
+}
+
+#[tokio::test]
+async fn test_max_redirects_limit() {
+    let server = endless_redirects(std::time::Duration::ZERO);
+    let url = format!("http://{}/redirect/0", server.addr());
+
+    let client = Client::builder()
+        .max_redirects(3)
+        .max_redirect_time(std::time::Duration::from_secs(30))
+        .build()
+        .unwrap();
+    let err = client.get(&url).send().await.unwrap_err();
+
+    assert!(err.is_redirect());
+    assert_eq!(
+        err.uri().unwrap().to_string(),
+        format!("http://{}/redirect/3", server.addr())
+    );
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(), "too many redirects");
+}
+
+#[tokio::test]
+async fn test_max_redirect_time_limit() {
+    let server = endless_redirects(std::time::Duration::from_millis(100));
+    let url = format!("http://{}/redirect/0", server.addr());
+
+    // Well within the hop limit, but the chain outlasts the time budget
+    let client = Client::builder()
+        .max_redirects(50)
+        .max_redirect_time(std::time::Duration::from_millis(250))
+        .build()
+        .unwrap();
+    let err = client.get(&url).send().await.unwrap_err();
+
+    assert!(err.is_redirect());
+    let hops = redirect_hop(err.uri().unwrap());
+    assert!(hops < 10, "followed {hops} redirects");
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(), "redirect time budget exceeded");
+}
@@ -1,8 +1,11 @@
 mod support;
 
-use std::sync::{
-    Arc,
-    atomic::{AtomicUsize, Ordering},
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use support::server;
@@ -50,6 +53,48 @@ async fn retries_apply_in_scope() {
     assert_eq!(resp.status(), 200);
 }
 
+#[tokio::test]
+async fn transient_retries_back_off_and_spare_non_idempotent_requests() {
+    let _ = pretty_env_logger::try_init();
+
+    let cnt = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let cnt = cnt.clone();
+        move |req| {
+            let cnt = cnt.clone();
+            async move {
+                if cnt.fetch_add(1, Ordering::Relaxed) == 0 || req.method() == http::Method::POST {
+                    http::Response::builder()
+                        .status(http::StatusCode::TOO_MANY_REQUESTS)
+                        .header(http::header::RETRY_AFTER, "1")
+                        .body(Default::default())
+                        .unwrap()
+                } else {
+                    http::Response::default()
+                }
+            }
+        }
+    });
+
+    let policy = wreq::retry::Policy::default()
+        .classify_transient()
+        .backoff(Duration::from_millis(10), Duration::from_millis(200));
+    let client = Client::builder().retry(policy).build().unwrap();
+    let url = format!("http://{}", server.addr());
+
+    // `Retry-After` is honored, up to the maximum backoff
+    let start = Instant::now();
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(cnt.load(Ordering::Relaxed), 2);
+
+    // POST is not idempotent, so its 429 is returned as is
+    let resp = client.post(&url).body("payload").send().await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(cnt.load(Ordering::Relaxed), 3);
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn default_retries_have_a_limit() {
     let _ = pretty_env_logger::try_init();
@@ -56,6 +56,79 @@ async fn test_unix_socket() {
     assert_eq!(body, "hello unix");
 }
 
+#[tokio::test]
+async fn test_client_unix_socket() {
+    let sock_path = random_sock_path();
+
+    let listener = UnixListener::bind(&sock_path).unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|req: Request<Incoming>| async move {
+                let body = format!("{} over unix", req.uri().path());
+                Ok::<_, hyper::Error>(Response::new(Full::new(bytes::Bytes::from(body))))
+            });
+            task::spawn(
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service),
+            );
+        }
+    });
+
+    // The socket is used even though the client has another proxy
+    let client = Client::builder()
+        .proxy(Proxy::http("http://127.0.0.1:1").unwrap())
+        .unix_socket(sock_path)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+
+    let resp = client
+        .get("http://localhost/v1.41/containers/json")
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    assert_eq!(body, "/v1.41/containers/json over unix");
+}
+
+#[tokio::test]
+async fn test_client_unix_socket_outlives_no_proxy() {
+    let sock_path = random_sock_path();
+
+    let listener = UnixListener::bind(&sock_path).unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|req: Request<Incoming>| async move {
+                let body = format!("{} over unix", req.uri().path());
+                Ok::<_, hyper::Error>(Response::new(Full::new(bytes::Bytes::from(body))))
+            });
+            task::spawn(
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service),
+            );
+        }
+    });
+
+    let client = Client::builder()
+        .unix_socket(sock_path)
+        .no_proxy()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+
+    // Nor does the proxy of a request take its place
+    let resp = client
+        .get("http://localhost/info")
+        .proxy(Proxy::http("http://127.0.0.1:1").unwrap())
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    assert_eq!(body, "/info over unix");
+}
+
 #[tokio::test]
 async fn test_proxy_unix_socket() {
     let sock_path = random_sock_path();
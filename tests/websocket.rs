@@ -0,0 +1,150 @@
+mod support;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use http::header;
+use hyper_util::rt::TokioIo;
+use support::server;
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{self, handshake::derive_accept_key, protocol::Role},
+};
+use wreq::{Client, ws::message::Message};
+
+fn switching_protocols(req: &http::Request<hyper::body::Incoming>) -> http::response::Builder {
+    let key = req.headers()[header::SEC_WEBSOCKET_KEY].as_bytes();
+    http::Response::builder()
+        .status(http::StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, derive_accept_key(key))
+}
+
+#[tokio::test]
+async fn websocket_handshake_headers() {
+    let server = server::http(move |req| {
+        assert_eq!(req.headers()[header::CONNECTION], "Upgrade");
+        assert_eq!(req.headers()[header::UPGRADE], "websocket");
+        assert_eq!(req.headers()[header::PRAGMA], "no-cache");
+        assert_eq!(req.headers()[header::CACHE_CONTROL], "no-cache");
+
+        let res = switching_protocols(&req)
+            .body(wreq::Body::default())
+            .unwrap();
+        async { res }
+    });
+
+    Client::new()
+        .websocket(format!("ws://{}", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .into_websocket()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn websocket_rejects_unrequested_extensions() {
+    let server = server::http(move |req| {
+        let res = switching_protocols(&req)
+            .header(header::SEC_WEBSOCKET_EXTENSIONS, "permessage-deflate")
+            .body(wreq::Body::default())
+            .unwrap();
+        async { res }
+    });
+
+    let err = Client::new()
+        .websocket(format!("ws://{}", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .into_websocket()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_upgrade());
+}
+
+#[tokio::test]
+async fn websocket_keepalive_sends_pings() {
+    let server = server::http(move |req| {
+        let res = switching_protocols(&req)
+            .body(wreq::Body::default())
+            .unwrap();
+
+        tokio::spawn(async move {
+            let upgraded = TokioIo::new(hyper::upgrade::on(req).await.unwrap());
+            let mut ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+
+            let msg = ws.next().await.unwrap().unwrap();
+            assert!(msg.is_ping());
+            ws.send(tungstenite::Message::text("pinged")).await.unwrap();
+            // Keep the connection open until the client hangs up
+            while ws.next().await.is_some() {}
+        });
+
+        async { res }
+    });
+
+    let mut websocket = Client::new()
+        .websocket(format!("ws://{}", server.addr()))
+        .keepalive(Duration::from_millis(100))
+        .send()
+        .await
+        .unwrap()
+        .into_websocket()
+        .await
+        .unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match websocket.next().await.unwrap().unwrap() {
+                Message::Text(text) => break text,
+                Message::Pong(_) => continue,
+                msg => panic!("unexpected message: {msg:?}"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(received.as_str(), "pinged");
+}
+
+#[tokio::test]
+async fn websocket_keepalive_detects_dead_peer() {
+    let server = server::http(move |req| {
+        let res = switching_protocols(&req)
+            .body(wreq::Body::default())
+            .unwrap();
+
+        tokio::spawn(async move {
+            // Hold the connection open without ever answering a ping
+            let _upgraded = hyper::upgrade::on(req).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        async { res }
+    });
+
+    let mut websocket = Client::new()
+        .websocket(format!("ws://{}", server.addr()))
+        .keepalive(Duration::from_millis(50))
+        .send()
+        .await
+        .unwrap()
+        .into_websocket()
+        .await
+        .unwrap();
+
+    let err = tokio::time::timeout(Duration::from_secs(5), websocket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert!(err.is_websocket());
+
+    // The timeout is reported once, then the stream ends
+    assert!(websocket.next().await.is_none());
+}